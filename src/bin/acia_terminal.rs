@@ -0,0 +1,99 @@
+use davepoo_6502::devices::acia::Acia;
+use davepoo_6502::devices::serial::{SerialHost, StreamSerial};
+use davepoo_6502::devices::DeviceBus;
+use davepoo_6502::frontend::{self, parse_word, Throttle};
+use davepoo_6502::loader::{self, RESET_VECTOR};
+use davepoo_6502::m6502::*;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: acia_terminal <image> <load-address> [--acia <address>] [--pty]
+
+Runs a ROM image with 64 KiB of RAM around it and a 6551 ACIA at <address>
+($5000 by default), starting at the reset vector. The ACIA is connected to
+this terminal, or with --pty to a new pseudo-terminal whose path is printed.
+Intel HEX (.hex), S-record (.s19, .srec) and PRG (.prg) images carry their
+own addresses, anything else is a raw binary loaded at <load-address>.";
+
+const CPU_HZ: u64 = 1_000_000;
+const CYCLES_PER_SLICE: s32 = 10_000;
+
+/** Run until the CPU halts, keeping the emulated clock close to real time */
+fn run<H: SerialHost + 'static>(mem: Mem, acia: Acia<H>, acia_address: Word, mut cpu: CPU) -> ExitCode {
+    let mut bus = DeviceBus::new(mem);
+    bus.attach(acia_address, 4, acia);
+    let mut throttle = Throttle::new(CPU_HZ);
+    loop {
+        let cycles = bus.execute(CYCLES_PER_SLICE, &mut cpu);
+        if let Some(code) = frontend::halted(&cpu, &bus) {
+            return code;
+        }
+        throttle.wait(cycles);
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut positional = Vec::new();
+    let mut acia_address = 0x5000;
+    let mut pty = false;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--acia" => match args.get(i + 1).and_then(|address| parse_word(address)) {
+                Some(address) => {
+                    acia_address = address;
+                    i += 1;
+                }
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            "--pty" => pty = true,
+            arg => positional.push(arg),
+        }
+        i += 1;
+    }
+
+    let [image_path, load_address] = positional[..] else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let Some(load_address) = parse_word(load_address) else {
+        eprintln!("invalid load address: {}", load_address);
+        return ExitCode::from(2);
+    };
+
+    let mut mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0, &mut mem);
+    if let Err(e) = loader::load_file(&mut mem, image_path, load_address) {
+        eprintln!("{}: {}", image_path, e);
+        return ExitCode::from(2);
+    }
+    cpu.set_pc(Word::from_le_bytes([mem[RESET_VECTOR], mem[RESET_VECTOR + 1]]));
+
+    if pty {
+        #[cfg(target_os = "linux")]
+        {
+            use davepoo_6502::devices::serial::PtySerial;
+            match PtySerial::open() {
+                Ok(host) => {
+                    println!("ACIA connected to {}", host.path().display());
+                    return run(mem, Acia::new(host), acia_address, cpu);
+                }
+                Err(e) => {
+                    eprintln!("cannot open a pseudo-terminal: {}", e);
+                    return ExitCode::from(2);
+                }
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            eprintln!("--pty is only supported on Linux");
+            return ExitCode::from(2);
+        }
+    }
+
+    run(mem, Acia::new(StreamSerial::stdio()), acia_address, cpu)
+}
@@ -0,0 +1,93 @@
+use davepoo_6502::devices::serial::StreamSerial;
+use davepoo_6502::frontend::{self, parse_word, Throttle};
+use davepoo_6502::loader;
+use davepoo_6502::m6502::*;
+use davepoo_6502::machines::apple1::{self, Apple1};
+use std::fs;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: apple1 [--rom <wozmon>] [--ram <KiB>] [--load <image> <address>]
+
+Runs an Apple I with 4 KiB of RAM (or --ram KiB, 8 for the expanded one)
+and the 256-byte ROM image given with --rom at $FF00, starting at its reset
+vector. --load puts a program in RAM as well, starting at its own start
+address when it carries one, or at <address> when there is no ROM. The keyboard and display are this terminal:
+typing is upper-cased and the display wraps at 40 columns.";
+
+const CYCLES_PER_SLICE: s32 = 10_000;
+
+fn usage() -> ExitCode {
+    eprintln!("{}", USAGE);
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut rom_path = None;
+    let mut ram_kib = 4;
+    let mut program = None;
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("--rom", Some(path)) => rom_path = Some(path.clone()),
+            ("--ram", Some(kib)) => match kib.parse::<usize>() {
+                Ok(kib) => ram_kib = kib,
+                Err(_) => return usage(),
+            },
+            ("--load", Some(path)) => match args.get(i + 2).and_then(|address| parse_word(address)) {
+                Some(address) => {
+                    program = Some((path.clone(), address));
+                    i += 1;
+                }
+                None => return usage(),
+            },
+            _ => return usage(),
+        }
+        i += 2;
+    }
+    if rom_path.is_none() && program.is_none() {
+        return usage();
+    }
+
+    let mut machine = Apple1::new(ram_kib * 1024, StreamSerial::stdio());
+    if let Some(path) = &rom_path {
+        let loaded = fs::read(path).map_err(loader::LoadError::from);
+        if let Err(e) = loaded.and_then(|rom| machine.load_rom(&rom)) {
+            eprintln!("{}: {}", path, e);
+            return ExitCode::from(2);
+        }
+    }
+    machine.reset();
+
+    if let Some((path, address)) = &program {
+        //loaded through a scratch 64 KiB, then copied to what the Apple I really has
+        let mut scratch = Mem::new();
+        let image = match loader::load_file(&mut scratch, path, *address) {
+            Ok(image) => image,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                return ExitCode::from(2);
+            }
+        };
+        for (address, data) in &image.segments {
+            for (offset, byte) in data.iter().enumerate() {
+                machine.bus.bus.write(address.wrapping_add(offset as Word), *byte);
+            }
+        }
+        match image.start {
+            Some(start) => machine.cpu.set_pc(start),
+            //without a ROM there's no reset vector, a raw binary is started where it was loaded
+            None if rom_path.is_none() => machine.cpu.set_pc(*address),
+            None => {}
+        }
+    }
+
+    let mut throttle = Throttle::new(apple1::CPU_HZ);
+    loop {
+        let cycles = machine.execute(CYCLES_PER_SLICE);
+        if let Some(code) = frontend::halted(&machine.cpu, &machine.bus) {
+            return code;
+        }
+        throttle.wait(cycles);
+    }
+}
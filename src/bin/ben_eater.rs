@@ -0,0 +1,73 @@
+use davepoo_6502::frontend::{self, draw_framed, Throttle};
+use davepoo_6502::m6502::*;
+use davepoo_6502::machines::ben_eater::{BenEater, LcdWiring, LCD_COLUMNS};
+use std::fs;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: ben_eater <rom> [--clock <hz>] [--four-bit]
+
+Runs a ROM image, e.g. vasm's a.out, on Ben Eater's breadboard 6502 and
+shows its 16x2 LCD in this terminal. The CPU runs at 1 MHz unless --clock
+says otherwise. --four-bit is for the LCD wired to port B alone, as in the
+videos that put the keyboard on port A.";
+
+const CYCLES_PER_SLICE: s32 = 10_000;
+
+fn usage() -> ExitCode {
+    eprintln!("{}", USAGE);
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut rom_path = None;
+    let mut hz = 1_000_000;
+    let mut wiring = LcdWiring::EightBit;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--clock" => match args.get(i + 1).and_then(|hz| hz.parse().ok()) {
+                Some(clock @ 1..) => {
+                    hz = clock;
+                    i += 1;
+                }
+                _ => return usage(),
+            },
+            "--four-bit" => wiring = LcdWiring::FourBit,
+            path if rom_path.is_none() => rom_path = Some(path.to_string()),
+            _ => return usage(),
+        }
+        i += 1;
+    }
+    let Some(rom_path) = rom_path else {
+        return usage();
+    };
+
+    let mut machine = BenEater::new(wiring, hz);
+    let loaded = fs::read(&rom_path).map_err(Into::into);
+    if let Err(e) = loaded.and_then(|rom| machine.load_rom(&rom)) {
+        eprintln!("{}: {}", rom_path, e);
+        return ExitCode::from(2);
+    }
+    machine.reset();
+
+    let mut throttle = Throttle::new(hz);
+    let mut shown: Option<Vec<String>> = None;
+    loop {
+        let cycles = machine.execute(CYCLES_PER_SLICE);
+        if let Some(code) = frontend::halted(&machine.cpu, &machine.bus) {
+            return code;
+        }
+
+        let lines = machine.lcd.text();
+        if shown.as_ref() != Some(&lines) {
+            if let Err(e) = draw_framed(&lines, LCD_COLUMNS, shown.is_some()) {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
+            shown = Some(lines);
+        }
+
+        throttle.wait(cycles);
+    }
+}
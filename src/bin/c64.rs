@@ -0,0 +1,122 @@
+use davepoo_6502::devices::serial::{SerialHost, StreamSerial};
+use davepoo_6502::frontend::{self, draw_framed, Throttle};
+use davepoo_6502::m6502::*;
+use davepoo_6502::machines::c64::{self, C64};
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: c64 --basic <rom> --kernal <rom> --chargen <rom> [--ntsc] [--cycles <count>]
+
+Runs a Commodore 64 with its ROM images and shows the text screen in this
+terminal. Lines typed on stdin go to the keyboard. With --cycles the C64
+runs that many cycles as fast as it can, prints the screen and exits.";
+
+const CYCLES_PER_SLICE: s32 = 20_000;
+
+fn usage() -> ExitCode {
+    eprintln!("{}", USAGE);
+    ExitCode::from(2)
+}
+
+fn fail(path: &str, e: impl Display) -> ExitCode {
+    eprintln!("{}: {}", path, e);
+    ExitCode::from(2)
+}
+
+/** What the command line asked for */
+struct Options {
+    basic: String,
+    kernal: String,
+    chargen: String,
+    ntsc: bool,
+    cycles: Option<u64>,
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let (mut basic, mut kernal, mut chargen) = (None, None, None);
+    let mut ntsc = false;
+    let mut cycles = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--basic" => basic = Some(args.next()?.clone()),
+            "--kernal" => kernal = Some(args.next()?.clone()),
+            "--chargen" => chargen = Some(args.next()?.clone()),
+            "--ntsc" => ntsc = true,
+            "--cycles" => cycles = Some(args.next()?.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some(Options {
+        basic: basic?,
+        kernal: kernal?,
+        chargen: chargen?,
+        ntsc,
+        cycles,
+    })
+}
+
+fn read(path: &str) -> Result<Vec<Byte>, ExitCode> {
+    fs::read(path).map_err(|e| fail(path, e))
+}
+
+/** Build the C64 with its ROMs and reset it */
+fn boot(options: &Options) -> Result<C64, ExitCode> {
+    let mut machine = if options.ntsc { C64::ntsc() } else { C64::new() };
+    let (basic, kernal, chargen) = (&options.basic, &options.kernal, &options.chargen);
+    machine.load_basic(&read(basic)?).map_err(|e| fail(basic, e))?;
+    machine.load_kernal(&read(kernal)?).map_err(|e| fail(kernal, e))?;
+    machine.load_char_rom(&read(chargen)?).map_err(|e| fail(chargen, e))?;
+    machine.reset();
+    Ok(machine)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(options) = parse_args(&args) else {
+        return usage();
+    };
+    let mut machine = match boot(&options) {
+        Ok(machine) => machine,
+        Err(code) => return code,
+    };
+    let hz = if options.ntsc { c64::NTSC_CPU_HZ } else { c64::PAL_CPU_HZ };
+    let mut keyboard = StreamSerial::new(io::stdin(), io::sink());
+
+    let mut throttle = Throttle::new(hz);
+    let mut shown: Option<Vec<String>> = None;
+    loop {
+        while let Some(byte) = keyboard.receive() {
+            machine.type_text(&(byte as char).to_string());
+        }
+        let cycles = machine.execute(CYCLES_PER_SLICE);
+        if let Some(code) = frontend::halted(&machine.cpu, &machine.bus) {
+            return code;
+        }
+
+        let lines = machine.screen_text();
+
+        if let Some(limit) = options.cycles {
+            throttle.add(cycles);
+            if throttle.cycles() >= limit {
+                return match draw_framed(&lines, c64::COLUMNS, false) {
+                    Ok(()) => ExitCode::SUCCESS,
+                    Err(e) => fail("stdout", e),
+                };
+            }
+            continue;
+        }
+
+        if shown.as_ref() != Some(&lines) {
+            if let Err(e) = draw_framed(&lines, c64::COLUMNS, shown.is_some()) {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
+            shown = Some(lines);
+        }
+
+        throttle.wait(cycles);
+    }
+}
@@ -0,0 +1,170 @@
+use davepoo_6502::devices::serial::{Disconnected, SerialHost, StreamSerial};
+use davepoo_6502::frontend::{self, parse_word, Throttle};
+use davepoo_6502::loader;
+use davepoo_6502::m6502::*;
+use davepoo_6502::machines::kim1::{self, Console, Key, Kim1};
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: kim1 --rom <6530-002> [--rom003 <6530-003>] [--ram <KiB>] [--keypad]
+            [--load <image> <address>] [--go <address>] [--cycles <count>]
+
+Runs a KIM-1 with its monitor ROM, talking to this terminal over the TTY,
+or with --keypad showing the LED display and taking keypad presses:
+0-9 a-f hex, m AD, v DA, + or space +, g GO, p PC, s ST and r RS.
+--load puts a program in memory and --go starts it instead of the monitor.
+With --cycles the KIM-1 runs that many cycles as fast as it can and exits,
+for scripts piping a session through stdin and stdout.";
+
+const CYCLES_PER_SLICE: s32 = 10_000;
+
+fn usage() -> ExitCode {
+    eprintln!("{}", USAGE);
+    ExitCode::from(2)
+}
+
+/** Draw the LEDs, over the previous drawing if there was one */
+fn draw(display: &[Byte; 6], redraw: bool) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    if redraw {
+        write!(out, "\x1b[3A")?;
+    }
+    for line in kim1::display_art(display) {
+        writeln!(out, "{}", line)?;
+    }
+    out.flush()
+}
+
+/** Run, reading keypad presses from `keyboard` and showing the LEDs if there is one */
+fn run<H: SerialHost>(mut machine: Kim1<H>, cycles: Option<u64>, keyboard: Option<StreamSerial>) -> ExitCode {
+    let mut throttle = Throttle::new(kim1::CPU_HZ);
+    let mut shown = None;
+    let mut keyboard = keyboard;
+    loop {
+        if let Some(keyboard) = &mut keyboard {
+            while let Some(byte) = keyboard.receive() {
+                if let Some(key) = Key::from_char(byte as char) {
+                    machine.press(key);
+                }
+            }
+            let display = machine.display();
+            if shown != Some(display) {
+                if let Err(e) = draw(&display, shown.is_some()) {
+                    eprintln!("{}", e);
+                    return ExitCode::FAILURE;
+                }
+                shown = Some(display);
+            }
+        }
+
+        let used = machine.execute(CYCLES_PER_SLICE);
+        if let Some(code) = frontend::halted(&machine.cpu, &machine.bus) {
+            return code;
+        }
+        match cycles {
+            Some(limit) => {
+                throttle.add(used);
+                if throttle.cycles() >= limit {
+                    return ExitCode::SUCCESS;
+                }
+            }
+            None => throttle.wait(used),
+        }
+    }
+}
+
+/** What the command line asked for */
+struct Options {
+    rom_002: String,
+    rom_003: Option<String>,
+    ram_kib: usize,
+    console: Console,
+    program: Option<(String, Word)>,
+    go: Option<Word>,
+    cycles: Option<u64>,
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut rom_002 = None;
+    let mut options = Options {
+        rom_002: String::new(),
+        rom_003: None,
+        ram_kib: 1,
+        console: Console::Tty,
+        program: None,
+        go: None,
+        cycles: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--keypad" => options.console = Console::Keypad,
+            "--rom" => rom_002 = Some(args.next()?.clone()),
+            "--rom003" => options.rom_003 = Some(args.next()?.clone()),
+            "--ram" => options.ram_kib = args.next()?.parse().ok()?,
+            "--load" => {
+                let path = args.next()?.clone();
+                options.program = Some((path, parse_word(args.next()?)?));
+            }
+            "--go" => options.go = Some(parse_word(args.next()?)?),
+            "--cycles" => options.cycles = Some(args.next()?.parse().ok()?),
+            _ => return None,
+        }
+    }
+    options.rom_002 = rom_002?;
+    Some(options)
+}
+
+fn fail(path: &str, e: impl Display) -> ExitCode {
+    eprintln!("{}: {}", path, e);
+    ExitCode::from(2)
+}
+
+/** Build the KIM-1, load its ROMs and the program, and point the CPU where it should start */
+fn boot<H: SerialHost>(options: &Options, terminal: H) -> Result<Kim1<H>, ExitCode> {
+    let mut machine = Kim1::new(options.ram_kib * 1024, options.console, terminal);
+    let path = &options.rom_002;
+    let rom = fs::read(path).map_err(|e| fail(path, e))?;
+    machine.load_rom_002(&rom).map_err(|e| fail(path, e))?;
+    if let Some(path) = &options.rom_003 {
+        let rom = fs::read(path).map_err(|e| fail(path, e))?;
+        machine.load_rom_003(&rom).map_err(|e| fail(path, e))?;
+    }
+    machine.reset();
+
+    let mut go = options.go;
+    if let Some((path, address)) = &options.program {
+        //loaded through a scratch 64 KiB, then written over the KIM-1's own map
+        let mut scratch = Mem::new();
+        let image = loader::load_file(&mut scratch, path, *address).map_err(|e| fail(path, e))?;
+        for (address, data) in &image.segments {
+            for (offset, byte) in data.iter().enumerate() {
+                machine.bus.write(address.wrapping_add(offset as Word), *byte);
+            }
+        }
+        go = go.or(image.start);
+    }
+    if let Some(address) = go {
+        machine.cpu.set_pc(address);
+    }
+
+    Ok(machine)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(options) = parse_args(&args) else {
+        return usage();
+    };
+
+    //stdin goes to the TTY or to the keypad, not both
+    let booted = match options.console {
+        Console::Tty => boot(&options, StreamSerial::stdio()).map(|machine| run(machine, options.cycles, None)),
+        Console::Keypad => {
+            boot(&options, Disconnected).map(|machine| run(machine, options.cycles, Some(StreamSerial::new(io::stdin(), io::sink()))))
+        }
+    };
+    booted.unwrap_or_else(|code| code)
+}
@@ -0,0 +1,89 @@
+use davepoo_6502::frontend::parse_word;
+use davepoo_6502::loader;
+use davepoo_6502::m6502::*;
+use davepoo_6502::trace_diff::*;
+use std::fs;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: trace_diff <reference.log> <image> <load-address> [--context <lines>]
+
+Runs the image alongside a nestest-style reference trace and stops at the
+first instruction where PC, registers, flags or the cycle count differ.
+The CPU starts from the state on the first line of the reference.
+Intel HEX (.hex), S-record (.s19, .srec) and PRG (.prg) images carry their
+own addresses, anything else is a raw binary loaded at <load-address>.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut positional = Vec::new();
+    let mut context = 5;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--context" {
+            match args.get(i + 1).and_then(|lines| lines.parse().ok()) {
+                Some(lines) => context = lines,
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            }
+            i += 2;
+        } else {
+            positional.push(args[i].as_str());
+            i += 1;
+        }
+    }
+
+    let [reference_path, image_path, load_address] = positional[..] else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let Some(load_address) = parse_word(load_address) else {
+        eprintln!("invalid load address: {}", load_address);
+        return ExitCode::from(2);
+    };
+
+    let reference = match fs::read_to_string(reference_path) {
+        Ok(reference) => reference,
+        Err(e) => {
+            eprintln!("{}: {}", reference_path, e);
+            return ExitCode::from(2);
+        }
+    };
+
+    //the first line tells us where and how to start
+    let Some(start) = reference.lines().next().and_then(TraceRecord::parse) else {
+        eprintln!("{}: cannot parse the first line", reference_path);
+        return ExitCode::from(2);
+    };
+
+    let mut mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(start.pc, &mut mem);
+    start.apply_to(&mut cpu);
+    if let Err(e) = loader::load_file(&mut mem, image_path, load_address) {
+        eprintln!("{}: {}", image_path, e);
+        return ExitCode::from(2);
+    }
+
+    match diff_against_reference(
+        reference.as_bytes(),
+        &mut cpu,
+        &mut mem,
+        start.cycles.unwrap_or(0),
+        context,
+    ) {
+        Ok(None) => {
+            println!("no divergence from {}", reference_path);
+            ExitCode::SUCCESS
+        }
+        Ok(Some(divergence)) => {
+            print!("{}", divergence);
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("{}: {}", reference_path, e);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! Code coverage: which bytes ran as opcodes or operands, were read or were written
+//!
+//! Coverage maps back to source lines through a LineMap, and out to an LCOV report
+//! that genhtml and most editors understand.
+
+use crate::disassembler::{decode, AddressingMode, OPCODES};
+use crate::m6502::*;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/** flags kept for every address */
+pub const EXECUTED_OPCODE: Byte = 1 << 0;
+pub const EXECUTED_OPERAND: Byte = 1 << 1;
+pub const READ: Byte = 1 << 2;
+pub const WRITTEN: Byte = 1 << 3;
+
+/** How often a conditional branch went each way */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BranchStats {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/** One source line and the bytes it assembled to */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineSpan {
+    pub file: String,
+    /** 1-based */
+    pub line: u32,
+    pub start: Word,
+    pub size: Word,
+    /** false for lines holding data (.byte, .word...), which aren't expected to execute */
+    pub code: bool,
+}
+
+/** Where each source line ended up in memory */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineMap {
+    pub spans: Vec<LineSpan>,
+}
+
+impl LineMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_code(&mut self, file: &str, line: u32, start: Word, size: Word) {
+        self.spans.push(LineSpan {
+            file: file.to_string(),
+            line,
+            start,
+            size,
+            code: true,
+        });
+    }
+
+    pub fn add_data(&mut self, file: &str, line: u32, start: Word, size: Word) {
+        self.spans.push(LineSpan {
+            file: file.to_string(),
+            line,
+            start,
+            size,
+            code: false,
+        });
+    }
+
+    /** @return the line `address` was assembled from */
+    pub fn line_of(&self, address: Word) -> Option<&LineSpan> {
+        self.spans
+            .iter()
+            .find(|span| address >= span.start && (address as u32) < span.start as u32 + span.size as u32)
+    }
+}
+
+/** Passes accesses through to the real bus, remembering the addresses the program reads and writes */
+pub(crate) struct Recorder<'a, B: Bus> {
+    bus: &'a mut B,
+    pub(crate) reads: Vec<Word>,
+    pub(crate) writes: Vec<Word>,
+}
+
+impl<'a, B: Bus> Recorder<'a, B> {
+    pub(crate) fn new(bus: &'a mut B) -> Self {
+        Self {
+            bus,
+            reads: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+}
+
+impl<'a, B: Bus> Bus for Recorder<'a, B> {
+    fn read(&mut self, address: Word) -> Byte {
+        self.reads.push(address);
+        self.bus.read(address)
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        self.writes.push(address);
+        self.bus.write(address, value);
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        self.bus.peek(address)
+    }
+
+    /** not remembered, the program didn't ask for the byte */
+    fn dummy_read(&mut self, address: Word) -> Byte {
+        self.bus.dummy_read(address)
+    }
+}
+
+pub struct Coverage {
+    flags: Vec<Byte>,
+    /** times an instruction started at each address */
+    executions: Vec<u64>,
+    branches: BTreeMap<Word, BranchStats>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self {
+            flags: vec![0; 0x10000],
+            executions: vec![0; 0x10000],
+            branches: BTreeMap::new(),
+        }
+    }
+
+    /** @return EXECUTED_OPCODE, EXECUTED_OPERAND, READ and WRITTEN or-ed together */
+    pub fn flags(&self, address: Word) -> Byte {
+        self.flags[address as usize]
+    }
+
+    pub fn executions(&self, address: Word) -> u64 {
+        self.executions[address as usize]
+    }
+
+    pub fn branch(&self, address: Word) -> Option<BranchStats> {
+        self.branches.get(&address).copied()
+    }
+
+    /** @return the branches executed so far that only ever went one way */
+    pub fn one_way_branches(&self) -> Vec<Word> {
+        self.branches
+            .iter()
+            .filter(|(_, stats)| stats.taken == 0 || stats.not_taken == 0)
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    /**
+     * Execute one instruction (or take one interrupt) and record what it touched
+     * @return the number of cycles that were used
+     * */
+    pub fn step<B: Bus>(&mut self, cpu: &mut CPU, memory: &mut B) -> s32 {
+        let pc = cpu.pc();
+        let interrupt = cpu.interrupt_pending();
+        let opcode = OPCODES[memory.peek(pc) as usize];
+
+        let mut recorder = Recorder::new(memory);
+        let cycles = cpu.step(&mut recorder);
+        let Recorder { reads, writes, .. } = recorder;
+
+        let instruction = pc..pc.saturating_add(opcode.size());
+        if !interrupt {
+            self.executions[pc as usize] += 1;
+            self.flags[pc as usize] |= EXECUTED_OPCODE;
+            for offset in 1..opcode.size() {
+                self.flags[pc.wrapping_add(offset) as usize] |= EXECUTED_OPERAND;
+            }
+            if opcode.mode == AddressingMode::Relative {
+                let target = decode(memory, pc).branch_target();
+                let stats = self.branches.entry(pc).or_default();
+                if cpu.pc() == target && target != pc.wrapping_add(opcode.size()) {
+                    stats.taken += 1;
+                } else {
+                    stats.not_taken += 1;
+                }
+            }
+        }
+        for address in reads {
+            if interrupt || !instruction.contains(&address) {
+                self.flags[address as usize] |= READ;
+            }
+        }
+        for address in writes {
+            self.flags[address as usize] |= WRITTEN;
+        }
+
+        cycles
+    }
+
+    /**
+     * Same as CPU::execute, recording coverage along the way
+     * @return the number of cycles that were used
+     * */
+    pub fn execute<B: Bus>(&mut self, cycles: s32, cpu: &mut CPU, memory: &mut B) -> s32 {
+        let mut cycles_used = 0;
+        while cycles_used < cycles {
+            let step_cycles = self.step(cpu, memory);
+            if step_cycles == 0 {
+                break;
+            }
+            cycles_used += step_cycles;
+        }
+
+        cycles_used
+    }
+
+    /** @return the times the instructions of a code line ran, the most of any of them */
+    fn line_hits(&self, span: &LineSpan) -> u64 {
+        (0..span.size)
+            .map(|offset| self.executions(span.start.wrapping_add(offset)))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /**
+     * Write an LCOV tracefile covering every line in `lines`
+     * - `memory` holds the program, to find the branches that never ran
+     * - `symbols` (name -> address) become functions, hit when their first byte ran
+     * */
+    pub fn write_lcov<W: Write, B: Bus>(
+        &self,
+        mut out: W,
+        memory: &B,
+        lines: &LineMap,
+        symbols: &BTreeMap<String, Word>,
+    ) -> io::Result<()> {
+        let mut files: BTreeMap<&str, Vec<&LineSpan>> = BTreeMap::new();
+        for span in &lines.spans {
+            files.entry(span.file.as_str()).or_default().push(span);
+        }
+
+        for (file, mut spans) in files {
+            spans.sort_by_key(|span| (span.line, span.start));
+            writeln!(out, "TN:")?;
+            writeln!(out, "SF:{}", file)?;
+
+            let mut functions: Vec<(&String, u32, Word)> = symbols
+                .iter()
+                .filter_map(|(name, address)| {
+                    let span = lines.line_of(*address)?;
+                    (span.file == file && span.code).then_some((name, span.line, *address))
+                })
+                .collect();
+            functions.sort_by_key(|(name, line, _)| (*line, name.as_str()));
+            for (name, line, _) in &functions {
+                writeln!(out, "FN:{},{}", line, name)?;
+            }
+            for (name, _, address) in &functions {
+                writeln!(out, "FNDA:{},{}", self.executions(*address), name)?;
+            }
+            writeln!(out, "FNF:{}", functions.len())?;
+            writeln!(
+                out,
+                "FNH:{}",
+                functions.iter().filter(|(_, _, address)| self.executions(*address) > 0).count()
+            )?;
+
+            let (mut branches_found, mut branches_hit) = (0, 0);
+            for span in spans.iter().filter(|span| span.code) {
+                let hits = self.line_hits(span);
+                let mut address = span.start;
+                let mut block = 0;
+                while (address as u32) < span.start as u32 + span.size as u32 {
+                    let instruction = decode(memory, address);
+                    if instruction.opcode.mode == AddressingMode::Relative {
+                        let stats = self.branch(address).unwrap_or_default();
+                        for (branch, count) in [stats.taken, stats.not_taken].iter().enumerate() {
+                            if hits == 0 {
+                                writeln!(out, "BRDA:{},{},{},-", span.line, block, branch)?;
+                            } else {
+                                writeln!(out, "BRDA:{},{},{},{}", span.line, block, branch, count)?;
+                            }
+                            branches_found += 1;
+                            if *count > 0 {
+                                branches_hit += 1;
+                            }
+                        }
+                        block += 1;
+                    }
+                    address = address.wrapping_add(instruction.opcode.size());
+                    if address == 0 {
+                        break;
+                    }
+                }
+            }
+            writeln!(out, "BRF:{}", branches_found)?;
+            writeln!(out, "BRH:{}", branches_hit)?;
+
+            let code: Vec<&&LineSpan> = spans.iter().filter(|span| span.code).collect();
+            for span in &code {
+                writeln!(out, "DA:{},{}", span.line, self.line_hits(span))?;
+            }
+            writeln!(out, "LF:{}", code.len())?;
+            writeln!(out, "LH:{}", code.iter().filter(|span| self.line_hits(span) > 0).count())?;
+            writeln!(out, "end_of_record")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn save_lcov<P: AsRef<Path>, B: Bus>(
+        &self,
+        path: P,
+        memory: &B,
+        lines: &LineMap,
+        symbols: &BTreeMap<String, Word>,
+    ) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_lcov(&mut out, memory, lines, symbols)?;
+        out.flush()
+    }
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
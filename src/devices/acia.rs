@@ -0,0 +1,247 @@
+//! MOS 6551 Asynchronous Communications Interface Adapter
+//!
+//! A serial port with four registers: data, status, command and control. Bytes take
+//! as long as the programmed baud rate says to go through, in CPU cycles.
+//! The host side waits for the program to read each byte before offering the next,
+//! so pasting into a monitor never overruns it.
+
+use super::serial::SerialHost;
+use super::Device;
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+//registers, by offset from the base address
+pub const DATA: Byte = 0x0;
+/** reads the status, writing it does a programmed reset */
+pub const STATUS: Byte = 0x1;
+pub const COMMAND: Byte = 0x2;
+pub const CONTROL: Byte = 0x3;
+
+//status bits
+pub const STATUS_PARITY_ERROR: Byte = 0x01;
+pub const STATUS_FRAMING_ERROR: Byte = 0x02;
+pub const STATUS_OVERRUN: Byte = 0x04;
+/** receiver data register full */
+pub const STATUS_RDRF: Byte = 0x08;
+/** transmitter data register empty */
+pub const STATUS_TDRE: Byte = 0x10;
+pub const STATUS_DCD: Byte = 0x20;
+pub const STATUS_DSR: Byte = 0x40;
+pub const STATUS_IRQ: Byte = 0x80;
+
+/** baud rates selected by the low 4 bits of the control register, 0 being the external clock */
+const BAUD_RATES: [u32; 16] = [
+    115_200, 50, 75, 110, 135, 150, 300, 600, 1200, 1800, 2400, 3600, 4800, 7200, 9600, 19_200,
+];
+
+pub struct Acia<H: SerialHost> {
+    pub host: H,
+    /** the CPU clock, to turn baud rates into cycles */
+    cpu_hz: u32,
+    command: Byte,
+    control: Byte,
+    rx_data: Byte,
+    rx_full: bool,
+    overrun: bool,
+    tx_data: Byte,
+    tx_empty: bool,
+    /** latched until the status is read */
+    irq: bool,
+    /** cycles until the byte being sent is out */
+    tx_timer: u32,
+    /** cycles until the receiver can take the next byte */
+    rx_timer: u32,
+}
+
+impl<H: SerialHost> Acia<H> {
+    /** An ACIA on a 1 MHz system */
+    pub fn new(host: H) -> Self {
+        Self::with_clock(host, 1_000_000)
+    }
+
+    pub fn with_clock(host: H, cpu_hz: u32) -> Self {
+        Self {
+            host,
+            cpu_hz,
+            command: 0x02,
+            control: 0,
+            rx_data: 0,
+            rx_full: false,
+            overrun: false,
+            tx_data: 0,
+            tx_empty: true,
+            irq: false,
+            tx_timer: 0,
+            rx_timer: 0,
+        }
+    }
+
+    /** What the RES pin does */
+    pub fn reset(&mut self) {
+        self.command = 0x02;
+        self.control = 0;
+        self.rx_full = false;
+        self.overrun = false;
+        self.tx_empty = true;
+        self.irq = false;
+        self.tx_timer = 0;
+        self.rx_timer = 0;
+    }
+
+    /** DTR: the receiver only works while the program says it's ready */
+    fn ready(&self) -> bool {
+        self.command & 0x01 != 0
+    }
+
+    fn rx_irq_enabled(&self) -> bool {
+        self.command & 0x02 == 0
+    }
+
+    fn tx_irq_enabled(&self) -> bool {
+        (self.command >> 2) & 0b11 == 0b01
+    }
+
+    fn echo(&self) -> bool {
+        self.command & 0x10 != 0 && (self.command >> 2) & 0b11 == 0
+    }
+
+    /** @return how many cycles one frame takes: start bit, data bits, parity and stop bits */
+    pub fn cycles_per_byte(&self) -> u32 {
+        let data_bits = 8 - ((self.control >> 5) & 0b11) as u32;
+        let parity_bits = ((self.command >> 5) & 1) as u32;
+        let stop_bits = if self.control & 0x80 != 0 { 2 } else { 1 };
+        let baud = BAUD_RATES[(self.control & 0x0F) as usize];
+
+        (self.cpu_hz as u64 * (1 + data_bits + parity_bits + stop_bits) as u64 / baud as u64).max(1) as u32
+    }
+
+    pub fn irq(&self) -> bool {
+        self.irq
+    }
+
+    /** Read a register without side effects - what a debugger sees */
+    pub fn peek(&self, register: Byte) -> Byte {
+        match register & 0x03 {
+            DATA => self.rx_data,
+            STATUS => {
+                (self.overrun as Byte) << 2
+                    | (self.rx_full as Byte) << 3
+                    | (self.tx_empty as Byte) << 4
+                    | (self.irq as Byte) << 7
+            }
+            COMMAND => self.command,
+            _ => self.control,
+        }
+    }
+
+    /** Read a register as the CPU does - reading the status acknowledges the interrupt */
+    pub fn read(&mut self, register: Byte) -> Byte {
+        let value = self.peek(register);
+        match register & 0x03 {
+            DATA => {
+                self.rx_full = false;
+                self.overrun = false;
+            }
+            STATUS => self.irq = false,
+            _ => {}
+        }
+        value
+    }
+
+    pub fn write(&mut self, register: Byte, value: Byte) {
+        match register & 0x03 {
+            DATA => {
+                self.tx_data = value;
+                self.tx_empty = false;
+                self.tx_timer = self.cycles_per_byte();
+            }
+            STATUS => {
+                self.command &= 0xE0;
+                self.overrun = false;
+            }
+            COMMAND => self.command = value,
+            _ => self.control = value,
+        }
+    }
+
+    /** Advance the transmitter and the receiver by `cycles` CPU cycles */
+    pub fn tick(&mut self, cycles: u32) {
+        if !self.tx_empty {
+            self.tx_timer = self.tx_timer.saturating_sub(cycles);
+            if self.tx_timer == 0 {
+                self.host.transmit(self.tx_data);
+                self.tx_empty = true;
+                if self.tx_irq_enabled() {
+                    self.irq = true;
+                }
+            }
+        }
+
+        self.rx_timer = self.rx_timer.saturating_sub(cycles);
+        if self.rx_timer == 0 && self.ready() && !self.rx_full {
+            if let Some(byte) = self.host.receive() {
+                self.rx_data = byte;
+                self.rx_full = true;
+                self.rx_timer = self.cycles_per_byte();
+                if self.echo() {
+                    self.host.transmit(byte);
+                }
+                if self.rx_irq_enabled() {
+                    self.irq = true;
+                }
+            }
+        }
+    }
+}
+
+/** 4 registers, mirrored across whatever the ACIA is mapped over */
+impl<H: SerialHost + 'static> Device for Acia<H> {
+    fn read(&mut self, offset: Word) -> Byte {
+        Acia::read(self, (offset & 0x03) as Byte)
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        Acia::write(self, (offset & 0x03) as Byte, value)
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        Acia::peek(self, (offset & 0x03) as Byte)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        Acia::tick(self, cycles)
+    }
+
+    fn irq(&self) -> bool {
+        Acia::irq(self)
+    }
+
+    /** The host end of the line is outside the snapshot, only the chip is saved */
+    fn save_state(&self) -> Vec<Byte> {
+        StateWriter::new()
+            .bytes(&[self.command, self.control, self.rx_data])
+            .flag(self.rx_full)
+            .flag(self.overrun)
+            .byte(self.tx_data)
+            .flag(self.tx_empty)
+            .flag(self.irq)
+            .long(self.tx_timer)
+            .long(self.rx_timer)
+            .into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        self.command = state.byte()?;
+        self.control = state.byte()?;
+        self.rx_data = state.byte()?;
+        self.rx_full = state.flag()?;
+        self.overrun = state.flag()?;
+        self.tx_data = state.byte()?;
+        self.tx_empty = state.flag()?;
+        self.irq = state.flag()?;
+        self.tx_timer = state.long()?;
+        self.rx_timer = state.long()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+//! MOS 6526 Complex Interface Adapter
+//!
+//! Two 8-bit ports with data direction registers, two 16-bit interval timers that can be
+//! chained, and an interrupt control register, all behind 16 registers.
+//! The time-of-day clock and the serial port only keep what is written to them:
+//! nothing drives TOD or CNT in the machines built here.
+
+use super::Device;
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+//registers, by offset from the base address
+pub const PRA: Byte = 0x0;
+pub const PRB: Byte = 0x1;
+pub const DDRA: Byte = 0x2;
+pub const DDRB: Byte = 0x3;
+pub const TA_LO: Byte = 0x4;
+pub const TA_HI: Byte = 0x5;
+pub const TB_LO: Byte = 0x6;
+pub const TB_HI: Byte = 0x7;
+pub const TOD_10THS: Byte = 0x8;
+pub const TOD_SEC: Byte = 0x9;
+pub const TOD_MIN: Byte = 0xA;
+pub const TOD_HR: Byte = 0xB;
+pub const SDR: Byte = 0xC;
+/** reading gives the interrupt flags and clears them, writing sets or clears mask bits */
+pub const ICR: Byte = 0xD;
+pub const CRA: Byte = 0xE;
+pub const CRB: Byte = 0xF;
+
+//interrupt flags, as found in ICR
+pub const IRQ_TA: Byte = 0x01;
+pub const IRQ_TB: Byte = 0x02;
+pub const IRQ_ALARM: Byte = 0x04;
+pub const IRQ_SP: Byte = 0x08;
+pub const IRQ_FLAG: Byte = 0x10;
+/** read: an enabled interrupt is flagged - write: set the mask bits written as 1, rather than clear them */
+pub const IRQ_SET: Byte = 0x80;
+
+//control register bits, the same in CRA and CRB
+pub const CR_START: Byte = 0x01;
+/** stop after the next underflow */
+pub const CR_ONE_SHOT: Byte = 0x08;
+/** copy the latch into the counter, never read back */
+pub const CR_LOAD: Byte = 0x10;
+/** CRB bits 5-6: what timer B counts */
+pub const CRB_COUNT_MASK: Byte = 0x60;
+pub const CRB_COUNT_TA: Byte = 0x40;
+
+#[derive(Debug, Clone)]
+struct Timer {
+    counter: u16,
+    latch: u16,
+    control: Byte,
+}
+
+impl Timer {
+    fn new() -> Self {
+        Self {
+            counter: 0xFFFF,
+            latch: 0xFFFF,
+            control: 0,
+        }
+    }
+
+    fn running(&self) -> bool {
+        self.control & CR_START != 0
+    }
+
+    fn write_latch(&mut self, high: bool, value: Byte) {
+        let [lo, hi] = self.latch.to_le_bytes();
+        self.latch = if high { Word::from_le_bytes([lo, value]) } else { Word::from_le_bytes([value, hi]) };
+        //a stopped timer is loaded when its high byte is written
+        if high && !self.running() {
+            self.counter = self.latch;
+        }
+    }
+
+    fn write_control(&mut self, value: Byte) {
+        if value & CR_LOAD != 0 {
+            self.counter = self.latch;
+        }
+        self.control = value & !CR_LOAD;
+    }
+
+    /** Count one pulse @return true on underflow */
+    fn count(&mut self) -> bool {
+        if self.counter > 0 {
+            self.counter -= 1;
+            return false;
+        }
+        self.counter = self.latch;
+        if self.control & CR_ONE_SHOT != 0 {
+            self.control &= !CR_START;
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cia {
+    pra: Byte,
+    prb: Byte,
+    ddra: Byte,
+    ddrb: Byte,
+    port_a_input: Byte,
+    port_b_input: Byte,
+
+    timer_a: Timer,
+    timer_b: Timer,
+    tod: [Byte; 4],
+    sdr: Byte,
+
+    flags: Byte,
+    mask: Byte,
+}
+
+impl Cia {
+    pub fn new() -> Self {
+        Self {
+            pra: 0,
+            prb: 0,
+            ddra: 0,
+            ddrb: 0,
+            port_a_input: 0xFF,
+            port_b_input: 0xFF,
+            timer_a: Timer::new(),
+            timer_b: Timer::new(),
+            tod: [0, 0, 0, 0x01],
+            sdr: 0,
+            flags: 0,
+            mask: 0,
+        }
+    }
+
+    /** What the RES pin does: ports become inputs, timers stop and interrupts are disabled */
+    pub fn reset(&mut self) {
+        let (port_a_input, port_b_input) = (self.port_a_input, self.port_b_input);
+        *self = Self::new();
+        self.port_a_input = port_a_input;
+        self.port_b_input = port_b_input;
+    }
+
+    pub fn irq(&self) -> bool {
+        self.flags & self.mask != 0
+    }
+
+    /** @return the levels on the port A pins */
+    pub fn port_a(&self) -> Byte {
+        (self.pra & self.ddra) | (self.port_a_input & !self.ddra)
+    }
+
+    /** @return the levels on the port B pins */
+    pub fn port_b(&self) -> Byte {
+        (self.prb & self.ddrb) | (self.port_b_input & !self.ddrb)
+    }
+
+    /** Drive the port A pins from outside - only the bits set as inputs are seen */
+    pub fn set_port_a_input(&mut self, value: Byte) {
+        self.port_a_input = value;
+    }
+
+    pub fn set_port_b_input(&mut self, value: Byte) {
+        self.port_b_input = value;
+    }
+
+    /** A falling edge on the FLAG pin, e.g. from the cassette read line */
+    pub fn flag(&mut self) {
+        self.flags |= IRQ_FLAG;
+    }
+
+    /** Read a register without side effects - what a debugger sees */
+    pub fn peek(&self, register: Byte) -> Byte {
+        match register & 0x0F {
+            PRA => self.port_a(),
+            PRB => self.port_b(),
+            DDRA => self.ddra,
+            DDRB => self.ddrb,
+            TA_LO => self.timer_a.counter.to_le_bytes()[0],
+            TA_HI => self.timer_a.counter.to_le_bytes()[1],
+            TB_LO => self.timer_b.counter.to_le_bytes()[0],
+            TB_HI => self.timer_b.counter.to_le_bytes()[1],
+            TOD_10THS..=TOD_HR => self.tod[(register - TOD_10THS) as usize],
+            SDR => self.sdr,
+            ICR => self.flags | if self.irq() { IRQ_SET } else { 0 },
+            CRA => self.timer_a.control,
+            _ => self.timer_b.control,
+        }
+    }
+
+    /** Read a register as the CPU does */
+    pub fn read(&mut self, register: Byte) -> Byte {
+        let value = self.peek(register);
+        if register & 0x0F == ICR {
+            self.flags = 0;
+        }
+        value
+    }
+
+    pub fn write(&mut self, register: Byte, value: Byte) {
+        match register & 0x0F {
+            PRA => self.pra = value,
+            PRB => self.prb = value,
+            DDRA => self.ddra = value,
+            DDRB => self.ddrb = value,
+            TA_LO => self.timer_a.write_latch(false, value),
+            TA_HI => self.timer_a.write_latch(true, value),
+            TB_LO => self.timer_b.write_latch(false, value),
+            TB_HI => self.timer_b.write_latch(true, value),
+            TOD_10THS..=TOD_HR => self.tod[(register - TOD_10THS) as usize] = value,
+            SDR => self.sdr = value,
+            ICR => {
+                if value & IRQ_SET != 0 {
+                    self.mask |= value & !IRQ_SET;
+                } else {
+                    self.mask &= !value;
+                }
+            }
+            CRA => self.timer_a.write_control(value),
+            _ => self.timer_b.write_control(value),
+        }
+    }
+
+    /**
+     * Advance the timers by `cycles` φ2 cycles
+     * - timer B counts φ2 or timer A's underflows, a CNT count mode stands still
+     * */
+    pub fn tick(&mut self, cycles: u32) {
+        let b_source = self.timer_b.control & CRB_COUNT_MASK;
+        for _ in 0..cycles {
+            let mut a_underflow = false;
+            if self.timer_a.running() {
+                a_underflow = self.timer_a.count();
+                if a_underflow {
+                    self.flags |= IRQ_TA;
+                }
+            }
+
+            let b_counts = match b_source {
+                0 => true,
+                CRB_COUNT_TA => a_underflow,
+                _ => false,
+            };
+            if self.timer_b.running() && b_counts && self.timer_b.count() {
+                self.flags |= IRQ_TB;
+            }
+        }
+    }
+}
+
+impl Default for Cia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for Cia {
+    fn read(&mut self, offset: Word) -> Byte {
+        Cia::read(self, (offset & 0x0F) as Byte)
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        Cia::write(self, (offset & 0x0F) as Byte, value)
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        Cia::peek(self, (offset & 0x0F) as Byte)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        Cia::tick(self, cycles)
+    }
+
+    fn irq(&self) -> bool {
+        Cia::irq(self)
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = StateWriter::new()
+            .bytes(&[self.pra, self.prb, self.ddra, self.ddrb, self.port_a_input, self.port_b_input]);
+        for timer in [&self.timer_a, &self.timer_b] {
+            state = state.word(timer.counter).word(timer.latch).byte(timer.control);
+        }
+        state
+            .bytes(&self.tod)
+            .bytes(&[self.sdr, self.flags, self.mask])
+            .into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        self.pra = state.byte()?;
+        self.prb = state.byte()?;
+        self.ddra = state.byte()?;
+        self.ddrb = state.byte()?;
+        self.port_a_input = state.byte()?;
+        self.port_b_input = state.byte()?;
+        for timer in [&mut self.timer_a, &mut self.timer_b] {
+            timer.counter = state.word()?;
+            timer.latch = state.word()?;
+            timer.control = state.byte()?;
+        }
+        state.fill(&mut self.tod)?;
+        self.sdr = state.byte()?;
+        self.flags = state.byte()?;
+        self.mask = state.byte()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,386 @@
+//! Hitachi HD44780 character LCD controller
+//!
+//! Driven through its pins rather than the bus: E, RS, R/W and D0-D7, usually from a VIA port.
+//! Transfers happen on the falling edge of E, in 8-bit mode or as two nibbles on D4-D7 in 4-bit mode.
+//! Commands take effect at once; the busy flag only tells the program how long a real one would take.
+
+use crate::m6502::*;
+
+pub const CLEAR_DISPLAY: Byte = 0x01;
+pub const RETURN_HOME: Byte = 0x02;
+/** or-ed with ENTRY_INCREMENT and ENTRY_SHIFT */
+pub const ENTRY_MODE_SET: Byte = 0x04;
+pub const ENTRY_INCREMENT: Byte = 0x02;
+pub const ENTRY_SHIFT: Byte = 0x01;
+/** or-ed with DISPLAY_ON, CURSOR_ON and BLINK_ON */
+pub const DISPLAY_CONTROL: Byte = 0x08;
+pub const DISPLAY_ON: Byte = 0x04;
+pub const CURSOR_ON: Byte = 0x02;
+pub const BLINK_ON: Byte = 0x01;
+/** or-ed with SHIFT_DISPLAY and SHIFT_RIGHT, without SHIFT_DISPLAY the cursor moves */
+pub const CURSOR_SHIFT: Byte = 0x10;
+pub const SHIFT_DISPLAY: Byte = 0x08;
+pub const SHIFT_RIGHT: Byte = 0x04;
+/** or-ed with EIGHT_BIT, TWO_LINES and FONT_5X10 */
+pub const FUNCTION_SET: Byte = 0x20;
+pub const EIGHT_BIT: Byte = 0x10;
+pub const TWO_LINES: Byte = 0x08;
+pub const FONT_5X10: Byte = 0x04;
+/** or-ed with a 6-bit CGRAM address */
+pub const SET_CGRAM_ADDRESS: Byte = 0x40;
+/** or-ed with a 7-bit DDRAM address */
+pub const SET_DDRAM_ADDRESS: Byte = 0x80;
+
+pub const BUSY_FLAG: Byte = 0x80;
+
+/** characters in each of the two lines of display data RAM */
+const LINE_LENGTH: usize = 40;
+const DDRAM_SIZE: usize = 2 * LINE_LENGTH;
+/** where the second line starts in display data RAM */
+const SECOND_LINE: Byte = 0x40;
+
+#[derive(Debug, Clone)]
+pub struct Hd44780 {
+    columns: usize,
+    rows: usize,
+    cycles_per_us: f64,
+
+    ddram: [Byte; DDRAM_SIZE],
+    cgram: [Byte; 64],
+    address: Byte,
+    /** the address counter points into CGRAM rather than DDRAM */
+    in_cgram: bool,
+    increment: bool,
+    shift_on_entry: bool,
+    /** how many characters the display is shifted to the left */
+    shift: usize,
+    display_on: bool,
+    cursor_on: bool,
+    blink_on: bool,
+    eight_bit: bool,
+    two_lines: bool,
+
+    e: bool,
+    rs: bool,
+    rw: bool,
+    /** the first half of a 4-bit transfer */
+    high_nibble: Option<Byte>,
+    busy_cycles: u32,
+}
+
+impl Hd44780 {
+    /** A `columns` x `rows` display clocked from a 1 MHz CPU, e.g. 16 x 2 */
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Self::with_clock(columns, rows, 1_000_000)
+    }
+
+    /** Count busy times in cycles of a CPU running at `hz` */
+    pub fn with_clock(columns: usize, rows: usize, hz: u64) -> Self {
+        Self {
+            columns,
+            rows,
+            cycles_per_us: hz as f64 / 1_000_000.0,
+            ddram: [b' '; DDRAM_SIZE],
+            cgram: [0; 64],
+            address: 0,
+            in_cgram: false,
+            increment: true,
+            shift_on_entry: false,
+            shift: 0,
+            display_on: false,
+            cursor_on: false,
+            blink_on: false,
+            eight_bit: true,
+            two_lines: false,
+            e: false,
+            rs: false,
+            rw: false,
+            high_nibble: None,
+            busy_cycles: 0,
+        }
+    }
+
+    pub fn busy(&self) -> bool {
+        self.busy_cycles > 0
+    }
+
+    /** @return the address counter */
+    pub fn address(&self) -> Byte {
+        self.address
+    }
+
+    pub fn display_on(&self) -> bool {
+        self.display_on
+    }
+
+    pub fn cursor_on(&self) -> bool {
+        self.cursor_on
+    }
+
+    pub fn blink_on(&self) -> bool {
+        self.blink_on
+    }
+
+    pub fn eight_bit(&self) -> bool {
+        self.eight_bit
+    }
+
+    /** @return the eight rows of a custom character, 5 pixels each */
+    pub fn glyph(&self, code: Byte) -> [Byte; 8] {
+        let start = (code as usize & 0x07) * 8;
+        self.cgram[start..start + 8].try_into().unwrap()
+    }
+
+    /**
+     * Set the levels on E, RS, R/W and D0-D7 - in 4-bit mode only D4-D7 are looked at
+     * - a falling edge on E completes the transfer
+     * */
+    pub fn set_pins(&mut self, e: bool, rs: bool, rw: bool, data: Byte) {
+        if self.e && !e {
+            if rw {
+                self.end_read(rs);
+            } else {
+                self.end_write(rs, data);
+            }
+        }
+        self.e = e;
+        self.rs = rs;
+        self.rw = rw;
+    }
+
+    /**
+     * @return what the controller drives D0-D7 with while E is high during a read, in 4-bit mode
+     * the current nibble on D4-D7
+     * */
+    pub fn output(&self) -> Option<Byte> {
+        if !self.e || !self.rw {
+            return None;
+        }
+
+        let byte = if self.rs { self.read_ram(self.address) } else { self.status() };
+        Some(match (self.eight_bit, self.high_nibble) {
+            (true, _) => byte,
+            (false, None) => byte & 0xF0,
+            (false, Some(_)) => byte << 4,
+        })
+    }
+
+    fn status(&self) -> Byte {
+        (if self.busy() { BUSY_FLAG } else { 0 }) | (self.address & 0x7F)
+    }
+
+    fn end_read(&mut self, rs: bool) {
+        if !self.eight_bit {
+            //the high nibble has been read, the low one comes next
+            if self.high_nibble.is_none() {
+                self.high_nibble = Some(0);
+                return;
+            }
+            self.high_nibble = None;
+        }
+        if rs {
+            self.advance();
+        }
+    }
+
+    fn end_write(&mut self, rs: bool, data: Byte) {
+        let byte = if self.eight_bit {
+            data
+        } else {
+            match self.high_nibble.take() {
+                None => {
+                    self.high_nibble = Some(data & 0xF0);
+                    return;
+                }
+                Some(high) => high | (data >> 4),
+            }
+        };
+
+        if rs {
+            self.write_data(byte);
+        } else {
+            self.command(byte);
+        }
+    }
+
+    /** Advance by `cycles` CPU cycles */
+    pub fn tick(&mut self, cycles: u32) {
+        self.busy_cycles = self.busy_cycles.saturating_sub(cycles);
+    }
+
+    fn busy_for(&mut self, us: u32) {
+        self.busy_cycles = (us as f64 * self.cycles_per_us).ceil() as u32;
+    }
+
+    fn command(&mut self, command: Byte) {
+        let us = match command.leading_zeros() {
+            0 => {
+                self.in_cgram = false;
+                self.address = self.ddram_address(command & 0x7F);
+                37
+            }
+            1 => {
+                self.in_cgram = true;
+                self.address = command & 0x3F;
+                37
+            }
+            2 => {
+                self.eight_bit = command & EIGHT_BIT != 0;
+                self.two_lines = command & TWO_LINES != 0;
+                self.high_nibble = None;
+                37
+            }
+            3 => {
+                let right = command & SHIFT_RIGHT != 0;
+                if command & SHIFT_DISPLAY != 0 {
+                    self.shift_display(right);
+                } else {
+                    self.move_cursor(right);
+                }
+                37
+            }
+            4 => {
+                self.display_on = command & DISPLAY_ON != 0;
+                self.cursor_on = command & CURSOR_ON != 0;
+                self.blink_on = command & BLINK_ON != 0;
+                37
+            }
+            5 => {
+                self.increment = command & ENTRY_INCREMENT != 0;
+                self.shift_on_entry = command & ENTRY_SHIFT != 0;
+                37
+            }
+            6 => {
+                self.in_cgram = false;
+                self.address = 0;
+                self.shift = 0;
+                1520
+            }
+            7 => {
+                self.ddram.fill(b' ');
+                self.in_cgram = false;
+                self.address = 0;
+                self.shift = 0;
+                self.increment = true;
+                1520
+            }
+            _ => 0,
+        };
+        self.busy_for(us);
+    }
+
+    fn write_data(&mut self, byte: Byte) {
+        if self.in_cgram {
+            self.cgram[self.address as usize & 0x3F] = byte & 0x1F;
+        } else {
+            self.ddram[self.ddram_index(self.address)] = byte;
+            if self.shift_on_entry {
+                self.shift_display(!self.increment);
+            }
+        }
+        self.advance();
+        self.busy_for(41);
+    }
+
+    fn read_ram(&self, address: Byte) -> Byte {
+        if self.in_cgram {
+            self.cgram[address as usize & 0x3F]
+        } else {
+            self.ddram[self.ddram_index(address)]
+        }
+    }
+
+    fn advance(&mut self) {
+        let increment = self.increment;
+        self.move_cursor(increment);
+    }
+
+    fn move_cursor(&mut self, forward: bool) {
+        if self.in_cgram {
+            self.address = if forward { self.address + 1 } else { self.address.wrapping_sub(1) } & 0x3F;
+            return;
+        }
+
+        //on two lines, the end of the first runs into the second and the second wraps to the first
+        let index = self.ddram_index(self.address);
+        let index = if forward { (index + 1) % DDRAM_SIZE } else { (index + DDRAM_SIZE - 1) % DDRAM_SIZE };
+        self.address = self.address_of_index(index);
+    }
+
+    fn shift_display(&mut self, right: bool) {
+        self.shift = if right { (self.shift + LINE_LENGTH - 1) % LINE_LENGTH } else { (self.shift + 1) % LINE_LENGTH };
+    }
+
+    /** Bring a DDRAM address into range: 0x00-0x4F on one line, 0x00-0x27 and 0x40-0x67 on two */
+    fn ddram_address(&self, address: Byte) -> Byte {
+        self.address_of_index(self.ddram_index(address))
+    }
+
+    fn ddram_index(&self, address: Byte) -> usize {
+        if self.two_lines {
+            let line = (address >= SECOND_LINE) as usize;
+            line * LINE_LENGTH + (address & 0x3F) as usize % LINE_LENGTH
+        } else {
+            address as usize % DDRAM_SIZE
+        }
+    }
+
+    fn address_of_index(&self, index: usize) -> Byte {
+        if self.two_lines && index >= LINE_LENGTH {
+            SECOND_LINE + (index - LINE_LENGTH) as Byte
+        } else {
+            index as Byte
+        }
+    }
+
+    /** @return the character codes on each row of the glass, blank while the display is off */
+    pub fn codes(&self) -> Vec<Vec<Byte>> {
+        (0..self.rows)
+            .map(|row| {
+                (0..self.columns)
+                    .map(|column| {
+                        if !self.display_on {
+                            return b' ';
+                        }
+                        //4-line displays continue each DDRAM line on the row after next
+                        let line = row % 2;
+                        let column = column + (row / 2) * self.columns;
+                        let index = if self.two_lines {
+                            line * LINE_LENGTH + (column + self.shift) % LINE_LENGTH
+                        } else {
+                            (row * self.columns + column + self.shift) % DDRAM_SIZE
+                        };
+                        self.ddram[index]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /** @return each row of the glass as text, see character() */
+    pub fn text(&self) -> Vec<String> {
+        self.codes()
+            .iter()
+            .map(|row| row.iter().map(|code| character(*code)).collect())
+            .collect()
+    }
+}
+
+/**
+ * The character the A00 character ROM shows for `code`
+ * @return '▒' for the custom characters and '?' where the katakana and symbols have no close match
+ * */
+pub fn character(code: Byte) -> char {
+    match code {
+        0x00..=0x0F => '▒',
+        b'\\' => '¥',
+        0x7E => '→',
+        0x7F => '←',
+        0x20..=0x7D => code as char,
+        0xDF => '°',
+        0xE4 => 'µ',
+        0xF4 => 'Ω',
+        0xF7 => 'π',
+        _ => '?',
+    }
+}
@@ -0,0 +1,75 @@
+//! Mapper 3, CNROM: NROM's PRG with a switchable 8 KiB CHR ROM bank
+//!
+//! Any write to $8000-$FFFF picks the bank, bus conflicts aside as with UxROM.
+
+use super::{open_bus, Mapper, Memory, PRG_ROM};
+use crate::devices::Device;
+use crate::loader::ines::{Mirroring, Rom};
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+const CHR_BANK_SIZE: usize = 0x2000;
+
+#[derive(Debug, Clone)]
+pub struct Cnrom {
+    pub memory: Memory,
+    mirroring: Mirroring,
+    chr_bank: usize,
+}
+
+impl Cnrom {
+    pub fn new(rom: Rom) -> Self {
+        Self {
+            memory: Memory::new(&rom),
+            mirroring: rom.mirroring,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Device for Cnrom {
+    fn read(&mut self, address: Word) -> Byte {
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        if address >= PRG_ROM {
+            self.chr_bank = value as usize;
+        } else {
+            self.memory.write_prg_ram(address, value);
+        }
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        if address >= PRG_ROM {
+            self.memory.prg(0, 0x8000, address - PRG_ROM)
+        } else {
+            self.memory.prg_ram(address).unwrap_or(open_bus(address))
+        }
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        self.memory.save_state(StateWriter::new()).long(self.chr_bank as u32).into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        self.memory.restore_state(&mut state)?;
+        self.chr_bank = state.long()? as usize;
+        Ok(())
+    }
+}
+
+impl Mapper for Cnrom {
+    fn chr_read(&self, address: Word) -> Byte {
+        self.memory.chr(self.chr_bank, CHR_BANK_SIZE, address)
+    }
+
+    fn chr_write(&mut self, address: Word, value: Byte) {
+        self.memory.write_chr(self.chr_bank, CHR_BANK_SIZE, address, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
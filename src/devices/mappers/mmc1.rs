@@ -0,0 +1,178 @@
+//! Mapper 1, MMC1 (SxROM): PRG and CHR banking loaded a bit at a time through a shift register
+//!
+//! A write to $8000-$FFFF with bit 7 set clears the shift register and fixes the last
+//! PRG bank at $C000. Otherwise bit 0 is shifted in, and the fifth write stores the five
+//! bits in the register picked by address bits 13-14 of that write:
+//! - $8000 control: mirroring (bits 0-1), PRG mode (2-3), 4 KiB CHR banks (4)
+//! - $A000 CHR bank 0, and the upper 256 KiB of PRG on SUROM
+//! - $C000 CHR bank 1
+//! - $E000 PRG bank (0-3), PRG RAM disabled (4)
+//!
+//! Consecutive writes on back-to-back cycles aren't ignored as on the chip.
+
+use super::{open_bus, Mapper, Memory, PRG_ROM};
+use crate::devices::Device;
+use crate::loader::ines::{Mirroring, Rom};
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+pub const SHIFT_RESET: Byte = 0x80;
+/** PRG mode 3: 16 KiB switched at $8000, the last bank fixed at $C000 */
+pub const CONTROL_PRG_FIX_LAST: Byte = 0x0C;
+const CONTROL_PRG_MODE: Byte = 0x0C;
+const CONTROL_CHR_4K: Byte = 0x10;
+const PRG_RAM_DISABLE: Byte = 0x10;
+/** SUROM's 512 KiB are two 256 KiB halves, picked by CHR bank 0's bit 4 */
+const PRG_OUTER_SIZE: usize = 0x40000;
+
+#[derive(Debug, Clone)]
+pub struct Mmc1 {
+    pub memory: Memory,
+    /** the cartridge's wiring, used if the header fixed four screens */
+    mirroring: Mirroring,
+    shift: Byte,
+    writes: u8,
+    control: Byte,
+    chr_0: Byte,
+    chr_1: Byte,
+    prg: Byte,
+}
+
+impl Mmc1 {
+    pub fn new(rom: Rom) -> Self {
+        Self {
+            memory: Memory::new(&rom),
+            mirroring: rom.mirroring,
+            shift: 0,
+            writes: 0,
+            control: CONTROL_PRG_FIX_LAST,
+            chr_0: 0,
+            chr_1: 0,
+            prg: 0,
+        }
+    }
+
+    pub fn control(&self) -> Byte {
+        self.control
+    }
+
+    fn load(&mut self, address: Word, value: Byte) {
+        if value & SHIFT_RESET != 0 {
+            self.shift = 0;
+            self.writes = 0;
+            self.control |= CONTROL_PRG_FIX_LAST;
+            return;
+        }
+
+        self.shift |= (value & 0x01) << self.writes;
+        self.writes += 1;
+        if self.writes < 5 {
+            return;
+        }
+        match address & 0x6000 {
+            0x0000 => self.control = self.shift,
+            0x2000 => self.chr_0 = self.shift,
+            0x4000 => self.chr_1 = self.shift,
+            _ => self.prg = self.shift,
+        }
+        self.shift = 0;
+        self.writes = 0;
+    }
+
+    /** @return the 16 KiB PRG bank at `address` in $8000-$FFFF */
+    fn prg_bank(&self, address: Word) -> usize {
+        let outer = if self.memory.prg_rom.len() > PRG_OUTER_SIZE { (self.chr_0 & 0x10) as usize } else { 0 };
+        let bank = (self.prg & 0x0F) as usize;
+        let last = (self.memory.prg_banks(0x4000) - 1) & 0x0F;
+        let upper = address >= 0xC000;
+        outer
+            | match (self.control & CONTROL_PRG_MODE) >> 2 {
+                0 | 1 => (bank & !1) | upper as usize,
+                2 if upper => bank,
+                2 => 0,
+                _ if upper => last,
+                _ => bank,
+            }
+    }
+
+    /** @return the 4 KiB CHR bank at `address` in $0000-$1FFF */
+    fn chr_bank(&self, address: Word) -> usize {
+        let upper = address >= 0x1000;
+        if self.control & CONTROL_CHR_4K != 0 {
+            (if upper { self.chr_1 } else { self.chr_0 }) as usize
+        } else {
+            (self.chr_0 & !1) as usize | upper as usize
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg & PRG_RAM_DISABLE == 0
+    }
+}
+
+impl Device for Mmc1 {
+    fn read(&mut self, address: Word) -> Byte {
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        if address >= PRG_ROM {
+            self.load(address, value);
+        } else if self.prg_ram_enabled() {
+            self.memory.write_prg_ram(address, value);
+        }
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        if address >= PRG_ROM {
+            self.memory.prg(self.prg_bank(address), 0x4000, address)
+        } else if self.prg_ram_enabled() {
+            self.memory.prg_ram(address).unwrap_or(open_bus(address))
+        } else {
+            open_bus(address)
+        }
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        self.memory
+            .save_state(StateWriter::new())
+            .byte(self.shift)
+            .byte(self.writes)
+            .bytes(&[self.control, self.chr_0, self.chr_1, self.prg])
+            .into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        self.memory.restore_state(&mut state)?;
+        self.shift = state.byte()?;
+        self.writes = state.byte()?;
+        self.control = state.byte()?;
+        self.chr_0 = state.byte()?;
+        self.chr_1 = state.byte()?;
+        self.prg = state.byte()?;
+        Ok(())
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn chr_read(&self, address: Word) -> Byte {
+        self.memory.chr(self.chr_bank(address), 0x1000, address)
+    }
+
+    fn chr_write(&mut self, address: Word, value: Byte) {
+        self.memory.write_chr(self.chr_bank(address), 0x1000, address, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.mirroring == Mirroring::FourScreen {
+            return Mirroring::FourScreen;
+        }
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Mapper 4, MMC3 (TxROM): 8 KiB PRG and 1-2 KiB CHR banks, and a scanline counter IRQ
+//!
+//! Registers are picked by the address range and whether it is even or odd:
+//! - $8000 bank select: which of R0-R7 the next $8001 sets, PRG mode (bit 6), CHR inversion (bit 7)
+//! - $8001 bank data
+//! - $A000 mirroring, $A001 PRG RAM enable (bit 7) and write protect (bit 6)
+//! - $C000 IRQ latch, $C001 IRQ reload
+//! - $E000 IRQ disable and acknowledge, $E001 IRQ enable
+//!
+//! The PPU here draws nothing, so the counter is clocked by whoever calls
+//! `Mapper::scanline`, once for each line drawn with rendering on.
+
+use super::{open_bus, Mapper, Memory, PRG_ROM};
+use crate::devices::Device;
+use crate::loader::ines::{Mirroring, Rom};
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+
+pub const SELECT_PRG_MODE: Byte = 0x40;
+pub const SELECT_CHR_INVERSION: Byte = 0x80;
+pub const PRG_RAM_ENABLE: Byte = 0x80;
+pub const PRG_RAM_PROTECT: Byte = 0x40;
+
+#[derive(Debug, Clone)]
+pub struct Mmc3 {
+    pub memory: Memory,
+    /** the header's, used if it fixed four screens */
+    four_screen: bool,
+    select: Byte,
+    /** R0-R7: two 2 KiB CHR banks, four 1 KiB CHR banks, then two 8 KiB PRG banks */
+    banks: [Byte; 8],
+    vertical: bool,
+    prg_ram: Byte,
+    irq_latch: Byte,
+    irq_counter: Byte,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq: bool,
+}
+
+impl Mmc3 {
+    pub fn new(rom: Rom) -> Self {
+        Self {
+            memory: Memory::new(&rom),
+            four_screen: rom.mirroring == Mirroring::FourScreen,
+            select: 0,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+            vertical: rom.mirroring == Mirroring::Vertical,
+            prg_ram: PRG_RAM_ENABLE,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq: false,
+        }
+    }
+
+    /** @return the 8 KiB PRG bank at `address` in $8000-$FFFF */
+    fn prg_bank(&self, address: Word) -> usize {
+        let banks = self.memory.prg_banks(PRG_BANK_SIZE);
+        let slot = ((address - PRG_ROM) / PRG_BANK_SIZE as Word) as usize;
+        //PRG mode swaps the switchable $8000 and the fixed $C000
+        let slot = if self.select & SELECT_PRG_MODE != 0 && slot != 1 && slot != 3 { 2 - slot } else { slot };
+        match slot {
+            0 => self.banks[6] as usize,
+            1 => self.banks[7] as usize,
+            2 => banks - 2,
+            _ => banks - 1,
+        }
+    }
+
+    /** @return the 1 KiB CHR bank at `address` in $0000-$1FFF */
+    fn chr_bank(&self, address: Word) -> usize {
+        let mut slot = (address as usize / CHR_BANK_SIZE) & 0x07;
+        //inversion swaps the 2 KiB banks at $0000 with the 1 KiB banks at $1000
+        if self.select & SELECT_CHR_INVERSION != 0 {
+            slot ^= 0x04;
+        }
+        match slot {
+            0..=3 => (self.banks[slot / 2] & !1) as usize | (slot & 1),
+            _ => self.banks[slot - 2] as usize,
+        }
+    }
+
+    fn prg_ram_readable(&self) -> bool {
+        self.prg_ram & PRG_RAM_ENABLE != 0
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_readable() && self.prg_ram & PRG_RAM_PROTECT == 0
+    }
+}
+
+impl Device for Mmc3 {
+    fn read(&mut self, address: Word) -> Byte {
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        if address < PRG_ROM {
+            if self.prg_ram_writable() {
+                self.memory.write_prg_ram(address, value);
+            }
+            return;
+        }
+
+        match (address & 0xE000, address & 0x0001) {
+            (0x8000, 0) => self.select = value,
+            (0x8000, _) => self.banks[(self.select & 0x07) as usize] = value,
+            (0xA000, 0) => self.vertical = value & 0x01 == 0,
+            (0xA000, _) => self.prg_ram = value,
+            (0xC000, 0) => self.irq_latch = value,
+            (0xC000, _) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (_, 0) => {
+                self.irq_enabled = false;
+                self.irq = false;
+            }
+            _ => self.irq_enabled = true,
+        }
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        if address >= PRG_ROM {
+            self.memory.prg(self.prg_bank(address), PRG_BANK_SIZE, address)
+        } else if self.prg_ram_readable() {
+            self.memory.prg_ram(address).unwrap_or(open_bus(address))
+        } else {
+            open_bus(address)
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        self.memory
+            .save_state(StateWriter::new())
+            .byte(self.select)
+            .bytes(&self.banks)
+            .flag(self.vertical)
+            .bytes(&[self.prg_ram, self.irq_latch, self.irq_counter])
+            .flag(self.irq_reload)
+            .flag(self.irq_enabled)
+            .flag(self.irq)
+            .into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        self.memory.restore_state(&mut state)?;
+        self.select = state.byte()?;
+        state.fill(&mut self.banks)?;
+        self.vertical = state.flag()?;
+        self.prg_ram = state.byte()?;
+        self.irq_latch = state.byte()?;
+        self.irq_counter = state.byte()?;
+        self.irq_reload = state.flag()?;
+        self.irq_enabled = state.flag()?;
+        self.irq = state.flag()?;
+        Ok(())
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn chr_read(&self, address: Word) -> Byte {
+        self.memory.chr(self.chr_bank(address), CHR_BANK_SIZE, address)
+    }
+
+    fn chr_write(&mut self, address: Word, value: Byte) {
+        self.memory.write_chr(self.chr_bank(address), CHR_BANK_SIZE, address, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.four_screen {
+            Mirroring::FourScreen
+        } else if self.vertical {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        }
+    }
+
+    /** Reload the counter if it ran out or was asked to, count it down otherwise, and interrupt on reaching 0 */
+    fn scanline(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq = true;
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! NES cartridge boards, by iNES mapper number
+//!
+//! A board is a `Device` on the CPU side, decoding $4020-$FFFF and handed the address
+//! itself as the offset, as `NesBus` does. The PPU side, the 8 KiB of CHR at $0000-$1FFF
+//! and the nametable mirroring, is what the rest of `Mapper` is for.
+//! Every board gets PRG RAM at $6000-$7FFF if the header asks for any, which test ROMs
+//! use to report their results.
+
+use super::Device;
+use crate::loader::ines::{Mirroring, Rom};
+use crate::loader::LoadError;
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+pub mod cnrom;
+pub mod mmc1;
+pub mod mmc3;
+pub mod nrom;
+pub mod uxrom;
+
+pub const PRG_RAM: Word = 0x6000;
+pub const PRG_ROM: Word = 0x8000;
+pub const PRG_RAM_SIZE: usize = 0x2000;
+
+pub trait Mapper: Device {
+    /** Read the pattern tables as the PPU does, `address` being $0000-$1FFF */
+    fn chr_read(&self, address: Word) -> Byte;
+
+    /** Write the pattern tables, which only sticks if they are RAM */
+    fn chr_write(&mut self, address: Word, value: Byte);
+
+    fn mirroring(&self) -> Mirroring;
+
+    /**
+     * A scanline has been drawn, which is what the PPU's A12 rising edges tell boards
+     * counting them - the caller supplies this clock, the PPU here draws nothing
+     * */
+    fn scanline(&mut self) {}
+}
+
+/**
+ * The board for a .nes file
+ * @return an error for mapper numbers not implemented here
+ * */
+pub fn mapper(rom: Rom) -> Result<Box<dyn Mapper>, LoadError> {
+    Ok(match rom.mapper {
+        0 => Box::new(nrom::Nrom::new(rom)),
+        1 => Box::new(mmc1::Mmc1::new(rom)),
+        2 => Box::new(uxrom::Uxrom::new(rom)),
+        3 => Box::new(cnrom::Cnrom::new(rom)),
+        4 => Box::new(mmc3::Mmc3::new(rom)),
+        number => {
+            return Err(LoadError::Malformed {
+                offset: 6,
+                message: format!("mapper {} isn't supported", number),
+            })
+        }
+    })
+}
+
+impl Device for Box<dyn Mapper> {
+    fn read(&mut self, offset: Word) -> Byte {
+        (**self).read(offset)
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        (**self).write(offset, value)
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        (**self).peek(offset)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        (**self).tick(cycles)
+    }
+
+    fn irq(&self) -> bool {
+        (**self).irq()
+    }
+
+    fn nmi(&self) -> bool {
+        (**self).nmi()
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        (**self).save_state()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        (**self).restore_state(state)
+    }
+}
+
+/** The memories every board has, and reading them through banks */
+#[derive(Debug, Clone)]
+pub struct Memory {
+    pub prg_rom: Vec<Byte>,
+    pub prg_ram: Vec<Byte>,
+    /** CHR ROM, or CHR RAM if the cartridge has no ROM */
+    pub chr: Vec<Byte>,
+    pub chr_is_ram: bool,
+}
+
+impl Memory {
+    pub fn new(rom: &Rom) -> Self {
+        let chr_is_ram = rom.chr_rom.is_empty();
+        let chr = if chr_is_ram { vec![0; rom.chr_ram_size.max(0x2000)] } else { rom.chr_rom.clone() };
+        let mut prg_ram = vec![0; (rom.prg_ram_size + rom.prg_nvram_size).min(PRG_RAM_SIZE)];
+        //a trainer goes to $7000
+        if let Some(trainer) = &rom.trainer {
+            if prg_ram.len() == PRG_RAM_SIZE {
+                prg_ram[0x1000..0x1000 + trainer.len()].copy_from_slice(trainer);
+            }
+        }
+
+        Self {
+            prg_rom: rom.prg_rom.clone(),
+            prg_ram,
+            chr,
+            chr_is_ram,
+        }
+    }
+
+    /** Append the RAMs, what every board's state starts with - ROM is the cartridge's, not state */
+    pub fn save_state(&self, state: StateWriter) -> StateWriter {
+        let chr_ram: &[Byte] = if self.chr_is_ram { &self.chr } else { &[] };
+        state.block(&self.prg_ram).block(chr_ram)
+    }
+
+    pub fn restore_state(&mut self, state: &mut StateReader) -> Result<(), SnapshotError> {
+        let prg_ram = state.block()?;
+        let chr_ram = state.block()?;
+        let chr_size = if self.chr_is_ram { self.chr.len() } else { 0 };
+        if prg_ram.len() != self.prg_ram.len() || chr_ram.len() != chr_size {
+            return Err(SnapshotError::Bus("the snapshot is of a board with other RAM sizes".to_string()));
+        }
+
+        self.prg_ram = prg_ram;
+        if self.chr_is_ram {
+            self.chr = chr_ram;
+        }
+        Ok(())
+    }
+
+    /** @return how many `size` byte banks of PRG ROM there are */
+    pub fn prg_banks(&self, size: usize) -> usize {
+        (self.prg_rom.len() / size).max(1)
+    }
+
+    pub fn chr_banks(&self, size: usize) -> usize {
+        (self.chr.len() / size).max(1)
+    }
+
+    /** @return the byte at `offset` into PRG ROM bank `bank` of `size` bytes, banks past the end wrapping around */
+    pub fn prg(&self, bank: usize, size: usize, offset: Word) -> Byte {
+        let bank = bank % self.prg_banks(size);
+        self.prg_rom[(bank * size + offset as usize % size) % self.prg_rom.len()]
+    }
+
+    pub fn chr(&self, bank: usize, size: usize, offset: Word) -> Byte {
+        let bank = bank % self.chr_banks(size);
+        self.chr[(bank * size + offset as usize % size) % self.chr.len()]
+    }
+
+    pub fn write_chr(&mut self, bank: usize, size: usize, offset: Word, value: Byte) {
+        if self.chr_is_ram {
+            let bank = bank % self.chr_banks(size);
+            let index = (bank * size + offset as usize % size) % self.chr.len();
+            self.chr[index] = value;
+        }
+    }
+
+    /** @return the PRG RAM byte at `address` in $6000-$7FFF, or None if there's none there */
+    pub fn prg_ram(&self, address: Word) -> Option<Byte> {
+        let index = address.checked_sub(PRG_RAM)? as usize;
+        (index < PRG_RAM_SIZE && !self.prg_ram.is_empty()).then(|| self.prg_ram[index % self.prg_ram.len()])
+    }
+
+    pub fn write_prg_ram(&mut self, address: Word, value: Byte) {
+        let Some(index) = address.checked_sub(PRG_RAM) else {
+            return;
+        };
+        let length = self.prg_ram.len();
+        if (index as usize) < PRG_RAM_SIZE && length > 0 {
+            self.prg_ram[index as usize % length] = value;
+        }
+    }
+}
+
+/** What is left on the data bus where the board doesn't answer: the high byte of the address */
+pub fn open_bus(address: Word) -> Byte {
+    (address >> 8) as Byte
+}
@@ -0,0 +1,63 @@
+//! Mapper 0, NROM: 16 or 32 KiB of PRG ROM and 8 KiB of CHR, no banking
+
+use super::{open_bus, Mapper, Memory, PRG_ROM};
+use crate::devices::Device;
+use crate::loader::ines::{Mirroring, Rom};
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+#[derive(Debug, Clone)]
+pub struct Nrom {
+    pub memory: Memory,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(rom: Rom) -> Self {
+        Self {
+            memory: Memory::new(&rom),
+            mirroring: rom.mirroring,
+        }
+    }
+}
+
+/** 16 KiB of PRG ROM shows at both $8000 and $C000 */
+impl Device for Nrom {
+    fn read(&mut self, address: Word) -> Byte {
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        self.memory.write_prg_ram(address, value);
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        if address >= PRG_ROM {
+            self.memory.prg(0, 0x8000, address - PRG_ROM)
+        } else {
+            self.memory.prg_ram(address).unwrap_or(open_bus(address))
+        }
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        self.memory.save_state(StateWriter::new()).into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        self.memory.restore_state(&mut StateReader::new(state))
+    }
+}
+
+impl Mapper for Nrom {
+    fn chr_read(&self, address: Word) -> Byte {
+        self.memory.chr(0, 0x2000, address)
+    }
+
+    fn chr_write(&mut self, address: Word, value: Byte) {
+        self.memory.write_chr(0, 0x2000, address, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
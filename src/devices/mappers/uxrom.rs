@@ -0,0 +1,77 @@
+//! Mapper 2, UxROM: a switchable 16 KiB PRG bank at $8000 with the last one fixed at $C000,
+//! and 8 KiB of CHR RAM
+//!
+//! Any write to $8000-$FFFF picks the bank. The real boards see the ROM's byte on the bus
+//! at the same time, which games avoid by writing what's there, so that isn't modelled.
+
+use super::{open_bus, Mapper, Memory, PRG_ROM};
+use crate::devices::Device;
+use crate::loader::ines::{Mirroring, Rom};
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+const BANK_SIZE: usize = 0x4000;
+
+#[derive(Debug, Clone)]
+pub struct Uxrom {
+    pub memory: Memory,
+    mirroring: Mirroring,
+    bank: usize,
+}
+
+impl Uxrom {
+    pub fn new(rom: Rom) -> Self {
+        Self {
+            memory: Memory::new(&rom),
+            mirroring: rom.mirroring,
+            bank: 0,
+        }
+    }
+}
+
+impl Device for Uxrom {
+    fn read(&mut self, address: Word) -> Byte {
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        if address >= PRG_ROM {
+            self.bank = value as usize;
+        } else {
+            self.memory.write_prg_ram(address, value);
+        }
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        match address {
+            0xC000.. => self.memory.prg(self.memory.prg_banks(BANK_SIZE) - 1, BANK_SIZE, address),
+            PRG_ROM.. => self.memory.prg(self.bank, BANK_SIZE, address),
+            _ => self.memory.prg_ram(address).unwrap_or(open_bus(address)),
+        }
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        self.memory.save_state(StateWriter::new()).long(self.bank as u32).into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        self.memory.restore_state(&mut state)?;
+        self.bank = state.long()? as usize;
+        Ok(())
+    }
+}
+
+impl Mapper for Uxrom {
+    fn chr_read(&self, address: Word) -> Byte {
+        self.memory.chr(0, 0x2000, address)
+    }
+
+    fn chr_write(&mut self, address: Word, value: Byte) {
+        self.memory.write_chr(0, 0x2000, address, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
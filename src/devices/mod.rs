@@ -0,0 +1,273 @@
+//! Peripheral chips to put on the bus next to memory
+//!
+//! Every chip implements `Device`, and a `DeviceBus` maps any number of them over
+//! a `Bus`, clocks them alongside the CPU and wire-ORs their interrupt outputs.
+
+use crate::m6502::*;
+use crate::snapshot::{SaveState, SnapshotError, StateReader, StateWriter};
+use std::any::Any;
+use std::marker::PhantomData;
+
+pub mod acia;
+pub mod cia;
+pub mod hd44780;
+pub mod mappers;
+pub mod pia;
+pub mod ppu;
+pub mod riot;
+pub mod rriot;
+pub mod serial;
+pub mod via;
+pub mod vic_ii;
+
+/** A memory-mapped peripheral */
+pub trait Device: Any {
+    /** Read the register at `offset` as the CPU does, with whatever side effects that has */
+    fn read(&mut self, offset: Word) -> Byte;
+
+    fn write(&mut self, offset: Word, value: Byte);
+
+    /** Read the register at `offset` without side effects - what a debugger sees */
+    fn peek(&self, offset: Word) -> Byte;
+
+    /** Advance the device's clock by `cycles` CPU cycles */
+    fn tick(&mut self, _cycles: u32) {}
+
+    /** @return true while the device pulls the IRQ line low */
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /** @return true while the device pulls the NMI line low */
+    fn nmi(&self) -> bool {
+        false
+    }
+
+    /** @return the registers and anything else restore_state() needs to pick up where the device was */
+    fn save_state(&self) -> Vec<Byte> {
+        Vec::new()
+    }
+
+    fn restore_state(&mut self, _state: &[Byte]) -> Result<(), SnapshotError> {
+        Ok(())
+    }
+}
+
+/** Names a device attached to a DeviceBus, remembering its type */
+pub struct DeviceId<D> {
+    index: usize,
+    device: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for DeviceId<D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<D> Copy for DeviceId<D> {}
+
+/** A window of the address space decoded by one device */
+#[derive(Debug, Clone, Copy)]
+struct Mapping {
+    base: Word,
+    size: u32,
+    device: usize,
+    /** the device offset `base` corresponds to */
+    offset: Word,
+}
+
+impl Mapping {
+    fn offset_of(&self, address: Word) -> Option<Word> {
+        let distance = address.wrapping_sub(self.base) as u32;
+        (distance < self.size).then(|| self.offset.wrapping_add(distance as Word))
+    }
+}
+
+/**
+ * Devices mapped over a bus, e.g. memory
+ * - addresses no device decodes fall through to `bus`
+ * - where mappings overlap, the one made first wins
+ * */
+pub struct DeviceBus<B: Bus> {
+    pub bus: B,
+    devices: Vec<Box<dyn Device>>,
+    /** whether each device's IRQ and NMI outputs are connected to the CPU */
+    wired: Vec<bool>,
+    mappings: Vec<Mapping>,
+}
+
+impl<B: Bus> DeviceBus<B> {
+    pub fn new(bus: B) -> Self {
+        Self {
+            bus,
+            devices: Vec::new(),
+            wired: Vec::new(),
+            mappings: Vec::new(),
+        }
+    }
+
+    /**
+     * Add a device with offsets 0..size mapped from `base`
+     * @return the handle to get at the device later
+     * */
+    pub fn attach<D: Device>(&mut self, base: Word, size: u32, device: D) -> DeviceId<D> {
+        let id = DeviceId {
+            index: self.devices.len(),
+            device: PhantomData,
+        };
+        self.devices.push(Box::new(device));
+        self.wired.push(true);
+        self.map(id, base, size, 0);
+        id
+    }
+
+    /**
+     * Map `size` more bytes of a device from `base`, starting at device offset `offset`
+     * - for mirrors, or chips like the RIOT whose RAM and I/O are selected separately
+     * */
+    pub fn map<D>(&mut self, id: DeviceId<D>, base: Word, size: u32, offset: Word) {
+        self.mappings.push(Mapping {
+            base,
+            size,
+            device: id.index,
+            offset,
+        });
+    }
+
+    /** Leave a device's IRQ and NMI outputs unconnected, as the Apple I does with its PIA */
+    pub fn disconnect_interrupts<D>(&mut self, id: DeviceId<D>) {
+        self.wired[id.index] = false;
+    }
+
+    pub fn device<D: Device>(&self, id: DeviceId<D>) -> &D {
+        let device: &dyn Any = self.devices[id.index].as_ref();
+        device.downcast_ref().expect("DeviceId of another DeviceBus")
+    }
+
+    pub fn device_mut<D: Device>(&mut self, id: DeviceId<D>) -> &mut D {
+        let device: &mut dyn Any = self.devices[id.index].as_mut();
+        device.downcast_mut().expect("DeviceId of another DeviceBus")
+    }
+
+    /** @return true if any connected device pulls the IRQ line low */
+    pub fn irq(&self) -> bool {
+        self.connected().any(|device| device.irq())
+    }
+
+    /** @return true if any connected device pulls the NMI line low */
+    pub fn nmi(&self) -> bool {
+        self.connected().any(|device| device.nmi())
+    }
+
+    fn connected(&self) -> impl Iterator<Item = &dyn Device> {
+        self.devices
+            .iter()
+            .zip(&self.wired)
+            .filter(|(_, wired)| **wired)
+            .map(|(device, _)| device.as_ref())
+    }
+
+    /** Clock every device by `cycles` cycles and drive the CPU's interrupt inputs from them */
+    pub fn tick(&mut self, cycles: u32, cpu: &mut CPU) {
+        for device in &mut self.devices {
+            device.tick(cycles);
+        }
+        cpu.set_irq(self.irq());
+        cpu.set_nmi(self.nmi());
+    }
+
+    /**
+     * Execute one instruction, then clock the devices for as long as it took
+     * @return the number of cycles that were used
+     * */
+    pub fn step(&mut self, cpu: &mut CPU) -> s32 {
+        let cycles = cpu.step(self);
+        self.tick(cycles as u32, cpu);
+        cycles
+    }
+
+    /**
+     * Same as CPU::execute, with the devices running in lockstep
+     * @return the number of cycles that were used
+     * */
+    pub fn execute(&mut self, cycles: s32, cpu: &mut CPU) -> s32 {
+        let mut cycles_used = 0;
+        while cycles_used < cycles {
+            let step_cycles = self.step(cpu);
+            if step_cycles == 0 {
+                break;
+            }
+            cycles_used += step_cycles;
+        }
+
+        cycles_used
+    }
+
+    fn decode(&self, address: Word) -> Option<(usize, Word)> {
+        self.mappings
+            .iter()
+            .find_map(|mapping| mapping.offset_of(address).map(|offset| (mapping.device, offset)))
+    }
+}
+
+impl<B: Bus> Bus for DeviceBus<B> {
+    fn read(&mut self, address: Word) -> Byte {
+        match self.decode(address) {
+            Some((device, offset)) => self.devices[device].read(offset),
+            None => self.bus.read(address),
+        }
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        match self.decode(address) {
+            Some((device, offset)) => self.devices[device].write(offset, value),
+            None => self.bus.write(address, value),
+        }
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        match self.decode(address) {
+            Some((device, offset)) => self.devices[device].peek(offset),
+            None => self.bus.peek(address),
+        }
+    }
+}
+
+/** The memory comes from `bus`, the device state is each device's, in the order they were attached */
+impl<B: Bus + SaveState> SaveState for DeviceBus<B> {
+    fn save_memory(&self) -> Vec<Byte> {
+        self.bus.save_memory()
+    }
+
+    fn restore_memory(&mut self, memory: &[Byte]) -> Result<(), SnapshotError> {
+        self.bus.restore_memory(memory)
+    }
+
+    fn save_devices(&self) -> Option<Vec<Byte>> {
+        let state = self
+            .devices
+            .iter()
+            .fold(StateWriter::new().long(self.devices.len() as u32), |state, device| {
+                state.block(&device.save_state())
+            });
+        Some(state.into_bytes())
+    }
+
+    fn restore_devices(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut reader = StateReader::new(state);
+        let count = reader.long()? as usize;
+        if count != self.devices.len() {
+            return Err(SnapshotError::Bus(format!(
+                "the snapshot has {} devices, the bus {}",
+                count,
+                self.devices.len()
+            )));
+        }
+        for device in &mut self.devices {
+            device.restore_state(&reader.block()?)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+//! MOS 6520 / Motorola 6821 Peripheral Interface Adapter
+//!
+//! Two 8-bit ports, each with a data direction register sharing its address with the
+//! output register (bit 2 of the control register picks which), and two control lines
+//! that can interrupt or handshake. The two IRQ outputs are usually tied together.
+
+use super::Device;
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+//registers, by offset from the base address (RS1, RS0)
+/** the port A output register or DDRA, depending on CRA bit 2 */
+pub const PORT_A: Byte = 0x0;
+pub const CRA: Byte = 0x1;
+/** the port B output register or DDRB, depending on CRB bit 2 */
+pub const PORT_B: Byte = 0x2;
+pub const CRB: Byte = 0x3;
+
+//control register bits
+pub const CR_C1_IRQ_ENABLE: Byte = 0x01;
+pub const CR_C1_RISING: Byte = 0x02;
+/** 1 selects the output register, 0 the data direction register */
+pub const CR_OUTPUT_REGISTER: Byte = 0x04;
+pub const CR_C2_IRQ_FLAG: Byte = 0x40;
+pub const CR_C1_IRQ_FLAG: Byte = 0x80;
+
+/** One half of the PIA: a port, its control register and its two control lines */
+#[derive(Debug, Clone)]
+struct Side {
+    output: Byte,
+    ddr: Byte,
+    input: Byte,
+    control: Byte,
+    c1: bool,
+    c2: bool,
+    c2_out: bool,
+    /** cycles left on a pulse-mode low */
+    c2_pulse: u8,
+}
+
+impl Side {
+    fn new() -> Self {
+        Self {
+            output: 0,
+            ddr: 0,
+            input: 0xFF,
+            control: 0,
+            c1: true,
+            c2: true,
+            c2_out: true,
+            c2_pulse: 0,
+        }
+    }
+
+    fn pins(&self) -> Byte {
+        (self.output & self.ddr) | (self.input & !self.ddr)
+    }
+
+    fn c2_is_output(&self) -> bool {
+        self.control & 0x20 != 0
+    }
+
+    fn irq(&self) -> bool {
+        (self.control & CR_C1_IRQ_FLAG != 0 && self.control & CR_C1_IRQ_ENABLE != 0)
+            || (self.control & CR_C2_IRQ_FLAG != 0 && !self.c2_is_output() && self.control & 0x08 != 0)
+    }
+
+    fn c2_level(&self) -> bool {
+        match (self.c2_is_output(), self.control & 0x10 != 0) {
+            (false, _) => self.c2,
+            //manual output: bit 3 is the level
+            (true, true) => self.control & 0x08 != 0,
+            (true, false) => self.c2_out,
+        }
+    }
+
+    fn set_c1(&mut self, level: bool) {
+        let rising = self.control & CR_C1_RISING != 0;
+        if level != self.c1 && level == rising {
+            self.control |= CR_C1_IRQ_FLAG;
+            //handshake mode: C2 goes back high on the active C1 edge
+            if self.control & 0x38 == 0x20 {
+                self.c2_out = true;
+            }
+        }
+        self.c1 = level;
+    }
+
+    fn set_c2(&mut self, level: bool) {
+        let rising = self.control & 0x10 != 0;
+        if !self.c2_is_output() && level != self.c2 && level == rising {
+            self.control |= CR_C2_IRQ_FLAG;
+        }
+        self.c2 = level;
+    }
+
+    /** Start the C2 handshake or pulse, for an access that triggers one */
+    fn c2_strobe(&mut self) {
+        match self.control & 0x38 {
+            0x20 => self.c2_out = false,
+            0x28 => {
+                self.c2_out = false;
+                self.c2_pulse = 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn write_control(&mut self, value: Byte) {
+        //the flags are read-only
+        self.control = (self.control & 0xC0) | (value & 0x3F);
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if self.c2_pulse > 0 && cycles > 0 {
+            self.c2_pulse = 0;
+            self.c2_out = true;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pia {
+    a: Side,
+    b: Side,
+}
+
+impl Pia {
+    pub fn new() -> Self {
+        Self {
+            a: Side::new(),
+            b: Side::new(),
+        }
+    }
+
+    /** What the RES pin does: everything cleared, ports become inputs */
+    pub fn reset(&mut self) {
+        let (input_a, input_b) = (self.a.input, self.b.input);
+        *self = Self::new();
+        self.a.input = input_a;
+        self.b.input = input_b;
+    }
+
+    pub fn irq_a(&self) -> bool {
+        self.a.irq()
+    }
+
+    pub fn irq_b(&self) -> bool {
+        self.b.irq()
+    }
+
+    /** @return true if either IRQ output is active, as when both are wired to the CPU */
+    pub fn irq(&self) -> bool {
+        self.irq_a() || self.irq_b()
+    }
+
+    pub fn port_a(&self) -> Byte {
+        self.a.pins()
+    }
+
+    pub fn port_b(&self) -> Byte {
+        self.b.pins()
+    }
+
+    /** Drive the port A pins from outside - only the bits set as inputs are seen */
+    pub fn set_port_a_input(&mut self, value: Byte) {
+        self.a.input = value;
+    }
+
+    pub fn set_port_b_input(&mut self, value: Byte) {
+        self.b.input = value;
+    }
+
+    pub fn set_ca1(&mut self, level: bool) {
+        self.a.set_c1(level);
+    }
+
+    pub fn set_ca2(&mut self, level: bool) {
+        self.a.set_c2(level);
+    }
+
+    pub fn set_cb1(&mut self, level: bool) {
+        self.b.set_c1(level);
+    }
+
+    pub fn set_cb2(&mut self, level: bool) {
+        self.b.set_c2(level);
+    }
+
+    pub fn ca2(&self) -> bool {
+        self.a.c2_level()
+    }
+
+    pub fn cb2(&self) -> bool {
+        self.b.c2_level()
+    }
+
+    /** Read a register without side effects - what a debugger sees */
+    pub fn peek(&self, register: Byte) -> Byte {
+        match register & 0x03 {
+            PORT_A if self.a.control & CR_OUTPUT_REGISTER != 0 => self.a.pins(),
+            PORT_A => self.a.ddr,
+            CRA => self.a.control,
+            //output bits read back as written, whatever the load on the pins
+            PORT_B if self.b.control & CR_OUTPUT_REGISTER != 0 => {
+                (self.b.output & self.b.ddr) | (self.b.input & !self.b.ddr)
+            }
+            PORT_B => self.b.ddr,
+            _ => self.b.control,
+        }
+    }
+
+    /** Read a register as the CPU does - reading a port clears its interrupt flags */
+    pub fn read(&mut self, register: Byte) -> Byte {
+        let value = self.peek(register);
+        match register & 0x03 {
+            PORT_A if self.a.control & CR_OUTPUT_REGISTER != 0 => {
+                self.a.control &= !(CR_C1_IRQ_FLAG | CR_C2_IRQ_FLAG);
+                //CA2 handshakes on reads of port A
+                self.a.c2_strobe();
+            }
+            PORT_B if self.b.control & CR_OUTPUT_REGISTER != 0 => {
+                self.b.control &= !(CR_C1_IRQ_FLAG | CR_C2_IRQ_FLAG);
+            }
+            _ => {}
+        }
+        value
+    }
+
+    pub fn write(&mut self, register: Byte, value: Byte) {
+        match register & 0x03 {
+            PORT_A if self.a.control & CR_OUTPUT_REGISTER != 0 => self.a.output = value,
+            PORT_A => self.a.ddr = value,
+            CRA => self.a.write_control(value),
+            PORT_B if self.b.control & CR_OUTPUT_REGISTER != 0 => {
+                self.b.output = value;
+                //CB2 handshakes on writes of port B
+                self.b.c2_strobe();
+            }
+            PORT_B => self.b.ddr = value,
+            _ => self.b.write_control(value),
+        }
+    }
+
+    /** Advance by `cycles` cycles - only the C2 pulses need it */
+    pub fn tick(&mut self, cycles: u32) {
+        self.a.tick(cycles);
+        self.b.tick(cycles);
+    }
+}
+
+impl Default for Pia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/** 4 registers, mirrored across whatever the PIA is mapped over */
+impl Device for Pia {
+    fn read(&mut self, offset: Word) -> Byte {
+        Pia::read(self, (offset & 0x03) as Byte)
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        Pia::write(self, (offset & 0x03) as Byte, value)
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        Pia::peek(self, (offset & 0x03) as Byte)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        Pia::tick(self, cycles)
+    }
+
+    fn irq(&self) -> bool {
+        Pia::irq(self)
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        [&self.a, &self.b]
+            .iter()
+            .fold(StateWriter::new(), |state, side| {
+                state
+                    .bytes(&[side.output, side.ddr, side.input, side.control])
+                    .flag(side.c1)
+                    .flag(side.c2)
+                    .flag(side.c2_out)
+                    .byte(side.c2_pulse)
+            })
+            .into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        for side in [&mut self.a, &mut self.b] {
+            side.output = state.byte()?;
+            side.ddr = state.byte()?;
+            side.input = state.byte()?;
+            side.control = state.byte()?;
+            side.c1 = state.flag()?;
+            side.c2 = state.flag()?;
+            side.c2_out = state.flag()?;
+            side.c2_pulse = state.byte()?;
+        }
+        Ok(())
+    }
+}
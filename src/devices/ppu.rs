@@ -0,0 +1,259 @@
+//! Ricoh 2C02 picture processing unit, as far as the CPU can see it
+//!
+//! Eight registers, repeated every 8 bytes from $2000 to $3FFF. No picture is drawn:
+//! VRAM and OAM keep what is written to them, the frame timing raises the vblank flag
+//! and its NMI, and sprite 0 never hits. VRAM is a flat 16 KiB with no nametable mirroring,
+//! the cartridge's CHR is not looked at.
+
+use super::Device;
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+//registers, by offset from the base address
+pub const PPUCTRL: Byte = 0;
+pub const PPUMASK: Byte = 1;
+pub const PPUSTATUS: Byte = 2;
+pub const OAMADDR: Byte = 3;
+pub const OAMDATA: Byte = 4;
+pub const PPUSCROLL: Byte = 5;
+pub const PPUADDR: Byte = 6;
+pub const PPUDATA: Byte = 7;
+
+//PPUCTRL bits
+/** PPUADDR goes up by 32 rather than 1 with each PPUDATA access */
+pub const CTRL_INCREMENT_32: Byte = 0x04;
+pub const CTRL_NMI_ENABLE: Byte = 0x80;
+
+//PPUSTATUS bits
+pub const STATUS_VBLANK: Byte = 0x80;
+
+pub const OAM_SIZE: usize = 256;
+const VRAM_SIZE: usize = 0x4000;
+/** where the palette starts, it's read without the buffer */
+const PALETTE: Word = 0x3F00;
+
+/** three dots to each CPU cycle, 341 dots to a line, 262 lines to a frame */
+pub const DOTS_PER_CPU_CYCLE: u32 = 3;
+pub const DOTS_PER_LINE: u32 = 341;
+pub const LINES: u32 = 262;
+const VBLANK_START: u32 = 241 * DOTS_PER_LINE + 1;
+const VBLANK_END: u32 = 261 * DOTS_PER_LINE + 1;
+
+#[derive(Debug, Clone)]
+pub struct Ppu {
+    ctrl: Byte,
+    mask: Byte,
+    status: Byte,
+    oam_address: Byte,
+    oam: [Byte; OAM_SIZE],
+    vram: Vec<Byte>,
+    address: Word,
+    scroll: (Byte, Byte),
+    /** the second write of PPUSCROLL or PPUADDR comes next */
+    second_write: bool,
+    read_buffer: Byte,
+    /** the last value written to any register, what the unused bits read back */
+    latch: Byte,
+    /** dots into the frame */
+    dot: u32,
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Self {
+            ctrl: 0,
+            mask: 0,
+            status: 0,
+            oam_address: 0,
+            oam: [0; OAM_SIZE],
+            vram: vec![0; VRAM_SIZE],
+            address: 0,
+            scroll: (0, 0),
+            second_write: false,
+            read_buffer: 0,
+            latch: 0,
+            dot: 0,
+        }
+    }
+
+    /** What RESET does: PPUCTRL, PPUMASK and the write toggle are cleared, memory is kept */
+    pub fn reset(&mut self) {
+        self.ctrl = 0;
+        self.mask = 0;
+        self.second_write = false;
+        self.read_buffer = 0;
+        self.scroll = (0, 0);
+    }
+
+    /** @return true while the vblank flag is up and PPUCTRL lets it through */
+    pub fn nmi(&self) -> bool {
+        self.status & STATUS_VBLANK != 0 && self.ctrl & CTRL_NMI_ENABLE != 0
+    }
+
+    pub fn ctrl(&self) -> Byte {
+        self.ctrl
+    }
+
+    pub fn mask(&self) -> Byte {
+        self.mask
+    }
+
+    pub fn oam(&self) -> &[Byte; OAM_SIZE] {
+        &self.oam
+    }
+
+    /** @return the byte at `address` in the 16 KiB the PPU sees */
+    pub fn vram(&self, address: Word) -> Byte {
+        self.vram[address as usize % VRAM_SIZE]
+    }
+
+    /** @return the scroll position last written to PPUSCROLL */
+    pub fn scroll(&self) -> (Byte, Byte) {
+        self.scroll
+    }
+
+    /** @return the scanline being drawn, 0-239 visible, 241-260 vertical blank and 261 pre-render */
+    pub fn scanline(&self) -> u32 {
+        self.dot / DOTS_PER_LINE
+    }
+
+    /** Read a register without side effects - what a debugger sees */
+    pub fn peek(&self, register: Byte) -> Byte {
+        match register & 0x07 {
+            PPUSTATUS => self.status | (self.latch & 0x1F),
+            OAMDATA => self.oam[self.oam_address as usize],
+            PPUDATA if self.address >= PALETTE => self.vram(self.address),
+            PPUDATA => self.read_buffer,
+            _ => self.latch,
+        }
+    }
+
+    /** Read a register as the CPU does */
+    pub fn read(&mut self, register: Byte) -> Byte {
+        let value = self.peek(register);
+        match register & 0x07 {
+            PPUSTATUS => {
+                self.status &= !STATUS_VBLANK;
+                self.second_write = false;
+            }
+            PPUDATA => {
+                self.read_buffer = self.vram(self.address);
+                self.advance();
+            }
+            _ => {}
+        }
+        value
+    }
+
+    pub fn write(&mut self, register: Byte, value: Byte) {
+        self.latch = value;
+        match register & 0x07 {
+            PPUCTRL => self.ctrl = value,
+            PPUMASK => self.mask = value,
+            OAMADDR => self.oam_address = value,
+            OAMDATA => {
+                self.oam[self.oam_address as usize] = value;
+                self.oam_address = self.oam_address.wrapping_add(1);
+            }
+            PPUSCROLL => {
+                if self.second_write {
+                    self.scroll.1 = value;
+                } else {
+                    self.scroll.0 = value;
+                }
+                self.second_write = !self.second_write;
+            }
+            PPUADDR => {
+                let [lo, hi] = self.address.to_le_bytes();
+                self.address = if self.second_write {
+                    Word::from_le_bytes([value, hi])
+                } else {
+                    Word::from_le_bytes([lo, value & 0x3F])
+                };
+                self.second_write = !self.second_write;
+            }
+            PPUDATA => {
+                self.vram[self.address as usize % VRAM_SIZE] = value;
+                self.advance();
+            }
+            _ => {}
+        }
+    }
+
+    fn advance(&mut self) {
+        let step = if self.ctrl & CTRL_INCREMENT_32 != 0 { 32 } else { 1 };
+        self.address = self.address.wrapping_add(step) & 0x3FFF;
+    }
+
+    /** Advance by `cycles` CPU cycles, raising the vblank flag at the start of line 241 and dropping it on line 261 */
+    pub fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles * DOTS_PER_CPU_CYCLE {
+            self.dot += 1;
+            if self.dot == VBLANK_START {
+                self.status |= STATUS_VBLANK;
+            } else if self.dot == VBLANK_END {
+                self.status &= !STATUS_VBLANK;
+            } else if self.dot == DOTS_PER_LINE * LINES {
+                self.dot = 0;
+            }
+        }
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for Ppu {
+    fn read(&mut self, offset: Word) -> Byte {
+        Ppu::read(self, offset as Byte)
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        Ppu::write(self, offset as Byte, value)
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        Ppu::peek(self, offset as Byte)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        Ppu::tick(self, cycles)
+    }
+
+    fn nmi(&self) -> bool {
+        Ppu::nmi(self)
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        StateWriter::new()
+            .bytes(&[self.ctrl, self.mask, self.status, self.oam_address])
+            .bytes(&self.oam)
+            .block(&self.vram)
+            .word(self.address)
+            .bytes(&[self.scroll.0, self.scroll.1])
+            .flag(self.second_write)
+            .bytes(&[self.read_buffer, self.latch])
+            .long(self.dot)
+            .into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        self.ctrl = state.byte()?;
+        self.mask = state.byte()?;
+        self.status = state.byte()?;
+        self.oam_address = state.byte()?;
+        state.fill(&mut self.oam)?;
+        self.vram = state.block()?;
+        self.address = state.word()?;
+        self.scroll = (state.byte()?, state.byte()?);
+        self.second_write = state.flag()?;
+        self.read_buffer = state.byte()?;
+        self.latch = state.byte()?;
+        self.dot = state.long()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+//! MOS 6532 RAM-I/O-Timer
+//!
+//! 128 bytes of RAM, two 8-bit ports with data direction registers, an interval timer
+//! with a 1, 8, 64 or 1024 cycle prescaler, and an edge detector on PA7.
+//! RAM and I/O are selected separately (the RS pin), so they are usually mapped at different addresses.
+
+use super::Device;
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+pub const RAM_SIZE: usize = 128;
+/** where the I/O registers start in the Device offsets, after the RAM */
+pub const IO_OFFSET: Word = 0x80;
+
+//I/O registers, by offset from the I/O base address (A0-A4)
+pub const ORA: Byte = 0x00;
+pub const DDRA: Byte = 0x01;
+pub const ORB: Byte = 0x02;
+pub const DDRB: Byte = 0x03;
+/** reading it gives the timer, with A3 enabling the timer interrupt */
+pub const TIMER: Byte = 0x04;
+/** reading it gives the interrupt flags and clears the PA7 one */
+pub const INTERRUPT_FLAGS: Byte = 0x05;
+/** writes with A4 clear set up the PA7 edge detector: A0 positive edge, A1 interrupt enable */
+pub const EDGE_CONTROL: Byte = 0x04;
+/** or-ed into EDGE_CONTROL to detect rising edges instead of falling ones */
+pub const EDGE_POSITIVE: Byte = 0x01;
+/** or-ed into EDGE_CONTROL to enable the PA7 interrupt */
+pub const EDGE_IRQ_ENABLE: Byte = 0x02;
+/** writes with A4 set start the timer: A0-A1 pick the prescaler, A3 enables the interrupt */
+pub const TIMER_1: Byte = 0x14;
+pub const TIMER_8: Byte = 0x15;
+pub const TIMER_64: Byte = 0x16;
+pub const TIMER_1024: Byte = 0x17;
+/** or-ed into a timer register offset to enable its interrupt */
+pub const TIMER_IRQ_ENABLE: Byte = 0x08;
+
+//interrupt flags
+pub const FLAG_TIMER: Byte = 0x80;
+pub const FLAG_PA7: Byte = 0x40;
+
+const PRESCALERS: [u16; 4] = [1, 8, 64, 1024];
+
+#[derive(Debug, Clone)]
+pub struct Riot {
+    pub ram: [Byte; RAM_SIZE],
+    ora: Byte,
+    orb: Byte,
+    ddra: Byte,
+    ddrb: Byte,
+    port_a_input: Byte,
+    port_b_input: Byte,
+
+    timer: Byte,
+    prescaler: u16,
+    /** cycles until the timer next counts down */
+    prescale_count: u16,
+    timer_irq_enabled: bool,
+
+    pa7_positive_edge: bool,
+    pa7_irq_enabled: bool,
+    pa7_level: bool,
+
+    flags: Byte,
+}
+
+impl Riot {
+    pub fn new() -> Self {
+        Self {
+            ram: [0; RAM_SIZE],
+            ora: 0,
+            orb: 0,
+            ddra: 0,
+            ddrb: 0,
+            port_a_input: 0xFF,
+            port_b_input: 0xFF,
+            timer: 0xFF,
+            prescaler: 1024,
+            prescale_count: 1024,
+            timer_irq_enabled: false,
+            pa7_positive_edge: false,
+            pa7_irq_enabled: false,
+            pa7_level: true,
+            flags: 0,
+        }
+    }
+
+    /** What the RES pin does: ports become inputs, interrupts are disabled */
+    pub fn reset(&mut self) {
+        self.ora = 0;
+        self.orb = 0;
+        self.ddra = 0;
+        self.ddrb = 0;
+        self.timer_irq_enabled = false;
+        self.pa7_irq_enabled = false;
+        self.pa7_positive_edge = false;
+        self.flags = 0;
+        self.pa7_level = self.port_a() & 0x80 != 0;
+    }
+
+    pub fn irq(&self) -> bool {
+        (self.flags & FLAG_TIMER != 0 && self.timer_irq_enabled) || (self.flags & FLAG_PA7 != 0 && self.pa7_irq_enabled)
+    }
+
+    /** @return the levels on the port A pins */
+    pub fn port_a(&self) -> Byte {
+        (self.ora & self.ddra) | (self.port_a_input & !self.ddra)
+    }
+
+    /** @return the levels on the port B pins */
+    pub fn port_b(&self) -> Byte {
+        (self.orb & self.ddrb) | (self.port_b_input & !self.ddrb)
+    }
+
+    /** Drive the port A pins from outside - only the bits set as inputs are seen */
+    pub fn set_port_a_input(&mut self, value: Byte) {
+        self.port_a_input = value;
+        self.detect_pa7_edge();
+    }
+
+    pub fn set_port_b_input(&mut self, value: Byte) {
+        self.port_b_input = value;
+    }
+
+    /** PA7 is watched whether it's an input or an output */
+    fn detect_pa7_edge(&mut self) {
+        let level = self.port_a() & 0x80 != 0;
+        if level != self.pa7_level && level == self.pa7_positive_edge {
+            self.flags |= FLAG_PA7;
+        }
+        self.pa7_level = level;
+    }
+
+    /** Read an I/O register without side effects - what a debugger sees */
+    pub fn peek(&self, register: Byte) -> Byte {
+        if register & 0x04 == 0 {
+            match register & 0x03 {
+                ORA => self.port_a(),
+                DDRA => self.ddra,
+                ORB => self.port_b(),
+                _ => self.ddrb,
+            }
+        } else if register & 0x01 == 0 {
+            self.timer
+        } else {
+            self.flags
+        }
+    }
+
+    /** Read an I/O register as the CPU does */
+    pub fn read(&mut self, register: Byte) -> Byte {
+        let value = self.peek(register);
+        if register & 0x04 != 0 {
+            if register & 0x01 == 0 {
+                //reading the timer acknowledges its interrupt
+                self.flags &= !FLAG_TIMER;
+                self.timer_irq_enabled = register & TIMER_IRQ_ENABLE != 0;
+            } else {
+                self.flags &= !FLAG_PA7;
+            }
+        }
+        value
+    }
+
+    pub fn write(&mut self, register: Byte, value: Byte) {
+        if register & 0x04 == 0 {
+            match register & 0x03 {
+                ORA => self.ora = value,
+                DDRA => self.ddra = value,
+                ORB => self.orb = value,
+                _ => self.ddrb = value,
+            }
+            self.detect_pa7_edge();
+        } else if register & 0x10 != 0 {
+            self.prescaler = PRESCALERS[(register & 0x03) as usize];
+            self.prescale_count = self.prescaler;
+            self.timer = value;
+            self.timer_irq_enabled = register & TIMER_IRQ_ENABLE != 0;
+            self.flags &= !FLAG_TIMER;
+        } else {
+            self.pa7_positive_edge = register & EDGE_POSITIVE != 0;
+            self.pa7_irq_enabled = register & EDGE_IRQ_ENABLE != 0;
+        }
+    }
+
+    /** Read the RAM, `offset` being taken modulo 128 */
+    pub fn read_ram(&self, offset: Word) -> Byte {
+        self.ram[offset as usize % RAM_SIZE]
+    }
+
+    pub fn write_ram(&mut self, offset: Word, value: Byte) {
+        self.ram[offset as usize % RAM_SIZE] = value;
+    }
+
+    /**
+     * Advance the interval timer by `cycles` cycles
+     * - once it passes zero it flags the interrupt and keeps counting down every cycle
+     * */
+    pub fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.prescale_count -= 1;
+            if self.prescale_count > 0 {
+                continue;
+            }
+
+            if self.timer == 0 {
+                self.flags |= FLAG_TIMER;
+                self.prescaler = 1;
+            }
+            self.timer = self.timer.wrapping_sub(1);
+            self.prescale_count = self.prescaler;
+        }
+    }
+}
+
+impl Default for Riot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+ * Offsets below IO_OFFSET are the RAM, from IO_OFFSET up the I/O registers - map the
+ * two separately with DeviceBus::map()
+ * */
+impl Device for Riot {
+    fn read(&mut self, offset: Word) -> Byte {
+        if offset < IO_OFFSET {
+            self.read_ram(offset)
+        } else {
+            Riot::read(self, (offset & 0x1F) as Byte)
+        }
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        if offset < IO_OFFSET {
+            self.write_ram(offset, value)
+        } else {
+            Riot::write(self, (offset & 0x1F) as Byte, value)
+        }
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        if offset < IO_OFFSET {
+            self.read_ram(offset)
+        } else {
+            Riot::peek(self, (offset & 0x1F) as Byte)
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        Riot::tick(self, cycles)
+    }
+
+    fn irq(&self) -> bool {
+        Riot::irq(self)
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        StateWriter::new()
+            .bytes(&self.ram)
+            .bytes(&[self.ora, self.orb, self.ddra, self.ddrb, self.port_a_input, self.port_b_input])
+            .byte(self.timer)
+            .word(self.prescaler)
+            .word(self.prescale_count)
+            .flag(self.timer_irq_enabled)
+            .flag(self.pa7_positive_edge)
+            .flag(self.pa7_irq_enabled)
+            .flag(self.pa7_level)
+            .byte(self.flags)
+            .into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        state.fill(&mut self.ram)?;
+        self.ora = state.byte()?;
+        self.orb = state.byte()?;
+        self.ddra = state.byte()?;
+        self.ddrb = state.byte()?;
+        self.port_a_input = state.byte()?;
+        self.port_b_input = state.byte()?;
+        self.timer = state.byte()?;
+        self.prescaler = state.word()?;
+        self.prescale_count = state.word()?;
+        self.timer_irq_enabled = state.flag()?;
+        self.pa7_positive_edge = state.flag()?;
+        self.pa7_irq_enabled = state.flag()?;
+        self.pa7_level = state.flag()?;
+        self.flags = state.byte()?;
+        Ok(())
+    }
+}
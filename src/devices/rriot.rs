@@ -0,0 +1,184 @@
+//! MOS 6530 ROM-RAM-I/O-Timer
+//!
+//! 1 KiB of mask ROM, 64 bytes of RAM, two I/O ports and the interval timer of the
+//! 6532, without its PA7 edge detector. Like the 6532, it is mapped in pieces.
+
+use super::riot::{self, Riot};
+use super::Device;
+use crate::loader::LoadError;
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+pub const ROM_SIZE: usize = 0x400;
+pub const RAM_SIZE: usize = 64;
+/** where the RAM starts in the Device offsets, after the ROM */
+pub const RAM_OFFSET: Word = 0x400;
+/** where the I/O registers start in the Device offsets, after the RAM */
+pub const IO_OFFSET: Word = 0x440;
+
+//I/O registers, by offset from the I/O base address
+pub const PAD: Byte = 0x00;
+pub const PADD: Byte = 0x01;
+pub const PBD: Byte = 0x02;
+pub const PBDD: Byte = 0x03;
+/** reading it gives the timer, writing it starts the timer counting every cycle */
+pub const TIMER_1: Byte = 0x04;
+pub const TIMER_8: Byte = 0x05;
+pub const TIMER_64: Byte = 0x06;
+pub const TIMER_1024: Byte = 0x07;
+/** reading it gives the timer flag in bit 7 */
+pub const INTERRUPT_FLAG: Byte = 0x07;
+/** or-ed into a timer register offset to enable its interrupt */
+pub const TIMER_IRQ_ENABLE: Byte = 0x08;
+
+#[derive(Debug, Clone)]
+pub struct Rriot {
+    pub rom: [Byte; ROM_SIZE],
+    pub ram: [Byte; RAM_SIZE],
+    /** the ports and the timer are the 6532's */
+    io: Riot,
+}
+
+impl Rriot {
+    /** A 6530 with an erased ROM */
+    pub fn new() -> Self {
+        Self {
+            rom: [0xFF; ROM_SIZE],
+            ram: [0; RAM_SIZE],
+            io: Riot::new(),
+        }
+    }
+
+    /** Put the mask ROM contents in, e.g. a dump of the KIM-1's 6530-002 */
+    pub fn load_rom(&mut self, rom: &[Byte]) -> Result<(), LoadError> {
+        if rom.len() > ROM_SIZE {
+            return Err(LoadError::TooLarge {
+                address: 0,
+                length: rom.len(),
+            });
+        }
+        self.rom[..rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.io.reset();
+    }
+
+    pub fn irq(&self) -> bool {
+        self.io.irq()
+    }
+
+    pub fn port_a(&self) -> Byte {
+        self.io.port_a()
+    }
+
+    pub fn port_b(&self) -> Byte {
+        self.io.port_b()
+    }
+
+    pub fn set_port_a_input(&mut self, value: Byte) {
+        self.io.set_port_a_input(value);
+    }
+
+    pub fn set_port_b_input(&mut self, value: Byte) {
+        self.io.set_port_b_input(value);
+    }
+
+    /** With A2 set a write starts the timer, the 6532 tells that apart from edge control by A4 */
+    fn riot_register(register: Byte) -> Byte {
+        if register & 0x04 != 0 {
+            register | 0x10
+        } else {
+            register & 0x03
+        }
+    }
+
+    /** Read an I/O register without side effects - what a debugger sees */
+    pub fn peek(&self, register: Byte) -> Byte {
+        let value = self.io.peek(Self::riot_register(register & 0x0F));
+        if register & 0x05 == 0x05 {
+            value & riot::FLAG_TIMER
+        } else {
+            value
+        }
+    }
+
+    pub fn read(&mut self, register: Byte) -> Byte {
+        let value = self.io.read(Self::riot_register(register & 0x0F));
+        if register & 0x05 == 0x05 {
+            value & riot::FLAG_TIMER
+        } else {
+            value
+        }
+    }
+
+    pub fn write(&mut self, register: Byte, value: Byte) {
+        self.io.write(Self::riot_register(register & 0x0F), value);
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        self.io.tick(cycles);
+    }
+}
+
+impl Default for Rriot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+ * Offsets below RAM_OFFSET are the ROM, then the RAM, and from IO_OFFSET up the I/O
+ * registers - map them separately with DeviceBus::map()
+ * */
+impl Device for Rriot {
+    fn read(&mut self, offset: Word) -> Byte {
+        if offset < IO_OFFSET {
+            Device::peek(self, offset)
+        } else {
+            Rriot::read(self, (offset & 0x0F) as Byte)
+        }
+    }
+
+    /** The ROM ignores writes */
+    fn write(&mut self, offset: Word, value: Byte) {
+        if offset >= IO_OFFSET {
+            Rriot::write(self, (offset & 0x0F) as Byte, value);
+        } else if offset >= RAM_OFFSET {
+            self.ram[(offset - RAM_OFFSET) as usize] = value;
+        }
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        if offset < RAM_OFFSET {
+            self.rom[offset as usize]
+        } else if offset < IO_OFFSET {
+            self.ram[(offset - RAM_OFFSET) as usize]
+        } else {
+            Rriot::peek(self, (offset & 0x0F) as Byte)
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        Rriot::tick(self, cycles)
+    }
+
+    fn irq(&self) -> bool {
+        Rriot::irq(self)
+    }
+
+    /** The ROM is the machine's, not state */
+    fn save_state(&self) -> Vec<Byte> {
+        StateWriter::new()
+            .bytes(&self.ram)
+            .block(&self.io.save_state())
+            .into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        state.fill(&mut self.ram)?;
+        self.io.restore_state(&state.block()?)
+    }
+}
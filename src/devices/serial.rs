@@ -0,0 +1,193 @@
+//! The host side of a serial port: where transmitted bytes go and received ones come from
+
+use crate::m6502::*;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+pub trait SerialHost {
+    /** @return the next byte from the host if one is waiting, never blocking */
+    fn receive(&mut self) -> Option<Byte>;
+
+    fn transmit(&mut self, byte: Byte);
+}
+
+/** Nothing plugged in */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Disconnected;
+
+impl SerialHost for Disconnected {
+    fn receive(&mut self) -> Option<Byte> {
+        None
+    }
+
+    fn transmit(&mut self, _byte: Byte) {}
+}
+
+/** Bytes in memory, for tests and scripted sessions */
+#[derive(Debug, Clone, Default)]
+pub struct BufferedSerial {
+    pub input: VecDeque<Byte>,
+    pub output: Vec<Byte>,
+}
+
+impl BufferedSerial {
+    pub fn new(input: &[Byte]) -> Self {
+        Self {
+            input: input.iter().copied().collect(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl SerialHost for BufferedSerial {
+    fn receive(&mut self) -> Option<Byte> {
+        self.input.pop_front()
+    }
+
+    fn transmit(&mut self, byte: Byte) {
+        self.output.push(byte);
+    }
+}
+
+/** Collects what a reader thread reads, so receiving never blocks the emulation */
+struct Reader {
+    bytes: Receiver<Byte>,
+}
+
+impl Reader {
+    fn spawn<R: Read + Send + 'static>(mut input: R) -> Self {
+        let (sender, bytes) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 256];
+            //stops at end of file, on errors, or once the receiving side is gone
+            while let Ok(read @ 1..) = input.read(&mut buffer) {
+                if buffer[..read].iter().any(|byte| sender.send(*byte).is_err()) {
+                    break;
+                }
+            }
+        });
+
+        Self { bytes }
+    }
+
+    fn receive(&mut self) -> Option<Byte> {
+        self.bytes.try_recv().ok()
+    }
+}
+
+/**
+ * Any reader and writer, e.g. stdin/stdout or the two ends of a pipe
+ * - the reader is drained on its own thread
+ * */
+pub struct StreamSerial {
+    reader: Reader,
+    writer: Box<dyn Write>,
+}
+
+impl StreamSerial {
+    pub fn new<R: Read + Send + 'static, W: Write + 'static>(reader: R, writer: W) -> Self {
+        Self {
+            reader: Reader::spawn(reader),
+            writer: Box::new(writer),
+        }
+    }
+
+    /** The terminal the emulator runs in, or whatever its stdio is connected to */
+    pub fn stdio() -> Self {
+        Self::new(io::stdin(), io::stdout())
+    }
+}
+
+impl SerialHost for StreamSerial {
+    fn receive(&mut self) -> Option<Byte> {
+        self.reader.receive()
+    }
+
+    fn transmit(&mut self, byte: Byte) {
+        //a closed pipe is like an unplugged cable, the 6502 can't tell
+        let _ = self.writer.write_all(&[byte]).and_then(|_| self.writer.flush());
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use pty::PtySerial;
+
+#[cfg(target_os = "linux")]
+mod pty {
+    use super::{Reader, SerialHost};
+    use crate::m6502::*;
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::io::{self, Write};
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::path::{Path, PathBuf};
+
+    fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    /**
+     * A Linux pseudo-terminal: connect a terminal program (screen, minicom, picocom)
+     * or an expect script to path()
+     * */
+    pub struct PtySerial {
+        reader: Reader,
+        master: File,
+        /** kept open so reads don't fail while no one is connected */
+        _slave: File,
+        path: PathBuf,
+    }
+
+    impl PtySerial {
+        pub fn open() -> io::Result<Self> {
+            // SAFETY: plain libc calls on a descriptor we own, with buffers sized as passed
+            unsafe {
+                let fd = check(libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY))?;
+                let master = File::from_raw_fd(fd);
+                check(libc::grantpt(fd))?;
+                check(libc::unlockpt(fd))?;
+                let mut name = [0 as libc::c_char; 128];
+                let error = libc::ptsname_r(fd, name.as_mut_ptr(), name.len());
+                if error != 0 {
+                    return Err(io::Error::from_raw_os_error(error));
+                }
+                let path = PathBuf::from(CStr::from_ptr(name.as_ptr()).to_string_lossy().into_owned());
+
+                //raw mode: bytes go through as they are, without echo or line editing
+                let slave = File::options().read(true).write(true).open(&path)?;
+                let mut termios: libc::termios = std::mem::zeroed();
+                check(libc::tcgetattr(slave.as_raw_fd(), &mut termios))?;
+                libc::cfmakeraw(&mut termios);
+                check(libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios))?;
+
+                Ok(Self {
+                    reader: Reader::spawn(master.try_clone()?),
+                    master,
+                    _slave: slave,
+                    path,
+                })
+            }
+        }
+
+        /** @return the terminal device to connect to, e.g. /dev/pts/3 */
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl SerialHost for PtySerial {
+        fn receive(&mut self) -> Option<Byte> {
+            self.reader.receive()
+        }
+
+        fn transmit(&mut self, byte: Byte) {
+            let _ = self.master.write_all(&[byte]);
+        }
+    }
+}
@@ -0,0 +1,677 @@
+//! MOS 6522 Versatile Interface Adapter
+//!
+//! Two 8-bit ports with data direction registers, two 16-bit timers, a shift register
+//! and four handshake/interrupt lines (CA1, CA2, CB1, CB2), all behind 16 registers.
+//! The timers count φ2 cycles, so tick() must be fed the cycles the CPU used.
+
+use super::Device;
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+//registers, by offset from the base address
+pub const ORB: Byte = 0x0;
+pub const ORA: Byte = 0x1;
+pub const DDRB: Byte = 0x2;
+pub const DDRA: Byte = 0x3;
+pub const T1C_L: Byte = 0x4;
+pub const T1C_H: Byte = 0x5;
+pub const T1L_L: Byte = 0x6;
+pub const T1L_H: Byte = 0x7;
+pub const T2C_L: Byte = 0x8;
+pub const T2C_H: Byte = 0x9;
+pub const SR: Byte = 0xA;
+pub const ACR: Byte = 0xB;
+pub const PCR: Byte = 0xC;
+pub const IFR: Byte = 0xD;
+pub const IER: Byte = 0xE;
+/** port A without the handshake */
+pub const ORA_NH: Byte = 0xF;
+
+//interrupt flags, as found in IFR and IER
+pub const IRQ_CA2: Byte = 0x01;
+pub const IRQ_CA1: Byte = 0x02;
+pub const IRQ_SR: Byte = 0x04;
+pub const IRQ_CB2: Byte = 0x08;
+pub const IRQ_CB1: Byte = 0x10;
+pub const IRQ_T2: Byte = 0x20;
+pub const IRQ_T1: Byte = 0x40;
+pub const IRQ_ANY: Byte = 0x80;
+
+/** What a shift register mode (ACR bits 2-4) shifts with */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShiftClock {
+    Disabled,
+    T2,
+    Phi2,
+    External,
+}
+
+/** How a CA2/CB2 line is set up (3 bits of PCR) */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    /** interrupt on an edge, cleared with the port access unless independent */
+    Input { positive: bool, independent: bool },
+    /** low after the port access, high again on the active C1 edge */
+    Handshake,
+    /** low for one cycle after the port access */
+    Pulse,
+    Manual(bool),
+}
+
+impl Control {
+    fn from_pcr(bits: Byte) -> Self {
+        match bits & 0b111 {
+            0b000 => Control::Input { positive: false, independent: false },
+            0b001 => Control::Input { positive: false, independent: true },
+            0b010 => Control::Input { positive: true, independent: false },
+            0b011 => Control::Input { positive: true, independent: true },
+            0b100 => Control::Handshake,
+            0b101 => Control::Pulse,
+            0b110 => Control::Manual(false),
+            _ => Control::Manual(true),
+        }
+    }
+}
+
+/**
+ * One 6522
+ * - ports read the levels on the pins: outputs as driven, inputs as set from outside
+ * - unconnected inputs read high, like the pull-ups on a real board
+ * */
+#[derive(Debug, Clone)]
+pub struct Via {
+    ora: Byte,
+    orb: Byte,
+    ddra: Byte,
+    ddrb: Byte,
+    port_a_input: Byte,
+    port_b_input: Byte,
+    /** port values captured on the active C1 edge, when latching is on */
+    ira_latch: Byte,
+    irb_latch: Byte,
+
+    t1_counter: Word,
+    t1_latch: Word,
+    /** one-shot mode interrupts once per write of T1C-H */
+    t1_armed: bool,
+    /** free-run mode reloads the counter from the latch the cycle after reaching zero */
+    t1_reload: bool,
+    pb7: bool,
+
+    t2_counter: Word,
+    t2_latch_low: Byte,
+    t2_armed: bool,
+
+    sr: Byte,
+    /** bits shifted since the last SR access, 8 when done */
+    sr_count: u8,
+    /** cycles until the next shift for the T2 and φ2 clocked modes */
+    sr_timer: Word,
+
+    acr: Byte,
+    pcr: Byte,
+    ifr: Byte,
+    ier: Byte,
+
+    ca1: bool,
+    ca2: bool,
+    cb1: bool,
+    cb2: bool,
+    ca2_out: bool,
+    cb2_out: bool,
+    /** cycles left on a pulse-mode low */
+    ca2_pulse: u8,
+    cb2_pulse: u8,
+}
+
+impl Via {
+    pub fn new() -> Self {
+        Self {
+            ora: 0,
+            orb: 0,
+            ddra: 0,
+            ddrb: 0,
+            port_a_input: 0xFF,
+            port_b_input: 0xFF,
+            ira_latch: 0,
+            irb_latch: 0,
+            t1_counter: 0xFFFF,
+            t1_latch: 0xFFFF,
+            t1_armed: false,
+            t1_reload: false,
+            pb7: true,
+            t2_counter: 0xFFFF,
+            t2_latch_low: 0xFF,
+            t2_armed: false,
+            sr: 0,
+            sr_count: 8,
+            sr_timer: 0,
+            acr: 0,
+            pcr: 0,
+            ifr: 0,
+            ier: 0,
+            ca1: true,
+            ca2: true,
+            cb1: true,
+            cb2: true,
+            ca2_out: true,
+            cb2_out: true,
+            ca2_pulse: 0,
+            cb2_pulse: 0,
+        }
+    }
+
+    /** What the RES pin does: clear every register but the timers and the shift register */
+    pub fn reset(&mut self) {
+        let (t1_counter, t1_latch, t2_counter, t2_latch_low, sr) =
+            (self.t1_counter, self.t1_latch, self.t2_counter, self.t2_latch_low, self.sr);
+        let (port_a_input, port_b_input) = (self.port_a_input, self.port_b_input);
+        *self = Self {
+            t1_counter,
+            t1_latch,
+            t2_counter,
+            t2_latch_low,
+            sr,
+            port_a_input,
+            port_b_input,
+            ..Self::new()
+        };
+    }
+
+    /** @return true while an enabled interrupt is flagged, i.e. the IRQ output is pulled low */
+    pub fn irq(&self) -> bool {
+        self.ifr & self.ier & 0x7F != 0
+    }
+
+    fn ifr_value(&self) -> Byte {
+        if self.irq() {
+            self.ifr | IRQ_ANY
+        } else {
+            self.ifr
+        }
+    }
+
+    fn set_flag(&mut self, flag: Byte) {
+        self.ifr |= flag;
+    }
+
+    fn clear_flag(&mut self, flag: Byte) {
+        self.ifr &= !flag;
+    }
+
+    fn t1_controls_pb7(&self) -> bool {
+        self.acr & 0x80 != 0
+    }
+
+    fn t1_free_run(&self) -> bool {
+        self.acr & 0x40 != 0
+    }
+
+    fn t2_counts_pulses(&self) -> bool {
+        self.acr & 0x20 != 0
+    }
+
+    /** @return the clock of the shift register, and whether it shifts out */
+    fn shift_mode(&self) -> (ShiftClock, bool) {
+        match (self.acr >> 2) & 0b111 {
+            0b000 => (ShiftClock::Disabled, false),
+            0b001 => (ShiftClock::T2, false),
+            0b010 => (ShiftClock::Phi2, false),
+            0b011 => (ShiftClock::External, false),
+            0b100 => (ShiftClock::T2, true),
+            0b101 => (ShiftClock::T2, true),
+            0b110 => (ShiftClock::Phi2, true),
+            _ => (ShiftClock::External, true),
+        }
+    }
+
+    /** mode 100 shifts out forever, without ever flagging an interrupt */
+    fn shift_free_running(&self) -> bool {
+        (self.acr >> 2) & 0b111 == 0b100
+    }
+
+    fn ca2_control(&self) -> Control {
+        Control::from_pcr(self.pcr >> 1)
+    }
+
+    fn cb2_control(&self) -> Control {
+        Control::from_pcr(self.pcr >> 5)
+    }
+
+    /** @return the levels on the port A pins */
+    pub fn port_a(&self) -> Byte {
+        (self.ora & self.ddra) | (self.port_a_input & !self.ddra)
+    }
+
+    /** @return the levels on the port B pins, PB7 included when T1 drives it */
+    pub fn port_b(&self) -> Byte {
+        let pins = (self.orb & self.ddrb) | (self.port_b_input & !self.ddrb);
+        if self.t1_controls_pb7() {
+            (pins & 0x7F) | ((self.pb7 as Byte) << 7)
+        } else {
+            pins
+        }
+    }
+
+    /** Drive the port A pins from outside - only the bits set as inputs are seen */
+    pub fn set_port_a_input(&mut self, value: Byte) {
+        self.port_a_input = value;
+    }
+
+    /** Drive the port B pins from outside - a falling PB6 counts a T2 pulse */
+    pub fn set_port_b_input(&mut self, value: Byte) {
+        let pb6_fell = self.port_b_input & 0x40 != 0 && value & 0x40 == 0;
+        self.port_b_input = value;
+
+        if pb6_fell && self.t2_counts_pulses() {
+            self.t2_counter = self.t2_counter.wrapping_sub(1);
+            if self.t2_counter == 0 && self.t2_armed {
+                self.t2_armed = false;
+                self.set_flag(IRQ_T2);
+            }
+        }
+    }
+
+    /** @return the level of CA2, as driven by the VIA in the output modes */
+    pub fn ca2(&self) -> bool {
+        match self.ca2_control() {
+            Control::Input { .. } => self.ca2,
+            Control::Manual(level) => level,
+            _ => self.ca2_out,
+        }
+    }
+
+    /** @return the level of CB2, as driven by the VIA in the output modes */
+    pub fn cb2(&self) -> bool {
+        let (clock, out) = self.shift_mode();
+        if clock != ShiftClock::Disabled && out {
+            return self.cb2_out;
+        }
+        match self.cb2_control() {
+            Control::Input { .. } => self.cb2,
+            Control::Manual(level) => level,
+            _ => self.cb2_out,
+        }
+    }
+
+    pub fn set_ca1(&mut self, level: bool) {
+        let positive = self.pcr & 0x01 != 0;
+        if level != self.ca1 && level == positive {
+            self.set_flag(IRQ_CA1);
+            self.ira_latch = self.port_a();
+            if self.ca2_control() == Control::Handshake {
+                self.ca2_out = true;
+            }
+        }
+        self.ca1 = level;
+    }
+
+    pub fn set_ca2(&mut self, level: bool) {
+        if let Control::Input { positive, .. } = self.ca2_control() {
+            if level != self.ca2 && level == positive {
+                self.set_flag(IRQ_CA2);
+            }
+        }
+        self.ca2 = level;
+    }
+
+    /** CB1 is also the shift clock in the external modes, shifting on the rising edge */
+    pub fn set_cb1(&mut self, level: bool) {
+        let positive = self.pcr & 0x10 != 0;
+        if level != self.cb1 {
+            if level == positive {
+                self.set_flag(IRQ_CB1);
+                self.irb_latch = self.port_b();
+                if self.cb2_control() == Control::Handshake {
+                    self.cb2_out = true;
+                }
+            }
+            if level && self.shift_mode().0 == ShiftClock::External {
+                self.shift();
+            }
+        }
+        self.cb1 = level;
+    }
+
+    pub fn set_cb2(&mut self, level: bool) {
+        if let Control::Input { positive, .. } = self.cb2_control() {
+            if level != self.cb2 && level == positive {
+                self.set_flag(IRQ_CB2);
+            }
+        }
+        self.cb2 = level;
+    }
+
+    /** Clear the C1/C2 flags (C2 unless independent) and run the C2 output handshake */
+    fn port_a_handshake(&mut self) {
+        self.clear_flag(IRQ_CA1);
+        match self.ca2_control() {
+            Control::Input { independent: false, .. } => self.clear_flag(IRQ_CA2),
+            //CA2 handshakes on reads and writes, CB2 on writes only
+            Control::Handshake => self.ca2_out = false,
+            Control::Pulse => {
+                self.ca2_out = false;
+                self.ca2_pulse = 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn port_b_handshake(&mut self, on_write: bool) {
+        self.clear_flag(IRQ_CB1);
+        match self.cb2_control() {
+            Control::Input { independent: false, .. } => self.clear_flag(IRQ_CB2),
+            Control::Handshake if on_write => self.cb2_out = false,
+            Control::Pulse if on_write => {
+                self.cb2_out = false;
+                self.cb2_pulse = 1;
+            }
+            _ => {}
+        }
+    }
+
+    /** Read a register without side effects - what a debugger sees */
+    pub fn peek(&self, register: Byte) -> Byte {
+        match register & 0x0F {
+            ORB => {
+                let pins = if self.acr & 0x02 != 0 { self.irb_latch } else { self.port_b() };
+                let value = (self.orb & self.ddrb) | (pins & !self.ddrb);
+                //T1 drives PB7 over ORB and DDRB
+                if self.t1_controls_pb7() {
+                    (value & 0x7F) | ((self.pb7 as Byte) << 7)
+                } else {
+                    value
+                }
+            }
+            ORA | ORA_NH => {
+                if self.acr & 0x01 != 0 {
+                    self.ira_latch
+                } else {
+                    self.port_a()
+                }
+            }
+            DDRB => self.ddrb,
+            DDRA => self.ddra,
+            T1C_L => self.t1_counter as Byte,
+            T1C_H => (self.t1_counter >> 8) as Byte,
+            T1L_L => self.t1_latch as Byte,
+            T1L_H => (self.t1_latch >> 8) as Byte,
+            T2C_L => self.t2_counter as Byte,
+            T2C_H => (self.t2_counter >> 8) as Byte,
+            SR => self.sr,
+            ACR => self.acr,
+            PCR => self.pcr,
+            IFR => self.ifr_value(),
+            _ => self.ier | 0x80,
+        }
+    }
+
+    /** Read a register as the CPU does, clearing flags and handshaking */
+    pub fn read(&mut self, register: Byte) -> Byte {
+        let value = self.peek(register);
+        match register & 0x0F {
+            ORB => self.port_b_handshake(false),
+            ORA => self.port_a_handshake(),
+            T1C_L => self.clear_flag(IRQ_T1),
+            T2C_L => self.clear_flag(IRQ_T2),
+            SR => self.start_shift(),
+            _ => {}
+        }
+        value
+    }
+
+    pub fn write(&mut self, register: Byte, value: Byte) {
+        match register & 0x0F {
+            ORB => {
+                self.orb = value;
+                self.port_b_handshake(true);
+            }
+            ORA => {
+                self.ora = value;
+                self.port_a_handshake();
+            }
+            ORA_NH => self.ora = value,
+            DDRB => self.ddrb = value,
+            DDRA => self.ddra = value,
+            T1C_L | T1L_L => self.t1_latch = (self.t1_latch & 0xFF00) | value as Word,
+            T1C_H => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | (value as Word) << 8;
+                self.t1_counter = self.t1_latch;
+                self.t1_armed = true;
+                self.t1_reload = false;
+                self.clear_flag(IRQ_T1);
+                if self.t1_controls_pb7() {
+                    self.pb7 = false;
+                }
+            }
+            T1L_H => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | (value as Word) << 8;
+                self.clear_flag(IRQ_T1);
+            }
+            T2C_L => self.t2_latch_low = value,
+            T2C_H => {
+                self.t2_counter = (value as Word) << 8 | self.t2_latch_low as Word;
+                self.t2_armed = true;
+                self.clear_flag(IRQ_T2);
+            }
+            SR => {
+                self.sr = value;
+                self.start_shift();
+            }
+            ACR => {
+                self.acr = value;
+                if self.t1_controls_pb7() && !self.t1_armed {
+                    self.pb7 = true;
+                }
+            }
+            PCR => self.pcr = value,
+            IFR => self.ifr &= !value,
+            _ => {
+                if value & 0x80 != 0 {
+                    self.ier |= value & 0x7F;
+                } else {
+                    self.ier &= !value;
+                }
+            }
+        }
+    }
+
+    fn start_shift(&mut self) {
+        self.clear_flag(IRQ_SR);
+        self.sr_count = 0;
+        self.sr_timer = self.shift_period();
+    }
+
+    /** @return the cycles between shifts for the internally clocked modes */
+    fn shift_period(&self) -> Word {
+        match self.shift_mode().0 {
+            ShiftClock::T2 => self.t2_latch_low as Word + 2,
+            _ => 2,
+        }
+    }
+
+    /** Shift one bit, in from CB2 or out to CB2 */
+    fn shift(&mut self) {
+        let (clock, out) = self.shift_mode();
+        if clock == ShiftClock::Disabled || (self.sr_count >= 8 && !self.shift_free_running()) {
+            return;
+        }
+
+        if out {
+            //shifting out rotates, so the byte is still there afterwards
+            let bit = self.sr >> 7;
+            self.cb2_out = bit == 1;
+            self.sr = (self.sr << 1) | bit;
+        } else {
+            self.sr = (self.sr << 1) | self.cb2 as Byte;
+        }
+
+        self.sr_count = self.sr_count.saturating_add(1);
+        if self.sr_count == 8 && !self.shift_free_running() {
+            self.set_flag(IRQ_SR);
+        }
+    }
+
+    /** Advance the timers, the shift register and the C2 pulses by `cycles` φ2 cycles */
+    pub fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.tick_t1();
+            self.tick_t2();
+
+            if matches!(self.shift_mode().0, ShiftClock::T2 | ShiftClock::Phi2) {
+                self.sr_timer = self.sr_timer.saturating_sub(1);
+                if self.sr_timer == 0 {
+                    self.shift();
+                    self.sr_timer = self.shift_period();
+                }
+            }
+
+            if self.ca2_pulse > 0 {
+                self.ca2_pulse -= 1;
+                if self.ca2_pulse == 0 {
+                    self.ca2_out = true;
+                }
+            }
+            if self.cb2_pulse > 0 {
+                self.cb2_pulse -= 1;
+                if self.cb2_pulse == 0 {
+                    self.cb2_out = true;
+                }
+            }
+        }
+    }
+
+    /**
+     * T1 counts down from the latch and times out one cycle after reaching 0,
+     * so a free-running period is latch + 2 cycles
+     * */
+    fn tick_t1(&mut self) {
+        if self.t1_reload {
+            self.t1_reload = false;
+            self.t1_counter = self.t1_latch;
+            return;
+        }
+        if self.t1_counter != 0 {
+            self.t1_counter -= 1;
+            return;
+        }
+
+        self.t1_counter = 0xFFFF;
+        if self.t1_free_run() {
+            self.t1_reload = true;
+            self.set_flag(IRQ_T1);
+            self.pb7 = !self.pb7;
+        } else if self.t1_armed {
+            self.t1_armed = false;
+            self.set_flag(IRQ_T1);
+            self.pb7 = true;
+        }
+    }
+
+    /** T2 is one-shot only, it keeps counting down after timing out */
+    fn tick_t2(&mut self) {
+        if self.t2_counts_pulses() {
+            return;
+        }
+        if self.t2_counter != 0 {
+            self.t2_counter -= 1;
+            return;
+        }
+
+        self.t2_counter = 0xFFFF;
+        if self.t2_armed {
+            self.t2_armed = false;
+            self.set_flag(IRQ_T2);
+        }
+    }
+}
+
+impl Default for Via {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/** 16 registers, mirrored across whatever the VIA is mapped over */
+impl Device for Via {
+    fn read(&mut self, offset: Word) -> Byte {
+        Via::read(self, (offset & 0x0F) as Byte)
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        Via::write(self, (offset & 0x0F) as Byte, value)
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        Via::peek(self, (offset & 0x0F) as Byte)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        Via::tick(self, cycles)
+    }
+
+    fn irq(&self) -> bool {
+        Via::irq(self)
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        StateWriter::new()
+            .bytes(&[self.ora, self.orb, self.ddra, self.ddrb])
+            .bytes(&[self.port_a_input, self.port_b_input, self.ira_latch, self.irb_latch])
+            .word(self.t1_counter)
+            .word(self.t1_latch)
+            .flag(self.t1_armed)
+            .flag(self.t1_reload)
+            .flag(self.pb7)
+            .word(self.t2_counter)
+            .byte(self.t2_latch_low)
+            .flag(self.t2_armed)
+            .bytes(&[self.sr, self.sr_count])
+            .word(self.sr_timer)
+            .bytes(&[self.acr, self.pcr, self.ifr, self.ier])
+            .flag(self.ca1)
+            .flag(self.ca2)
+            .flag(self.cb1)
+            .flag(self.cb2)
+            .flag(self.ca2_out)
+            .flag(self.cb2_out)
+            .bytes(&[self.ca2_pulse, self.cb2_pulse])
+            .into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        self.ora = state.byte()?;
+        self.orb = state.byte()?;
+        self.ddra = state.byte()?;
+        self.ddrb = state.byte()?;
+        self.port_a_input = state.byte()?;
+        self.port_b_input = state.byte()?;
+        self.ira_latch = state.byte()?;
+        self.irb_latch = state.byte()?;
+        self.t1_counter = state.word()?;
+        self.t1_latch = state.word()?;
+        self.t1_armed = state.flag()?;
+        self.t1_reload = state.flag()?;
+        self.pb7 = state.flag()?;
+        self.t2_counter = state.word()?;
+        self.t2_latch_low = state.byte()?;
+        self.t2_armed = state.flag()?;
+        self.sr = state.byte()?;
+        self.sr_count = state.byte()?;
+        self.sr_timer = state.word()?;
+        self.acr = state.byte()?;
+        self.pcr = state.byte()?;
+        self.ifr = state.byte()?;
+        self.ier = state.byte()?;
+        self.ca1 = state.flag()?;
+        self.ca2 = state.flag()?;
+        self.cb1 = state.flag()?;
+        self.cb2 = state.flag()?;
+        self.ca2_out = state.flag()?;
+        self.cb2_out = state.flag()?;
+        self.ca2_pulse = state.byte()?;
+        self.cb2_pulse = state.byte()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+//! MOS 6567/6569 VIC-II video chip, as far as a program can see it
+//!
+//! No picture is drawn: the 47 registers keep what is written to them, the raster counter
+//! runs through the lines of a frame and the raster compare interrupt fires. Programs read
+//! the text screen straight from memory instead. Bad lines don't steal any CPU cycles.
+
+use super::Device;
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+//registers, by offset from the base address - they repeat every 64 bytes
+/** bit 7 is bit 8 of the raster line */
+pub const CONTROL_1: Byte = 0x11;
+/** read: the raster line - write: the line to interrupt on */
+pub const RASTER: Byte = 0x12;
+pub const CONTROL_2: Byte = 0x16;
+/** video matrix and character base, in 1 KiB and 2 KiB steps of the VIC's 16 KiB bank */
+pub const MEMORY_POINTERS: Byte = 0x18;
+/** reading gives the interrupt flags, writing 1s acknowledges them */
+pub const INTERRUPT_FLAGS: Byte = 0x19;
+pub const INTERRUPT_MASK: Byte = 0x1A;
+pub const SPRITE_SPRITE_COLLISION: Byte = 0x1E;
+pub const SPRITE_DATA_COLLISION: Byte = 0x1F;
+pub const BORDER_COLOR: Byte = 0x20;
+pub const BACKGROUND_COLOR: Byte = 0x21;
+
+//interrupt flags
+pub const IRQ_RASTER: Byte = 0x01;
+pub const IRQ_ANY: Byte = 0x80;
+
+/** the registers that exist, the rest read $FF */
+const REGISTERS: usize = 0x2F;
+
+pub const PAL_LINES: u16 = 312;
+pub const PAL_CYCLES_PER_LINE: u32 = 63;
+pub const NTSC_LINES: u16 = 263;
+pub const NTSC_CYCLES_PER_LINE: u32 = 65;
+
+#[derive(Debug, Clone)]
+pub struct VicII {
+    lines: u16,
+    cycles_per_line: u32,
+
+    registers: [Byte; REGISTERS],
+    raster: u16,
+    compare: u16,
+    /** cycles spent on the current line */
+    line_cycles: u32,
+    flags: Byte,
+}
+
+impl VicII {
+    /** A VIC with `lines` raster lines of `cycles_per_line` cycles each */
+    pub fn new(lines: u16, cycles_per_line: u32) -> Self {
+        Self {
+            lines,
+            cycles_per_line,
+            registers: [0; REGISTERS],
+            raster: 0,
+            compare: 0,
+            line_cycles: 0,
+            flags: 0,
+        }
+    }
+
+    /** The 6569 of European machines */
+    pub fn pal() -> Self {
+        Self::new(PAL_LINES, PAL_CYCLES_PER_LINE)
+    }
+
+    /** The 6567R8 of American machines */
+    pub fn ntsc() -> Self {
+        Self::new(NTSC_LINES, NTSC_CYCLES_PER_LINE)
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new(self.lines, self.cycles_per_line);
+    }
+
+    pub fn irq(&self) -> bool {
+        self.flags & self.registers[INTERRUPT_MASK as usize] & 0x0F != 0
+    }
+
+    /** @return the line being drawn */
+    pub fn raster(&self) -> u16 {
+        self.raster
+    }
+
+    /** Read a register without side effects - what a debugger sees */
+    pub fn peek(&self, register: Byte) -> Byte {
+        let register = register & 0x3F;
+        let value = match self.registers.get(register as usize) {
+            Some(value) => *value,
+            None => return 0xFF,
+        };
+        //unused bits read as 1
+        match register {
+            CONTROL_1 => (value & 0x7F) | ((self.raster >> 1) as Byte & 0x80),
+            RASTER => self.raster as Byte,
+            CONTROL_2 => value | 0xC0,
+            MEMORY_POINTERS => value | 0x01,
+            INTERRUPT_FLAGS => self.flags | 0x70 | if self.irq() { IRQ_ANY } else { 0 },
+            INTERRUPT_MASK => value | 0xF0,
+            //nothing is drawn, so nothing collides
+            SPRITE_SPRITE_COLLISION | SPRITE_DATA_COLLISION => 0,
+            BORDER_COLOR.. => value | 0xF0,
+            _ => value,
+        }
+    }
+
+    pub fn write(&mut self, register: Byte, value: Byte) {
+        let register = register & 0x3F;
+        match register {
+            CONTROL_1 => self.set_compare((self.compare & 0xFF) | ((value as u16 & 0x80) << 1)),
+            RASTER => self.set_compare((self.compare & 0x100) | value as u16),
+            INTERRUPT_FLAGS => {
+                self.flags &= !value;
+                return;
+            }
+            _ => {}
+        }
+        if let Some(register) = self.registers.get_mut(register as usize) {
+            *register = value;
+        }
+    }
+
+    /** Moving the compare line onto the current one triggers at once */
+    fn set_compare(&mut self, compare: u16) {
+        if compare != self.compare && compare == self.raster {
+            self.flags |= IRQ_RASTER;
+        }
+        self.compare = compare;
+    }
+
+    /** Advance the raster by `cycles` cycles */
+    pub fn tick(&mut self, cycles: u32) {
+        self.line_cycles += cycles;
+        while self.line_cycles >= self.cycles_per_line {
+            self.line_cycles -= self.cycles_per_line;
+            self.raster = (self.raster + 1) % self.lines;
+            if self.raster == self.compare {
+                self.flags |= IRQ_RASTER;
+            }
+        }
+    }
+}
+
+impl Device for VicII {
+    fn read(&mut self, offset: Word) -> Byte {
+        VicII::peek(self, offset as Byte)
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        VicII::write(self, offset as Byte, value)
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        VicII::peek(self, offset as Byte)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        VicII::tick(self, cycles)
+    }
+
+    fn irq(&self) -> bool {
+        VicII::irq(self)
+    }
+
+    /** The line count and length are the video standard's, not state */
+    fn save_state(&self) -> Vec<Byte> {
+        StateWriter::new()
+            .bytes(&self.registers)
+            .word(self.raster)
+            .word(self.compare)
+            .long(self.line_cycles)
+            .byte(self.flags)
+            .into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        state.fill(&mut self.registers)?;
+        self.raster = state.word()?;
+        self.compare = state.word()?;
+        self.line_cycles = state.long()?;
+        self.flags = state.byte()?;
+        Ok(())
+    }
+}
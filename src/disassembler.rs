@@ -0,0 +1,443 @@
+//! Opcode table and disassembler covering all 256 opcodes
+
+use crate::m6502::*;
+use crate::symbols::Symbols;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+impl AddressingMode {
+    /** @return the number of bytes following the opcode */
+    pub fn operand_bytes(&self) -> Word {
+        match self {
+            AddressingMode::Implied | AddressingMode::Accumulator => 0,
+            AddressingMode::Absolute
+            | AddressingMode::AbsoluteX
+            | AddressingMode::AbsoluteY
+            | AddressingMode::Indirect => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    /** not part of the documented instruction set */
+    pub illegal: bool,
+}
+
+impl Opcode {
+    /** @return the length of the whole instruction in bytes */
+    pub fn size(&self) -> Word {
+        1 + self.mode.operand_bytes()
+    }
+}
+
+const fn op(mnemonic: &'static str, mode: AddressingMode, illegal: bool) -> Opcode {
+    Opcode {
+        mnemonic,
+        mode,
+        illegal,
+    }
+}
+
+use AddressingMode::*;
+
+pub const OPCODES: [Opcode; 256] = [
+    op("BRK", Implied, false), //$00
+    op("ORA", IndirectX, false), //$01
+    op("KIL", Implied, true), //$02
+    op("SLO", IndirectX, true), //$03
+    op("NOP", ZeroPage, true), //$04
+    op("ORA", ZeroPage, false), //$05
+    op("ASL", ZeroPage, false), //$06
+    op("SLO", ZeroPage, true), //$07
+    op("PHP", Implied, false), //$08
+    op("ORA", Immediate, false), //$09
+    op("ASL", Accumulator, false), //$0A
+    op("ANC", Immediate, true), //$0B
+    op("NOP", Absolute, true), //$0C
+    op("ORA", Absolute, false), //$0D
+    op("ASL", Absolute, false), //$0E
+    op("SLO", Absolute, true), //$0F
+    op("BPL", Relative, false), //$10
+    op("ORA", IndirectY, false), //$11
+    op("KIL", Implied, true), //$12
+    op("SLO", IndirectY, true), //$13
+    op("NOP", ZeroPageX, true), //$14
+    op("ORA", ZeroPageX, false), //$15
+    op("ASL", ZeroPageX, false), //$16
+    op("SLO", ZeroPageX, true), //$17
+    op("CLC", Implied, false), //$18
+    op("ORA", AbsoluteY, false), //$19
+    op("NOP", Implied, true), //$1A
+    op("SLO", AbsoluteY, true), //$1B
+    op("NOP", AbsoluteX, true), //$1C
+    op("ORA", AbsoluteX, false), //$1D
+    op("ASL", AbsoluteX, false), //$1E
+    op("SLO", AbsoluteX, true), //$1F
+    op("JSR", Absolute, false), //$20
+    op("AND", IndirectX, false), //$21
+    op("KIL", Implied, true), //$22
+    op("RLA", IndirectX, true), //$23
+    op("BIT", ZeroPage, false), //$24
+    op("AND", ZeroPage, false), //$25
+    op("ROL", ZeroPage, false), //$26
+    op("RLA", ZeroPage, true), //$27
+    op("PLP", Implied, false), //$28
+    op("AND", Immediate, false), //$29
+    op("ROL", Accumulator, false), //$2A
+    op("ANC", Immediate, true), //$2B
+    op("BIT", Absolute, false), //$2C
+    op("AND", Absolute, false), //$2D
+    op("ROL", Absolute, false), //$2E
+    op("RLA", Absolute, true), //$2F
+    op("BMI", Relative, false), //$30
+    op("AND", IndirectY, false), //$31
+    op("KIL", Implied, true), //$32
+    op("RLA", IndirectY, true), //$33
+    op("NOP", ZeroPageX, true), //$34
+    op("AND", ZeroPageX, false), //$35
+    op("ROL", ZeroPageX, false), //$36
+    op("RLA", ZeroPageX, true), //$37
+    op("SEC", Implied, false), //$38
+    op("AND", AbsoluteY, false), //$39
+    op("NOP", Implied, true), //$3A
+    op("RLA", AbsoluteY, true), //$3B
+    op("NOP", AbsoluteX, true), //$3C
+    op("AND", AbsoluteX, false), //$3D
+    op("ROL", AbsoluteX, false), //$3E
+    op("RLA", AbsoluteX, true), //$3F
+    op("RTI", Implied, false), //$40
+    op("EOR", IndirectX, false), //$41
+    op("KIL", Implied, true), //$42
+    op("SRE", IndirectX, true), //$43
+    op("NOP", ZeroPage, true), //$44
+    op("EOR", ZeroPage, false), //$45
+    op("LSR", ZeroPage, false), //$46
+    op("SRE", ZeroPage, true), //$47
+    op("PHA", Implied, false), //$48
+    op("EOR", Immediate, false), //$49
+    op("LSR", Accumulator, false), //$4A
+    op("ALR", Immediate, true), //$4B
+    op("JMP", Absolute, false), //$4C
+    op("EOR", Absolute, false), //$4D
+    op("LSR", Absolute, false), //$4E
+    op("SRE", Absolute, true), //$4F
+    op("BVC", Relative, false), //$50
+    op("EOR", IndirectY, false), //$51
+    op("KIL", Implied, true), //$52
+    op("SRE", IndirectY, true), //$53
+    op("NOP", ZeroPageX, true), //$54
+    op("EOR", ZeroPageX, false), //$55
+    op("LSR", ZeroPageX, false), //$56
+    op("SRE", ZeroPageX, true), //$57
+    op("CLI", Implied, false), //$58
+    op("EOR", AbsoluteY, false), //$59
+    op("NOP", Implied, true), //$5A
+    op("SRE", AbsoluteY, true), //$5B
+    op("NOP", AbsoluteX, true), //$5C
+    op("EOR", AbsoluteX, false), //$5D
+    op("LSR", AbsoluteX, false), //$5E
+    op("SRE", AbsoluteX, true), //$5F
+    op("RTS", Implied, false), //$60
+    op("ADC", IndirectX, false), //$61
+    op("KIL", Implied, true), //$62
+    op("RRA", IndirectX, true), //$63
+    op("NOP", ZeroPage, true), //$64
+    op("ADC", ZeroPage, false), //$65
+    op("ROR", ZeroPage, false), //$66
+    op("RRA", ZeroPage, true), //$67
+    op("PLA", Implied, false), //$68
+    op("ADC", Immediate, false), //$69
+    op("ROR", Accumulator, false), //$6A
+    op("ARR", Immediate, true), //$6B
+    op("JMP", Indirect, false), //$6C
+    op("ADC", Absolute, false), //$6D
+    op("ROR", Absolute, false), //$6E
+    op("RRA", Absolute, true), //$6F
+    op("BVS", Relative, false), //$70
+    op("ADC", IndirectY, false), //$71
+    op("KIL", Implied, true), //$72
+    op("RRA", IndirectY, true), //$73
+    op("NOP", ZeroPageX, true), //$74
+    op("ADC", ZeroPageX, false), //$75
+    op("ROR", ZeroPageX, false), //$76
+    op("RRA", ZeroPageX, true), //$77
+    op("SEI", Implied, false), //$78
+    op("ADC", AbsoluteY, false), //$79
+    op("NOP", Implied, true), //$7A
+    op("RRA", AbsoluteY, true), //$7B
+    op("NOP", AbsoluteX, true), //$7C
+    op("ADC", AbsoluteX, false), //$7D
+    op("ROR", AbsoluteX, false), //$7E
+    op("RRA", AbsoluteX, true), //$7F
+    op("NOP", Immediate, true), //$80
+    op("STA", IndirectX, false), //$81
+    op("NOP", Immediate, true), //$82
+    op("SAX", IndirectX, true), //$83
+    op("STY", ZeroPage, false), //$84
+    op("STA", ZeroPage, false), //$85
+    op("STX", ZeroPage, false), //$86
+    op("SAX", ZeroPage, true), //$87
+    op("DEY", Implied, false), //$88
+    op("NOP", Immediate, true), //$89
+    op("TXA", Implied, false), //$8A
+    op("XAA", Immediate, true), //$8B
+    op("STY", Absolute, false), //$8C
+    op("STA", Absolute, false), //$8D
+    op("STX", Absolute, false), //$8E
+    op("SAX", Absolute, true), //$8F
+    op("BCC", Relative, false), //$90
+    op("STA", IndirectY, false), //$91
+    op("KIL", Implied, true), //$92
+    op("AHX", IndirectY, true), //$93
+    op("STY", ZeroPageX, false), //$94
+    op("STA", ZeroPageX, false), //$95
+    op("STX", ZeroPageY, false), //$96
+    op("SAX", ZeroPageY, true), //$97
+    op("TYA", Implied, false), //$98
+    op("STA", AbsoluteY, false), //$99
+    op("TXS", Implied, false), //$9A
+    op("TAS", AbsoluteY, true), //$9B
+    op("SHY", AbsoluteX, true), //$9C
+    op("STA", AbsoluteX, false), //$9D
+    op("SHX", AbsoluteY, true), //$9E
+    op("AHX", AbsoluteY, true), //$9F
+    op("LDY", Immediate, false), //$A0
+    op("LDA", IndirectX, false), //$A1
+    op("LDX", Immediate, false), //$A2
+    op("LAX", IndirectX, true), //$A3
+    op("LDY", ZeroPage, false), //$A4
+    op("LDA", ZeroPage, false), //$A5
+    op("LDX", ZeroPage, false), //$A6
+    op("LAX", ZeroPage, true), //$A7
+    op("TAY", Implied, false), //$A8
+    op("LDA", Immediate, false), //$A9
+    op("TAX", Implied, false), //$AA
+    op("LAX", Immediate, true), //$AB
+    op("LDY", Absolute, false), //$AC
+    op("LDA", Absolute, false), //$AD
+    op("LDX", Absolute, false), //$AE
+    op("LAX", Absolute, true), //$AF
+    op("BCS", Relative, false), //$B0
+    op("LDA", IndirectY, false), //$B1
+    op("KIL", Implied, true), //$B2
+    op("LAX", IndirectY, true), //$B3
+    op("LDY", ZeroPageX, false), //$B4
+    op("LDA", ZeroPageX, false), //$B5
+    op("LDX", ZeroPageY, false), //$B6
+    op("LAX", ZeroPageY, true), //$B7
+    op("CLV", Implied, false), //$B8
+    op("LDA", AbsoluteY, false), //$B9
+    op("TSX", Implied, false), //$BA
+    op("LAS", AbsoluteY, true), //$BB
+    op("LDY", AbsoluteX, false), //$BC
+    op("LDA", AbsoluteX, false), //$BD
+    op("LDX", AbsoluteY, false), //$BE
+    op("LAX", AbsoluteY, true), //$BF
+    op("CPY", Immediate, false), //$C0
+    op("CMP", IndirectX, false), //$C1
+    op("NOP", Immediate, true), //$C2
+    op("DCP", IndirectX, true), //$C3
+    op("CPY", ZeroPage, false), //$C4
+    op("CMP", ZeroPage, false), //$C5
+    op("DEC", ZeroPage, false), //$C6
+    op("DCP", ZeroPage, true), //$C7
+    op("INY", Implied, false), //$C8
+    op("CMP", Immediate, false), //$C9
+    op("DEX", Implied, false), //$CA
+    op("AXS", Immediate, true), //$CB
+    op("CPY", Absolute, false), //$CC
+    op("CMP", Absolute, false), //$CD
+    op("DEC", Absolute, false), //$CE
+    op("DCP", Absolute, true), //$CF
+    op("BNE", Relative, false), //$D0
+    op("CMP", IndirectY, false), //$D1
+    op("KIL", Implied, true), //$D2
+    op("DCP", IndirectY, true), //$D3
+    op("NOP", ZeroPageX, true), //$D4
+    op("CMP", ZeroPageX, false), //$D5
+    op("DEC", ZeroPageX, false), //$D6
+    op("DCP", ZeroPageX, true), //$D7
+    op("CLD", Implied, false), //$D8
+    op("CMP", AbsoluteY, false), //$D9
+    op("NOP", Implied, true), //$DA
+    op("DCP", AbsoluteY, true), //$DB
+    op("NOP", AbsoluteX, true), //$DC
+    op("CMP", AbsoluteX, false), //$DD
+    op("DEC", AbsoluteX, false), //$DE
+    op("DCP", AbsoluteX, true), //$DF
+    op("CPX", Immediate, false), //$E0
+    op("SBC", IndirectX, false), //$E1
+    op("NOP", Immediate, true), //$E2
+    op("ISB", IndirectX, true), //$E3
+    op("CPX", ZeroPage, false), //$E4
+    op("SBC", ZeroPage, false), //$E5
+    op("INC", ZeroPage, false), //$E6
+    op("ISB", ZeroPage, true), //$E7
+    op("INX", Implied, false), //$E8
+    op("SBC", Immediate, false), //$E9
+    op("NOP", Implied, false), //$EA
+    op("SBC", Immediate, true), //$EB
+    op("CPX", Absolute, false), //$EC
+    op("SBC", Absolute, false), //$ED
+    op("INC", Absolute, false), //$EE
+    op("ISB", Absolute, true), //$EF
+    op("BEQ", Relative, false), //$F0
+    op("SBC", IndirectY, false), //$F1
+    op("KIL", Implied, true), //$F2
+    op("ISB", IndirectY, true), //$F3
+    op("NOP", ZeroPageX, true), //$F4
+    op("SBC", ZeroPageX, false), //$F5
+    op("INC", ZeroPageX, false), //$F6
+    op("ISB", ZeroPageX, true), //$F7
+    op("SED", Implied, false), //$F8
+    op("SBC", AbsoluteY, false), //$F9
+    op("NOP", Implied, true), //$FA
+    op("ISB", AbsoluteY, true), //$FB
+    op("NOP", AbsoluteX, true), //$FC
+    op("SBC", AbsoluteX, false), //$FD
+    op("INC", AbsoluteX, false), //$FE
+    op("ISB", AbsoluteX, true), //$FF
+];
+
+/** A decoded instruction as it sits in memory */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: Word,
+    pub opcode: Opcode,
+    /** the opcode byte followed by its operand bytes */
+    pub bytes: Vec<Byte>,
+}
+
+impl Instruction {
+    /** @return the operand as a byte or little endian word (0 for implied instructions) */
+    pub fn operand(&self) -> Word {
+        match self.bytes.len() {
+            2 => self.bytes[1] as Word,
+            3 => Word::from_le_bytes([self.bytes[1], self.bytes[2]]),
+            _ => 0,
+        }
+    }
+
+    /** @return the destination of a relative branch */
+    pub fn branch_target(&self) -> Word {
+        let offset = self.operand() as Byte as i8;
+        self.address
+            .wrapping_add(self.opcode.size())
+            .wrapping_add(offset as Word)
+    }
+
+    /** @return the instruction as assembly source, e.g. "LDA ($80),Y" */
+    pub fn text(&self) -> String {
+        self.text_with(&|address| format!("${:04X}", address))
+    }
+
+    /**
+     * Like text(), but absolute addresses and branch targets are rendered by `name`
+     * - lets callers substitute labels for addresses
+     * */
+    pub fn text_with(&self, name: &dyn Fn(Word) -> String) -> String {
+        let mnemonic = self.opcode.mnemonic;
+        let operand = self.operand();
+        match self.opcode.mode {
+            Implied => mnemonic.to_string(),
+            Accumulator => format!("{} A", mnemonic),
+            Immediate => format!("{} #${:02X}", mnemonic, operand),
+            ZeroPage => format!("{} ${:02X}", mnemonic, operand),
+            ZeroPageX => format!("{} ${:02X},X", mnemonic, operand),
+            ZeroPageY => format!("{} ${:02X},Y", mnemonic, operand),
+            Absolute => format!("{} {}", mnemonic, name(operand)),
+            AbsoluteX => format!("{} {},X", mnemonic, name(operand)),
+            AbsoluteY => format!("{} {},Y", mnemonic, name(operand)),
+            Indirect => format!("{} ({})", mnemonic, name(operand)),
+            IndirectX => format!("{} (${:02X},X)", mnemonic, operand),
+            IndirectY => format!("{} (${:02X}),Y", mnemonic, operand),
+            Relative => format!("{} {}", mnemonic, name(self.branch_target())),
+        }
+    }
+}
+
+/** Decode the instruction at `address` without disturbing the bus */
+pub fn decode<B: Bus>(bus: &B, address: Word) -> Instruction {
+    let opcode = OPCODES[bus.peek(address) as usize];
+    let bytes = (0..opcode.size())
+        .map(|offset| bus.peek(address.wrapping_add(offset)))
+        .collect();
+
+    Instruction {
+        address,
+        opcode,
+        bytes,
+    }
+}
+
+/**
+ * Disassemble `count` instructions starting at `address`
+ * @return one "ADDR  BYTES  TEXT" line per instruction
+ * */
+pub fn disassemble<B: Bus>(bus: &B, address: Word, count: usize) -> Vec<String> {
+    let mut address = address;
+    let mut lines = Vec::with_capacity(count);
+    for _ in 0..count {
+        let instruction = decode(bus, address);
+        lines.push(format!(
+            "{:04X}  {:<8}  {}",
+            address,
+            hex_bytes(&instruction.bytes),
+            instruction.text()
+        ));
+        address = address.wrapping_add(instruction.opcode.size());
+    }
+
+    lines
+}
+
+/**
+ * Like disassemble(), with labels in operands and a `label:` line before every labelled address
+ * */
+pub fn disassemble_with<B: Bus>(bus: &B, address: Word, count: usize, symbols: &Symbols) -> Vec<String> {
+    let mut address = address;
+    let mut lines = Vec::with_capacity(count);
+    for _ in 0..count {
+        if let Some(name) = symbols.name_of(address) {
+            lines.push(format!("{}:", name));
+        }
+        let instruction = decode(bus, address);
+        lines.push(format!(
+            "{:04X}  {:<8}  {}",
+            address,
+            hex_bytes(&instruction.bytes),
+            instruction.text_with(&|address| symbols.label(address))
+        ));
+        address = address.wrapping_add(instruction.opcode.size());
+    }
+
+    lines
+}
+
+/** "A9 42" style dump of instruction bytes */
+pub fn hex_bytes(bytes: &[Byte]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,77 @@
+//! What the machine binaries share: parsing addresses, drawing screens and running in real time
+
+use crate::m6502::*;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/** An address in hex, with or without a leading `$` or `0x` */
+pub fn parse_word(text: &str) -> Option<Word> {
+    let text = text.trim_start_matches('$').trim_start_matches("0x");
+    u16::from_str_radix(text, 16).ok()
+}
+
+/** Draw text `columns` wide in a frame, over the previous one if there was one */
+pub fn draw_framed(lines: &[String], columns: usize, redraw: bool) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    if redraw {
+        write!(out, "\x1b[{}A", lines.len() + 2)?;
+    }
+    let border = format!("+{}+", "-".repeat(columns));
+    writeln!(out, "{}", border)?;
+    for line in lines {
+        writeln!(out, "|{}|", line)?;
+    }
+    writeln!(out, "{}", border)?;
+    out.flush()
+}
+
+/** Keeps an emulated clock close to real time by sleeping whenever it gets ahead */
+pub struct Throttle {
+    start: Instant,
+    hz: u64,
+    cycles: u64,
+}
+
+impl Throttle {
+    pub fn new(hz: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            hz,
+            cycles: 0,
+        }
+    }
+
+    /** Count `cycles` more, without waiting */
+    pub fn add(&mut self, cycles: s32) {
+        self.cycles += cycles.max(0) as u64;
+    }
+
+    /** Count `cycles` more and sleep until real time catches up with them */
+    pub fn wait(&mut self, cycles: s32) {
+        self.add(cycles);
+        let due = Duration::from_micros(self.cycles * 1_000_000 / self.hz);
+        if let Some(ahead) = due.checked_sub(self.start.elapsed()) {
+            thread::sleep(ahead);
+        }
+    }
+
+    /** @return the cycles counted so far */
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+}
+
+/**
+ * Say so if the CPU halted on an opcode it doesn't run
+ * @return the code to exit with, or None while the CPU still runs
+ * */
+pub fn halted<B: Bus>(cpu: &CPU, bus: &B) -> Option<ExitCode> {
+    if cpu.halted() {
+        eprintln!("halted on ${:02X} at ${:04X}", bus.peek(cpu.pc()), cpu.pc());
+        Some(ExitCode::FAILURE)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,127 @@
+//! Harness for self-checking test programs such as Klaus Dormann's
+//! 6502_functional_test and 6502_decimal_test
+//!
+//! Those programs report failure by trapping: jumping or branching to themselves.
+//! Reaching the trap at the success address means every test passed.
+
+use crate::m6502::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/** How a test program ended */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /** PC got stuck at the success address */
+    Success { cycles: u64 },
+    /** PC got stuck anywhere else, the address identifies the failing test in the listing */
+    Trapped { pc: Word, cycles: u64 },
+    /** the test program reached the success address but left an error code behind */
+    Failed { pc: Word, error: Byte, cycles: u64 },
+    /** the CPU halted on an opcode the core doesn't run, PC is left on it */
+    Halted { pc: Word, cycles: u64 },
+    /** the cycle budget ran out before PC got stuck */
+    TimedOut { pc: Word, cycles: u64 },
+}
+
+impl Outcome {
+    pub fn is_success(&self) -> bool {
+        matches!(self, Outcome::Success { .. })
+    }
+}
+
+/** Where a test image goes and how to tell whether it passed */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestRom {
+    pub load_address: Word,
+    pub entry: Word,
+    pub success: Word,
+    /** a byte the program sets non-zero on failure, e.g. ERROR in the decimal test */
+    pub error_flag: Option<Word>,
+    pub max_cycles: u64,
+}
+
+impl TestRom {
+    /**
+     * 6502_functional_test.bin as published: a 64 KiB image loaded at $0000,
+     * started at $0400, and trapping at $3469 on success
+     * */
+    pub fn functional_test() -> Self {
+        Self {
+            load_address: 0x0000,
+            entry: 0x0400,
+            success: 0x3469,
+            error_flag: None,
+            max_cycles: 100_000_000,
+        }
+    }
+
+    /**
+     * 6502_decimal_test.bin assembled at $0200, with ERROR at $000B
+     * - the success address depends on how it was assembled, take it from the listing
+     * */
+    pub fn decimal_test(success: Word) -> Self {
+        Self {
+            load_address: 0x0200,
+            entry: 0x0200,
+            success,
+            error_flag: Some(0x000B),
+            max_cycles: 100_000_000,
+        }
+    }
+
+    /** Load `image`, start it at the entry point and run it until it traps */
+    pub fn run(&self, image: &[Byte]) -> Outcome {
+        let mut mem = Mem::new();
+        let mut cpu = CPU::new();
+        cpu.reset(self.entry, &mut mem);
+        for (offset, byte) in image.iter().enumerate() {
+            mem[self.load_address.wrapping_add(offset as Word)] = *byte;
+        }
+
+        let outcome = run_until_trap(&mut cpu, &mut mem, self.success, self.max_cycles);
+        match (outcome, self.error_flag) {
+            (Outcome::Success { cycles }, Some(error_flag)) if mem[error_flag] != 0 => Outcome::Failed {
+                pc: self.success,
+                error: mem[error_flag],
+                cycles,
+            },
+            _ => outcome,
+        }
+    }
+
+    /** Same as run(), with the image read from a local file */
+    pub fn run_file<P: AsRef<Path>>(&self, path: P) -> io::Result<Outcome> {
+        let image = fs::read(path)?;
+        Ok(self.run(&image))
+    }
+}
+
+/**
+ * Step until an instruction leaves PC where it was
+ * @return Success if that happened at `success`, Halted if the CPU jammed rather than trapped
+ * */
+pub fn run_until_trap<B: Bus>(cpu: &mut CPU, memory: &mut B, success: Word, max_cycles: u64) -> Outcome {
+    let mut cycles: u64 = 0;
+    while cycles < max_cycles {
+        let pc = cpu.pc();
+        cycles += cpu.step(memory) as u64;
+
+        //a jammed CPU leaves PC where it was too, that's not a trap
+        if cpu.halted() {
+            return Outcome::Halted { pc, cycles };
+        }
+        if cpu.pc() == pc {
+            return if pc == success {
+                Outcome::Success { cycles }
+            } else {
+                Outcome::Trapped { pc, cycles }
+            };
+        }
+    }
+
+    Outcome::TimedOut {
+        pc: cpu.pc(),
+        cycles,
+    }
+}
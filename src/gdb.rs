@@ -0,0 +1,489 @@
+//! GDB remote serial protocol stub for the 6502 core
+
+use crate::m6502::*;
+use crate::symbols::Symbols;
+use std::collections::BTreeSet;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+/** Register numbers as seen by the debugger front-end */
+pub const REG_A: usize = 0;
+pub const REG_X: usize = 1;
+pub const REG_Y: usize = 2;
+pub const REG_SP: usize = 3;
+pub const REG_PC: usize = 4;
+pub const REG_P: usize = 5;
+
+const SIGINT: Byte = 2;
+const SIGILL: Byte = 4;
+const SIGTRAP: Byte = 5;
+
+/** Ctrl-C sent by the front-end to interrupt a running target */
+const INTERRUPT: u8 = 0x03;
+
+/** The largest packet we accept or send, as advertised in qSupported */
+const PACKET_SIZE: usize = 0x1000;
+
+/** How many instructions run between checks for an interrupt request */
+const INTERRUPT_POLL_INTERVAL: u32 = 1024;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.m6502.core">
+    <reg name="a" bitsize="8" regnum="0"/>
+    <reg name="x" bitsize="8"/>
+    <reg name="y" bitsize="8"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="p" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+/** What the session loop should do after a packet was handled */
+enum Action {
+    Reply(String),
+    Step,
+    Continue,
+    /** Send the (optional) reply, then end the session */
+    Close(Option<String>),
+}
+
+/** Why the target stopped */
+enum StopReason {
+    Step,
+    SoftwareBreakpoint,
+    HardwareBreakpoint,
+    Interrupted,
+    /** The CPU jammed on an opcode it doesn't run, it won't move again without a reset */
+    Halted,
+}
+
+/**
+ * A GDB remote serial protocol server driving a CPU and its bus.
+ * - registers are exposed in the order A, X, Y, SP, PC, P
+ * - software (Z0) and hardware (Z1) breakpoints both stop before the instruction at the address runs
+ * - memory reads use Bus::peek, so inspecting I/O registers never disturbs a device
+ */
+pub struct GdbStub<B: Bus> {
+    pub cpu: CPU,
+    pub bus: B,
+    sw_breakpoints: BTreeSet<Word>,
+    hw_breakpoints: BTreeSet<Word>,
+    no_ack_mode: bool,
+}
+
+impl<B: Bus> GdbStub<B> {
+    pub fn new(cpu: CPU, bus: B) -> Self {
+        Self {
+            cpu,
+            bus,
+            sw_breakpoints: BTreeSet::new(),
+            hw_breakpoints: BTreeSet::new(),
+            no_ack_mode: false,
+        }
+    }
+
+    /** Insert a software breakpoint without a front-end asking for it */
+    pub fn add_breakpoint(&mut self, address: Word) {
+        self.sw_breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: Word) -> bool {
+        self.sw_breakpoints.remove(&address)
+    }
+
+    /**
+     * Insert a software breakpoint by name, e.g. "main_loop", or by address, e.g. "$C012"
+     * @return the address, None if `symbols` doesn't know the name
+     * */
+    pub fn add_breakpoint_at(&mut self, symbols: &Symbols, name: &str) -> Option<Word> {
+        let address = symbols.resolve(name)?;
+        self.add_breakpoint(address);
+        Some(address)
+    }
+
+    /** Wait for one debugger connection on `address` and serve it until it detaches */
+    pub fn listen<A: ToSocketAddrs>(&mut self, address: A) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+        self.serve(stream)
+    }
+
+    /** Serve a single debugger session over an already connected stream */
+    pub fn serve(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        self.no_ack_mode = false;
+
+        while let Some(packet) = self.read_packet(&mut stream)? {
+            match self.handle_packet(&packet) {
+                Action::Reply(reply) => {
+                    self.write_packet(&mut stream, &reply)?;
+                    //the OK to QStartNoAckMode is still acknowledged
+                    if packet == "QStartNoAckMode" {
+                        self.no_ack_mode = true;
+                    }
+                }
+                Action::Step => {
+                    self.cpu.step(&mut self.bus);
+                    let reply = if self.cpu.halted() {
+                        self.stop_reply(StopReason::Halted)
+                    } else {
+                        self.stop_reply(StopReason::Step)
+                    };
+                    self.write_packet(&mut stream, &reply)?;
+                }
+                Action::Continue => match self.run(&mut stream)? {
+                    Some(reason) => {
+                        let reply = self.stop_reply(reason);
+                        self.write_packet(&mut stream, &reply)?;
+                    }
+                    None => return Ok(()),
+                },
+                Action::Close(reply) => {
+                    if let Some(reply) = reply {
+                        self.write_packet(&mut stream, &reply)?;
+                    }
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Run until a breakpoint is hit, the CPU halts or the front-end sends an interrupt
+     * @return None if the connection was closed while running
+     * */
+    fn run(&mut self, stream: &mut TcpStream) -> io::Result<Option<StopReason>> {
+        let mut until_poll = INTERRUPT_POLL_INTERVAL;
+        loop {
+            //always move off the current PC, otherwise we'd stop on the breakpoint we're sitting on
+            self.cpu.step(&mut self.bus);
+            if self.cpu.halted() {
+                return Ok(Some(StopReason::Halted));
+            }
+
+            let pc = self.cpu.pc();
+            if self.sw_breakpoints.contains(&pc) {
+                return Ok(Some(StopReason::SoftwareBreakpoint));
+            }
+            if self.hw_breakpoints.contains(&pc) {
+                return Ok(Some(StopReason::HardwareBreakpoint));
+            }
+
+            until_poll -= 1;
+            if until_poll == 0 {
+                until_poll = INTERRUPT_POLL_INTERVAL;
+                stream.set_nonblocking(true)?;
+                let mut byte = [0u8; 1];
+                let polled = stream.read(&mut byte);
+                stream.set_nonblocking(false)?;
+                match polled {
+                    Ok(0) => return Ok(None),
+                    Ok(_) if byte[0] == INTERRUPT => return Ok(Some(StopReason::Interrupted)),
+                    Ok(_) => {}
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+
+    fn stop_reply(&self, reason: StopReason) -> String {
+        match reason {
+            StopReason::Step => format!("S{:02x}", SIGTRAP),
+            StopReason::SoftwareBreakpoint => format!("T{:02x}swbreak:;", SIGTRAP),
+            StopReason::HardwareBreakpoint => format!("T{:02x}hwbreak:;", SIGTRAP),
+            StopReason::Interrupted => format!("S{:02x}", SIGINT),
+            StopReason::Halted => format!("S{:02x}", SIGILL),
+        }
+    }
+
+    fn handle_packet(&mut self, packet: &str) -> Action {
+        //the payload may hold any character, split after the first one rather than its first byte
+        let command_length = packet.chars().next().map_or(0, char::len_utf8);
+        let (command, args) = packet.split_at(command_length);
+        let reply = match command {
+            "?" if self.cpu.halted() => self.stop_reply(StopReason::Halted),
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => self.read_registers(),
+            "G" => self.write_registers(args),
+            "p" => self.read_register(args),
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "Z" => self.insert_breakpoint(args),
+            "z" => self.remove_breakpoint_packet(args),
+            "H" => "OK".to_string(),
+            "s" | "c" => {
+                if !args.is_empty() {
+                    match u16::from_str_radix(args, 16) {
+                        Ok(address) => self.cpu.set_pc(address),
+                        Err(_) => return Action::Reply("E01".to_string()),
+                    }
+                }
+                return if command == "s" { Action::Step } else { Action::Continue };
+            }
+            "k" => return Action::Close(None),
+            "D" => return Action::Close(Some("OK".to_string())),
+            "q" | "Q" | "v" => return self.handle_query(packet),
+            _ => String::new(),
+        };
+
+        Action::Reply(reply)
+    }
+
+    fn handle_query(&mut self, packet: &str) -> Action {
+        let reply = if packet.starts_with("qSupported") {
+            format!("PacketSize={:x};QStartNoAckMode+;qXfer:features:read+;swbreak+;hwbreak+", PACKET_SIZE)
+        } else if packet == "QStartNoAckMode" {
+            "OK".to_string()
+        } else if packet == "qAttached" {
+            "1".to_string()
+        } else if packet == "qC" {
+            "QC1".to_string()
+        } else if packet == "qfThreadInfo" {
+            "m1".to_string()
+        } else if packet == "qsThreadInfo" {
+            "l".to_string()
+        } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            read_xfer(TARGET_XML, range)
+        } else if packet == "vCont?" {
+            "vCont;c;s".to_string()
+        } else if let Some(actions) = packet.strip_prefix("vCont;") {
+            //a single thread, so only the first action matters
+            match actions.as_bytes().first() {
+                Some(b's') => return Action::Step,
+                Some(b'c') => return Action::Continue,
+                _ => "E01".to_string(),
+            }
+        } else {
+            String::new()
+        };
+
+        Action::Reply(reply)
+    }
+
+    fn read_registers(&self) -> String {
+        let pc = self.cpu.pc().to_le_bytes();
+        to_hex(&[
+            self.cpu.a(),
+            self.cpu.x(),
+            self.cpu.y(),
+            self.cpu.sp(),
+            pc[0],
+            pc[1],
+            self.cpu.status(),
+        ])
+    }
+
+    fn write_registers(&mut self, args: &str) -> String {
+        match from_hex(args) {
+            Some(bytes) if bytes.len() == 7 => {
+                self.cpu.set_a(bytes[0]);
+                self.cpu.set_x(bytes[1]);
+                self.cpu.set_y(bytes[2]);
+                self.cpu.set_sp(bytes[3]);
+                self.cpu.set_pc(u16::from_le_bytes([bytes[4], bytes[5]]));
+                self.cpu.set_status(bytes[6]);
+                "OK".to_string()
+            }
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn read_register(&self, args: &str) -> String {
+        match usize::from_str_radix(args, 16) {
+            Ok(REG_A) => to_hex(&[self.cpu.a()]),
+            Ok(REG_X) => to_hex(&[self.cpu.x()]),
+            Ok(REG_Y) => to_hex(&[self.cpu.y()]),
+            Ok(REG_SP) => to_hex(&[self.cpu.sp()]),
+            Ok(REG_PC) => to_hex(&self.cpu.pc().to_le_bytes()),
+            Ok(REG_P) => to_hex(&[self.cpu.status()]),
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn write_register(&mut self, args: &str) -> String {
+        let Some((register, value)) = args.split_once('=') else {
+            return "E01".to_string();
+        };
+        let (Ok(register), Some(value)) = (usize::from_str_radix(register, 16), from_hex(value)) else {
+            return "E01".to_string();
+        };
+
+        match (register, value.as_slice()) {
+            (REG_A, [a]) => self.cpu.set_a(*a),
+            (REG_X, [x]) => self.cpu.set_x(*x),
+            (REG_Y, [y]) => self.cpu.set_y(*y),
+            (REG_SP, [sp]) => self.cpu.set_sp(*sp),
+            (REG_PC, [lo, hi]) => self.cpu.set_pc(u16::from_le_bytes([*lo, *hi])),
+            (REG_P, [p]) => self.cpu.set_status(*p),
+            _ => return "E01".to_string(),
+        }
+        "OK".to_string()
+    }
+
+    fn read_memory(&self, args: &str) -> String {
+        let Some((address, length)) = parse_address_length(args) else {
+            return "E01".to_string();
+        };
+        //two hex digits a byte have to fit in one reply
+        if length > PACKET_SIZE / 2 {
+            return "E01".to_string();
+        }
+
+        let bytes: Vec<Byte> = (0..length)
+            .map(|offset| self.bus.peek(address.wrapping_add(offset as Word)))
+            .collect();
+        to_hex(&bytes)
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let Some((range, data)) = args.split_once(':') else {
+            return "E01".to_string();
+        };
+        let (Some((address, length)), Some(data)) = (parse_address_length(range), from_hex(data)) else {
+            return "E01".to_string();
+        };
+        if data.len() != length {
+            return "E01".to_string();
+        }
+
+        for (offset, value) in data.into_iter().enumerate() {
+            self.bus.write(address.wrapping_add(offset as Word), value);
+        }
+        "OK".to_string()
+    }
+
+    fn insert_breakpoint(&mut self, args: &str) -> String {
+        match parse_breakpoint(args) {
+            Some(('0', address)) => self.sw_breakpoints.insert(address),
+            Some(('1', address)) => self.hw_breakpoints.insert(address),
+            //watchpoints are not supported
+            _ => return String::new(),
+        };
+        "OK".to_string()
+    }
+
+    fn remove_breakpoint_packet(&mut self, args: &str) -> String {
+        match parse_breakpoint(args) {
+            Some(('0', address)) => self.sw_breakpoints.remove(&address),
+            Some(('1', address)) => self.hw_breakpoints.remove(&address),
+            _ => return String::new(),
+        };
+        "OK".to_string()
+    }
+
+    /** @return the packet payload, or None if the connection was closed */
+    fn read_packet(&self, stream: &mut TcpStream) -> io::Result<Option<String>> {
+        loop {
+            //skip acks and anything else until the start of a packet
+            match read_u8(stream)? {
+                Some(b'$') => {}
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+
+            let mut payload = Vec::new();
+            loop {
+                match read_u8(stream)? {
+                    Some(b'#') => break,
+                    Some(byte) => payload.push(byte),
+                    None => return Ok(None),
+                }
+            }
+            let mut checksum = [0u8; 2];
+            stream.read_exact(&mut checksum)?;
+
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+            if !self.no_ack_mode {
+                if expected != Some(checksum_of(&payload)) {
+                    stream.write_all(b"-")?;
+                    continue;
+                }
+                stream.write_all(b"+")?;
+            }
+
+            return Ok(Some(String::from_utf8_lossy(&payload).into_owned()));
+        }
+    }
+
+    fn write_packet(&self, stream: &mut TcpStream, payload: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", payload, checksum_of(payload.as_bytes()));
+        loop {
+            stream.write_all(packet.as_bytes())?;
+            if self.no_ack_mode {
+                return Ok(());
+            }
+            match read_u8(stream)? {
+                Some(b'-') => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+fn read_u8(stream: &mut TcpStream) -> io::Result<Option<u8>> {
+    let mut byte = [0u8; 1];
+    match stream.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/** The modulo 256 sum of the payload */
+pub fn checksum_of(payload: &[u8]) -> u8 {
+    payload.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn to_hex(bytes: &[Byte]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<Byte>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+/** "addr,length" as used by the m and M packets */
+fn parse_address_length(args: &str) -> Option<(Word, usize)> {
+    let (address, length) = args.split_once(',')?;
+    Some((
+        u16::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+/** "type,addr,kind" as used by the Z and z packets */
+fn parse_breakpoint(args: &str) -> Option<(char, Word)> {
+    let mut fields = args.split(',');
+    let kind = fields.next()?.chars().next()?;
+    let address = u16::from_str_radix(fields.next()?, 16).ok()?;
+    Some((kind, address))
+}
+
+/** Answer a qXfer read of "offset,length" into `document` */
+fn read_xfer(document: &str, range: &str) -> String {
+    let Some((offset, length)) = range.split_once(',') else {
+        return "E01".to_string();
+    };
+    let (Ok(offset), Ok(length)) = (usize::from_str_radix(offset, 16), usize::from_str_radix(length, 16)) else {
+        return "E01".to_string();
+    };
+
+    let start = offset.min(document.len());
+    let end = offset.saturating_add(length).min(document.len());
+    let marker = if end == document.len() { 'l' } else { 'm' };
+    format!("{}{}", marker, &document[start..end])
+}
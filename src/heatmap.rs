@@ -0,0 +1,247 @@
+//! Memory access heatmap: reads, writes and executes per address, as a 256x256 image
+//!
+//! One pixel per byte, one row per page. Red is written, green is read, blue is
+//! executed, brighter for more accesses (on a log scale), black for untouched.
+
+use crate::coverage::Recorder;
+use crate::disassembler::OPCODES;
+use crate::m6502::*;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 256;
+
+pub struct Heatmap {
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+    /** opcode and operand bytes fetched to run instructions */
+    executes: Vec<u64>,
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self {
+            reads: vec![0; 0x10000],
+            writes: vec![0; 0x10000],
+            executes: vec![0; 0x10000],
+        }
+    }
+
+    pub fn reads(&self, address: Word) -> u64 {
+        self.reads[address as usize]
+    }
+
+    pub fn writes(&self, address: Word) -> u64 {
+        self.writes[address as usize]
+    }
+
+    pub fn executes(&self, address: Word) -> u64 {
+        self.executes[address as usize]
+    }
+
+    /**
+     * Execute one instruction (or take one interrupt) and count its accesses
+     * @return the number of cycles that were used
+     * */
+    pub fn step<B: Bus>(&mut self, cpu: &mut CPU, memory: &mut B) -> s32 {
+        let pc = cpu.pc();
+        let interrupt = cpu.interrupt_pending();
+        let size = OPCODES[memory.peek(pc) as usize].size();
+
+        let mut recorder = Recorder::new(memory);
+        let cycles = cpu.step(&mut recorder);
+
+        let instruction = pc..pc.saturating_add(size);
+        if !interrupt {
+            for address in instruction.clone() {
+                self.executes[address as usize] += 1;
+            }
+        }
+        for address in recorder.reads {
+            if interrupt || !instruction.contains(&address) {
+                self.reads[address as usize] += 1;
+            }
+        }
+        for address in recorder.writes {
+            self.writes[address as usize] += 1;
+        }
+
+        cycles
+    }
+
+    /**
+     * Same as CPU::execute, counting accesses along the way
+     * @return the number of cycles that were used
+     * */
+    pub fn execute<B: Bus>(&mut self, cycles: s32, cpu: &mut CPU, memory: &mut B) -> s32 {
+        let mut cycles_used = 0;
+        while cycles_used < cycles {
+            let step_cycles = self.step(cpu, memory);
+            if step_cycles == 0 {
+                break;
+            }
+            cycles_used += step_cycles;
+        }
+
+        cycles_used
+    }
+
+    fn maxima(&self) -> [u64; 3] {
+        [&self.writes, &self.reads, &self.executes].map(|counts| counts.iter().copied().max().unwrap_or(0))
+    }
+
+    /** @return the RGB colour of the pixel for `address` */
+    pub fn pixel(&self, address: Word) -> [Byte; 3] {
+        let [max_writes, max_reads, max_executes] = self.maxima();
+        let address = address as usize;
+        [
+            brightness(self.writes[address], max_writes),
+            brightness(self.reads[address], max_reads),
+            brightness(self.executes[address], max_executes),
+        ]
+    }
+
+    /** @return the image as RGB rows, $0000 top left, $FFFF bottom right */
+    fn rgb(&self) -> Vec<Byte> {
+        let [max_writes, max_reads, max_executes] = self.maxima();
+        let mut rgb = Vec::with_capacity(WIDTH * HEIGHT * 3);
+        for address in 0..0x10000 {
+            rgb.push(brightness(self.writes[address], max_writes));
+            rgb.push(brightness(self.reads[address], max_reads));
+            rgb.push(brightness(self.executes[address], max_executes));
+        }
+        rgb
+    }
+
+    /** @return a binary (P6) PPM image */
+    pub fn to_ppm(&self) -> Vec<Byte> {
+        let mut ppm = format!("P6\n{} {}\n255\n", WIDTH, HEIGHT).into_bytes();
+        ppm.extend_from_slice(&self.rgb());
+        ppm
+    }
+
+    /** @return a truecolour PNG image, stored without compression */
+    pub fn to_png(&self) -> Vec<Byte> {
+        let rgb = self.rgb();
+        let mut scanlines = Vec::with_capacity(HEIGHT * (1 + WIDTH * 3));
+        for row in rgb.chunks(WIDTH * 3) {
+            //filter type: none
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(HEIGHT as u32).to_be_bytes());
+        //8 bits per channel, truecolour, deflate, adaptive filtering, not interlaced
+        ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        write_chunk(&mut png, b"IHDR", &ihdr);
+        write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /** Save as PNG if the path ends in .png, PPM otherwise */
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let is_png = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        fs::write(path, if is_png { self.to_png() } else { self.to_ppm() })
+    }
+
+    /** Write one line per page that was accessed at all */
+    pub fn write_summary<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(
+            out,
+            "{:<6} {:>10} {:>10} {:>10} {:>8}",
+            "page", "reads", "writes", "executes", "touched"
+        )?;
+        for page in 0..256 {
+            let range = page * 256..(page + 1) * 256;
+            let sum = |counts: &[u64]| counts[range.clone()].iter().sum::<u64>();
+            let touched = range
+                .clone()
+                .filter(|&address| self.reads[address] + self.writes[address] + self.executes[address] > 0)
+                .count();
+            if touched == 0 {
+                continue;
+            }
+
+            writeln!(
+                out,
+                "${:02X}    {:>10} {:>10} {:>10} {:>8}",
+                page,
+                sum(&self.reads),
+                sum(&self.writes),
+                sum(&self.executes),
+                format!("{}/256", touched)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/** Log scale from 64 to 255, so anything touched at all stands out from black */
+fn brightness(count: u64, max: u64) -> Byte {
+    if count == 0 {
+        return 0;
+    }
+    let scale = (1.0 + count as f64).ln() / (1.0 + max as f64).ln();
+    (64.0 + 191.0 * scale).round() as Byte
+}
+
+fn write_chunk(png: &mut Vec<Byte>, kind: &[Byte; 4], data: &[Byte]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/** A zlib stream of uncompressed deflate blocks */
+fn zlib_stored(data: &[Byte]) -> Vec<Byte> {
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        zlib.push(last as Byte);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+fn crc32(data: &[Byte]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[Byte]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
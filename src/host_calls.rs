@@ -0,0 +1,112 @@
+//! Trap calls to chosen addresses into Rust closures
+//!
+//! Handy for stubbing out ROM routines such as CHROUT: when PC reaches a hooked address
+//! the closure runs instead of the 6502 code there, then execution continues as if the
+//! routine ended with RTS.
+
+use crate::m6502::*;
+use std::collections::BTreeMap;
+
+/** What to do once a host call returns */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /** return to the caller as RTS would */
+    Return,
+    /** stop executing, leaving PC on the hooked address */
+    Stop,
+}
+
+/** A host call asked to stop execution */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stopped {
+    pub address: Word,
+    /** cycles used before stopping */
+    pub cycles: s32,
+}
+
+pub type HostCall<'a, B> = Box<dyn FnMut(&mut CPU, &mut B) -> Flow + 'a>;
+
+/** Closures registered by address, run in place of the code at that address */
+pub struct HostCalls<'a, B: Bus> {
+    calls: BTreeMap<Word, HostCall<'a, B>>,
+}
+
+impl<'a, B: Bus> HostCalls<'a, B> {
+    pub fn new() -> Self {
+        Self {
+            calls: BTreeMap::new(),
+        }
+    }
+
+    /** Run `call` whenever PC reaches `address`, replacing any earlier one */
+    pub fn register<F>(&mut self, address: Word, call: F)
+    where
+        F: FnMut(&mut CPU, &mut B) -> Flow + 'a,
+    {
+        self.calls.insert(address, Box::new(call));
+    }
+
+    pub fn unregister(&mut self, address: Word) -> bool {
+        self.calls.remove(&address).is_some()
+    }
+
+    pub fn is_registered(&self, address: Word) -> bool {
+        self.calls.contains_key(&address)
+    }
+
+    /**
+     * Run the host call at PC, if there is one
+     * @return the cycles the simulated RTS took, None if PC isn't hooked
+     * */
+    pub fn dispatch(&mut self, cpu: &mut CPU, memory: &mut B) -> Option<Result<s32, Stopped>> {
+        let address = cpu.pc();
+        let call = self.calls.get_mut(&address)?;
+
+        Some(match call(cpu, memory) {
+            Flow::Return => {
+                //the opcode fetch RTS would have made
+                let mut cycles = -1;
+                cpu.return_from_subroutine(&mut cycles, memory);
+                Ok(-cycles)
+            }
+            Flow::Stop => Err(Stopped { address, cycles: 0 }),
+        })
+    }
+
+    /**
+     * Execute one instruction, or one host call if PC is hooked
+     * @return the number of cycles that were used
+     * */
+    pub fn step(&mut self, cpu: &mut CPU, memory: &mut B) -> Result<s32, Stopped> {
+        match self.dispatch(cpu, memory) {
+            Some(result) => result,
+            None => Ok(cpu.step(memory)),
+        }
+    }
+
+    /**
+     * Same as CPU::execute, running host calls along the way
+     * @return the number of cycles that were used
+     * */
+    pub fn execute(&mut self, cycles: s32, cpu: &mut CPU, memory: &mut B) -> Result<s32, Stopped> {
+        let mut cycles_used = 0;
+        while cycles_used < cycles {
+            let step_cycles = self.step(cpu, memory).map_err(|stopped| Stopped {
+                cycles: cycles_used,
+                ..stopped
+            })?;
+            if step_cycles == 0 {
+                break;
+            }
+            cycles_used += step_cycles;
+        }
+
+        Ok(cycles_used)
+    }
+}
+
+impl<'a, B: Bus> Default for HostCalls<'a, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,13 +1,59 @@
 
+#[allow(unused_parens)] //the accessors generated by #[bitfield] wrap field types in parens
 pub mod m6502 {
     use modular_bitfield::*;
     use std::ops::{Index, IndexMut};
 
     pub type Byte = u8;
     pub type Word = u16;
+    #[allow(non_camel_case_types)]
     pub type s32 = i32;
 
+    /** Anything the CPU can read from and write to over its address/data bus */
+    pub trait Bus {
+        /** read 1 byte as the CPU would (may have side effects on devices) */
+        fn read(&mut self, address: Word) -> Byte;
+
+        /** write 1 byte as the CPU would */
+        fn write(&mut self, address: Word, value: Byte);
+
+        /** read 1 byte without any side effects - used by debuggers and tracers */
+        fn peek(&self, address: Word) -> Byte;
+
+        /** read 1 byte the CPU throws away while it's busy inside an instruction
+         * - still a real read with the same side effects, tools can override it to tell them apart
+         * */
+        fn dummy_read(&mut self, address: Word) -> Byte {
+            self.read(address)
+        }
+    }
+
+    /** Anything that runs one instruction at a time: a machine, or the CPU with a tool watching it */
+    pub trait Step {
+        /** Execute one instruction
+         * @return the number of cycles that were used, 0 if nothing more can run
+         * */
+        fn step(&mut self) -> s32;
+
+        /** Step until at least `cycles` have been used, or nothing more can run
+         * @return the number of cycles that were used
+         * */
+        fn execute(&mut self, cycles: s32) -> s32 {
+            let mut cycles_used = 0;
+            while cycles_used < cycles {
+                let step_cycles = self.step();
+                if step_cycles == 0 {
+                    break;
+                }
+                cycles_used += step_cycles;
+            }
+
+            cycles_used
+        }
+    }
+
     const MAX_MEM: usize = 1024 * 64;
+    #[derive(Clone)]
     pub struct Mem
     {
         data: [Byte; MAX_MEM],
@@ -27,7 +73,24 @@ pub mod m6502 {
         }
     }
 
-    impl Mem {
+    impl Default for Mem {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Bus for Mem {
+        fn read(&mut self, address: Word) -> Byte {
+            self[address]
+        }
+
+        fn write(&mut self, address: Word, value: Byte) {
+            self[address] = value;
+        }
+
+        fn peek(&self, address: Word) -> Byte {
+            self[address]
+        }
     }
 
     impl Index<u16> for Mem {
@@ -44,6 +107,16 @@ pub mod m6502 {
         }
     }
 
+    /** @return true if indexing took `base` into another page, which costs the CPU a cycle */
+    fn crosses_page(base: Word, indexed: Word) -> bool {
+        (base ^ indexed) & 0xFF00 != 0
+    }
+
+    /** @return where the CPU reads before it has carried into the high byte: `indexed`'s low byte in `base`'s page */
+    fn uncorrected(base: Word, indexed: Word) -> Word {
+        (base & 0xFF00) | (indexed & 0x00FF)
+    }
+
     #[bitfield]
     #[derive(Debug, Clone)]
     pub struct CPU {
@@ -61,15 +134,28 @@ pub mod m6502 {
         pub b: specifiers::B1, //status flag
         pub v: specifiers::B1, //status flag
         pub n: specifiers::B1, //status flag
-        n2_dummy: specifiers::B1, //status flag
+
+        irq_line: specifiers::B1, //interrupt inputs
+        nmi_line: specifiers::B1, //interrupt inputs
+        nmi_latched: specifiers::B1, //an NMI edge waiting to be serviced
+        jammed: specifiers::B1, //stopped on an opcode this core doesn't run
+        decimal_disabled: specifiers::B1, //D is ignored by ADC/SBC, as on the 2A03
+        #[skip]
+        __: specifiers::B4,
+    }
+
+    impl Default for CPU {
+        fn default() -> Self {
+            Self::new()
+        }
     }
 
     impl CPU {
-        fn load_register(
+        fn load_register<B: Bus>(
             &mut self,
             address: Word,
             register_setter: fn(&mut CPU, u8),
-            memory: &Mem,
+            memory: &mut B,
             cycles: &mut s32,
         ) {
             let value: Byte = self.read_byte(cycles, address, memory);
@@ -92,6 +178,8 @@ pub mod m6502 {
             self.set_b(0);
             self.set_v(0);
             self.set_n(0);
+            self.set_nmi_latched(0);
+            self.set_jammed(0);
 
             memory.initialize();
         }
@@ -123,6 +211,14 @@ pub mod m6502 {
 
         pub const INS_JSR: Byte = 0x20;
         pub const INS_RTS: Byte = 0x60;
+        pub const INS_JMP_ABS: Byte = 0x4C;
+        pub const INS_JMP_IND: Byte = 0x6C;
+        //RTI
+        pub const INS_RTI: Byte = 0x40;
+
+        //interrupt vectors
+        pub const NMI_VECTOR: Word = 0xFFFA;
+        pub const IRQ_VECTOR: Word = 0xFFFE;
 
         //STA
         pub const INS_STA_ZP: Byte = 0x85;
@@ -135,6 +231,7 @@ pub mod m6502 {
 
         //STX
         pub const INS_STX_ZP: Byte = 0x86;
+        pub const INS_STX_ZPY: Byte = 0x96;
         pub const INS_STX_ABS: Byte = 0x8E;
 
         //STY
@@ -142,6 +239,161 @@ pub mod m6502 {
         pub const INS_STY_ZPX: Byte = 0x94;
         pub const INS_STY_ABS: Byte = 0x8C;
 
+        //ORA
+        pub const INS_ORA_IM: Byte = 0x09;
+        pub const INS_ORA_ZP: Byte = 0x05;
+        pub const INS_ORA_ZPX: Byte = 0x15;
+        pub const INS_ORA_ABS: Byte = 0x0D;
+        pub const INS_ORA_ABSX: Byte = 0x1D;
+        pub const INS_ORA_ABSY: Byte = 0x19;
+        pub const INS_ORA_INDX: Byte = 0x01;
+        pub const INS_ORA_INDY: Byte = 0x11;
+
+        //AND
+        pub const INS_AND_IM: Byte = 0x29;
+        pub const INS_AND_ZP: Byte = 0x25;
+        pub const INS_AND_ZPX: Byte = 0x35;
+        pub const INS_AND_ABS: Byte = 0x2D;
+        pub const INS_AND_ABSX: Byte = 0x3D;
+        pub const INS_AND_ABSY: Byte = 0x39;
+        pub const INS_AND_INDX: Byte = 0x21;
+        pub const INS_AND_INDY: Byte = 0x31;
+
+        //EOR
+        pub const INS_EOR_IM: Byte = 0x49;
+        pub const INS_EOR_ZP: Byte = 0x45;
+        pub const INS_EOR_ZPX: Byte = 0x55;
+        pub const INS_EOR_ABS: Byte = 0x4D;
+        pub const INS_EOR_ABSX: Byte = 0x5D;
+        pub const INS_EOR_ABSY: Byte = 0x59;
+        pub const INS_EOR_INDX: Byte = 0x41;
+        pub const INS_EOR_INDY: Byte = 0x51;
+
+        //ADC
+        pub const INS_ADC_IM: Byte = 0x69;
+        pub const INS_ADC_ZP: Byte = 0x65;
+        pub const INS_ADC_ZPX: Byte = 0x75;
+        pub const INS_ADC_ABS: Byte = 0x6D;
+        pub const INS_ADC_ABSX: Byte = 0x7D;
+        pub const INS_ADC_ABSY: Byte = 0x79;
+        pub const INS_ADC_INDX: Byte = 0x61;
+        pub const INS_ADC_INDY: Byte = 0x71;
+
+        //SBC
+        pub const INS_SBC_IM: Byte = 0xE9;
+        pub const INS_SBC_ZP: Byte = 0xE5;
+        pub const INS_SBC_ZPX: Byte = 0xF5;
+        pub const INS_SBC_ABS: Byte = 0xED;
+        pub const INS_SBC_ABSX: Byte = 0xFD;
+        pub const INS_SBC_ABSY: Byte = 0xF9;
+        pub const INS_SBC_INDX: Byte = 0xE1;
+        pub const INS_SBC_INDY: Byte = 0xF1;
+
+        //CMP
+        pub const INS_CMP_IM: Byte = 0xC9;
+        pub const INS_CMP_ZP: Byte = 0xC5;
+        pub const INS_CMP_ZPX: Byte = 0xD5;
+        pub const INS_CMP_ABS: Byte = 0xCD;
+        pub const INS_CMP_ABSX: Byte = 0xDD;
+        pub const INS_CMP_ABSY: Byte = 0xD9;
+        pub const INS_CMP_INDX: Byte = 0xC1;
+        pub const INS_CMP_INDY: Byte = 0xD1;
+
+        //CPX
+        pub const INS_CPX_IM: Byte = 0xE0;
+        pub const INS_CPX_ZP: Byte = 0xE4;
+        pub const INS_CPX_ABS: Byte = 0xEC;
+
+        //CPY
+        pub const INS_CPY_IM: Byte = 0xC0;
+        pub const INS_CPY_ZP: Byte = 0xC4;
+        pub const INS_CPY_ABS: Byte = 0xCC;
+
+        //BIT
+        pub const INS_BIT_ZP: Byte = 0x24;
+        pub const INS_BIT_ABS: Byte = 0x2C;
+
+        //ASL
+        pub const INS_ASL: Byte = 0x0A;
+        pub const INS_ASL_ZP: Byte = 0x06;
+        pub const INS_ASL_ZPX: Byte = 0x16;
+        pub const INS_ASL_ABS: Byte = 0x0E;
+        pub const INS_ASL_ABSX: Byte = 0x1E;
+
+        //LSR
+        pub const INS_LSR: Byte = 0x4A;
+        pub const INS_LSR_ZP: Byte = 0x46;
+        pub const INS_LSR_ZPX: Byte = 0x56;
+        pub const INS_LSR_ABS: Byte = 0x4E;
+        pub const INS_LSR_ABSX: Byte = 0x5E;
+
+        //ROL
+        pub const INS_ROL: Byte = 0x2A;
+        pub const INS_ROL_ZP: Byte = 0x26;
+        pub const INS_ROL_ZPX: Byte = 0x36;
+        pub const INS_ROL_ABS: Byte = 0x2E;
+        pub const INS_ROL_ABSX: Byte = 0x3E;
+
+        //ROR
+        pub const INS_ROR: Byte = 0x6A;
+        pub const INS_ROR_ZP: Byte = 0x66;
+        pub const INS_ROR_ZPX: Byte = 0x76;
+        pub const INS_ROR_ABS: Byte = 0x6E;
+        pub const INS_ROR_ABSX: Byte = 0x7E;
+
+        //INC
+        pub const INS_INC_ZP: Byte = 0xE6;
+        pub const INS_INC_ZPX: Byte = 0xF6;
+        pub const INS_INC_ABS: Byte = 0xEE;
+        pub const INS_INC_ABSX: Byte = 0xFE;
+
+        //DEC
+        pub const INS_DEC_ZP: Byte = 0xC6;
+        pub const INS_DEC_ZPX: Byte = 0xD6;
+        pub const INS_DEC_ABS: Byte = 0xCE;
+        pub const INS_DEC_ABSX: Byte = 0xDE;
+
+        //register increments and transfers
+        pub const INS_INX: Byte = 0xE8;
+        pub const INS_INY: Byte = 0xC8;
+        pub const INS_DEX: Byte = 0xCA;
+        pub const INS_DEY: Byte = 0x88;
+        pub const INS_TAX: Byte = 0xAA;
+        pub const INS_TAY: Byte = 0xA8;
+        pub const INS_TXA: Byte = 0x8A;
+        pub const INS_TYA: Byte = 0x98;
+        pub const INS_TSX: Byte = 0xBA;
+        pub const INS_TXS: Byte = 0x9A;
+
+        //stack
+        pub const INS_PHA: Byte = 0x48;
+        pub const INS_PHP: Byte = 0x08;
+        pub const INS_PLA: Byte = 0x68;
+        pub const INS_PLP: Byte = 0x28;
+
+        //branches
+        pub const INS_BPL: Byte = 0x10;
+        pub const INS_BMI: Byte = 0x30;
+        pub const INS_BVC: Byte = 0x50;
+        pub const INS_BVS: Byte = 0x70;
+        pub const INS_BCC: Byte = 0x90;
+        pub const INS_BCS: Byte = 0xB0;
+        pub const INS_BNE: Byte = 0xD0;
+        pub const INS_BEQ: Byte = 0xF0;
+
+        //status flags
+        pub const INS_CLC: Byte = 0x18;
+        pub const INS_SEC: Byte = 0x38;
+        pub const INS_CLI: Byte = 0x58;
+        pub const INS_SEI: Byte = 0x78;
+        pub const INS_CLV: Byte = 0xB8;
+        pub const INS_CLD: Byte = 0xD8;
+        pub const INS_SED: Byte = 0xF8;
+
+        //system
+        pub const INS_BRK: Byte = 0x00;
+        pub const INS_NOP: Byte = 0xEA;
+
 
         /**Sets the correct Process status after a load register instruction
          * - LDA, LDY, LDZ
@@ -151,45 +403,237 @@ pub mod m6502 {
             self.set_n(if register & 0b10000000 == 0 {0} else {1});
         }
 
+        /** Read the operand at `address` and hand it to `operation` - AND, ADC, CMP and the like */
+        fn operate<B: Bus>(
+            &mut self,
+            address: Word,
+            operation: fn(&mut CPU, Byte),
+            memory: &mut B,
+            cycles: &mut s32,
+        ) {
+            let value: Byte = self.read_byte(cycles, address, memory);
+            operation(self, value);
+        }
+
+        /** Read, modify and write back the byte at `address` - ASL, INC and the like
+         * - the unmodified value is written back first, while the new one is worked out
+         * */
+        fn read_modify_write<B: Bus>(
+            &mut self,
+            address: Word,
+            operation: fn(&mut CPU, Byte) -> Byte,
+            memory: &mut B,
+            cycles: &mut s32,
+        ) {
+            let value: Byte = self.read_byte(cycles, address, memory);
+            self.write_byte(value, cycles, address, memory);
+            let result = operation(self, value);
+            self.write_byte(result, cycles, address, memory);
+        }
+
+        fn ora(&mut self, value: Byte) {
+            self.set_a(self.a() | value);
+            self.load_register_set_status(self.a());
+        }
+
+        fn and(&mut self, value: Byte) {
+            self.set_a(self.a() & value);
+            self.load_register_set_status(self.a());
+        }
+
+        fn eor(&mut self, value: Byte) {
+            self.set_a(self.a() ^ value);
+            self.load_register_set_status(self.a());
+        }
+
+        /** @return true if ADC and SBC work in BCD */
+        fn decimal_mode(&self) -> bool {
+            self.d() == 1 && self.decimal_disabled() == 0
+        }
+
+        /** Binary add with carry, setting all of N, V, Z and C */
+        fn add(&mut self, value: Byte) {
+            let a = self.a() as Word;
+            let value = value as Word;
+            let sum = a + value + self.c() as Word;
+            self.set_c((sum > 0xFF) as Byte);
+            self.set_v((((a ^ sum) & !(a ^ value)) & 0x80 != 0) as Byte);
+            self.set_a(sum as Byte);
+            self.load_register_set_status(self.a());
+        }
+
+        /** Add with carry, in BCD when D is set
+         * - in decimal mode N, V and Z come out as the NMOS 6502 leaves them, not from the BCD result
+         * */
+        fn adc(&mut self, value: Byte) {
+            if !self.decimal_mode() {
+                self.add(value);
+                return;
+            }
+
+            let a = self.a() as Word;
+            let value = value as Word;
+            let carry = self.c() as Word;
+            let sum = a + value + carry;
+            let mut lo = (a & 0x0F) + (value & 0x0F) + carry;
+            if lo > 0x09 {
+                lo += 0x06;
+            }
+            let mut hi = (a & 0xF0) + (value & 0xF0) + if lo > 0x0F { 0x10 } else { 0 };
+            self.set_z((sum & 0xFF == 0) as Byte);
+            self.set_n((hi & 0x80 != 0) as Byte);
+            self.set_v((((a ^ hi) & !(a ^ value)) & 0x80 != 0) as Byte);
+            if hi > 0x90 {
+                hi += 0x60;
+            }
+            self.set_c((hi > 0xFF) as Byte);
+            self.set_a(((hi & 0xF0) | (lo & 0x0F)) as Byte);
+        }
+
+        /** Subtract with borrow, in BCD when D is set
+         * - the flags always come from the binary subtraction, as on the NMOS 6502
+         * */
+        fn sbc(&mut self, value: Byte) {
+            let a = self.a();
+            let borrow = 1 - self.c() as i16;
+            self.add(!value);
+            if !self.decimal_mode() {
+                return;
+            }
+
+            let mut lo = (a & 0x0F) as i16 - (value & 0x0F) as i16 - borrow;
+            let mut hi = (a >> 4) as i16 - (value >> 4) as i16;
+            if lo < 0 {
+                lo -= 0x06;
+                hi -= 1;
+            }
+            if hi < 0 {
+                hi -= 0x06;
+            }
+            self.set_a((((hi << 4) | (lo & 0x0F)) & 0xFF) as Byte);
+        }
+
+        /** Set the flags for `register` - `value`, as CMP, CPX and CPY do */
+        fn compare(&mut self, register: Byte, value: Byte) {
+            self.set_c((register >= value) as Byte);
+            self.load_register_set_status(register.wrapping_sub(value));
+        }
+
+        fn cmp(&mut self, value: Byte) {
+            self.compare(self.a(), value);
+        }
+
+        fn cpx(&mut self, value: Byte) {
+            self.compare(self.x(), value);
+        }
+
+        fn cpy(&mut self, value: Byte) {
+            self.compare(self.y(), value);
+        }
+
+        /** Z from A AND the operand, N and V straight from bits 7 and 6 of the operand */
+        fn bit(&mut self, value: Byte) {
+            self.set_z((self.a() & value == 0) as Byte);
+            self.set_n(value >> 7);
+            self.set_v((value >> 6) & 1);
+        }
+
+        fn asl(&mut self, value: Byte) -> Byte {
+            let result = value << 1;
+            self.set_c(value >> 7);
+            self.load_register_set_status(result);
+            result
+        }
+
+        fn lsr(&mut self, value: Byte) -> Byte {
+            let result = value >> 1;
+            self.set_c(value & 1);
+            self.load_register_set_status(result);
+            result
+        }
+
+        fn rol(&mut self, value: Byte) -> Byte {
+            let result = (value << 1) | self.c();
+            self.set_c(value >> 7);
+            self.load_register_set_status(result);
+            result
+        }
+
+        fn ror(&mut self, value: Byte) -> Byte {
+            let result = (value >> 1) | (self.c() << 7);
+            self.set_c(value & 1);
+            self.load_register_set_status(result);
+            result
+        }
+
+        fn inc(&mut self, value: Byte) -> Byte {
+            let result = value.wrapping_add(1);
+            self.load_register_set_status(result);
+            result
+        }
+
+        fn dec(&mut self, value: Byte) -> Byte {
+            let result = value.wrapping_sub(1);
+            self.load_register_set_status(result);
+            result
+        }
+
+        /** Relative addressing - a cycle more if the branch is taken, another if it lands in another page */
+        fn branch_if<B: Bus>(&mut self, condition: bool, cycles: &mut s32, memory: &mut B) {
+            let offset = self.fetch_byte(cycles, memory) as i8;
+            if !condition {
+                return;
+            }
+
+            //the next opcode is read and thrown away while the offset is added, again while PCH is fixed up
+            let pc = self.pc();
+            let target = pc.wrapping_add_signed(offset as i16);
+            self.dummy_read(cycles, pc, memory);
+            if crosses_page(pc, target) {
+                self.dummy_read(cycles, uncorrected(pc, target), memory);
+            }
+            self.set_pc(target);
+        }
+
         /**
          * Addressing mode - Zero page 
          */
-        fn addr_zero_page(&mut self, cycles: &mut s32, memory: &Mem) -> Word {
-            let zero_page_address = self.fetch_byte(cycles, memory);
-            zero_page_address as Word
+        fn addr_zero_page<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
+            self.fetch_byte(cycles, memory) as Word
         }
 
         /* Addresing mode - zero page with x offset */
-        fn addr_zero_page_x(&mut self, cycles: &mut s32, memory: &Mem) -> Word {
+        fn addr_zero_page_x<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
             let mut zero_page_address: Word = self.fetch_byte(cycles, memory) as Word;
+            //the unindexed address is read while X is added
+            self.dummy_read(cycles, zero_page_address, memory);
             zero_page_address = (zero_page_address + self.x() as Word) & 0xFF;
-            *cycles = *cycles - 1;
 
             zero_page_address
         }
 
         /* Addresing mode - zero page with y offset */
-        fn addr_zero_page_y(&mut self, cycles: &mut s32, memory: &Mem) -> Word {
+        fn addr_zero_page_y<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
             let mut zero_page_address: Word = self.fetch_byte(cycles, memory) as Word;
+            //the unindexed address is read while Y is added
+            self.dummy_read(cycles, zero_page_address, memory);
             zero_page_address = (zero_page_address + self.y() as Word) & 0xFF;
-            *cycles = *cycles - 1;
 
             zero_page_address
         }
 
         /** Addressing mode - Absolute */
-        fn addr_absolute(&mut self, cycles: &mut s32, memory: &Mem) -> Word {
-            let abs_address = self.fetch_word(cycles, memory);
-            abs_address
+        fn addr_absolute<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
+            self.fetch_word(cycles, memory)
         }
 
         /** Addressing mode - Absolute with X offset*/
-        fn addr_absolute_x(&mut self, cycles: &mut s32, memory: &Mem) -> Word {
-            let mut abs_address = self.fetch_word(cycles, memory);
-            let abs_address_x = abs_address + self.x() as Word;
+        fn addr_absolute_x<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
+            let abs_address = self.fetch_word(cycles, memory);
+            let abs_address_x = abs_address.wrapping_add(self.x() as Word);
 
-            if abs_address_x - abs_address >= 0xFF {
-                *cycles = *cycles - 1;
+            if crosses_page(abs_address, abs_address_x) {
+                self.dummy_read(cycles, uncorrected(abs_address, abs_address_x), memory);
             }
 
             abs_address_x
@@ -199,30 +643,31 @@ pub mod m6502 {
          * - Always takes a cycle for the X page boundary
          * - See "STA Absolute,X
          * */
-        fn addr_absolute_x_5(&mut self, cycles: &mut s32, memory: &Mem) -> Word {
+        fn addr_absolute_x_5<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
             let abs_address = self.fetch_word(cycles, memory);
-            let abs_address_x = abs_address + self.x() as Word;
+            let abs_address_x = abs_address.wrapping_add(self.x() as Word);
 
-            *cycles = *cycles - 1;
+            self.dummy_read(cycles, uncorrected(abs_address, abs_address_x), memory);
 
             abs_address_x
         }
 
         /** Addressing mode - Indirect X | Indexed Indirect */
-        fn addr_indirect_x(&mut self, cycles: &mut s32, memory: &Mem) -> Word {
-            let mut zp_address: Word = self.fetch_byte(cycles, memory) as Word;
-            zp_address = zp_address + self.x() as Word;
-            *cycles = *cycles - 1;
-            let effective_address: Word = self.read_word(cycles, zp_address, memory);
-            effective_address
+        fn addr_indirect_x<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
+            let zp_address: Byte = self.fetch_byte(cycles, memory);
+            //the pointer is read unindexed while X is added
+            self.dummy_read(cycles, zp_address as Word, memory);
+            let zp_address: Word = zp_address.wrapping_add(self.x()) as Word;
+            //the pointer wraps around within the zero page
+            self.read_word_within_page(cycles, zp_address, memory)
         }
 
         /** Addressing mode - Absolute with Y offset*/
-        fn addr_absolute_y(&mut self, cycles: &mut s32, memory: &Mem) -> Word {
-            let mut abs_address: Word = self.fetch_word(cycles, memory);
-            let abs_address_y = abs_address + self.y() as Word;
-            if abs_address_y - abs_address >= 0xFF {
-                *cycles = *cycles - 1;
+        fn addr_absolute_y<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
+            let abs_address: Word = self.fetch_word(cycles, memory);
+            let abs_address_y = abs_address.wrapping_add(self.y() as Word);
+            if crosses_page(abs_address, abs_address_y) {
+                self.dummy_read(cycles, uncorrected(abs_address, abs_address_y), memory);
             }
 
             abs_address_y
@@ -232,31 +677,67 @@ pub mod m6502 {
          * - Always takes a cycle for the Y page boundary
          * - See "STA Absolute,Y
          * */
-        fn addr_absolute_y_5(&mut self, cycles: &mut s32, memory: &Mem) -> Word {
+        fn addr_absolute_y_5<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
             let abs_address: Word = self.fetch_word(cycles, memory);
-            let abs_address_y = abs_address + self.y() as Word;
-            *cycles = *cycles - 1;
+            let abs_address_y = abs_address.wrapping_add(self.y() as Word);
+            self.dummy_read(cycles, uncorrected(abs_address, abs_address_y), memory);
 
             abs_address_y
         }
 
         /** Addressing mode - Indirect Y | Indirect Indexed */
-        fn addr_indirect_y(&mut self, cycles: &mut s32, memory: &Mem) -> Word {
-            let mut zp_address: Word = self.fetch_byte(cycles, memory) as Word;
-            let effective_address: Word = self.read_word(cycles, zp_address, memory);
-            let effective_address_y = effective_address + self.y() as Word;
-            if effective_address_y - effective_address >= 0xFF {
-                *cycles = *cycles - 1;
+        fn addr_indirect_y<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
+            let zp_address: Word = self.fetch_byte(cycles, memory) as Word;
+            let effective_address: Word = self.read_word_within_page(cycles, zp_address, memory);
+            let effective_address_y = effective_address.wrapping_add(self.y() as Word);
+            if crosses_page(effective_address, effective_address_y) {
+                self.dummy_read(cycles, uncorrected(effective_address, effective_address_y), memory);
             }
 
             effective_address_y
         }
 
+        /** Addressing mode - Indirect Y | Indirect Indexed
+         * - Always takes a cycle for the Y page boundary
+         * - See "STA Indirect,Y
+         * */
+        fn addr_indirect_y_6<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Word {
+            let zp_address: Word = self.fetch_byte(cycles, memory) as Word;
+            let effective_address: Word = self.read_word_within_page(cycles, zp_address, memory);
+            let effective_address_y = effective_address.wrapping_add(self.y() as Word);
+            self.dummy_read(cycles, uncorrected(effective_address, effective_address_y), memory);
+
+            effective_address_y
+        }
+
+        /** Execute exactly one instruction
+         * @return the number of cycles that were used
+         * */
+        pub fn step<B: Bus>(&mut self, memory: &mut B) -> s32 {
+            self.execute(1, memory)
+        }
+
         //@return the number of cycles that were used
-        pub fn execute(&mut self, cycles: s32, memory: &mut Mem) -> s32 {
+        pub fn execute<B: Bus>(&mut self, cycles: s32, memory: &mut B) -> s32 {
             let cycles_requested = cycles;
             let mut cycles = cycles;
-            while cycles > 0 {
+            while cycles > 0 && self.jammed() == 0 {
+                //interrupts are only taken between instructions, NMI first
+                if self.nmi_latched() == 1 {
+                    self.set_nmi_latched(0);
+                    //the next opcode is read twice and thrown away
+                    self.dummy_read(&mut cycles, self.pc(), memory);
+                    self.dummy_read(&mut cycles, self.pc(), memory);
+                    self.interrupt(Self::NMI_VECTOR, self.status() & !0x10, &mut cycles, memory);
+                    continue;
+                }
+                if self.irq_line() == 1 && self.i() == 0 {
+                    self.dummy_read(&mut cycles, self.pc(), memory);
+                    self.dummy_read(&mut cycles, self.pc(), memory);
+                    self.interrupt(Self::IRQ_VECTOR, self.status() & !0x10, &mut cycles, memory);
+                    continue;
+                }
+
                 let ins: Byte = self.fetch_byte(&mut cycles, memory);
 
                 match ins {
@@ -293,11 +774,11 @@ pub mod m6502 {
                         self.load_register(address, CPU::set_a, memory, &mut cycles);
                     }
                     Self::INS_LDY_ZPX => {
-                        let mut address: Word = self.addr_zero_page_x(&mut cycles, memory);
+                        let address: Word = self.addr_zero_page_x(&mut cycles, memory);
                         self.load_register(address, CPU::set_y, memory, &mut cycles);
                     }
                     Self::INS_LDA_ZPX => {
-                        let mut address: Word = self.addr_zero_page_x(&mut cycles, memory);
+                        let address: Word = self.addr_zero_page_x(&mut cycles, memory);
                         self.load_register(address, CPU::set_a, memory, &mut cycles);
                     }
                     Self::INS_LDA_ABS => {
@@ -342,7 +823,7 @@ pub mod m6502 {
                         self.load_register(effective_address_y, CPU::set_a, memory, &mut cycles);
                     }
                     Self::INS_STA_INDY => {
-                        let effective_address_y = self.addr_indirect_y(&mut cycles, memory);
+                        let effective_address_y = self.addr_indirect_y_6(&mut cycles, memory);
                         self.write_byte(self.a(), &mut cycles, effective_address_y, memory);
                     }
                     Self::INS_STA_ZP => {
@@ -394,100 +875,742 @@ pub mod m6502 {
                         self.write_byte(self.a(), &mut cycles, address, memory);
                     }
                     Self::INS_JSR => {
-                        let sub_addr: Word = self.fetch_word(&mut cycles, memory);
-                        self.push_pc_to_stack(&mut cycles, memory);
-                        self.set_pc(sub_addr);
-                        cycles = cycles - 1;
+                        //the high byte of the address is only fetched after the return address is pushed,
+                        //which is why that points at it rather than past it
+                        let lo_byte = self.fetch_byte(&mut cycles, memory);
+                        self.dummy_read(&mut cycles, self.sp_to_address(), memory);
+                        let [pc_lo, pc_hi] = self.pc().to_le_bytes();
+                        self.push_byte_to_stack(pc_hi, &mut cycles, memory);
+                        self.push_byte_to_stack(pc_lo, &mut cycles, memory);
+                        let hi_byte = self.fetch_byte(&mut cycles, memory);
+                        self.set_pc(Word::from_le_bytes([lo_byte, hi_byte]));
                     }
                     Self::INS_RTS => {
-                        let return_address = self.pop_word_from_stack(&mut cycles, memory);
-                        self.set_pc(return_address + 1);
-                        cycles = cycles - 2;
+                        self.return_from_subroutine(&mut cycles, memory);
+                    }
+                    Self::INS_JMP_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.set_pc(address);
+                    }
+                    Self::INS_JMP_IND => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        let address = self.read_word_within_page(&mut cycles, address, memory);
+                        self.set_pc(address);
+                    }
+                    Self::INS_RTI => {
+                        //the byte after the opcode and the stack are read and thrown away before the pulls
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                        self.dummy_read(&mut cycles, self.sp_to_address(), memory);
+                        //B and bit 5 aren't real flags, pulling P leaves them alone
+                        let status = self.pop_byte_from_stack(&mut cycles, memory);
+                        self.set_status((status & !0x10) | (self.b() << 4));
+                        let lo_byte = self.pop_byte_from_stack(&mut cycles, memory);
+                        let hi_byte = self.pop_byte_from_stack(&mut cycles, memory);
+                        self.set_pc(Word::from_le_bytes([lo_byte, hi_byte]));
+                    }
+                    Self::INS_ORA_IM => {
+                        let value: Byte = self.fetch_byte(&mut cycles, memory);
+                        self.ora(value);
+                    }
+                    Self::INS_ORA_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.operate(address, CPU::ora, memory, &mut cycles);
+                    }
+                    Self::INS_ORA_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.operate(address, CPU::ora, memory, &mut cycles);
+                    }
+                    Self::INS_ORA_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.operate(address, CPU::ora, memory, &mut cycles);
+                    }
+                    Self::INS_ORA_ABSX => {
+                        let address = self.addr_absolute_x(&mut cycles, memory);
+                        self.operate(address, CPU::ora, memory, &mut cycles);
+                    }
+                    Self::INS_ORA_ABSY => {
+                        let address = self.addr_absolute_y(&mut cycles, memory);
+                        self.operate(address, CPU::ora, memory, &mut cycles);
+                    }
+                    Self::INS_ORA_INDX => {
+                        let address = self.addr_indirect_x(&mut cycles, memory);
+                        self.operate(address, CPU::ora, memory, &mut cycles);
+                    }
+                    Self::INS_ORA_INDY => {
+                        let address = self.addr_indirect_y(&mut cycles, memory);
+                        self.operate(address, CPU::ora, memory, &mut cycles);
+                    }
+                    Self::INS_AND_IM => {
+                        let value: Byte = self.fetch_byte(&mut cycles, memory);
+                        self.and(value);
+                    }
+                    Self::INS_AND_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.operate(address, CPU::and, memory, &mut cycles);
+                    }
+                    Self::INS_AND_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.operate(address, CPU::and, memory, &mut cycles);
+                    }
+                    Self::INS_AND_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.operate(address, CPU::and, memory, &mut cycles);
+                    }
+                    Self::INS_AND_ABSX => {
+                        let address = self.addr_absolute_x(&mut cycles, memory);
+                        self.operate(address, CPU::and, memory, &mut cycles);
+                    }
+                    Self::INS_AND_ABSY => {
+                        let address = self.addr_absolute_y(&mut cycles, memory);
+                        self.operate(address, CPU::and, memory, &mut cycles);
+                    }
+                    Self::INS_AND_INDX => {
+                        let address = self.addr_indirect_x(&mut cycles, memory);
+                        self.operate(address, CPU::and, memory, &mut cycles);
+                    }
+                    Self::INS_AND_INDY => {
+                        let address = self.addr_indirect_y(&mut cycles, memory);
+                        self.operate(address, CPU::and, memory, &mut cycles);
+                    }
+                    Self::INS_EOR_IM => {
+                        let value: Byte = self.fetch_byte(&mut cycles, memory);
+                        self.eor(value);
+                    }
+                    Self::INS_EOR_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.operate(address, CPU::eor, memory, &mut cycles);
+                    }
+                    Self::INS_EOR_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.operate(address, CPU::eor, memory, &mut cycles);
+                    }
+                    Self::INS_EOR_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.operate(address, CPU::eor, memory, &mut cycles);
+                    }
+                    Self::INS_EOR_ABSX => {
+                        let address = self.addr_absolute_x(&mut cycles, memory);
+                        self.operate(address, CPU::eor, memory, &mut cycles);
+                    }
+                    Self::INS_EOR_ABSY => {
+                        let address = self.addr_absolute_y(&mut cycles, memory);
+                        self.operate(address, CPU::eor, memory, &mut cycles);
+                    }
+                    Self::INS_EOR_INDX => {
+                        let address = self.addr_indirect_x(&mut cycles, memory);
+                        self.operate(address, CPU::eor, memory, &mut cycles);
+                    }
+                    Self::INS_EOR_INDY => {
+                        let address = self.addr_indirect_y(&mut cycles, memory);
+                        self.operate(address, CPU::eor, memory, &mut cycles);
+                    }
+                    Self::INS_ADC_IM => {
+                        let value: Byte = self.fetch_byte(&mut cycles, memory);
+                        self.adc(value);
+                    }
+                    Self::INS_ADC_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.operate(address, CPU::adc, memory, &mut cycles);
+                    }
+                    Self::INS_ADC_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.operate(address, CPU::adc, memory, &mut cycles);
+                    }
+                    Self::INS_ADC_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.operate(address, CPU::adc, memory, &mut cycles);
+                    }
+                    Self::INS_ADC_ABSX => {
+                        let address = self.addr_absolute_x(&mut cycles, memory);
+                        self.operate(address, CPU::adc, memory, &mut cycles);
+                    }
+                    Self::INS_ADC_ABSY => {
+                        let address = self.addr_absolute_y(&mut cycles, memory);
+                        self.operate(address, CPU::adc, memory, &mut cycles);
+                    }
+                    Self::INS_ADC_INDX => {
+                        let address = self.addr_indirect_x(&mut cycles, memory);
+                        self.operate(address, CPU::adc, memory, &mut cycles);
+                    }
+                    Self::INS_ADC_INDY => {
+                        let address = self.addr_indirect_y(&mut cycles, memory);
+                        self.operate(address, CPU::adc, memory, &mut cycles);
+                    }
+                    Self::INS_SBC_IM => {
+                        let value: Byte = self.fetch_byte(&mut cycles, memory);
+                        self.sbc(value);
+                    }
+                    Self::INS_SBC_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.operate(address, CPU::sbc, memory, &mut cycles);
+                    }
+                    Self::INS_SBC_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.operate(address, CPU::sbc, memory, &mut cycles);
+                    }
+                    Self::INS_SBC_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.operate(address, CPU::sbc, memory, &mut cycles);
+                    }
+                    Self::INS_SBC_ABSX => {
+                        let address = self.addr_absolute_x(&mut cycles, memory);
+                        self.operate(address, CPU::sbc, memory, &mut cycles);
+                    }
+                    Self::INS_SBC_ABSY => {
+                        let address = self.addr_absolute_y(&mut cycles, memory);
+                        self.operate(address, CPU::sbc, memory, &mut cycles);
+                    }
+                    Self::INS_SBC_INDX => {
+                        let address = self.addr_indirect_x(&mut cycles, memory);
+                        self.operate(address, CPU::sbc, memory, &mut cycles);
+                    }
+                    Self::INS_SBC_INDY => {
+                        let address = self.addr_indirect_y(&mut cycles, memory);
+                        self.operate(address, CPU::sbc, memory, &mut cycles);
+                    }
+                    Self::INS_CMP_IM => {
+                        let value: Byte = self.fetch_byte(&mut cycles, memory);
+                        self.cmp(value);
+                    }
+                    Self::INS_CMP_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.operate(address, CPU::cmp, memory, &mut cycles);
+                    }
+                    Self::INS_CMP_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.operate(address, CPU::cmp, memory, &mut cycles);
+                    }
+                    Self::INS_CMP_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.operate(address, CPU::cmp, memory, &mut cycles);
+                    }
+                    Self::INS_CMP_ABSX => {
+                        let address = self.addr_absolute_x(&mut cycles, memory);
+                        self.operate(address, CPU::cmp, memory, &mut cycles);
+                    }
+                    Self::INS_CMP_ABSY => {
+                        let address = self.addr_absolute_y(&mut cycles, memory);
+                        self.operate(address, CPU::cmp, memory, &mut cycles);
+                    }
+                    Self::INS_CMP_INDX => {
+                        let address = self.addr_indirect_x(&mut cycles, memory);
+                        self.operate(address, CPU::cmp, memory, &mut cycles);
+                    }
+                    Self::INS_CMP_INDY => {
+                        let address = self.addr_indirect_y(&mut cycles, memory);
+                        self.operate(address, CPU::cmp, memory, &mut cycles);
+                    }
+                    Self::INS_CPX_IM => {
+                        let value: Byte = self.fetch_byte(&mut cycles, memory);
+                        self.cpx(value);
+                    }
+                    Self::INS_CPX_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.operate(address, CPU::cpx, memory, &mut cycles);
+                    }
+                    Self::INS_CPX_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.operate(address, CPU::cpx, memory, &mut cycles);
+                    }
+                    Self::INS_CPY_IM => {
+                        let value: Byte = self.fetch_byte(&mut cycles, memory);
+                        self.cpy(value);
+                    }
+                    Self::INS_CPY_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.operate(address, CPU::cpy, memory, &mut cycles);
+                    }
+                    Self::INS_CPY_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.operate(address, CPU::cpy, memory, &mut cycles);
+                    }
+                    Self::INS_BIT_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.operate(address, CPU::bit, memory, &mut cycles);
+                    }
+                    Self::INS_BIT_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.operate(address, CPU::bit, memory, &mut cycles);
+                    }
+                    Self::INS_ASL => {
+                        let value = self.asl(self.a());
+                        self.set_a(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_ASL_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::asl, memory, &mut cycles);
+                    }
+                    Self::INS_ASL_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::asl, memory, &mut cycles);
+                    }
+                    Self::INS_ASL_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::asl, memory, &mut cycles);
+                    }
+                    Self::INS_ASL_ABSX => {
+                        let address = self.addr_absolute_x_5(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::asl, memory, &mut cycles);
+                    }
+                    Self::INS_LSR => {
+                        let value = self.lsr(self.a());
+                        self.set_a(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_LSR_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::lsr, memory, &mut cycles);
+                    }
+                    Self::INS_LSR_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::lsr, memory, &mut cycles);
+                    }
+                    Self::INS_LSR_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::lsr, memory, &mut cycles);
+                    }
+                    Self::INS_LSR_ABSX => {
+                        let address = self.addr_absolute_x_5(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::lsr, memory, &mut cycles);
+                    }
+                    Self::INS_ROL => {
+                        let value = self.rol(self.a());
+                        self.set_a(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_ROL_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::rol, memory, &mut cycles);
+                    }
+                    Self::INS_ROL_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::rol, memory, &mut cycles);
+                    }
+                    Self::INS_ROL_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::rol, memory, &mut cycles);
+                    }
+                    Self::INS_ROL_ABSX => {
+                        let address = self.addr_absolute_x_5(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::rol, memory, &mut cycles);
+                    }
+                    Self::INS_ROR => {
+                        let value = self.ror(self.a());
+                        self.set_a(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_ROR_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::ror, memory, &mut cycles);
+                    }
+                    Self::INS_ROR_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::ror, memory, &mut cycles);
+                    }
+                    Self::INS_ROR_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::ror, memory, &mut cycles);
+                    }
+                    Self::INS_ROR_ABSX => {
+                        let address = self.addr_absolute_x_5(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::ror, memory, &mut cycles);
+                    }
+                    Self::INS_INC_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::inc, memory, &mut cycles);
+                    }
+                    Self::INS_INC_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::inc, memory, &mut cycles);
+                    }
+                    Self::INS_INC_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::inc, memory, &mut cycles);
+                    }
+                    Self::INS_INC_ABSX => {
+                        let address = self.addr_absolute_x_5(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::inc, memory, &mut cycles);
+                    }
+                    Self::INS_DEC_ZP => {
+                        let address = self.addr_zero_page(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::dec, memory, &mut cycles);
+                    }
+                    Self::INS_DEC_ZPX => {
+                        let address = self.addr_zero_page_x(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::dec, memory, &mut cycles);
+                    }
+                    Self::INS_DEC_ABS => {
+                        let address = self.addr_absolute(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::dec, memory, &mut cycles);
+                    }
+                    Self::INS_DEC_ABSX => {
+                        let address = self.addr_absolute_x_5(&mut cycles, memory);
+                        self.read_modify_write(address, CPU::dec, memory, &mut cycles);
+                    }
+                    Self::INS_INX => {
+                        let value = self.x().wrapping_add(1);
+                        self.set_x(value);
+                        self.load_register_set_status(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_INY => {
+                        let value = self.y().wrapping_add(1);
+                        self.set_y(value);
+                        self.load_register_set_status(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_DEX => {
+                        let value = self.x().wrapping_sub(1);
+                        self.set_x(value);
+                        self.load_register_set_status(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_DEY => {
+                        let value = self.y().wrapping_sub(1);
+                        self.set_y(value);
+                        self.load_register_set_status(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_TAX => {
+                        let value = self.a();
+                        self.set_x(value);
+                        self.load_register_set_status(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_TAY => {
+                        let value = self.a();
+                        self.set_y(value);
+                        self.load_register_set_status(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_TXA => {
+                        let value = self.x();
+                        self.set_a(value);
+                        self.load_register_set_status(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_TYA => {
+                        let value = self.y();
+                        self.set_a(value);
+                        self.load_register_set_status(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_TSX => {
+                        let value = self.sp();
+                        self.set_x(value);
+                        self.load_register_set_status(value);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_TXS => {
+                        self.set_sp(self.x());
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_PHA => {
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                        self.push_byte_to_stack(self.a(), &mut cycles, memory);
+                    }
+                    Self::INS_PHP => {
+                        //the copy of P pushed by PHP has B set
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                        self.push_byte_to_stack(self.status() | 0x10, &mut cycles, memory);
+                    }
+                    Self::INS_PLA => {
+                        //the byte after the opcode, then the stack while the stack pointer is incremented
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                        self.dummy_read(&mut cycles, self.sp_to_address(), memory);
+                        let value = self.pop_byte_from_stack(&mut cycles, memory);
+                        self.set_a(value);
+                        self.load_register_set_status(value);
+                    }
+                    Self::INS_PLP => {
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                        self.dummy_read(&mut cycles, self.sp_to_address(), memory);
+                        let status = self.pop_byte_from_stack(&mut cycles, memory);
+                        self.set_status((status & !0x10) | (self.b() << 4));
+                    }
+                    Self::INS_BPL => {
+                        self.branch_if(self.n() == 0, &mut cycles, memory);
+                    }
+                    Self::INS_BMI => {
+                        self.branch_if(self.n() == 1, &mut cycles, memory);
+                    }
+                    Self::INS_BVC => {
+                        self.branch_if(self.v() == 0, &mut cycles, memory);
+                    }
+                    Self::INS_BVS => {
+                        self.branch_if(self.v() == 1, &mut cycles, memory);
+                    }
+                    Self::INS_BCC => {
+                        self.branch_if(self.c() == 0, &mut cycles, memory);
+                    }
+                    Self::INS_BCS => {
+                        self.branch_if(self.c() == 1, &mut cycles, memory);
+                    }
+                    Self::INS_BNE => {
+                        self.branch_if(self.z() == 0, &mut cycles, memory);
+                    }
+                    Self::INS_BEQ => {
+                        self.branch_if(self.z() == 1, &mut cycles, memory);
+                    }
+                    Self::INS_CLC => {
+                        self.set_c(0);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_SEC => {
+                        self.set_c(1);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_CLI => {
+                        self.set_i(0);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_SEI => {
+                        self.set_i(1);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_CLV => {
+                        self.set_v(0);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_CLD => {
+                        self.set_d(0);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_SED => {
+                        self.set_d(1);
+                        self.dummy_read(&mut cycles, self.pc(), memory);
+                    }
+                    Self::INS_STX_ZPY => {
+                        let address = self.addr_zero_page_y(&mut cycles, memory);
+                        self.write_byte(self.x(), &mut cycles, address, memory);
+                    }
+                    Self::INS_BRK => {
+                        //the byte after BRK is skipped, RTI returns past it
+                        self.fetch_byte(&mut cycles, memory);
+                        self.interrupt(Self::IRQ_VECTOR, self.status() | 0x10, &mut cycles, memory);
+                    }
+                    Self::INS_NOP => {
+                        self.dummy_read(&mut cycles, self.pc(), memory);
                     }
                     _ => {
-                        println!("Instruction not handled {}", ins);
+                        //an opcode this core doesn't run, stop on it rather than guess what it does
+                        self.set_pc(self.pc().wrapping_sub(1));
+                        self.set_jammed(1);
+                        cycles += 1;
+                        break;
                     }
                 }
             }
 
-            let num_cycles_used = cycles_requested - cycles;
-            return num_cycles_used as s32;
+            cycles_requested - cycles
         }
 
-        fn fetch_word(
+        fn fetch_word<B: Bus>(
             &mut self,
             cycles: &mut s32,
-            memory: &Mem
+            memory: &mut B
         ) -> Word {
             //6502 is little endian
-            let pc = self.pc();
+            let lo_byte = self.fetch_byte(cycles, memory);
+            let hi_byte = self.fetch_byte(cycles, memory);
 
-            self.set_pc(pc + 2);
-            *cycles = *cycles - 2;
-
-            u16::from_le_bytes([memory[pc], memory[pc + 1]])
+            u16::from_le_bytes([lo_byte, hi_byte])
         }
 
-        fn fetch_byte(
+        fn fetch_byte<B: Bus>(
             &mut self,
             cycles: &mut s32,
-            memory: &Mem
+            memory: &mut B
         ) -> Byte {
-            let data: Byte = memory[self.pc()];
-            self.set_pc(self.pc() + 1);
-            *cycles = *cycles - 1;
+            let data: Byte = memory.read(self.pc());
+            self.set_pc(self.pc().wrapping_add(1));
+            *cycles -= 1;
 
             data
         }
 
-        fn read_byte(
+        fn read_byte<B: Bus>(
             &mut self,
             cycles: &mut s32,
             address: Word,
-            memory: &Mem,
+            memory: &mut B,
         ) -> Byte {
-            let data: Byte = memory[address];
-            *cycles = *cycles - 1;
+            let data: Byte = memory.read(address);
+            *cycles -= 1;
 
             data
         }
 
-        fn read_word(
+        fn read_word<B: Bus>(
+            &mut self,
+            cycles: &mut s32,
+            address: Word,
+            memory: &mut B,
+        ) -> Word {
+            let lo_byte = self.read_byte(cycles, address, memory) as Word;
+            let hi_byte = self.read_byte(cycles, address.wrapping_add(1), memory) as Word;
+
+            lo_byte | (hi_byte << 8)
+        }
+
+        /** Read a word like the NMOS 6502 does for JMP (ind)
+         * - the high byte never carries into the next page, e.g. ($10FF) reads $10FF and $1000
+         * */
+        fn read_word_within_page<B: Bus>(
             &mut self,
             cycles: &mut s32,
             address: Word,
-            memory: &Mem,
+            memory: &mut B,
         ) -> Word {
+            let hi_address = (address & 0xFF00) | (address.wrapping_add(1) & 0x00FF);
             let lo_byte = self.read_byte(cycles, address, memory) as Word;
-            let hi_byte = self.read_byte(cycles, address + 1, memory) as Word;
+            let hi_byte = self.read_byte(cycles, hi_address, memory) as Word;
 
             lo_byte | (hi_byte << 8)
         }
 
+        /** A cycle the CPU is busy inside, it still reads `address` and throws the value away
+         * - devices see these reads, e.g. a dummy read of a status register can acknowledge it
+         * */
+        fn dummy_read<B: Bus>(
+            &mut self,
+            cycles: &mut s32,
+            address: Word,
+            memory: &mut B,
+        ) {
+            memory.dummy_read(address);
+            *cycles -= 1;
+        }
+
         /** write 1 byte to memory */
-        fn write_byte(
+        fn write_byte<B: Bus>(
             &self,
             value: Byte,
             cycles: &mut s32,
             address: Word,
-            memory: &mut Mem,
+            memory: &mut B,
         ) {
-            memory[address] = value;
-            *cycles = *cycles - 1;
+            memory.write(address, value);
+            *cycles -= 1;
         }
 
         /** write 2 bytes to memory */
-        pub fn write_word(
+        pub fn write_word<B: Bus>(
             &mut self,
             value: Word,
             cycles: &mut s32,
             address: Word,
-            memory: &mut Mem,
+            memory: &mut B,
         ) {
-            memory[address] = (value & 0xFF) as Byte;
-            memory[address + 1] = (value >> 8) as Byte;
+            memory.write(address, (value & 0xFF) as Byte);
+            memory.write(address.wrapping_add(1), (value >> 8) as Byte);
 
-            *cycles = *cycles - 2;
+            *cycles -= 2;
+        }
+
+        /** @return the processor status packed as NV-BDIZC (bit 5 always set) */
+        pub fn status(&self) -> Byte {
+            self.c()
+                | self.z() << 1
+                | self.i() << 2
+                | self.d() << 3
+                | self.b() << 4
+                | 1 << 5
+                | self.v() << 6
+                | self.n() << 7
+        }
+
+        /** Unpack NV-BDIZC into the individual status flags */
+        pub fn set_status(&mut self, status: Byte) {
+            self.set_c(status & 1);
+            self.set_z((status >> 1) & 1);
+            self.set_i((status >> 2) & 1);
+            self.set_d((status >> 3) & 1);
+            self.set_b((status >> 4) & 1);
+            self.set_v((status >> 6) & 1);
+            self.set_n((status >> 7) & 1);
+        }
+
+        /** Drive the IRQ input - level triggered, taken between instructions while I is clear */
+        pub fn set_irq(&mut self, asserted: bool) {
+            self.set_irq_line(asserted as Byte);
+        }
+
+        pub fn irq(&self) -> bool {
+            self.irq_line() == 1
+        }
+
+        /** Drive the NMI input - edge triggered, asserting it latches one NMI */
+        pub fn set_nmi(&mut self, asserted: bool) {
+            if asserted && self.nmi_line() == 0 {
+                self.set_nmi_latched(1);
+            }
+            self.set_nmi_line(asserted as Byte);
+        }
+
+        pub fn nmi(&self) -> bool {
+            self.nmi_line() == 1
+        }
+
+        /** @return true if the CPU stopped on an opcode it doesn't run, PC is left on the opcode
+         * - only a reset gets it going again
+         * */
+        pub fn halted(&self) -> bool {
+            self.jammed() == 1
+        }
+
+        pub fn set_halted(&mut self, halted: bool) {
+            self.set_jammed(halted as Byte);
+        }
+
+        /** Leave D to the program, or ignore it in ADC/SBC as the 2A03 does - D can still be set and pushed */
+        pub fn set_decimal_enabled(&mut self, enabled: bool) {
+            self.set_decimal_disabled(!enabled as Byte);
+        }
+
+        pub fn decimal_enabled(&self) -> bool {
+            self.decimal_disabled() == 0
+        }
+
+        /** @return true if the next step will service an interrupt rather than execute an instruction */
+        pub fn interrupt_pending(&self) -> bool {
+            self.nmi_latched() == 1 || (self.irq_line() == 1 && self.i() == 0)
+        }
+
+        /** @return the interrupt inputs packed as bit 0 IRQ, bit 1 NMI, bit 2 NMI latched */
+        pub fn interrupt_state(&self) -> Byte {
+            self.irq_line() | self.nmi_line() << 1 | self.nmi_latched() << 2
+        }
+
+        pub fn set_interrupt_state(&mut self, state: Byte) {
+            self.set_irq_line(state & 1);
+            self.set_nmi_line((state >> 1) & 1);
+            self.set_nmi_latched((state >> 2) & 1);
+        }
+
+        /** Push PC and `status`, set I and continue at the address in `vector`
+         * - 5 cycles, the 2 before it are the caller's: dummy reads for IRQ/NMI, opcode and padding for BRK
+         * - B is only set in the copy of P pushed by BRK/PHP
+         * */
+        fn interrupt<B: Bus>(&mut self, vector: Word, status: Byte, cycles: &mut s32, memory: &mut B) {
+            let [pc_lo, pc_hi] = self.pc().to_le_bytes();
+            self.push_byte_to_stack(pc_hi, cycles, memory);
+            self.push_byte_to_stack(pc_lo, cycles, memory);
+            self.push_byte_to_stack(status, cycles, memory);
+            self.set_i(1);
+            let address = self.read_word(cycles, vector, memory);
+            self.set_pc(address);
+        }
+
+        fn push_byte_to_stack<B: Bus>(&mut self, value: Byte, cycles: &mut s32, memory: &mut B) {
+            self.write_byte(value, cycles, self.sp_to_address(), memory);
+            self.set_sp(self.sp().wrapping_sub(1));
+        }
+
+        fn pop_byte_from_stack<B: Bus>(&mut self, cycles: &mut s32, memory: &mut B) -> Byte {
+            self.set_sp(self.sp().wrapping_add(1));
+            self.read_byte(cycles, self.sp_to_address(), memory)
         }
 
         /** @return the stack pointer as a full 16-bit address (in the 1st page)*/
@@ -495,31 +1618,67 @@ pub mod m6502 {
             0x100 | (self.sp() as Word)
         }
 
-        /** Push the PC-1 onto the stack */
-        pub fn push_pc_to_stack(
+        /** Push the PC-1 onto the stack, high byte first */
+        pub fn push_pc_to_stack<B: Bus>(
             &mut self,
             cycles: &mut s32,
-            memory: &mut Mem,
+            memory: &mut B,
         ) {
-            self.write_word(self.pc() - 1, cycles, self.sp_to_address() - 1, memory,);
-            self.set_sp(self.sp() - 2);
+            let [pc_lo, pc_hi] = self.pc().wrapping_sub(1).to_le_bytes();
+            self.push_byte_to_stack(pc_hi, cycles, memory);
+            self.push_byte_to_stack(pc_lo, cycles, memory);
         }
 
-        /** Pop a word from the stack */
-        pub fn pop_word_from_stack(
+        /** Pull the return address pushed by JSR and continue after it - what RTS does */
+        pub fn return_from_subroutine<B: Bus>(
             &mut self,
             cycles: &mut s32,
-            memory: &mut Mem,
-        ) -> Word {
-            let value_from_stack = self.read_word(cycles, self.sp_to_address() + 1, memory);
-            self.set_sp(self.sp() + 2);
+            memory: &mut B,
+        ) {
+            self.dummy_read(cycles, self.pc(), memory);
+            let return_address = self.pop_word_from_stack(cycles, memory);
+            //the pulled address is read and thrown away while it's moved past the JSR
+            self.dummy_read(cycles, return_address, memory);
+            self.set_pc(return_address.wrapping_add(1));
+        }
 
-            *cycles = *cycles - 1;
+        /** Pop a word from the stack
+         * - the stack is read and thrown away first, while the stack pointer is incremented
+         * */
+        pub fn pop_word_from_stack<B: Bus>(
+            &mut self,
+            cycles: &mut s32,
+            memory: &mut B,
+        ) -> Word {
+            self.dummy_read(cycles, self.sp_to_address(), memory);
+            let lo_byte = self.pop_byte_from_stack(cycles, memory);
+            let hi_byte = self.pop_byte_from_stack(cycles, memory);
 
-            value_from_stack
+            Word::from_le_bytes([lo_byte, hi_byte])
         }
     }
 }
 
+pub mod coverage;
+pub mod devices;
+pub mod disassembler;
+pub mod frontend;
+pub mod functional_test;
+pub mod gdb;
+pub mod heatmap;
+pub mod host_calls;
+pub mod loader;
+pub mod m2a03;
+pub mod m6510;
+pub mod machines;
+pub mod profiler;
+pub mod rewind;
+pub mod sim65;
+pub mod single_step;
+pub mod snapshot;
+pub mod symbols;
+pub mod trace;
+pub mod trace_diff;
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,98 @@
+//! Intel HEX
+//!
+//! `:LLAAAATT<data>CC` per line, CC being the two's complement of the sum of all other bytes
+
+use super::{record_bytes, Image, LoadError};
+use crate::m6502::*;
+
+const DATA: Byte = 0x00;
+const END_OF_FILE: Byte = 0x01;
+const EXTENDED_SEGMENT_ADDRESS: Byte = 0x02;
+const START_SEGMENT_ADDRESS: Byte = 0x03;
+const EXTENDED_LINEAR_ADDRESS: Byte = 0x04;
+const START_LINEAR_ADDRESS: Byte = 0x05;
+
+pub fn parse(text: &str) -> Result<Image, LoadError> {
+    let mut image = Image::default();
+    //upper address bits set by record types 02 and 04
+    let mut base: u32 = 0;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some(hex) = line.strip_prefix(':') else {
+            return Err(LoadError::Syntax {
+                line: line_number,
+                message: "record does not start with ':'".to_string(),
+            });
+        };
+
+        let bytes = record_bytes(hex, line_number)?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(LoadError::Syntax {
+                line: line_number,
+                message: "record length does not match its byte count".to_string(),
+            });
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 1);
+        let expected = body.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg();
+        if checksum[0] != expected {
+            return Err(LoadError::Checksum {
+                line: line_number,
+                expected,
+                actual: checksum[0],
+            });
+        }
+
+        let offset = u16::from_be_bytes([body[1], body[2]]) as u32;
+        let data = &body[4..];
+        match body[3] {
+            DATA => {
+                let address = base + offset;
+                let last = address + data.len().max(1) as u32 - 1;
+                if last > 0xFFFF {
+                    return Err(LoadError::OutOfRange {
+                        line: line_number,
+                        address: last,
+                    });
+                }
+                image.push(address as Word, data);
+            }
+            END_OF_FILE => return Ok(image),
+            EXTENDED_SEGMENT_ADDRESS if data.len() == 2 => {
+                base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 4;
+            }
+            EXTENDED_LINEAR_ADDRESS if data.len() == 2 => {
+                base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 16;
+            }
+            //CS:IP, only IP means anything to a 6502
+            START_SEGMENT_ADDRESS if data.len() == 4 => {
+                image.start = Some(u16::from_be_bytes([data[2], data[3]]));
+            }
+            START_LINEAR_ADDRESS if data.len() == 4 => {
+                let address = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                if address > 0xFFFF {
+                    return Err(LoadError::OutOfRange {
+                        line: line_number,
+                        address,
+                    });
+                }
+                image.start = Some(address as Word);
+            }
+            record_type => {
+                return Err(LoadError::Syntax {
+                    line: line_number,
+                    message: format!("unsupported record type {:02X}", record_type),
+                })
+            }
+        }
+    }
+
+    Err(LoadError::Syntax {
+        line: text.lines().count(),
+        message: "missing end of file record".to_string(),
+    })
+}
@@ -0,0 +1,153 @@
+//! iNES and NES 2.0: the .nes cartridge dumps
+//!
+//! A 16 byte header saying what board the cartridge is and how big its memories are,
+//! an optional 512 byte trainer, then the PRG ROM and the CHR ROM.
+
+use super::LoadError;
+use crate::m6502::*;
+
+pub const HEADER_SIZE: usize = 16;
+pub const TRAINER_SIZE: usize = 512;
+pub const PRG_BANK_SIZE: usize = 0x4000;
+pub const CHR_BANK_SIZE: usize = 0x2000;
+const MAGIC: &[Byte; 4] = b"NES\x1A";
+
+/** How the PPU's two nametables fill its four */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    /** $2000 and $2400 are the same, as are $2800 and $2C00 - for vertical scrolling */
+    Horizontal,
+    Vertical,
+    /** all four are the first nametable */
+    SingleScreenLower,
+    SingleScreenUpper,
+    /** the cartridge brings the RAM for all four */
+    FourScreen,
+}
+
+/** What a .nes file holds */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rom {
+    /** the header was NES 2.0 rather than iNES */
+    pub nes2: bool,
+    pub mapper: u16,
+    /** NES 2.0 only, 0 otherwise */
+    pub submapper: Byte,
+    pub mirroring: Mirroring,
+    /** the PRG RAM is kept by a battery */
+    pub battery: bool,
+    /** loaded at $7000 on the boards that have one */
+    pub trainer: Option<Vec<Byte>>,
+    pub prg_rom: Vec<Byte>,
+    pub chr_rom: Vec<Byte>,
+    /** volatile and battery-backed PRG RAM, in bytes */
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    /** CHR RAM, in bytes - there is CHR RAM when there is no CHR ROM */
+    pub chr_ram_size: usize,
+}
+
+/** A NES 2.0 ROM size: a count of `unit` banks, or 2^E * (MM * 2 + 1) bytes if the high nibble is $F */
+fn rom_size(lsb: Byte, msb: Byte, unit: usize) -> usize {
+    if msb == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0x03) as usize * 2 + 1;
+        2usize.saturating_pow(exponent).saturating_mul(multiplier)
+    } else {
+        (((msb as usize) << 8) | lsb as usize) * unit
+    }
+}
+
+/** A NES 2.0 RAM size: 64 << n bytes, none for 0 */
+fn ram_size(shift: Byte) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
+
+pub fn parse(data: &[Byte]) -> Result<Rom, LoadError> {
+    let Some(header) = data.get(..HEADER_SIZE) else {
+        return Err(LoadError::Malformed {
+            offset: data.len(),
+            message: "too short for an iNES header".to_string(),
+        });
+    };
+    if &header[..4] != MAGIC {
+        return Err(LoadError::Malformed {
+            offset: 0,
+            message: "no NES<EOF> signature".to_string(),
+        });
+    }
+
+    let flags_6 = header[6];
+    let flags_7 = header[7];
+    let nes2 = flags_7 & 0x0C == 0x08;
+    let mirroring = if flags_6 & 0x08 != 0 {
+        Mirroring::FourScreen
+    } else if flags_6 & 0x01 != 0 {
+        Mirroring::Vertical
+    } else {
+        Mirroring::Horizontal
+    };
+
+    let (mapper, submapper, prg_size, chr_size, prg_ram_size, prg_nvram_size, chr_ram_size);
+    if nes2 {
+        mapper = (flags_6 >> 4) as u16 | (flags_7 & 0xF0) as u16 | ((header[8] & 0x0F) as u16) << 8;
+        submapper = header[8] >> 4;
+        prg_size = rom_size(header[4], header[9] & 0x0F, PRG_BANK_SIZE);
+        chr_size = rom_size(header[5], header[9] >> 4, CHR_BANK_SIZE);
+        prg_ram_size = ram_size(header[10] & 0x0F);
+        prg_nvram_size = ram_size(header[10] >> 4);
+        chr_ram_size = ram_size(header[11] & 0x0F) + ram_size(header[11] >> 4);
+    } else {
+        //DiskDude! and other signatures in bytes 7-15 of old dumps spoil the upper nibble
+        let upper = if header[12..].iter().any(|byte| *byte != 0) { 0 } else { flags_7 & 0xF0 };
+        mapper = ((flags_6 >> 4) | upper) as u16;
+        submapper = 0;
+        prg_size = header[4] as usize * PRG_BANK_SIZE;
+        chr_size = header[5] as usize * CHR_BANK_SIZE;
+        //0 means the 8 KiB that was taken for granted
+        let ram = header[8].max(1) as usize * 0x2000;
+        (prg_ram_size, prg_nvram_size) = if flags_6 & 0x02 != 0 { (0, ram) } else { (ram, 0) };
+        chr_ram_size = if chr_size == 0 { CHR_BANK_SIZE } else { 0 };
+    }
+
+    let mut offset = HEADER_SIZE;
+    let mut take = |size: usize, what: &str| {
+        let end = offset.checked_add(size).filter(|end| *end <= data.len());
+        let Some(end) = end else {
+            return Err(LoadError::Malformed {
+                offset,
+                message: format!("{} bytes of {} run past the end of the file", size, what),
+            });
+        };
+        let bytes = data[offset..end].to_vec();
+        offset = end;
+        Ok(bytes)
+    };
+    let trainer = if flags_6 & 0x04 != 0 { Some(take(TRAINER_SIZE, "trainer")?) } else { None };
+    let prg_rom = take(prg_size, "PRG ROM")?;
+    let chr_rom = take(chr_size, "CHR ROM")?;
+    if prg_rom.is_empty() {
+        return Err(LoadError::Malformed {
+            offset: 4,
+            message: "no PRG ROM".to_string(),
+        });
+    }
+
+    Ok(Rom {
+        nes2,
+        mapper,
+        submapper,
+        mirroring,
+        battery: flags_6 & 0x02 != 0,
+        trainer,
+        prg_rom,
+        chr_rom,
+        prg_ram_size,
+        prg_nvram_size,
+        chr_ram_size,
+    })
+}
@@ -0,0 +1,247 @@
+//! Loading program images into memory
+
+use crate::m6502::*;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub mod ihex;
+pub mod ines;
+pub mod o65;
+pub mod prg;
+pub mod srec;
+
+/** Where the CPU fetches its start address from after a reset */
+pub const RESET_VECTOR: Word = 0xFFFC;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    /** the line number is 1-based */
+    Syntax { line: usize, message: String },
+    Checksum { line: usize, expected: Byte, actual: Byte },
+    /** data would land outside the 64 KiB address space */
+    OutOfRange { line: usize, address: u32 },
+    /** a raw binary too long to fit between its load address and $FFFF */
+    TooLarge { address: Word, length: usize },
+    /** a binary format that doesn't follow its specification, the offset is in bytes */
+    Malformed { offset: usize, message: String },
+    /** an object refers to a symbol nobody provided */
+    Unresolved(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            LoadError::Checksum { line, expected, actual } => write!(
+                f,
+                "line {}: checksum is {:02X}, expected {:02X}",
+                line, actual, expected
+            ),
+            LoadError::OutOfRange { line, address } => {
+                write!(f, "line {}: address ${:X} is outside the 64 KiB address space", line, address)
+            }
+            LoadError::TooLarge { address, length } => write!(
+                f,
+                "{} bytes loaded at ${:04X} would run past $FFFF",
+                length, address
+            ),
+            LoadError::Malformed { offset, message } => write!(f, "offset {}: {}", offset, message),
+            LoadError::Unresolved(name) => write!(f, "unresolved symbol {}", name),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+/** A program as read from a file: blocks of bytes and where they go */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Image {
+    /** (address, bytes), in the order they appeared in the file */
+    pub segments: Vec<(Word, Vec<Byte>)>,
+    /** the start address, if the file carries one */
+    pub start: Option<Word>,
+}
+
+impl Image {
+    /** A raw binary that goes to `address` */
+    pub fn raw(address: Word, data: &[Byte]) -> Result<Self, LoadError> {
+        if address as usize + data.len() > 0x10000 {
+            return Err(LoadError::TooLarge {
+                address,
+                length: data.len(),
+            });
+        }
+
+        Ok(Self {
+            segments: vec![(address, data.to_vec())],
+            start: None,
+        })
+    }
+
+    /** Add `data` at `address`, merging with the previous segment when contiguous */
+    pub(crate) fn push(&mut self, address: Word, data: &[Byte]) {
+        if let Some((last_address, last_data)) = self.segments.last_mut() {
+            if *last_address as usize + last_data.len() == address as usize {
+                last_data.extend_from_slice(data);
+                return;
+            }
+        }
+        self.segments.push((address, data.to_vec()));
+    }
+
+    /**
+     * Copy every segment into memory
+     * - if the image carries a start address it is written to the reset vector
+     * */
+    pub fn load_into(&self, memory: &mut Mem) {
+        for (address, data) in &self.segments {
+            for (offset, byte) in data.iter().enumerate() {
+                memory[address.wrapping_add(offset as Word)] = *byte;
+            }
+        }
+        if let Some(start) = self.start {
+            let [lo, hi] = start.to_le_bytes();
+            memory[RESET_VECTOR] = lo;
+            memory[RESET_VECTOR + 1] = hi;
+        }
+    }
+}
+
+/** Load a raw binary at `address` */
+pub fn load_raw(memory: &mut Mem, address: Word, data: &[Byte]) -> Result<Image, LoadError> {
+    let image = Image::raw(address, data)?;
+    image.load_into(memory);
+    Ok(image)
+}
+
+/** Load an Intel HEX file, setting the reset vector if it carries a start address */
+pub fn load_ihex(memory: &mut Mem, text: &str) -> Result<Image, LoadError> {
+    let image = ihex::parse(text)?;
+    image.load_into(memory);
+    Ok(image)
+}
+
+/** Load a Motorola S-record file, setting the reset vector if it carries a start address */
+pub fn load_srec(memory: &mut Mem, text: &str) -> Result<Image, LoadError> {
+    let image = srec::parse(text)?;
+    image.load_into(memory);
+    Ok(image)
+}
+
+/** Load a Commodore PRG at the address in its first two bytes */
+pub fn load_prg(memory: &mut Mem, data: &[Byte]) -> Result<Image, LoadError> {
+    let image = prg::parse(data)?;
+    image.load_into(memory);
+    Ok(image)
+}
+
+/**
+ * Relocate an o65 object to `placement` and load it
+ * @return the relocated object, including its exported symbols
+ * */
+pub fn load_o65(
+    memory: &mut Mem,
+    data: &[Byte],
+    placement: &o65::Placement,
+    imports: &BTreeMap<String, Word>,
+) -> Result<o65::Object, LoadError> {
+    let object = o65::parse(data, placement, imports)?;
+    object.image.load_into(memory);
+    Ok(object)
+}
+
+/** Read an iNES or NES 2.0 cartridge dump, which goes on a board rather than into memory */
+pub fn load_nes<P: AsRef<Path>>(path: P) -> Result<ines::Rom, LoadError> {
+    ines::parse(&fs::read(path)?)
+}
+
+/** The formats load_file() can read */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Raw,
+    IntelHex,
+    SRecord,
+    Prg,
+}
+
+impl Format {
+    /**
+     * Tell the format from the file extension, case aside
+     * - .hex .ihx .ihex are Intel HEX, .s19 .s28 .s37 .srec .mot are S-records, .prg is a PRG
+     * - anything else is raw, a raw binary can start with any byte so its contents can't tell
+     * */
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        let extension = path
+            .as_ref()
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("hex" | "ihx" | "ihex") => Format::IntelHex,
+            Some("s19" | "s28" | "s37" | "srec" | "mot") => Format::SRecord,
+            Some("prg") => Format::Prg,
+            _ => Format::Raw,
+        }
+    }
+}
+
+/** Load a file, in the format its extension says, raw binaries go to `raw_address` */
+pub fn load_file<P: AsRef<Path>>(memory: &mut Mem, path: P, raw_address: Word) -> Result<Image, LoadError> {
+    let format = Format::from_path(&path);
+    load_file_as(memory, path, format, raw_address)
+}
+
+/** Load a file in `format`, whatever it's called, raw binaries go to `raw_address` */
+pub fn load_file_as<P: AsRef<Path>>(
+    memory: &mut Mem,
+    path: P,
+    format: Format,
+    raw_address: Word,
+) -> Result<Image, LoadError> {
+    match format {
+        Format::Raw => load_raw(memory, raw_address, &fs::read(path)?),
+        Format::IntelHex => load_ihex(memory, &fs::read_to_string(path)?),
+        Format::SRecord => load_srec(memory, &fs::read_to_string(path)?),
+        Format::Prg => load_prg(memory, &fs::read(path)?),
+    }
+}
+
+/** Decode the hex digits of one record, reporting errors against `line` */
+pub(crate) fn record_bytes(hex: &str, line: usize) -> Result<Vec<Byte>, LoadError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(LoadError::Syntax {
+            line,
+            message: "odd number of hex digits".to_string(),
+        });
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| LoadError::Syntax {
+                    line,
+                    message: format!("invalid hex digits at column {}", i + 2),
+                })
+        })
+        .collect()
+}
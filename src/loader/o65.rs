@@ -0,0 +1,261 @@
+//! o65 relocatable objects as produced by xa and cc65's ld65
+//!
+//! Only single (non-chained) 6502 objects are supported. Text, data, bss and zero page
+//! segments can be moved to caller-chosen addresses and every relocation entry is
+//! fixed up accordingly.
+
+use super::{Image, LoadError};
+use crate::m6502::*;
+use std::collections::BTreeMap;
+
+const MARKER: [Byte; 5] = [0x01, 0x00, b'o', b'6', b'5'];
+
+const MODE_65816: Word = 0x8000;
+const MODE_PAGE_RELOCATION: Word = 0x4000;
+const MODE_32_BIT: Word = 0x2000;
+const MODE_CHAIN: Word = 0x0400;
+const MODE_BSS_ZERO: Word = 0x0200;
+
+const SEGMENT_UNDEFINED: Byte = 0;
+const SEGMENT_ABSOLUTE: Byte = 1;
+const SEGMENT_TEXT: Byte = 2;
+const SEGMENT_DATA: Byte = 3;
+const SEGMENT_BSS: Byte = 4;
+const SEGMENT_ZERO: Byte = 5;
+
+const RELOC_WORD: Byte = 0x80;
+const RELOC_HIGH: Byte = 0x40;
+const RELOC_LOW: Byte = 0x20;
+
+/** Where the segments should end up, None keeps the layout of the object */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Placement {
+    pub text: Word,
+    /** defaults to right after text */
+    pub data: Option<Word>,
+    /** defaults to right after data */
+    pub bss: Option<Word>,
+    /** defaults to the zero page base in the header */
+    pub zero: Option<Word>,
+}
+
+/** A relocated object, ready to be loaded */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Object {
+    pub image: Image,
+    /** (base, length) of each segment after relocation */
+    pub text: (Word, Word),
+    pub data: (Word, Word),
+    pub bss: (Word, Word),
+    pub zero: (Word, Word),
+    /** exported globals with their relocated values */
+    pub exports: BTreeMap<String, Word>,
+}
+
+struct Reader<'a> {
+    data: &'a [Byte],
+    offset: usize,
+    wide: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, message: &str) -> LoadError {
+        LoadError::Malformed {
+            offset: self.offset,
+            message: message.to_string(),
+        }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [Byte], LoadError> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + length)
+            .ok_or_else(|| self.error("unexpected end of file"))?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<Byte, LoadError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<Word, LoadError> {
+        let bytes = self.bytes(2)?;
+        Ok(Word::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    /** a header field or count: 16 or 32 bits depending on the mode */
+    fn size(&mut self) -> Result<Word, LoadError> {
+        if !self.wide {
+            return self.word();
+        }
+        let bytes = self.bytes(4)?;
+        let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        Word::try_from(value).map_err(|_| self.error("value does not fit in 16 bits"))
+    }
+
+    fn name(&mut self) -> Result<String, LoadError> {
+        let rest = &self.data[self.offset.min(self.data.len())..];
+        let length = rest
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(|| self.error("unterminated name"))?;
+        let name = String::from_utf8_lossy(&rest[..length]).into_owned();
+        self.offset += length + 1;
+        Ok(name)
+    }
+}
+
+/**
+ * Parse an o65 object and relocate it to `placement`
+ * - `imports` resolves the object's undefined references by name
+ * */
+pub fn parse(data: &[Byte], placement: &Placement, imports: &BTreeMap<String, Word>) -> Result<Object, LoadError> {
+    let mut reader = Reader {
+        data,
+        offset: 0,
+        wide: false,
+    };
+
+    if reader.bytes(MARKER.len())? != MARKER {
+        return Err(LoadError::Malformed {
+            offset: 0,
+            message: "not an o65 object".to_string(),
+        });
+    }
+    if reader.byte()? != 0 {
+        return Err(reader.error("unsupported o65 version"));
+    }
+    let mode = reader.word()?;
+    if mode & MODE_65816 != 0 {
+        return Err(reader.error("65816 objects are not supported"));
+    }
+    if mode & MODE_CHAIN != 0 {
+        return Err(reader.error("chained objects are not supported"));
+    }
+    reader.wide = mode & MODE_32_BIT != 0;
+
+    let (tbase, tlen) = (reader.size()?, reader.size()?);
+    let (dbase, dlen) = (reader.size()?, reader.size()?);
+    let (bbase, blen) = (reader.size()?, reader.size()?);
+    let (zbase, zlen) = (reader.size()?, reader.size()?);
+    let _stack = reader.size()?;
+
+    //header options: length (including itself), type, data
+    loop {
+        let length = reader.byte()?;
+        if length == 0 {
+            break;
+        }
+        if length < 2 {
+            return Err(reader.error("invalid header option length"));
+        }
+        reader.bytes(length as usize - 1)?;
+    }
+
+    let mut text = reader.bytes(tlen as usize)?.to_vec();
+    let mut data_segment = reader.bytes(dlen as usize)?.to_vec();
+
+    let undefined_count = reader.size()?;
+    let mut undefined = Vec::with_capacity(undefined_count as usize);
+    for _ in 0..undefined_count {
+        let name = reader.name()?;
+        let address = *imports.get(&name).ok_or_else(|| LoadError::Unresolved(name.clone()))?;
+        undefined.push(address);
+    }
+
+    let new_tbase = placement.text;
+    let new_dbase = placement.data.unwrap_or(new_tbase.wrapping_add(tlen));
+    let new_bbase = placement.bss.unwrap_or(new_dbase.wrapping_add(dlen));
+    let new_zbase = placement.zero.unwrap_or(zbase);
+    let relocate = |segment: Byte, value: Word| -> Option<Word> {
+        Some(match segment {
+            SEGMENT_ABSOLUTE => value,
+            SEGMENT_TEXT => value.wrapping_add(new_tbase.wrapping_sub(tbase)),
+            SEGMENT_DATA => value.wrapping_add(new_dbase.wrapping_sub(dbase)),
+            SEGMENT_BSS => value.wrapping_add(new_bbase.wrapping_sub(bbase)),
+            SEGMENT_ZERO => value.wrapping_add(new_zbase.wrapping_sub(zbase)),
+            _ => return None,
+        })
+    };
+
+    let page_relocation = mode & MODE_PAGE_RELOCATION != 0;
+    for segment in [&mut text, &mut data_segment] {
+        //the first offset counts from the byte before the segment
+        let mut position: isize = -1;
+        loop {
+            let mut offset = reader.byte()?;
+            if offset == 0 {
+                break;
+            }
+            while offset == 255 {
+                position += 254;
+                offset = reader.byte()?;
+            }
+            position += offset as isize;
+
+            let type_byte = reader.byte()?;
+            let (kind, segment_id) = (type_byte & 0xE0, type_byte & 0x0F);
+            let symbol = if segment_id == SEGMENT_UNDEFINED {
+                let index = reader.size()? as usize;
+                Some(*undefined.get(index).ok_or_else(|| reader.error("undefined reference out of range"))?)
+            } else {
+                None
+            };
+            let fix = |value: Word| match symbol {
+                Some(address) => Some(value.wrapping_add(address)),
+                None => relocate(segment_id, value),
+            };
+
+            let at = position as usize;
+            let length = if kind == RELOC_WORD { 2 } else { 1 };
+            if position < 0 || at + length > segment.len() {
+                return Err(reader.error("relocation outside its segment"));
+            }
+            match kind {
+                RELOC_WORD => {
+                    let value = Word::from_le_bytes([segment[at], segment[at + 1]]);
+                    let value = fix(value).ok_or_else(|| reader.error("invalid relocation segment"))?;
+                    segment[at..at + 2].copy_from_slice(&value.to_le_bytes());
+                }
+                RELOC_HIGH => {
+                    let low = if page_relocation { 0 } else { reader.byte()? };
+                    let value = Word::from_le_bytes([low, segment[at]]);
+                    let value = fix(value).ok_or_else(|| reader.error("invalid relocation segment"))?;
+                    segment[at] = (value >> 8) as Byte;
+                }
+                RELOC_LOW => {
+                    let value = fix(segment[at] as Word).ok_or_else(|| reader.error("invalid relocation segment"))?;
+                    segment[at] = value as Byte;
+                }
+                _ => return Err(reader.error("unsupported relocation type")),
+            }
+        }
+    }
+
+    let export_count = reader.size()?;
+    let mut exports = BTreeMap::new();
+    for _ in 0..export_count {
+        let name = reader.name()?;
+        let segment_id = reader.byte()?;
+        let value = reader.size()?;
+        let value = relocate(segment_id, value).ok_or_else(|| reader.error("invalid export segment"))?;
+        exports.insert(name, value);
+    }
+
+    let mut image = Image::default();
+    image.push(new_tbase, &text);
+    image.push(new_dbase, &data_segment);
+    if mode & MODE_BSS_ZERO != 0 {
+        image.push(new_bbase, &vec![0; blen as usize]);
+    }
+
+    Ok(Object {
+        image,
+        text: (new_tbase, tlen),
+        data: (new_dbase, dlen),
+        bss: (new_bbase, blen),
+        zero: (new_zbase, zlen),
+        exports,
+    })
+}
@@ -0,0 +1,15 @@
+//! Commodore PRG: a little endian load address followed by the bytes to put there
+
+use super::{Image, LoadError};
+use crate::m6502::*;
+
+pub fn parse(data: &[Byte]) -> Result<Image, LoadError> {
+    let [lo, hi, program @ ..] = data else {
+        return Err(LoadError::Malformed {
+            offset: 0,
+            message: "too short to carry a load address".to_string(),
+        });
+    };
+
+    Image::raw(Word::from_le_bytes([*lo, *hi]), program)
+}
@@ -0,0 +1,85 @@
+//! Motorola S-record
+//!
+//! `S<type><count><address><data><checksum>` per line, the checksum being the one's
+//! complement of the sum of the count, address and data bytes
+
+use super::{record_bytes, Image, LoadError};
+use crate::m6502::*;
+
+pub fn parse(text: &str) -> Result<Image, LoadError> {
+    let mut image = Image::default();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut chars = line.chars();
+        let (Some('S'), Some(record_type)) = (chars.next(), chars.next()) else {
+            return Err(LoadError::Syntax {
+                line: line_number,
+                message: "record does not start with 'S' and a type".to_string(),
+            });
+        };
+
+        let address_length = match record_type {
+            '0' | '1' | '5' | '9' => 2,
+            '2' | '6' | '8' => 3,
+            '3' | '7' => 4,
+            _ => {
+                return Err(LoadError::Syntax {
+                    line: line_number,
+                    message: format!("unsupported record type S{}", record_type),
+                })
+            }
+        };
+
+        let bytes = record_bytes(&line[2..], line_number)?;
+        if bytes.len() < address_length + 2 || bytes.len() != bytes[0] as usize + 1 {
+            return Err(LoadError::Syntax {
+                line: line_number,
+                message: "record length does not match its byte count".to_string(),
+            });
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 1);
+        let expected = !body.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        if checksum[0] != expected {
+            return Err(LoadError::Checksum {
+                line: line_number,
+                expected,
+                actual: checksum[0],
+            });
+        }
+
+        let address = body[1..=address_length]
+            .iter()
+            .fold(0u32, |address, byte| (address << 8) | *byte as u32);
+        let data = &body[address_length + 1..];
+        match record_type {
+            '1' | '2' | '3' => {
+                let last = address + data.len().max(1) as u32 - 1;
+                if last > 0xFFFF {
+                    return Err(LoadError::OutOfRange {
+                        line: line_number,
+                        address: last,
+                    });
+                }
+                image.push(address as Word, data);
+            }
+            '7' | '8' | '9' => {
+                if address > 0xFFFF {
+                    return Err(LoadError::OutOfRange {
+                        line: line_number,
+                        address,
+                    });
+                }
+                image.start = Some(address as Word);
+            }
+            //header and record counts
+            _ => {}
+        }
+    }
+
+    Ok(image)
+}
@@ -0,0 +1,310 @@
+//! Ricoh 2A03: the NES's 6502, with the APU and sprite DMA on the same die
+//!
+//! The core is the 6502's with decimal mode cut out: the D flag can be set and pushed, but
+//! ADC and SBC stay binary, which a machine gets with `CPU::set_decimal_enabled(false)`.
+//! What the die adds is on the bus from $4000:
+//! - $4000-$4013 the sound channels, of which only the length counters and the DMC,
+//!   which fetches its samples from memory, are modelled - nothing is heard
+//! - $4014 the OAM DMA, copying a page to the PPU's sprite memory while the CPU waits
+//! - $4015 the channel enables and status, $4017 the frame counter and its IRQ
+//!
+//! Cycle counts are the NTSC 2A03's.
+
+use crate::m6502::*;
+
+pub const CPU_HZ: u64 = 1_789_773;
+
+//registers, by address
+pub const DMC_FREQUENCY: Word = 0x4010;
+pub const DMC_LOAD: Word = 0x4011;
+pub const DMC_ADDRESS: Word = 0x4012;
+pub const DMC_LENGTH: Word = 0x4013;
+/** writing page XX copies $XX00-$XXFF to OAMDATA */
+pub const OAM_DMA: Word = 0x4014;
+/** write: channel enables - read: length counters, DMC and interrupt status */
+pub const APU_STATUS: Word = 0x4015;
+pub const FRAME_COUNTER: Word = 0x4017;
+
+//APU_STATUS bits
+pub const STATUS_DMC: Byte = 0x10;
+pub const STATUS_FRAME_IRQ: Byte = 0x40;
+pub const STATUS_DMC_IRQ: Byte = 0x80;
+
+//FRAME_COUNTER bits
+pub const FRAME_FIVE_STEP: Byte = 0x80;
+pub const FRAME_IRQ_INHIBIT: Byte = 0x40;
+
+//DMC_FREQUENCY bits
+pub const DMC_IRQ_ENABLE: Byte = 0x80;
+pub const DMC_LOOP: Byte = 0x40;
+
+/** what the OAM DMA halts the CPU for, one more when it starts on an odd cycle */
+pub const OAM_DMA_CYCLES: u32 = 513;
+/** what a DMC sample fetch steals from the CPU */
+pub const DMC_STALL_CYCLES: u32 = 4;
+
+/**
+ * the sequences, in CPU cycles from their start: the 4-step one flags the IRQ over its last
+ * three - the quarter frames that clock the envelopes aren't needed without sound
+ * */
+const FOUR_STEP_PERIOD: u32 = 29830;
+const FIVE_STEP_PERIOD: u32 = 37282;
+const HALF_FRAMES_FOUR_STEP: [u32; 2] = [14913, 29829];
+const HALF_FRAMES_FIVE_STEP: [u32; 2] = [14913, 37281];
+
+/** CPU cycles between DMC output bits, by the rate index in DMC_FREQUENCY */
+const DMC_RATES: [u16; 16] = [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54];
+
+const LENGTHS: [Byte; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16,
+    28, 32, 30,
+];
+
+/** The delta modulation channel's sample reader */
+#[derive(Debug, Clone)]
+struct Dmc {
+    irq_enabled: bool,
+    looping: bool,
+    rate: u16,
+    level: Byte,
+    sample_address: Word,
+    sample_length: u16,
+
+    timer: u16,
+    bits_remaining: u8,
+    buffer: Option<Byte>,
+    address: Word,
+    bytes_remaining: u16,
+    irq: bool,
+}
+
+impl Dmc {
+    fn new() -> Self {
+        Self {
+            irq_enabled: false,
+            looping: false,
+            rate: DMC_RATES[0],
+            level: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            timer: DMC_RATES[0],
+            bits_remaining: 8,
+            buffer: None,
+            address: 0xC000,
+            bytes_remaining: 0,
+            irq: false,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    /** One CPU cycle: every `rate` of them the output unit shifts a bit out */
+    fn tick(&mut self) {
+        self.timer -= 1;
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.rate;
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            //the next byte goes from the buffer into the shifter, or there is silence
+            self.bits_remaining = 8;
+            self.buffer = None;
+        }
+    }
+
+    fn fill(&mut self, byte: Byte) {
+        self.buffer = Some(byte);
+        self.address = if self.address == 0xFFFF { 0x8000 } else { self.address + 1 };
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Apu {
+    /** pulse 1, pulse 2, triangle and noise */
+    lengths: [Byte; 4],
+    halted: [bool; 4],
+    enabled: [bool; 4],
+    dmc: Dmc,
+
+    five_step: bool,
+    irq_inhibit: bool,
+    frame_irq: bool,
+    /** CPU cycles into the frame counter's sequence */
+    frame_cycles: u32,
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Self {
+            lengths: [0; 4],
+            halted: [false; 4],
+            enabled: [false; 4],
+            dmc: Dmc::new(),
+            five_step: false,
+            irq_inhibit: false,
+            frame_irq: false,
+            frame_cycles: 0,
+        }
+    }
+
+    /** What RESET does: the channels are silenced, the frame counter keeps its mode */
+    pub fn reset(&mut self) {
+        let (five_step, irq_inhibit) = (self.five_step, self.irq_inhibit);
+        *self = Self::new();
+        self.five_step = five_step;
+        self.irq_inhibit = irq_inhibit;
+    }
+
+    pub fn irq(&self) -> bool {
+        self.frame_irq || self.dmc.irq
+    }
+
+    /** Read APU_STATUS without side effects */
+    pub fn peek_status(&self) -> Byte {
+        let mut status = 0;
+        for (channel, length) in self.lengths.iter().enumerate() {
+            if *length > 0 {
+                status |= 1 << channel;
+            }
+        }
+        if self.dmc.bytes_remaining > 0 {
+            status |= STATUS_DMC;
+        }
+        if self.frame_irq {
+            status |= STATUS_FRAME_IRQ;
+        }
+        if self.dmc.irq {
+            status |= STATUS_DMC_IRQ;
+        }
+        status
+    }
+
+    /** Read APU_STATUS as the CPU does, acknowledging the frame IRQ */
+    pub fn read_status(&mut self) -> Byte {
+        let status = self.peek_status();
+        self.frame_irq = false;
+        status
+    }
+
+    /** Write a register from $4000-$4013, APU_STATUS or FRAME_COUNTER */
+    pub fn write(&mut self, address: Word, value: Byte) {
+        match address {
+            //the length counter halt bits
+            0x4000 | 0x4004 | 0x400C => self.halted[(address as usize - 0x4000) / 4] = value & 0x20 != 0,
+            0x4008 => self.halted[2] = value & 0x80 != 0,
+            //writing the length loads the counter, if the channel is on
+            0x4003 | 0x4007 | 0x400B | 0x400F => {
+                let channel = (address as usize - 0x4000) / 4;
+                if self.enabled[channel] {
+                    self.lengths[channel] = LENGTHS[value as usize >> 3];
+                }
+            }
+            DMC_FREQUENCY => {
+                self.dmc.irq_enabled = value & DMC_IRQ_ENABLE != 0;
+                self.dmc.looping = value & DMC_LOOP != 0;
+                self.dmc.rate = DMC_RATES[value as usize & 0x0F];
+                if !self.dmc.irq_enabled {
+                    self.dmc.irq = false;
+                }
+            }
+            DMC_LOAD => self.dmc.level = value & 0x7F,
+            DMC_ADDRESS => self.dmc.sample_address = 0xC000 + value as Word * 64,
+            DMC_LENGTH => self.dmc.sample_length = value as u16 * 16 + 1,
+            APU_STATUS => {
+                for channel in 0..4 {
+                    self.enabled[channel] = value & (1 << channel) != 0;
+                    if !self.enabled[channel] {
+                        self.lengths[channel] = 0;
+                    }
+                }
+                if value & STATUS_DMC == 0 {
+                    self.dmc.bytes_remaining = 0;
+                } else if self.dmc.bytes_remaining == 0 {
+                    self.dmc.restart();
+                }
+                self.dmc.irq = false;
+            }
+            FRAME_COUNTER => {
+                self.five_step = value & FRAME_FIVE_STEP != 0;
+                self.irq_inhibit = value & FRAME_IRQ_INHIBIT != 0;
+                if self.irq_inhibit {
+                    self.frame_irq = false;
+                }
+                self.frame_cycles = 0;
+                //the 5-step mode clocks the units straight away
+                if self.five_step {
+                    self.half_frame();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /** @return the output level the DMC is at, 0-127 */
+    pub fn dmc_level(&self) -> Byte {
+        self.dmc.level
+    }
+
+    /** @return the address the DMC wants a sample byte from, while its buffer is empty */
+    pub fn dmc_request(&self) -> Option<Word> {
+        (self.dmc.buffer.is_none() && self.dmc.bytes_remaining > 0).then_some(self.dmc.address)
+    }
+
+    /** Hand the DMC the byte it asked for with dmc_request() */
+    pub fn dmc_fill(&mut self, byte: Byte) {
+        self.dmc.fill(byte);
+    }
+
+    fn half_frame(&mut self) {
+        for (length, halted) in self.lengths.iter_mut().zip(self.halted) {
+            if !halted && *length > 0 {
+                *length -= 1;
+            }
+        }
+    }
+
+    /**
+     * Advance by one CPU cycle
+     * - the caller fetches for the DMC when it asks, see dmc_request()
+     * */
+    pub fn tick(&mut self) {
+        self.dmc.tick();
+
+        self.frame_cycles += 1;
+        let half_frames = if self.five_step { HALF_FRAMES_FIVE_STEP } else { HALF_FRAMES_FOUR_STEP };
+        if half_frames.contains(&self.frame_cycles) {
+            self.half_frame();
+        }
+
+        if self.five_step {
+            if self.frame_cycles == FIVE_STEP_PERIOD {
+                self.frame_cycles = 0;
+            }
+        } else {
+            if self.frame_cycles >= FOUR_STEP_PERIOD - 2 && !self.irq_inhibit {
+                self.frame_irq = true;
+            }
+            if self.frame_cycles == FOUR_STEP_PERIOD {
+                self.frame_cycles = 0;
+            }
+        }
+    }
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,115 @@
+//! MOS 6510: the 6502 with a 6-bit I/O port of its own
+//!
+//! The port answers at $0000 (data direction) and $0001 (data) in place of memory,
+//! although the address and data still go out on the bus, so a write lands in the RAM
+//! underneath as well. The core is the 6502's: pair a `CPU` with an `IoPort` on its bus,
+//! either through a `DeviceBus` or by decoding the two addresses in front of the rest of
+//! memory, as the C64's PLA does since the port pins drive its banking.
+
+use crate::devices::Device;
+use crate::m6502::*;
+use crate::snapshot::{SnapshotError, StateReader, StateWriter};
+
+pub const DDR: Word = 0x0000;
+pub const PORT: Word = 0x0001;
+
+//what the C64 hangs on the pins
+/** low banks BASIC out, and with HIRAM low the I/O and character ROM too */
+pub const LORAM: Byte = 0x01;
+/** low banks the KERNAL out */
+pub const HIRAM: Byte = 0x02;
+/** low banks the character ROM in where the I/O would be */
+pub const CHAREN: Byte = 0x04;
+pub const CASSETTE_WRITE: Byte = 0x08;
+/** low while PLAY is held down on the datasette */
+pub const CASSETTE_SENSE: Byte = 0x10;
+/** low to run the datasette motor */
+pub const CASSETTE_MOTOR: Byte = 0x20;
+
+/** P0-P5, the 6510 has no P6 and P7 */
+const PINS: Byte = 0x3F;
+
+#[derive(Debug, Clone)]
+pub struct IoPort {
+    ddr: Byte,
+    data: Byte,
+    /** the levels on the pins set as inputs, pulled up by default */
+    input: Byte,
+}
+
+impl IoPort {
+    pub fn new() -> Self {
+        Self {
+            ddr: 0,
+            data: 0,
+            input: 0xFF,
+        }
+    }
+
+    /** What the RES pin does: every pin becomes an input */
+    pub fn reset(&mut self) {
+        self.ddr = 0;
+        self.data = 0;
+    }
+
+    /** @return the levels on P0-P5, outputs driven from the data register */
+    pub fn pins(&self) -> Byte {
+        ((self.data & self.ddr) | (self.input & !self.ddr)) & PINS
+    }
+
+    /** Drive the pins from outside - only the bits set as inputs are seen */
+    pub fn set_input(&mut self, value: Byte) {
+        self.input = value;
+    }
+
+    /** Read the register at DDR or PORT without side effects, any other address reads the port */
+    pub fn peek(&self, address: Word) -> Byte {
+        if address & 0x01 == DDR {
+            self.ddr
+        } else {
+            //P6 and P7 aren't bonded out, their latch bits read back as written
+            self.pins() | (self.data & !PINS)
+        }
+    }
+
+    pub fn write(&mut self, address: Word, value: Byte) {
+        if address & 0x01 == DDR {
+            self.ddr = value;
+        } else {
+            self.data = value;
+        }
+    }
+}
+
+impl Default for IoPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/** Offset 0 is the data direction register and 1 the data, attach it at $0000 with size 2 */
+impl Device for IoPort {
+    fn read(&mut self, offset: Word) -> Byte {
+        IoPort::peek(self, offset)
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        IoPort::write(self, offset, value)
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        IoPort::peek(self, offset)
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        StateWriter::new().bytes(&[self.ddr, self.data, self.input]).into_bytes()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        let mut state = StateReader::new(state);
+        self.ddr = state.byte()?;
+        self.data = state.byte()?;
+        self.input = state.byte()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! The Apple I: RAM from $0000, a 6820 PIA at $D010 for the keyboard and the
+//! display, and the 256-byte Woz Monitor at $FF00
+//!
+//! The keyboard and display are a terminal on the host side, any `SerialHost`.
+
+use crate::devices::pia::{self, Pia};
+use crate::devices::serial::SerialHost;
+use crate::devices::{DeviceBus, DeviceId};
+use crate::loader::LoadError;
+use crate::m6502::*;
+use super::{reset_cpu, Memory};
+
+pub const CPU_HZ: u64 = 1_022_727;
+pub const PIA_BASE: Word = 0xD010;
+pub const KBD: Word = PIA_BASE + pia::PORT_A as Word;
+pub const KBDCR: Word = PIA_BASE + pia::CRA as Word;
+pub const DSP: Word = PIA_BASE + pia::PORT_B as Word;
+pub const DSPCR: Word = PIA_BASE + pia::CRB as Word;
+pub const ROM_BASE: Word = 0xFF00;
+pub const ROM_SIZE: usize = 0x100;
+/** where the PIA's address space starts, RAM can't reach past it */
+pub const MAX_RAM_SIZE: usize = 0xD000;
+/** characters per line on the display */
+pub const COLUMNS: usize = 40;
+
+pub struct Apple1<H: SerialHost> {
+    pub cpu: CPU,
+    pub bus: DeviceBus<Memory>,
+    /** the keyboard and display */
+    pub terminal: H,
+    pia: DeviceId<Pia>,
+    column: usize,
+}
+
+impl<H: SerialHost> Apple1<H> {
+    /** An Apple I with `ram_size` bytes of RAM, 4096 or 8192 on a real one, and an empty ROM */
+    pub fn new(ram_size: usize, terminal: H) -> Self {
+        let mut bus = DeviceBus::new(Memory::new(ram_size.min(MAX_RAM_SIZE), ROM_SIZE));
+        let pia = bus.attach(PIA_BASE, 4, Pia::new());
+        //the PIA's IRQ pins go nowhere unless you solder them
+        bus.disconnect_interrupts(pia);
+        //PB7 is the display's busy line, and our display is never busy
+        bus.device_mut(pia).set_port_b_input(0x00);
+
+        Self {
+            cpu: CPU::new(),
+            bus,
+            terminal,
+            pia,
+            column: 0,
+        }
+    }
+
+    /** Put e.g. the Woz Monitor in ROM, ending at $FFFF */
+    pub fn load_rom(&mut self, rom: &[Byte]) -> Result<(), LoadError> {
+        if rom.len() > ROM_SIZE {
+            return Err(LoadError::TooLarge {
+                address: ROM_BASE,
+                length: rom.len(),
+            });
+        }
+        self.bus.bus.rom[ROM_SIZE - rom.len()..].copy_from_slice(rom);
+        Ok(())
+    }
+
+    pub fn pia(&self) -> &Pia {
+        self.bus.device(self.pia)
+    }
+
+    /** Press the reset button: the PIA is cleared and the CPU starts at the reset vector, RAM is kept */
+    pub fn reset(&mut self) {
+        self.bus.device_mut(self.pia).reset();
+        self.bus.device_mut(self.pia).set_port_b_input(0x00);
+        reset_cpu(&mut self.cpu, &self.bus);
+        self.column = 0;
+    }
+
+    /** A key is only taken from the terminal once the program has read the last one */
+    fn scan_keyboard(&mut self) {
+        if self.pia().peek(pia::CRA) & pia::CR_C1_IRQ_FLAG != 0 {
+            return;
+        }
+        let Some(key) = self.terminal.receive().and_then(keyboard_code) else {
+            return;
+        };
+
+        //the strobe is a pulse, so whichever CA1 edge is set up gets seen
+        let pia = self.bus.device_mut(self.pia);
+        pia.set_port_a_input(key);
+        pia.set_ca1(false);
+        pia.set_ca1(true);
+    }
+
+    /** Writing DSP in handshake mode pulls CB2 low, the display answers on CB1 */
+    fn refresh_display(&mut self) {
+        if self.pia().cb2() {
+            return;
+        }
+        let code = self.pia().port_b() & 0x7F;
+        let pia = self.bus.device_mut(self.pia);
+        pia.set_cb1(false);
+        pia.set_cb1(true);
+        self.display(code);
+    }
+
+    fn display(&mut self, code: Byte) {
+        if code == b'\r' {
+            self.terminal.transmit(b'\n');
+            self.column = 0;
+            return;
+        }
+        let Some(character) = display_character(code) else {
+            return;
+        };
+        if self.column == COLUMNS {
+            self.terminal.transmit(b'\n');
+            self.column = 0;
+        }
+        self.terminal.transmit(character);
+        self.column += 1;
+    }
+}
+
+impl<H: SerialHost> Step for Apple1<H> {
+    /**
+     * Execute one instruction, then pass a waiting key to the keyboard and a written
+     * character to the display
+     * @return the number of cycles that were used
+     * */
+    fn step(&mut self) -> s32 {
+        let cycles = self.bus.step(&mut self.cpu);
+        self.scan_keyboard();
+        self.refresh_display();
+        cycles
+    }
+}
+
+/**
+ * The code the Apple I keyboard sends for a byte typed on the host, with bit 7 set as on the real one
+ * @return None for what the keyboard can't send
+ * - the keyboard has no lower case, no newline and no backspace: they become upper case, CR and
+ *   the underscore the Woz Monitor takes as a rubout
+ * */
+pub fn keyboard_code(byte: Byte) -> Option<Byte> {
+    let code = match byte {
+        b'\n' | b'\r' => b'\r',
+        0x08 | 0x7F => b'_',
+        0x1B => 0x1B,
+        0x20..=0x7E => byte.to_ascii_uppercase(),
+        _ => return None,
+    };
+    Some(code | 0x80)
+}
+
+/**
+ * How the display shows a 7-bit code
+ * @return None for control codes, which the display ignores
+ * - the character generator only has upper case, the lower case codes show as their upper case
+ * */
+pub fn display_character(code: Byte) -> Option<Byte> {
+    match code & 0x7F {
+        0x20..=0x5F => Some(code & 0x7F),
+        0x60..=0x7E => Some((code & 0x7F).to_ascii_uppercase()),
+        _ => None,
+    }
+}
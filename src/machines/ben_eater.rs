@@ -0,0 +1,114 @@
+//! Ben Eater's breadboard 6502: 32K RAM, 32K EEPROM, a 6522 VIA at $6000 and a
+//! 16x2 HD44780 LCD hanging off the VIA's ports
+//!
+//! The address decoding is the one from the videos: RAM answers from $0000 to $3FFF
+//! (A14 is what selects the VIA, so half of the 62256 is out of reach), the VIA
+//! repeats its 16 registers from $6000 to $7FFF and the ROM fills $8000-$FFFF.
+
+use crate::devices::hd44780::Hd44780;
+use crate::devices::via::Via;
+use crate::devices::{DeviceBus, DeviceId};
+use crate::loader::LoadError;
+use crate::m6502::*;
+use super::{reset_cpu, Memory};
+
+pub const RAM_SIZE: usize = 0x4000;
+pub const VIA_BASE: Word = 0x6000;
+pub const VIA_SIZE: u32 = 0x2000;
+pub const ROM_BASE: Word = 0x8000;
+pub const ROM_SIZE: usize = 0x8000;
+pub const LCD_COLUMNS: usize = 16;
+pub const LCD_ROWS: usize = 2;
+
+/** How the LCD is connected to the VIA */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LcdWiring {
+    /** D0-D7 on port B, E, RW and RS on PA7, PA6 and PA5, as first built */
+    EightBit,
+    /** D4-D7 on PB0-PB3, E, RW and RS on PB6, PB5 and PB4, as rebuilt to free port A */
+    FourBit,
+}
+
+impl LcdWiring {
+    /** @return E, RS, RW and D0-D7 as the LCD sees them, given the levels on the VIA's ports */
+    fn lcd_pins(self, port_a: Byte, port_b: Byte) -> (bool, bool, bool, Byte) {
+        match self {
+            LcdWiring::EightBit => (port_a & 0x80 != 0, port_a & 0x20 != 0, port_a & 0x40 != 0, port_b),
+            LcdWiring::FourBit => (port_b & 0x40 != 0, port_b & 0x10 != 0, port_b & 0x20 != 0, port_b << 4),
+        }
+    }
+
+    /** @return the levels on port B while the LCD drives its data lines with `data` */
+    fn port_b_input(self, data: Byte) -> Byte {
+        match self {
+            LcdWiring::EightBit => data,
+            LcdWiring::FourBit => 0xF0 | (data >> 4),
+        }
+    }
+}
+
+pub struct BenEater {
+    pub cpu: CPU,
+    pub bus: DeviceBus<Memory>,
+    pub lcd: Hd44780,
+    via: DeviceId<Via>,
+    wiring: LcdWiring,
+}
+
+impl BenEater {
+    /** The machine with a blank EEPROM and the CPU clocked at `hz`, which the LCD's busy times follow */
+    pub fn new(wiring: LcdWiring, hz: u64) -> Self {
+        //the EEPROM's write enable is tied high, so it is ROM as far as the CPU goes
+        let mut bus = DeviceBus::new(Memory::new(RAM_SIZE, ROM_SIZE));
+        let via = bus.attach(VIA_BASE, VIA_SIZE, Via::new());
+
+        Self {
+            cpu: CPU::new(),
+            bus,
+            lcd: Hd44780::with_clock(LCD_COLUMNS, LCD_ROWS, hz),
+            via,
+            wiring,
+        }
+    }
+
+    /** Burn an image into the EEPROM, e.g. vasm's 32K a.out - a shorter one starts at $8000 */
+    pub fn load_rom(&mut self, rom: &[Byte]) -> Result<(), LoadError> {
+        if rom.len() > ROM_SIZE {
+            return Err(LoadError::TooLarge {
+                address: ROM_BASE,
+                length: rom.len(),
+            });
+        }
+        self.bus.bus.rom[..rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    pub fn via(&self) -> &Via {
+        self.bus.device(self.via)
+    }
+
+    /** Press the reset button: the VIA is cleared and the CPU starts at the reset vector, RAM is kept */
+    pub fn reset(&mut self) {
+        self.bus.device_mut(self.via).reset();
+        reset_cpu(&mut self.cpu, &self.bus);
+    }
+}
+
+impl Step for BenEater {
+    /**
+     * Execute one instruction, then let the LCD see the VIA's ports and answer a read
+     * @return the number of cycles that were used
+     * */
+    fn step(&mut self) -> s32 {
+        let cycles = self.bus.step(&mut self.cpu);
+        self.lcd.tick(cycles as u32);
+
+        let via = self.bus.device_mut(self.via);
+        let (e, rs, rw, data) = self.wiring.lcd_pins(via.port_a(), via.port_b());
+        self.lcd.set_pins(e, rs, rw, data);
+        let input = self.lcd.output().map_or(0xFF, |data| self.wiring.port_b_input(data));
+        via.set_port_b_input(input);
+
+        cycles
+    }
+}
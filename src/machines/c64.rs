@@ -0,0 +1,461 @@
+//! The Commodore 64: a 6510, 64 KiB of RAM, the BASIC, KERNAL and character ROMs,
+//! a VIC-II, a SID and two CIAs
+//!
+//! The PLA decides what the CPU sees from the 6510 port's LORAM, HIRAM and CHAREN pins:
+//! - $A000-$BFFF BASIC while LORAM and HIRAM are high
+//! - $D000-$DFFF the I/O chips while CHAREN is high, else the character ROM, unless
+//!   LORAM and HIRAM are both low
+//! - $E000-$FFFF the KERNAL while HIRAM is high
+//! - RAM everywhere else, and under all of it for writes
+//!
+//! No cartridge is plugged in. The VIC and the SID are there for the registers only, the
+//! CIAs' ports see no keys pressed and nothing on the serial bus: the text screen is read
+//! back from memory, and keys go straight into the KERNAL's keyboard buffer.
+
+use crate::devices::cia::Cia;
+use crate::devices::vic_ii::{self, VicII};
+use crate::devices::{Device, DeviceBus, DeviceId};
+use crate::loader::{self, LoadError};
+use crate::m6502::*;
+use crate::m6510::{self, IoPort, CHAREN, HIRAM, LORAM};
+use crate::snapshot::SnapshotError;
+use super::reset_cpu;
+use std::collections::VecDeque;
+
+pub const PAL_CPU_HZ: u64 = 985_248;
+pub const NTSC_CPU_HZ: u64 = 1_022_727;
+
+pub const BASIC_BASE: Word = 0xA000;
+pub const BASIC_SIZE: usize = 0x2000;
+pub const CHAR_BASE: Word = 0xD000;
+pub const CHAR_SIZE: usize = 0x1000;
+pub const KERNAL_BASE: Word = 0xE000;
+pub const KERNAL_SIZE: usize = 0x2000;
+
+pub const VIC: Word = 0xD000;
+pub const SID: Word = 0xD400;
+pub const COLOR_RAM: Word = 0xD800;
+pub const COLOR_RAM_SIZE: usize = 0x400;
+pub const CIA_1: Word = 0xDC00;
+pub const CIA_2: Word = 0xDD00;
+/** where the expansion port's I/O1 and I/O2 would be */
+pub const EXPANSION_IO: Word = 0xDE00;
+
+pub const COLUMNS: usize = 40;
+pub const ROWS: usize = 25;
+
+//KERNAL and BASIC variables
+/** NDX, the number of keys in the buffer */
+pub const KEYBOARD_COUNT: Word = 0x00C6;
+/** KEYD, the keyboard buffer */
+pub const KEYBOARD_BUFFER: Word = 0x0277;
+pub const KEYBOARD_BUFFER_SIZE: usize = 10;
+/** where BASIC programs are loaded */
+pub const BASIC_START: Word = 0x0801;
+/** VARTAB, ARYTAB and STREND: the end of the program, and of the variables that follow it */
+pub const BASIC_END_POINTERS: [Word; 3] = [0x002D, 0x002F, 0x0031];
+
+/** What the CPU sees at an address */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bank {
+    Ram,
+    Basic,
+    CharRom,
+    Io,
+    Kernal,
+}
+
+/** What the I/O area holds besides the chips: the SID's registers, color RAM and open bus */
+pub struct IoSpace {
+    /** only the low nibble of each byte exists */
+    pub color_ram: [Byte; COLOR_RAM_SIZE],
+}
+
+impl Bus for IoSpace {
+    fn read(&mut self, address: Word) -> Byte {
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        //the SID makes no sound here
+        if let COLOR_RAM..CIA_1 = address {
+            self.color_ram[(address - COLOR_RAM) as usize] = value & 0x0F;
+        }
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        match address {
+            SID..COLOR_RAM => peek_sid(address),
+            COLOR_RAM..CIA_1 => self.color_ram[(address - COLOR_RAM) as usize],
+            //nothing drives the data bus
+            _ => 0xFF,
+        }
+    }
+}
+
+/** A CIA whose interrupt output is wired to NMI rather than IRQ, as CIA 2's is */
+struct NmiCia(Cia);
+
+impl Device for NmiCia {
+    fn read(&mut self, offset: Word) -> Byte {
+        Device::read(&mut self.0, offset)
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        Device::write(&mut self.0, offset, value)
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        Device::peek(&self.0, offset)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.0.tick(cycles)
+    }
+
+    fn nmi(&self) -> bool {
+        self.0.irq()
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        self.0.save_state()
+    }
+
+    fn restore_state(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        self.0.restore_state(state)
+    }
+}
+
+/**
+ * The 6510 port, the PLA and the chips it selects, over a bus holding the RAM
+ * - the chips sit on a DeviceBus that only sees the addresses the PLA decodes as I/O
+ * */
+pub struct Pla<B: Bus> {
+    pub ram: B,
+    pub port: IoPort,
+    pub io: DeviceBus<IoSpace>,
+    vic: DeviceId<VicII>,
+    cia_1: DeviceId<Cia>,
+    cia_2: DeviceId<NmiCia>,
+    basic: Vec<Byte>,
+    char_rom: Vec<Byte>,
+    kernal: Vec<Byte>,
+}
+
+impl<B: Bus> Pla<B> {
+    /** Erased ROMs over `ram` */
+    pub fn new(ram: B, vic: VicII) -> Self {
+        let mut io = DeviceBus::new(IoSpace {
+            color_ram: [0; COLOR_RAM_SIZE],
+        });
+        //each chip's registers repeat all through its page or pages
+        let vic = io.attach(VIC, (SID - VIC) as u32, vic);
+        let cia_1 = io.attach(CIA_1, (CIA_2 - CIA_1) as u32, Cia::new());
+        let cia_2 = io.attach(CIA_2, (EXPANSION_IO - CIA_2) as u32, NmiCia(Cia::new()));
+        Self {
+            ram,
+            port: IoPort::new(),
+            io,
+            vic,
+            cia_1,
+            cia_2,
+            basic: vec![0xFF; BASIC_SIZE],
+            char_rom: vec![0xFF; CHAR_SIZE],
+            kernal: vec![0xFF; KERNAL_SIZE],
+        }
+    }
+
+    pub fn vic(&self) -> &VicII {
+        self.io.device(self.vic)
+    }
+
+    pub fn cia_1(&self) -> &Cia {
+        self.io.device(self.cia_1)
+    }
+
+    /** its interrupt output is wired to NMI */
+    pub fn cia_2(&self) -> &Cia {
+        &self.io.device(self.cia_2).0
+    }
+
+    /** Pull RESET on the port and the chips */
+    pub fn reset(&mut self) {
+        self.port.reset();
+        self.io.device_mut(self.vic).reset();
+        self.io.device_mut(self.cia_1).reset();
+        self.io.device_mut(self.cia_2).0.reset();
+    }
+
+    /** @return what a read from `address` selects with the port pins as they are */
+    pub fn bank(&self, address: Word) -> Bank {
+        let pins = self.port.pins();
+        let lo_hi = pins & (LORAM | HIRAM);
+        match address >> 12 {
+            0xA | 0xB if lo_hi == LORAM | HIRAM => Bank::Basic,
+            0xD if lo_hi != 0 && pins & CHAREN != 0 => Bank::Io,
+            0xD if lo_hi != 0 => Bank::CharRom,
+            0xE | 0xF if pins & HIRAM != 0 => Bank::Kernal,
+            _ => Bank::Ram,
+        }
+    }
+
+    /** @return the address in RAM of the text screen the VIC shows */
+    pub fn screen_address(&self) -> Word {
+        //the VIC's bank is picked by CIA 2's PA0-PA1, inverted
+        let bank = (!self.cia_2().port_a() & 0x03) as Word * 0x4000;
+        let matrix = (self.vic().peek(vic_ii::MEMORY_POINTERS) >> 4) as Word * 0x400;
+        bank + matrix
+    }
+
+    fn rom(&self, address: Word) -> Option<Byte> {
+        match self.bank(address) {
+            Bank::Basic => Some(self.basic[(address - BASIC_BASE) as usize]),
+            Bank::CharRom => Some(self.char_rom[(address - CHAR_BASE) as usize]),
+            Bank::Kernal => Some(self.kernal[(address - KERNAL_BASE) as usize]),
+            Bank::Ram | Bank::Io => None,
+        }
+    }
+}
+
+/** The SID's readable registers: the paddles, unplugged, and a silent voice 3 */
+fn peek_sid(address: Word) -> Byte {
+    match address & 0x1F {
+        0x19 | 0x1A => 0xFF,
+        _ => 0,
+    }
+}
+
+impl<B: Bus> Bus for Pla<B> {
+    fn read(&mut self, address: Word) -> Byte {
+        if address <= m6510::PORT {
+            return self.port.peek(address);
+        }
+        if self.bank(address) == Bank::Io {
+            return self.io.read(address);
+        }
+        match self.rom(address) {
+            Some(value) => value,
+            None => self.ram.read(address),
+        }
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        if address <= m6510::PORT {
+            self.port.write(address, value);
+        }
+        if self.bank(address) == Bank::Io {
+            self.io.write(address, value);
+        } else {
+            self.ram.write(address, value);
+        }
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        if address <= m6510::PORT {
+            return self.port.peek(address);
+        }
+        if self.bank(address) == Bank::Io {
+            return self.io.peek(address);
+        }
+        match self.rom(address) {
+            Some(value) => value,
+            None => self.ram.peek(address),
+        }
+    }
+}
+
+pub struct C64 {
+    pub cpu: CPU,
+    pub bus: Pla<Mem>,
+    /** PETSCII codes waiting for room in the keyboard buffer */
+    keys: VecDeque<Byte>,
+}
+
+impl C64 {
+    /** A PAL machine with erased ROMs */
+    pub fn new() -> Self {
+        Self::with_vic(VicII::pal())
+    }
+
+    /** An NTSC machine with erased ROMs */
+    pub fn ntsc() -> Self {
+        Self::with_vic(VicII::ntsc())
+    }
+
+    fn with_vic(vic: VicII) -> Self {
+        Self {
+            cpu: CPU::new(),
+            bus: Pla::new(Mem::new(), vic),
+            keys: VecDeque::new(),
+        }
+    }
+
+    pub fn load_basic(&mut self, rom: &[Byte]) -> Result<(), LoadError> {
+        load_rom(&mut self.bus.basic, BASIC_BASE, rom)
+    }
+
+    pub fn load_char_rom(&mut self, rom: &[Byte]) -> Result<(), LoadError> {
+        load_rom(&mut self.bus.char_rom, CHAR_BASE, rom)
+    }
+
+    /** Put the KERNAL in, an image shorter than 8 KiB ends at $FFFF so it holds the vectors */
+    pub fn load_kernal(&mut self, rom: &[Byte]) -> Result<(), LoadError> {
+        if rom.len() > KERNAL_SIZE {
+            return Err(LoadError::TooLarge {
+                address: KERNAL_BASE,
+                length: rom.len(),
+            });
+        }
+        self.bus.kernal[KERNAL_SIZE - rom.len()..].copy_from_slice(rom);
+        Ok(())
+    }
+
+    /**
+     * Load a PRG into RAM, as LOAD"...",8,1 would
+     * @return the address it was loaded at
+     * - a BASIC program, at $0801, also gets its end pointers set so RUN finds it
+     * */
+    pub fn load_prg(&mut self, prg: &[Byte]) -> Result<Word, LoadError> {
+        let image = loader::load_prg(&mut self.bus.ram, prg)?;
+        let (address, data) = &image.segments[0];
+        if *address == BASIC_START {
+            let [lo, hi] = (address + data.len() as Word).to_le_bytes();
+            for pointer in BASIC_END_POINTERS {
+                self.bus.ram[pointer] = lo;
+                self.bus.ram[pointer + 1] = hi;
+            }
+        }
+        Ok(*address)
+    }
+
+    /** Pull RESET: the chips and the port are cleared and the CPU starts at the KERNAL's reset vector, RAM is kept */
+    pub fn reset(&mut self) {
+        self.bus.reset();
+        self.keys.clear();
+        reset_cpu(&mut self.cpu, &self.bus);
+    }
+
+    /**
+     * Queue text to type, characters without a key are left out
+     * - keys go into the keyboard buffer while the KERNAL has emptied it, so wait for it to boot
+     * */
+    pub fn type_text(&mut self, text: &str) {
+        self.keys.extend(text.chars().filter_map(petscii));
+    }
+
+    /** @return true while typed keys are waiting to go into the keyboard buffer */
+    pub fn typing(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    fn feed_keyboard(&mut self) {
+        if self.keys.is_empty() || self.bus.ram[KEYBOARD_COUNT] != 0 {
+            return;
+        }
+        let count = self.keys.len().min(KEYBOARD_BUFFER_SIZE);
+        for (index, key) in self.keys.drain(..count).enumerate() {
+            self.bus.ram[KEYBOARD_BUFFER + index as Word] = key;
+        }
+        self.bus.ram[KEYBOARD_COUNT] = count as Byte;
+    }
+
+    /** @return the screen codes on each row of the text screen */
+    pub fn screen_codes(&self) -> Vec<Vec<Byte>> {
+        let address = self.bus.screen_address();
+        (0..ROWS)
+            .map(|row| {
+                (0..COLUMNS)
+                    .map(|column| self.bus.ram.peek(address.wrapping_add((row * COLUMNS + column) as Word)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /** @return each row of the text screen, see screen_character() */
+    pub fn screen_text(&self) -> Vec<String> {
+        //the upper/lower case set is at $1800 rather than $1000
+        let lowercase = self.bus.vic().peek(vic_ii::MEMORY_POINTERS) & 0x02 != 0;
+        self.screen_codes()
+            .iter()
+            .map(|row| row.iter().map(|code| screen_character(*code, lowercase)).collect())
+            .collect()
+    }
+}
+
+impl Step for C64 {
+    /**
+     * Execute one instruction, then clock the chips for as long as it took
+     * @return the number of cycles that were used
+     * */
+    fn step(&mut self) -> s32 {
+        let cycles = self.cpu.step(&mut self.bus);
+        self.bus.io.tick(cycles as u32, &mut self.cpu);
+        self.feed_keyboard();
+        cycles
+    }
+}
+
+impl Default for C64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn load_rom(rom: &mut [Byte], address: Word, image: &[Byte]) -> Result<(), LoadError> {
+    if image.len() > rom.len() {
+        return Err(LoadError::TooLarge {
+            address,
+            length: image.len(),
+        });
+    }
+    rom[..image.len()].copy_from_slice(image);
+    Ok(())
+}
+
+/**
+ * The PETSCII code of the key typing `character`
+ * @return None for characters without a key
+ * - letters of either case are the unshifted letter keys, as BASIC wants its keywords,
+ *   and '\n' is RETURN
+ * */
+pub fn petscii(character: char) -> Option<Byte> {
+    Some(match character {
+        '\n' | '\r' => 0x0D,
+        //DEL
+        '\x08' | '\x7F' => 0x14,
+        'a'..='z' => character.to_ascii_uppercase() as Byte,
+        ' '..='_' => character as Byte,
+        '£' => 0x5C,
+        '↑' => 0x5E,
+        '←' => 0x5F,
+        'π' => 0xFF,
+        _ => return None,
+    })
+}
+
+/**
+ * The character the character ROM shows for screen code `code`, reversed or not
+ * @return '?' for graphics without a close match
+ * - in the lower case set, codes 1-26 are lower case and $41-$5A upper case letters
+ * */
+pub fn screen_character(code: Byte, lowercase: bool) -> char {
+    let code = code & 0x7F;
+    match code {
+        0x01..=0x1A if lowercase => (b'a' + code - 0x01) as char,
+        0x41..=0x5A if lowercase => (b'A' + code - 0x41) as char,
+        0x00..=0x1F => match code {
+            0x1C => '£',
+            0x1E => '↑',
+            0x1F => '←',
+            _ => (code + 0x40) as char,
+        },
+        0x20..=0x3F => code as char,
+        0x40 | 0x43 => '─',
+        0x42 | 0x5D => '│',
+        0x5B => '┼',
+        0x5E if !lowercase => 'π',
+        0x60 => ' ',
+        0x66 => '▒',
+        _ => '?',
+    }
+}
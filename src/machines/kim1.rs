@@ -0,0 +1,368 @@
+//! The MOS KIM-1: 1 KiB of RAM, two 6530 RRIOTs holding the monitor and tape
+//! ROMs, a 23-key hex keypad, a six-digit LED display and a 20 mA TTY loop
+//!
+//! Only A0-A12 are decoded, so the 8 KiB map repeats all the way up to $FFFF:
+//! - $0000-$03FF RAM, up to $13FF with the expansion the board has room for
+//! - $1700/$1740 the I/O and timer of the 6530-003 and 6530-002
+//! - $1780/$17C0 their 64 bytes of RAM each
+//! - $1800/$1C00 their ROMs, the tape routines and the monitor
+//!
+//! The TTY is bit-banged by the monitor, which is too timing-bound to be worth
+//! simulating: the monitor's GETCH and OUTCH are trapped and talk to a `SerialHost`.
+
+use crate::devices::rriot::{self, Rriot};
+use crate::devices::serial::SerialHost;
+use crate::devices::{DeviceBus, DeviceId};
+use crate::loader::LoadError;
+use crate::m6502::*;
+use super::{reset_cpu, Memory};
+use std::collections::VecDeque;
+
+pub const CPU_HZ: u64 = 1_000_000;
+/** what A0-A12 cover, the map repeats every this many bytes */
+pub const MIRROR_SIZE: u32 = 0x2000;
+pub const RAM_SIZE: usize = 0x400;
+/** the expansion RAM runs up to the 6530s */
+pub const MAX_RAM_SIZE: usize = 0x1400;
+
+pub const IO_003: Word = 0x1700;
+pub const IO_002: Word = 0x1740;
+pub const RAM_003: Word = 0x1780;
+pub const RAM_002: Word = 0x17C0;
+pub const ROM_003: Word = 0x1800;
+pub const ROM_002: Word = 0x1C00;
+
+//monitor entry points and variables
+/** the reset routine jumps here to time a RUBOUT and set the baud rate */
+pub const DETCPS: Word = 0x1C2A;
+pub const START: Word = 0x1C4F;
+pub const GETCH: Word = 0x1E5A;
+pub const OUTCH: Word = 0x1EA0;
+pub const CNTL30: Word = 0x17F2;
+pub const CNTH30: Word = 0x17F3;
+
+/** the bit delay DETCPS would measure at 2400 baud, for programs that look at it */
+const BAUD_2400: (Byte, Byte) = (0x00, 0x0A);
+/** what a trapped GETCH costs while it waits for a key */
+const WAIT_CYCLES: s32 = 10;
+/** how long the LEDs keep their glow, longer than the monitor takes to scan all six */
+const FRAME_CYCLES: u32 = 20_000;
+/** how long a key is held down, and then left up before the next one */
+const KEY_CYCLES: u32 = 50_000;
+
+/** The keys on the keypad */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Hex(Byte),
+    /** AD, address mode */
+    Address,
+    /** DA, data mode */
+    Data,
+    Plus,
+    Go,
+    Pc,
+    /** ST, wired to NMI */
+    Stop,
+    /** RS, wired to RESET */
+    Reset,
+}
+
+impl Key {
+    /**
+     * The key a character on the host keyboard stands for
+     * @return None for characters without a key
+     * - 0-9 and a-f are the hex keys, m is AD, v is DA, + or space is +, g is GO,
+     *   p is PC, s is ST and r is RS
+     * */
+    pub fn from_char(character: char) -> Option<Key> {
+        Some(match character.to_ascii_lowercase() {
+            digit @ ('0'..='9' | 'a'..='f') => Key::Hex(digit.to_digit(16)? as Byte),
+            'm' => Key::Address,
+            'v' => Key::Data,
+            '+' | ' ' => Key::Plus,
+            'g' => Key::Go,
+            'p' => Key::Pc,
+            's' => Key::Stop,
+            'r' => Key::Reset,
+            _ => return None,
+        })
+    }
+
+    /** @return the keypad row the monitor selects to read the key, and its bit on port A */
+    fn matrix(self) -> Option<(Byte, Byte)> {
+        let code = match self {
+            Key::Hex(digit) => digit,
+            Key::Address => 0x10,
+            Key::Data => 0x11,
+            Key::Plus => 0x12,
+            Key::Go => 0x13,
+            Key::Pc => 0x14,
+            Key::Stop | Key::Reset => return None,
+        };
+        Some((code / 7, 1 << (code % 7)))
+    }
+}
+
+/** How the board talks to its user, chosen by the jumper that grounds PA0 */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Console {
+    Keypad,
+    Tty,
+}
+
+pub struct Kim1<H: SerialHost> {
+    pub cpu: CPU,
+    pub bus: DeviceBus<Memory>,
+    /** the TTY */
+    pub terminal: H,
+    pub console: Console,
+    /** the SST switch: NMI after every instruction outside the monitor ROM */
+    pub single_step: bool,
+    rriot_002: DeviceId<Rriot>,
+    rriot_003: DeviceId<Rriot>,
+
+    keys: VecDeque<Key>,
+    held: Option<Key>,
+    key_cycles: u32,
+
+    /** segments seen lit on each digit in this frame and the last */
+    lit: [Byte; 6],
+    display: [Byte; 6],
+    frame_cycles: u32,
+}
+
+impl<H: SerialHost> Kim1<H> {
+    /** A KIM-1 with `ram_size` bytes of RAM and erased ROMs */
+    pub fn new(ram_size: usize, console: Console, terminal: H) -> Self {
+        //RAM repeats in each 8 KiB, the ROMs are the 6530s'
+        let memory = Memory::mirrored(ram_size.min(MAX_RAM_SIZE), 0, MIRROR_SIZE as usize);
+        let mut bus = DeviceBus::new(memory);
+        let rriot_003 = bus.attach(ROM_003, rriot::ROM_SIZE as u32, Rriot::new());
+        let rriot_002 = bus.attach(ROM_002, rriot::ROM_SIZE as u32, Rriot::new());
+        for mirror in (0..0x10000).step_by(MIRROR_SIZE as usize) {
+            let mirror = mirror as Word;
+            for (id, io, ram, rom) in [(rriot_003, IO_003, RAM_003, ROM_003), (rriot_002, IO_002, RAM_002, ROM_002)] {
+                if mirror != 0 {
+                    bus.map(id, mirror + rom, rriot::ROM_SIZE as u32, 0);
+                }
+                bus.map(id, mirror + ram, rriot::RAM_SIZE as u32, rriot::RAM_OFFSET);
+                bus.map(id, mirror + io, 0x40, rriot::IO_OFFSET);
+            }
+        }
+        //the timers' interrupt outputs are only there if you add a jumper
+        bus.disconnect_interrupts(rriot_002);
+        bus.disconnect_interrupts(rriot_003);
+
+        Self {
+            cpu: CPU::new(),
+            bus,
+            terminal,
+            console,
+            single_step: false,
+            rriot_002,
+            rriot_003,
+            keys: VecDeque::new(),
+            held: None,
+            key_cycles: 0,
+            lit: [0; 6],
+            display: [0; 6],
+            frame_cycles: 0,
+        }
+    }
+
+    /** Put the monitor ROM in the 6530-002 */
+    pub fn load_rom_002(&mut self, rom: &[Byte]) -> Result<(), LoadError> {
+        self.bus.device_mut(self.rriot_002).load_rom(rom)
+    }
+
+    /** Put the tape ROM in the 6530-003 */
+    pub fn load_rom_003(&mut self, rom: &[Byte]) -> Result<(), LoadError> {
+        self.bus.device_mut(self.rriot_003).load_rom(rom)
+    }
+
+    pub fn rriot_002(&self) -> &Rriot {
+        self.bus.device(self.rriot_002)
+    }
+
+    pub fn rriot_003(&self) -> &Rriot {
+        self.bus.device(self.rriot_003)
+    }
+
+    /** Press the RS key: the 6530s are cleared and the CPU starts at the reset vector, RAM is kept */
+    pub fn reset(&mut self) {
+        self.bus.device_mut(self.rriot_002).reset();
+        self.bus.device_mut(self.rriot_003).reset();
+        reset_cpu(&mut self.cpu, &self.bus);
+    }
+
+    /** Queue a key press, keys are held down one after the other for as long as a finger would */
+    pub fn press(&mut self, key: Key) {
+        self.keys.push_back(key);
+    }
+
+    /** @return true while keys are being pressed */
+    pub fn typing(&self) -> bool {
+        self.held.is_some() || !self.keys.is_empty()
+    }
+
+    /** @return the segments lit on each digit, left to right, bit 0 being segment a */
+    pub fn display(&self) -> [Byte; 6] {
+        self.display
+    }
+
+    /** @return the cycles used if PC is on a monitor routine that's handled here */
+    fn trap(&mut self) -> Option<s32> {
+        if self.console != Console::Tty {
+            return None;
+        }
+
+        match self.cpu.pc() {
+            DETCPS => {
+                //no RUBOUT to time, and nothing needs the answer with GETCH and OUTCH trapped
+                self.bus.write(CNTH30, BAUD_2400.0);
+                self.bus.write(CNTL30, BAUD_2400.1);
+                self.cpu.set_pc(START);
+                Some(WAIT_CYCLES)
+            }
+            GETCH => {
+                let Some(byte) = self.terminal.receive() else {
+                    return Some(WAIT_CYCLES);
+                };
+                //the monitor echoes each bit as it comes in
+                let byte = if byte == b'\n' { b'\r' } else { byte & 0x7F };
+                self.terminal.transmit(byte);
+                self.cpu.set_a(byte);
+                self.cpu.set_y(0xFF);
+                self.cpu.set_z((byte == 0) as Byte);
+                self.cpu.set_n(0);
+                Some(self.return_from_trap())
+            }
+            OUTCH => {
+                let byte = self.cpu.a() & 0x7F;
+                self.terminal.transmit(byte);
+                self.cpu.set_y(0xFF);
+                Some(self.return_from_trap())
+            }
+            _ => None,
+        }
+    }
+
+    fn return_from_trap(&mut self) -> s32 {
+        //the opcode fetch RTS would have made
+        let mut cycles = -1;
+        self.cpu.return_from_subroutine(&mut cycles, &mut self.bus);
+        -cycles
+    }
+
+    /** Port B picks a keypad row or a display digit through a 74145, port A reads or lights it */
+    fn selected(&self) -> Byte {
+        (self.rriot_002().port_b() >> 1) & 0x0F
+    }
+
+    fn scan_keypad(&mut self, cycles: u32) {
+        self.key_cycles = self.key_cycles.saturating_sub(cycles);
+        if self.key_cycles == 0 {
+            if self.held.take().is_some() {
+                self.key_cycles = KEY_CYCLES;
+            } else if let Some(key) = self.keys.pop_front() {
+                match key {
+                    Key::Stop => self.cpu.set_nmi(true),
+                    Key::Reset => self.reset(),
+                    _ => self.held = Some(key),
+                }
+                self.key_cycles = KEY_CYCLES;
+            }
+        }
+
+        //PA7 is the TTY input, idle high
+        let mut input = 0xFF;
+        if let Some((row, bit)) = self.held.and_then(Key::matrix) {
+            if self.selected() == row {
+                input &= !bit;
+            }
+        }
+        if self.console == Console::Tty {
+            input &= !0x01;
+        }
+        self.bus.device_mut(self.rriot_002).set_port_a_input(input);
+    }
+
+    fn scan_display(&mut self, cycles: u32) {
+        let digit = self.selected();
+        if (4..10).contains(&digit) {
+            let rriot = self.rriot_002();
+            let segments = rriot.port_a() & rriot.peek(rriot::PADD) & 0x7F;
+            self.lit[digit as usize - 4] |= segments;
+        }
+
+        self.frame_cycles += cycles;
+        if self.frame_cycles >= FRAME_CYCLES {
+            self.display = self.lit;
+            self.lit = [0; 6];
+            self.frame_cycles = 0;
+        }
+    }
+}
+
+impl<H: SerialHost> Step for Kim1<H> {
+    /**
+     * Execute one instruction, or a trapped TTY routine
+     * @return the number of cycles that were used
+     * */
+    fn step(&mut self) -> s32 {
+        let cycles = match self.trap() {
+            Some(cycles) => {
+                self.bus.tick(cycles as u32, &mut self.cpu);
+                cycles
+            }
+            None => self.bus.step(&mut self.cpu),
+        };
+        self.scan_keypad(cycles as u32);
+        self.scan_display(cycles as u32);
+
+        if self.single_step && !(ROM_002..ROM_002 + rriot::ROM_SIZE as Word).contains(&(self.cpu.pc() % 0x2000)) {
+            self.cpu.set_nmi(true);
+        }
+        cycles
+    }
+}
+
+/** Segments a-g of the hex digits as the monitor draws them */
+const DIGITS: [Byte; 16] = [
+    0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F, 0x77, 0x7C, 0x39, 0x5E, 0x79, 0x71,
+];
+
+/**
+ * Read the display back as text
+ * @return one character per digit, ' ' when dark and '?' for patterns that aren't hex digits
+ * */
+pub fn display_text(display: &[Byte; 6]) -> String {
+    display
+        .iter()
+        .map(|segments| match segments {
+            0 => ' ',
+            _ => DIGITS
+                .iter()
+                .position(|digit| digit == segments)
+                .and_then(|digit| char::from_digit(digit as u32, 16))
+                .map_or('?', |digit| digit.to_ascii_uppercase()),
+        })
+        .collect()
+}
+
+/** Draw the display as three lines of seven-segment art, the address and data digits apart */
+pub fn display_art(display: &[Byte; 6]) -> [String; 3] {
+    let mut lines = [String::new(), String::new(), String::new()];
+    for (index, segments) in display.iter().enumerate() {
+        if index == 4 {
+            for line in &mut lines {
+                line.push_str("  ");
+            }
+        }
+        let lit = |segment: u8, on: char| if segments & (1 << segment) != 0 { on } else { ' ' };
+        lines[0].extend([' ', lit(0, '_'), ' ']);
+        lines[1].extend([lit(5, '|'), lit(6, '_'), lit(1, '|')]);
+        lines[2].extend([lit(4, '|'), lit(3, '_'), lit(2, '|')]);
+    }
+    lines
+}
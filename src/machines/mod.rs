@@ -0,0 +1,104 @@
+//! Complete computers built from the CPU, memory and the chips in `devices`
+
+use crate::loader::RESET_VECTOR;
+use crate::m6502::*;
+use crate::snapshot::{SaveState, SnapshotError};
+
+pub mod apple1;
+pub mod ben_eater;
+pub mod c64;
+pub mod kim1;
+pub mod nes;
+
+/**
+ * RAM from $0000 up and ROM ending at the top of the map, what the simpler boards have
+ * besides their chips - the map repeats every `mirror_size` bytes on boards that don't decode A13-A15
+ * */
+pub struct Memory {
+    pub ram: Vec<Byte>,
+    pub rom: Vec<Byte>,
+    mirror_size: usize,
+}
+
+impl Memory {
+    /** `ram_size` bytes of RAM and `rom_size` bytes of erased ROM */
+    pub fn new(ram_size: usize, rom_size: usize) -> Self {
+        Self::mirrored(ram_size, rom_size, 0x10000)
+    }
+
+    pub fn mirrored(ram_size: usize, rom_size: usize, mirror_size: usize) -> Self {
+        Self {
+            ram: vec![0; ram_size],
+            rom: vec![0xFF; rom_size],
+            mirror_size,
+        }
+    }
+
+    fn read_byte(&self, address: Word) -> Byte {
+        let address = address as usize % self.mirror_size;
+        let rom_base = self.mirror_size - self.rom.len();
+        if address < self.ram.len() {
+            self.ram[address]
+        } else if address >= rom_base {
+            self.rom[address - rom_base]
+        } else {
+            //nothing drives the data bus
+            0xFF
+        }
+    }
+}
+
+impl Bus for Memory {
+    fn read(&mut self, address: Word) -> Byte {
+        self.read_byte(address)
+    }
+
+    /** ROM ignores writes, so only RAM can be written */
+    fn write(&mut self, address: Word, value: Byte) {
+        let address = address as usize % self.mirror_size;
+        if let Some(byte) = self.ram.get_mut(address) {
+            *byte = value;
+        }
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        self.read_byte(address)
+    }
+}
+
+/** The RAM followed by the ROM, rather than the map they are mirrored across */
+impl SaveState for Memory {
+    fn save_memory(&self) -> Vec<Byte> {
+        [self.ram.as_slice(), self.rom.as_slice()].concat()
+    }
+
+    fn restore_memory(&mut self, memory: &[Byte]) -> Result<(), SnapshotError> {
+        if memory.len() != self.ram.len() + self.rom.len() {
+            return Err(SnapshotError::Bus(format!(
+                "expected {} bytes of RAM and {} of ROM, got {} bytes",
+                self.ram.len(),
+                self.rom.len(),
+                memory.len()
+            )));
+        }
+        let (ram, rom) = memory.split_at(self.ram.len());
+        self.ram.copy_from_slice(ram);
+        self.rom.copy_from_slice(rom);
+
+        Ok(())
+    }
+}
+
+/**
+ * Start the CPU over at the reset vector, as the RESET line does
+ * - the registers are cleared, I is set and SP is left at $FD by the three pushes the reset sequence fakes
+ * */
+pub fn reset_cpu<B: Bus>(cpu: &mut CPU, bus: &B) {
+    //whether D works is down to the chip, not its state
+    let decimal_enabled = cpu.decimal_enabled();
+    *cpu = CPU::new();
+    cpu.set_decimal_enabled(decimal_enabled);
+    cpu.set_pc(Word::from_le_bytes([bus.peek(RESET_VECTOR), bus.peek(RESET_VECTOR + 1)]));
+    cpu.set_sp(0xFD);
+    cpu.set_i(1);
+}
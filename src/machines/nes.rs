@@ -0,0 +1,186 @@
+//! The CPU side of the NES: a 2A03, 2 KiB of RAM, the PPU's registers and a cartridge
+//!
+//! - $0000-$07FF RAM, repeated up to $1FFF
+//! - $2000-$2007 the PPU, repeated up to $3FFF
+//! - $4000-$4017 the APU, OAM DMA and controller ports, $4018-$401F the disabled test registers
+//! - $4020-$FFFF the cartridge, a `Device` that is handed the address itself
+//!
+//! Enough to run CPU test ROMs headless: nothing is drawn or heard, and the controllers
+//! have no buttons pressed.
+
+use crate::devices::ppu::{self, Ppu};
+use crate::devices::Device;
+use crate::m2a03::{self, Apu, DMC_STALL_CYCLES, OAM_DMA_CYCLES};
+use crate::m6502::*;
+use super::reset_cpu;
+
+pub const RAM_SIZE: usize = 0x800;
+pub const PPU: Word = 0x2000;
+pub const APU: Word = 0x4000;
+pub const CONTROLLER_1: Word = 0x4016;
+/** reads controller 2, writes go to the frame counter */
+pub const CONTROLLER_2: Word = 0x4017;
+pub const CARTRIDGE: Word = 0x4020;
+
+/**
+ * The 2A03's address decoding, and the chips it clocks
+ * - not a DeviceBus: the DMC takes the bus over in the middle of the clock to fetch its
+ *   samples, and OAM DMA copies a page through it, so the APU needs the whole bus rather
+ *   than a window of it
+ * */
+pub struct NesBus<C: Device> {
+    pub ram: [Byte; RAM_SIZE],
+    pub ppu: Ppu,
+    pub apu: Apu,
+    pub cartridge: C,
+    /** the page written to OAM_DMA, copied once the instruction is done */
+    oam_dma: Option<Byte>,
+}
+
+impl<C: Device> NesBus<C> {
+    pub fn new(cartridge: C) -> Self {
+        Self {
+            ram: [0; RAM_SIZE],
+            ppu: Ppu::new(),
+            apu: Apu::new(),
+            cartridge,
+            oam_dma: None,
+        }
+    }
+
+    /** @return true while the APU or the cartridge pulls the IRQ line low */
+    pub fn irq(&self) -> bool {
+        self.apu.irq() || self.cartridge.irq()
+    }
+
+    /** @return true while the PPU pulls the NMI line low */
+    pub fn nmi(&self) -> bool {
+        self.ppu.nmi() || self.cartridge.nmi()
+    }
+
+    /**
+     * Clock the APU, PPU and cartridge by `cycles` cycles, fetching for the DMC as it asks
+     * @return the cycles the DMC's fetches stole from the CPU, the chips have been clocked for those too
+     * */
+    pub fn tick(&mut self, cycles: u32) -> u32 {
+        let mut stolen = 0;
+        let mut remaining = cycles;
+        while remaining > 0 {
+            remaining -= 1;
+            self.apu.tick();
+            if let Some(address) = self.apu.dmc_request() {
+                let byte = self.read(address);
+                self.apu.dmc_fill(byte);
+                remaining += DMC_STALL_CYCLES;
+                stolen += DMC_STALL_CYCLES;
+            }
+        }
+        self.ppu.tick(cycles + stolen);
+        self.cartridge.tick(cycles + stolen);
+        stolen
+    }
+
+    /** Copy the page that was written to OAM_DMA to OAMDATA @return true if there was one */
+    fn run_oam_dma(&mut self) -> bool {
+        let Some(page) = self.oam_dma.take() else {
+            return false;
+        };
+        for offset in 0..=0xFF {
+            let byte = self.read(Word::from_le_bytes([offset, page]));
+            self.ppu.write(ppu::OAMDATA, byte);
+        }
+        true
+    }
+
+    /** What is left on the data bus where nothing answers: the high byte of the address */
+    fn open_bus(address: Word) -> Byte {
+        (address >> 8) as Byte
+    }
+}
+
+impl<C: Device> Bus for NesBus<C> {
+    fn read(&mut self, address: Word) -> Byte {
+        match address {
+            ..PPU => self.ram[address as usize % RAM_SIZE],
+            PPU..APU => self.ppu.read(address as Byte),
+            m2a03::APU_STATUS => self.apu.read_status(),
+            CARTRIDGE.. => self.cartridge.read(address),
+            _ => Self::open_bus(address),
+        }
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        match address {
+            ..PPU => self.ram[address as usize % RAM_SIZE] = value,
+            PPU..APU => self.ppu.write(address as Byte, value),
+            m2a03::OAM_DMA => self.oam_dma = Some(value),
+            //the controllers' strobe, nothing to latch
+            CONTROLLER_1 => {}
+            CARTRIDGE.. => self.cartridge.write(address, value),
+            _ => self.apu.write(address, value),
+        }
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        match address {
+            ..PPU => self.ram[address as usize % RAM_SIZE],
+            PPU..APU => self.ppu.peek(address as Byte),
+            m2a03::APU_STATUS => self.apu.peek_status(),
+            CARTRIDGE.. => self.cartridge.peek(address),
+            _ => Self::open_bus(address),
+        }
+    }
+}
+
+pub struct Nes<C: Device> {
+    pub cpu: CPU,
+    pub bus: NesBus<C>,
+    /** CPU cycles since power on, stalls included */
+    cycles: u64,
+}
+
+impl<C: Device> Nes<C> {
+    pub fn new(cartridge: C) -> Self {
+        let mut cpu = CPU::new();
+        cpu.set_decimal_enabled(false);
+        Self {
+            cpu,
+            bus: NesBus::new(cartridge),
+            cycles: 0,
+        }
+    }
+
+    /** Press RESET: the CPU starts at the cartridge's reset vector with interrupts disabled, RAM is kept */
+    pub fn reset(&mut self) {
+        self.bus.ppu.reset();
+        self.bus.apu.reset();
+        self.bus.oam_dma = None;
+        reset_cpu(&mut self.cpu, &self.bus);
+    }
+
+    /** @return CPU cycles since power on, including the ones the CPU spent halted for DMA */
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+}
+
+impl<C: Device> Step for Nes<C> {
+    /**
+     * Execute one instruction, with the OAM DMA it started and the DMC fetches that
+     * happened while it ran
+     * @return the number of cycles that were used, stalls included
+     * */
+    fn step(&mut self) -> s32 {
+        let mut cycles = self.cpu.step(&mut self.bus) as u32;
+        if self.bus.run_oam_dma() {
+            //one more to line up with a read cycle if it starts on an odd one
+            cycles += OAM_DMA_CYCLES + ((self.cycles + cycles as u64) % 2) as u32;
+        }
+        cycles += self.bus.tick(cycles);
+        self.cycles += cycles as u64;
+
+        self.cpu.set_irq(self.bus.irq());
+        self.cpu.set_nmi(self.bus.nmi());
+        cycles as s32
+    }
+}
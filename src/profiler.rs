@@ -0,0 +1,374 @@
+//! Cycle-level profiler: where the time goes, per address and per subroutine
+//!
+//! Subroutines are found at run time from JSR targets, interrupt handlers from the
+//! interrupts taken and BRKs. Cycles are exclusive to the subroutine running when they were
+//! spent, and inclusive for it and every caller up the stack - once per subroutine,
+//! however deep it recursed. Taking an interrupt costs the handler, at its first address.
+
+use crate::m6502::*;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/** What one address cost */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AddressStats {
+    /** times an instruction started here */
+    pub hits: u64,
+    pub cycles: u64,
+}
+
+/** What one subroutine cost */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Subroutine {
+    pub address: Word,
+    pub calls: u64,
+    /** instructions and cycles spent in the subroutine itself */
+    pub exclusive_instructions: u64,
+    pub exclusive_cycles: u64,
+    /** including everything it called */
+    pub inclusive_instructions: u64,
+    pub inclusive_cycles: u64,
+}
+
+/** The cost of calls from one call site to one subroutine */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct CallCost {
+    calls: u64,
+    instructions: u64,
+    cycles: u64,
+}
+
+/** A subroutine that hasn't returned yet */
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    function: Word,
+    caller: Word,
+    call_site: Word,
+    entry_instructions: u64,
+    entry_cycles: u64,
+}
+
+pub struct Profiler {
+    addresses: Vec<AddressStats>,
+    /** (function, address) -> cost, the exclusive cost of each function by line */
+    lines: BTreeMap<(Word, Word), AddressStats>,
+    /** (caller, call site, callee) -> inclusive cost */
+    calls: BTreeMap<(Word, Word, Word), CallCost>,
+    /** function -> inclusive cost of the calls that returned, recursive ones not counted again */
+    inclusive: BTreeMap<Word, CallCost>,
+    /** the function running when profiling started */
+    entry: Option<Word>,
+    stack: Vec<Frame>,
+    instructions: u64,
+    cycles: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            addresses: vec![AddressStats::default(); 0x10000],
+            lines: BTreeMap::new(),
+            calls: BTreeMap::new(),
+            inclusive: BTreeMap::new(),
+            entry: None,
+            stack: Vec::new(),
+            instructions: 0,
+            cycles: 0,
+        }
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn address(&self, address: Word) -> AddressStats {
+        self.addresses[address as usize]
+    }
+
+    /** @return the subroutine running right now */
+    fn current(&self) -> Word {
+        self.stack
+            .last()
+            .map_or(self.entry.unwrap_or(0), |frame| frame.function)
+    }
+
+    fn call(&mut self, call_site: Word, function: Word) {
+        self.stack.push(Frame {
+            function,
+            caller: self.current(),
+            call_site,
+            entry_instructions: self.instructions,
+            entry_cycles: self.cycles,
+        });
+    }
+
+    /** @return true if `function` is running, further up the stack if not on top */
+    fn on_stack(&self, function: Word) -> bool {
+        self.entry == Some(function) || self.stack.iter().any(|frame| frame.function == function)
+    }
+
+    fn ret(&mut self) {
+        //more returns than calls, e.g. returning from code that ran before profiling
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+        let instructions = self.instructions - frame.entry_instructions;
+        let cycles = self.cycles - frame.entry_cycles;
+        let cost = self
+            .calls
+            .entry((frame.caller, frame.call_site, frame.function))
+            .or_default();
+        cost.calls += 1;
+        cost.instructions += instructions;
+        cost.cycles += cycles;
+
+        //a recursive call is already inside the inclusive cost of the outer one
+        if !self.on_stack(frame.function) {
+            let inclusive = self.inclusive.entry(frame.function).or_default();
+            inclusive.instructions += instructions;
+            inclusive.cycles += cycles;
+        }
+    }
+
+    /**
+     * Execute one instruction (or take one interrupt) and account for it
+     * @return the number of cycles that were used
+     * */
+    pub fn step<B: Bus>(&mut self, cpu: &mut CPU, memory: &mut B) -> s32 {
+        if cpu.halted() {
+            return 0;
+        }
+        let pc = cpu.pc();
+        self.entry.get_or_insert(pc);
+
+        if cpu.interrupt_pending() {
+            let cycles = cpu.step(memory);
+            let handler = cpu.pc();
+            self.call(pc, handler);
+            self.cycles += cycles as u64;
+            //no instruction ran, so no hit, but the cycles are the handler's
+            self.addresses[handler as usize].cycles += cycles as u64;
+            self.lines.entry((handler, handler)).or_default().cycles += cycles as u64;
+            return cycles;
+        }
+
+        let opcode = memory.peek(pc);
+        let function = self.current();
+        let cycles = cpu.step(memory);
+
+        self.instructions += 1;
+        self.cycles += cycles as u64;
+        let stats = &mut self.addresses[pc as usize];
+        stats.hits += 1;
+        stats.cycles += cycles as u64;
+        let line = self.lines.entry((function, pc)).or_default();
+        line.hits += 1;
+        line.cycles += cycles as u64;
+
+        match opcode {
+            CPU::INS_JSR | CPU::INS_BRK => self.call(pc, cpu.pc()),
+            CPU::INS_RTS | CPU::INS_RTI => self.ret(),
+            _ => {}
+        }
+
+        cycles
+    }
+
+    /**
+     * Same as CPU::execute, profiling every instruction
+     * @return the number of cycles that were used
+     * */
+    pub fn execute<B: Bus>(&mut self, cycles: s32, cpu: &mut CPU, memory: &mut B) -> s32 {
+        let mut cycles_used = 0;
+        while cycles_used < cycles {
+            let step_cycles = self.step(cpu, memory);
+            if step_cycles == 0 {
+                break;
+            }
+            cycles_used += step_cycles;
+        }
+
+        cycles_used
+    }
+
+    /**
+     * @return every subroutine seen, most inclusive cycles first
+     * - the code profiling started in counts as a subroutine called once
+     * - subroutines that haven't returned yet count what they used so far
+     * */
+    pub fn subroutines(&self) -> Vec<Subroutine> {
+        let mut subroutines: BTreeMap<Word, Subroutine> = BTreeMap::new();
+        fn entry(subroutines: &mut BTreeMap<Word, Subroutine>, address: Word) -> &mut Subroutine {
+            subroutines.entry(address).or_insert(Subroutine {
+                address,
+                ..Default::default()
+            })
+        }
+
+        if let Some(address) = self.entry {
+            let root = entry(&mut subroutines, address);
+            root.calls = 1;
+            root.inclusive_instructions = self.instructions;
+            root.inclusive_cycles = self.cycles;
+        }
+        for ((function, _), stats) in &self.lines {
+            let subroutine = entry(&mut subroutines, *function);
+            subroutine.exclusive_instructions += stats.hits;
+            subroutine.exclusive_cycles += stats.cycles;
+        }
+        for ((_, _, callee), cost) in &self.calls {
+            entry(&mut subroutines, *callee).calls += cost.calls;
+        }
+        for (function, cost) in &self.inclusive {
+            let subroutine = entry(&mut subroutines, *function);
+            subroutine.inclusive_instructions += cost.instructions;
+            subroutine.inclusive_cycles += cost.cycles;
+        }
+        for (depth, frame) in self.stack.iter().enumerate() {
+            let subroutine = entry(&mut subroutines, frame.function);
+            subroutine.calls += 1;
+            //only the outermost call of a function still running counts, the root already counts everything
+            let outermost = self.entry != Some(frame.function)
+                && !self.stack[..depth].iter().any(|outer| outer.function == frame.function);
+            if outermost {
+                subroutine.inclusive_instructions += self.instructions - frame.entry_instructions;
+                subroutine.inclusive_cycles += self.cycles - frame.entry_cycles;
+            }
+        }
+
+        let mut subroutines: Vec<Subroutine> = subroutines.into_values().collect();
+        subroutines.sort_by(|a, b| {
+            b.inclusive_cycles
+                .cmp(&a.inclusive_cycles)
+                .then(a.address.cmp(&b.address))
+        });
+        subroutines
+    }
+
+    /** @return the (address, stats) of every address executed, most cycles first */
+    pub fn hot_addresses(&self) -> Vec<(Word, AddressStats)> {
+        let mut addresses: Vec<(Word, AddressStats)> = self
+            .addresses
+            .iter()
+            .enumerate()
+            .filter(|(_, stats)| stats.hits > 0)
+            .map(|(address, stats)| (address as Word, *stats))
+            .collect();
+        addresses.sort_by(|(a, a_stats), (b, b_stats)| b_stats.cycles.cmp(&a_stats.cycles).then(a.cmp(b)));
+        addresses
+    }
+
+    /**
+     * Write the subroutine table, then the `top` hottest addresses
+     * - `name` turns an address into what to print, e.g. a label
+     * */
+    pub fn write_report<W: Write>(&self, mut out: W, top: usize, name: &dyn Fn(Word) -> String) -> io::Result<()> {
+        let percent = |cycles: u64| {
+            if self.cycles == 0 {
+                0.0
+            } else {
+                cycles as f64 * 100.0 / self.cycles as f64
+            }
+        };
+
+        writeln!(out, "{} instructions, {} cycles", self.instructions, self.cycles)?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{:>12} {:>7} {:>12} {:>7} {:>8}  subroutine",
+            "inclusive", "%", "exclusive", "%", "calls"
+        )?;
+        for subroutine in self.subroutines() {
+            writeln!(
+                out,
+                "{:>12} {:>6.2}% {:>12} {:>6.2}% {:>8}  {}",
+                subroutine.inclusive_cycles,
+                percent(subroutine.inclusive_cycles),
+                subroutine.exclusive_cycles,
+                percent(subroutine.exclusive_cycles),
+                subroutine.calls,
+                name(subroutine.address)
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "{:>12} {:>7} {:>10}  address", "cycles", "%", "hits")?;
+        for (address, stats) in self.hot_addresses().into_iter().take(top) {
+            writeln!(
+                out,
+                "{:>12} {:>6.2}% {:>10}  {}",
+                stats.cycles,
+                percent(stats.cycles),
+                stats.hits,
+                name(address)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Write the profile in callgrind format, for KCachegrind, QCachegrind and friends
+     * - positions are instruction addresses, events are instructions and cycles
+     * */
+    pub fn write_callgrind<W: Write>(&self, mut out: W, name: &dyn Fn(Word) -> String) -> io::Result<()> {
+        writeln!(out, "# callgrind format")?;
+        writeln!(out, "version: 1")?;
+        writeln!(out, "creator: davepoo_6502")?;
+        writeln!(out, "positions: instr")?;
+        writeln!(out, "events: Instructions Cycles")?;
+        writeln!(out, "summary: {} {}", self.instructions, self.cycles)?;
+
+        let mut functions: Vec<Word> = self.lines.keys().map(|(function, _)| *function).collect();
+        functions.extend(self.calls.keys().map(|(caller, _, _)| *caller));
+        functions.sort();
+        functions.dedup();
+
+        //calls still in progress count as if they returned now
+        let mut calls = self.calls.clone();
+        for frame in &self.stack {
+            let cost = calls.entry((frame.caller, frame.call_site, frame.function)).or_default();
+            cost.calls += 1;
+            cost.instructions += self.instructions - frame.entry_instructions;
+            cost.cycles += self.cycles - frame.entry_cycles;
+        }
+
+        for function in functions {
+            writeln!(out)?;
+            writeln!(out, "fn={}", name(function))?;
+            for ((_, address), stats) in self.lines.range((function, 0)..=(function, Word::MAX)) {
+                writeln!(out, "0x{:04X} {} {}", address, stats.hits, stats.cycles)?;
+            }
+            for ((_, call_site, callee), cost) in calls.range((function, 0, 0)..=(function, Word::MAX, Word::MAX)) {
+                writeln!(out, "cfn={}", name(*callee))?;
+                writeln!(out, "calls={} 0x{:04X}", cost.calls, callee)?;
+                writeln!(out, "0x{:04X} {} {}", call_site, cost.instructions, cost.cycles)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn save_callgrind<P: AsRef<Path>>(&self, path: P, name: &dyn Fn(Word) -> String) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_callgrind(&mut out, name)?;
+        out.flush()
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/** Name addresses the way the rest of the crate prints them, e.g. `$C000` */
+pub fn hex_name(address: Word) -> String {
+    format!("${:04X}", address)
+}
@@ -0,0 +1,227 @@
+//! Step backwards through a run: periodic snapshots plus a log of the inputs in between
+//!
+//! The core is deterministic: given the same CPU, bus contents and inputs it always
+//! executes the same way. So going back N instructions only takes restoring the nearest
+//! earlier snapshot and re-executing from there, feeding the logged inputs back in at
+//! the same instruction they originally arrived at.
+
+use crate::devices::DeviceBus;
+use crate::m6502::*;
+use crate::machines::Memory;
+use crate::snapshot::{SaveState, Snapshot, SnapshotError};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+
+/** Something from outside the CPU that changed the course of the run */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    Irq(bool),
+    Nmi(bool),
+    /** a device input showing up on the bus, e.g. a key landing in a keyboard register */
+    Write { address: Word, value: Byte },
+}
+
+impl InputEvent {
+    pub fn apply<B: Bus>(&self, cpu: &mut CPU, bus: &mut B) {
+        match *self {
+            InputEvent::Irq(asserted) => cpu.set_irq(asserted),
+            InputEvent::Nmi(asserted) => cpu.set_nmi(asserted),
+            InputEvent::Write { address, value } => bus.write(address, value),
+        }
+    }
+}
+
+/**
+ * A bus a run can be rewound on: its state can be saved, and it runs each instruction
+ * the same way during replay as the first time, devices clocked and all
+ * */
+pub trait Rewindable: Bus + SaveState {
+    /**
+     * Execute one instruction
+     * @return the number of cycles that were used
+     * */
+    fn step_cpu(&mut self, cpu: &mut CPU) -> s32;
+}
+
+impl Rewindable for Mem {
+    fn step_cpu(&mut self, cpu: &mut CPU) -> s32 {
+        cpu.step(self)
+    }
+}
+
+impl Rewindable for Memory {
+    fn step_cpu(&mut self, cpu: &mut CPU) -> s32 {
+        cpu.step(self)
+    }
+}
+
+impl<B: Bus + SaveState> Rewindable for DeviceBus<B> {
+    fn step_cpu(&mut self, cpu: &mut CPU) -> s32 {
+        self.step(cpu)
+    }
+}
+
+#[derive(Debug)]
+pub enum RewindError {
+    /** the target is older than the oldest snapshot still kept */
+    BeyondHistory { target: u64, oldest: u64 },
+    Snapshot(SnapshotError),
+}
+
+impl fmt::Display for RewindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RewindError::BeyondHistory { target, oldest } => write!(
+                f,
+                "cannot go back to instruction {}, history starts at {}",
+                target, oldest
+            ),
+            RewindError::Snapshot(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for RewindError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RewindError::Snapshot(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SnapshotError> for RewindError {
+    fn from(e: SnapshotError) -> Self {
+        RewindError::Snapshot(e)
+    }
+}
+
+/**
+ * Runs the CPU one instruction at a time while keeping enough history to go back
+ * - a snapshot is taken every `interval` instructions, the oldest dropped beyond `capacity`
+ * - inputs must go through input() so they can be replayed
+ * */
+pub struct Rewind {
+    interval: u64,
+    capacity: usize,
+    /** (instructions executed, snapshot), oldest first */
+    snapshots: VecDeque<(u64, Snapshot)>,
+    /** (instructions executed when it arrived, event), oldest first */
+    events: Vec<(u64, InputEvent)>,
+    instructions: u64,
+    cycles: u64,
+}
+
+impl Rewind {
+    /** Start recording from the current state of `cpu` and `bus` */
+    pub fn new<B: SaveState>(interval: u64, capacity: usize, cpu: &CPU, bus: &B) -> Self {
+        let mut snapshots = VecDeque::new();
+        snapshots.push_back((0, Snapshot::capture(cpu, 0, bus)));
+
+        Self {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            snapshots,
+            events: Vec::new(),
+            instructions: 0,
+            cycles: 0,
+        }
+    }
+
+    /** @return the number of instructions executed since recording started */
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /** @return the earliest instruction count step_back() can still reach */
+    pub fn oldest(&self) -> u64 {
+        self.snapshots.front().map_or(0, |(instructions, _)| *instructions)
+    }
+
+    /** Apply an input now and log it for replay */
+    pub fn input<B: Bus>(&mut self, event: InputEvent, cpu: &mut CPU, bus: &mut B) {
+        event.apply(cpu, bus);
+        self.events.push((self.instructions, event));
+    }
+
+    /**
+     * Execute one instruction, taking a snapshot afterwards when one is due
+     * @return the number of cycles that were used
+     * */
+    pub fn step<B: Rewindable>(&mut self, cpu: &mut CPU, bus: &mut B) -> s32 {
+        let cycles = bus.step_cpu(cpu);
+        self.cycles += cycles as u64;
+        self.instructions += 1;
+
+        if self.instructions.is_multiple_of(self.interval) {
+            self.snapshots
+                .push_back((self.instructions, Snapshot::capture(cpu, self.cycles, bus)));
+            if self.snapshots.len() > self.capacity {
+                self.snapshots.pop_front();
+                let oldest = self.oldest();
+                self.events.retain(|(instructions, _)| *instructions >= oldest);
+            }
+        }
+
+        cycles
+    }
+
+    /**
+     * Go back `count` instructions
+     * @return the instruction count now reached
+     * - the state is the one right after that instruction, before any input that followed it
+     * - everything recorded after it is forgotten
+     * */
+    pub fn step_back<B: Rewindable>(
+        &mut self,
+        count: u64,
+        cpu: &mut CPU,
+        bus: &mut B,
+    ) -> Result<u64, RewindError> {
+        let target = self.instructions.saturating_sub(count);
+        self.rewind_to(target, cpu, bus)?;
+        Ok(target)
+    }
+
+    /** Restore the nearest snapshot at or before `target` and re-execute up to it */
+    pub fn rewind_to<B: Rewindable>(
+        &mut self,
+        target: u64,
+        cpu: &mut CPU,
+        bus: &mut B,
+    ) -> Result<(), RewindError> {
+        if target < self.oldest() {
+            return Err(RewindError::BeyondHistory {
+                target,
+                oldest: self.oldest(),
+            });
+        }
+
+        self.snapshots.retain(|(instructions, _)| *instructions <= target);
+        let (start, snapshot) = self.snapshots.back().expect("the oldest snapshot is kept");
+        self.cycles = snapshot.restore(cpu, bus)?;
+        self.instructions = *start;
+
+        //replay, dropping the inputs that came at or after the target
+        let events = std::mem::take(&mut self.events);
+        let (replayed, _): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .partition(|(instructions, _)| *instructions < target);
+        let mut pending = replayed.iter().filter(|(instructions, _)| *instructions >= *start).peekable();
+        while self.instructions < target {
+            while let Some((_, event)) = pending.next_if(|(instructions, _)| *instructions == self.instructions) {
+                event.apply(cpu, bus);
+            }
+            self.cycles += bus.step_cpu(cpu) as u64;
+            self.instructions += 1;
+        }
+        self.events = replayed;
+
+        Ok(())
+    }
+}
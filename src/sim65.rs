@@ -0,0 +1,345 @@
+//! sim65-compatible paravirtualization, to run cc65 programs linked with `-t sim6502`
+//!
+//! The program calls into the host by JSRing to the addresses below. Arguments follow
+//! the cc65 calling convention: the last one in A/X, the others on the C stack whose
+//! pointer lives in the zero page location named in the program header.
+
+use crate::loader::LoadError;
+use crate::m6502::*;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+
+pub const MAGIC: &[Byte; 5] = b"sim65";
+pub const HEADER_VERSION: Byte = 2;
+pub const HEADER_LENGTH: usize = 12;
+
+pub const PV_OPEN: Word = 0xFFF4;
+pub const PV_CLOSE: Word = 0xFFF5;
+pub const PV_READ: Word = 0xFFF6;
+pub const PV_WRITE: Word = 0xFFF7;
+pub const PV_ARGS: Word = 0xFFF8;
+pub const PV_EXIT: Word = 0xFFF9;
+
+/** open() flags as cc65's fcntl.h defines them */
+const O_RDONLY: Word = 0x01;
+const O_WRONLY: Word = 0x02;
+const O_RDWR: Word = 0x03;
+const O_CREAT: Word = 0x10;
+const O_TRUNC: Word = 0x20;
+const O_APPEND: Word = 0x40;
+const O_EXCL: Word = 0x80;
+
+/** what the paravirtual calls return on failure */
+const FAILURE: Word = 0xFFFF;
+
+/** The header sim65 expects in front of the program */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /** 0 for the 6502, 1 for the 65C02 */
+    pub cpu: Byte,
+    /** zero page address of the C stack pointer */
+    pub sp_address: Byte,
+    pub load_address: Word,
+    pub reset_address: Word,
+}
+
+impl Header {
+    /** @return the header and the program that follows it */
+    pub fn parse(data: &[Byte]) -> Result<(Header, &[Byte]), LoadError> {
+        let malformed = |offset, message: &str| LoadError::Malformed {
+            offset,
+            message: message.to_string(),
+        };
+        if data.len() < HEADER_LENGTH || &data[..5] != MAGIC {
+            return Err(malformed(0, "not a sim65 program"));
+        }
+        if data[5] != HEADER_VERSION {
+            return Err(malformed(5, "unsupported sim65 header version"));
+        }
+        //the core only runs the NMOS 6502 instruction set
+        if data[6] != 0 {
+            return Err(malformed(6, "not a 6502 program, the 65C02 isn't supported"));
+        }
+
+        let header = Header {
+            cpu: data[6],
+            sp_address: data[7],
+            load_address: Word::from_le_bytes([data[8], data[9]]),
+            reset_address: Word::from_le_bytes([data[10], data[11]]),
+        };
+        Ok((header, &data[HEADER_LENGTH..]))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sim65Error {
+    /** the cycle budget ran out before the program called exit */
+    TimedOut { pc: Word, cycles: u64 },
+    /** the CPU halted on an opcode the core doesn't run, PC is left on it */
+    Halted { pc: Word, cycles: u64 },
+}
+
+/** A host stream or file behind one of the program's file descriptors */
+enum Handle {
+    Input(Box<dyn Read>),
+    Output(Box<dyn Write>),
+    File(File),
+}
+
+/** A cc65 program running with sim65's paravirtual host interface */
+pub struct Sim65 {
+    pub cpu: CPU,
+    pub mem: Mem,
+    pub header: Header,
+    args: Vec<String>,
+    handles: HashMap<Word, Handle>,
+}
+
+impl Sim65 {
+    /**
+     * Load a program with its sim65 header
+     * - `args` become argc/argv, the first one being the program name
+     * - stdin, stdout and stderr are those of the host
+     * */
+    pub fn load(program: &[Byte], args: Vec<String>) -> Result<Self, LoadError> {
+        let (header, code) = Header::parse(program)?;
+
+        let mut mem = Mem::new();
+        let mut cpu = CPU::new();
+        cpu.reset(header.reset_address, &mut mem);
+        crate::loader::load_raw(&mut mem, header.load_address, code)?;
+
+        let mut handles = HashMap::new();
+        handles.insert(0, Handle::Input(Box::new(io::stdin())));
+        handles.insert(1, Handle::Output(Box::new(io::stdout())));
+        handles.insert(2, Handle::Output(Box::new(io::stderr())));
+
+        Ok(Self {
+            cpu,
+            mem,
+            header,
+            args,
+            handles,
+        })
+    }
+
+    pub fn with_stdin<R: Read + 'static>(mut self, stdin: R) -> Self {
+        self.handles.insert(0, Handle::Input(Box::new(stdin)));
+        self
+    }
+
+    pub fn with_stdout<W: Write + 'static>(mut self, stdout: W) -> Self {
+        self.handles.insert(1, Handle::Output(Box::new(stdout)));
+        self
+    }
+
+    pub fn with_stderr<W: Write + 'static>(mut self, stderr: W) -> Self {
+        self.handles.insert(2, Handle::Output(Box::new(stderr)));
+        self
+    }
+
+    /**
+     * Run until the program calls exit, the CPU halts or `max_cycles` have been used
+     * @return the exit code the program passed in A
+     * */
+    pub fn run(&mut self, max_cycles: u64) -> Result<Byte, Sim65Error> {
+        let mut cycles: u64 = 0;
+        while cycles < max_cycles {
+            let pc = self.cpu.pc();
+            if (PV_OPEN..=PV_EXIT).contains(&pc) {
+                if pc == PV_EXIT {
+                    return Ok(self.cpu.a());
+                }
+                self.paravirtual_call(pc);
+                //as if the hook ended in RTS
+                let mut rts_cycles = -1;
+                self.cpu.return_from_subroutine(&mut rts_cycles, &mut self.mem);
+                cycles += (-rts_cycles) as u64;
+                continue;
+            }
+
+            cycles += self.cpu.step(&mut self.mem) as u64;
+            if self.cpu.halted() {
+                return Err(Sim65Error::Halted {
+                    pc: self.cpu.pc(),
+                    cycles,
+                });
+            }
+        }
+
+        Err(Sim65Error::TimedOut {
+            pc: self.cpu.pc(),
+            cycles,
+        })
+    }
+
+    fn paravirtual_call(&mut self, pc: Word) {
+        let result = match pc {
+            PV_OPEN => self.pv_open(),
+            PV_CLOSE => self.pv_close(),
+            PV_READ => self.pv_read(),
+            PV_WRITE => self.pv_write(),
+            PV_ARGS => self.pv_args(),
+            _ => FAILURE,
+        };
+        self.set_ax(result);
+    }
+
+    fn ax(&self) -> Word {
+        Word::from_le_bytes([self.cpu.a(), self.cpu.x()])
+    }
+
+    fn set_ax(&mut self, value: Word) {
+        let [lo, hi] = value.to_le_bytes();
+        self.cpu.set_a(lo);
+        self.cpu.set_x(hi);
+    }
+
+    fn read_word(&self, address: Word) -> Word {
+        Word::from_le_bytes([self.mem[address], self.mem[address.wrapping_add(1)]])
+    }
+
+    fn write_word(&mut self, address: Word, value: Word) {
+        let [lo, hi] = value.to_le_bytes();
+        self.mem[address] = lo;
+        self.mem[address.wrapping_add(1)] = hi;
+    }
+
+    fn c_stack_pointer(&self) -> Word {
+        self.read_word(self.header.sp_address as Word)
+    }
+
+    fn set_c_stack_pointer(&mut self, sp: Word) {
+        self.write_word(self.header.sp_address as Word, sp);
+    }
+
+    /** Read the word on top of the C stack, then drop `increment` bytes */
+    fn pop_param(&mut self, increment: Word) -> Word {
+        let sp = self.c_stack_pointer();
+        let value = self.read_word(sp);
+        self.set_c_stack_pointer(sp.wrapping_add(increment));
+        value
+    }
+
+    fn read_string(&self, address: Word) -> String {
+        let mut bytes = Vec::new();
+        let mut address = address;
+        while self.mem[address] != 0 && bytes.len() < 1024 {
+            bytes.push(self.mem[address]);
+            address = address.wrapping_add(1);
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /** int open(const char* name, int flags, ...) - Y holds the size of the arguments */
+    fn pv_open(&mut self) -> Word {
+        let extra = (self.cpu.y() as Word).saturating_sub(4);
+        let _mode = self.pop_param(extra);
+        let flags = self.pop_param(2);
+        let name = self.pop_param(2);
+        let path = self.read_string(name);
+
+        let mut options = OpenOptions::new();
+        match flags & 0x03 {
+            O_RDONLY => options.read(true),
+            O_WRONLY => options.write(true),
+            O_RDWR => options.read(true).write(true),
+            _ => return FAILURE,
+        };
+        if flags & O_EXCL != 0 && flags & O_CREAT != 0 {
+            options.create_new(true);
+        } else if flags & O_CREAT != 0 {
+            options.create(true);
+        }
+        options.truncate(flags & O_TRUNC != 0);
+        if flags & O_APPEND != 0 {
+            options.append(true);
+        }
+
+        match options.open(path) {
+            Ok(file) => {
+                let fd = (3..FAILURE).find(|fd| !self.handles.contains_key(fd)).unwrap_or(FAILURE);
+                self.handles.insert(fd, Handle::File(file));
+                fd
+            }
+            Err(_) => FAILURE,
+        }
+    }
+
+    /** int close(int fd) */
+    fn pv_close(&mut self) -> Word {
+        match self.handles.remove(&self.ax()) {
+            Some(_) => 0,
+            None => FAILURE,
+        }
+    }
+
+    /** int read(int fd, void* buf, unsigned count) */
+    fn pv_read(&mut self) -> Word {
+        let count = self.ax();
+        let buffer = self.pop_param(2);
+        let fd = self.pop_param(2);
+
+        let mut data = vec![0; count as usize];
+        let read = match self.handles.get_mut(&fd) {
+            Some(Handle::Input(input)) => input.read(&mut data),
+            Some(Handle::File(file)) => file.read(&mut data),
+            _ => return FAILURE,
+        };
+        match read {
+            Ok(read) => {
+                for (offset, byte) in data[..read].iter().enumerate() {
+                    self.mem[buffer.wrapping_add(offset as Word)] = *byte;
+                }
+                read as Word
+            }
+            Err(_) => FAILURE,
+        }
+    }
+
+    /** int write(int fd, const void* buf, unsigned count) */
+    fn pv_write(&mut self) -> Word {
+        let count = self.ax();
+        let buffer = self.pop_param(2);
+        let fd = self.pop_param(2);
+
+        let data: Vec<Byte> = (0..count).map(|offset| self.mem[buffer.wrapping_add(offset)]).collect();
+        let written = match self.handles.get_mut(&fd) {
+            Some(Handle::Output(output)) => output.write_all(&data).and_then(|_| output.flush()),
+            Some(Handle::File(file)) => file.write_all(&data),
+            _ => return FAILURE,
+        };
+        match written {
+            Ok(()) => count,
+            Err(_) => FAILURE,
+        }
+    }
+
+    /**
+     * Copy the arguments below the C stack and point *AX at the argv array
+     * @return argc
+     * */
+    fn pv_args(&mut self) -> Word {
+        let argv_pointer = self.ax();
+        let argc = self.args.len() as Word;
+        let mut sp = self.c_stack_pointer();
+        let mut argv = sp.wrapping_sub((argc + 1) * 2);
+        self.write_word(argv_pointer, argv);
+
+        sp = argv;
+        for arg in self.args.clone() {
+            let bytes = arg.as_bytes();
+            sp = sp.wrapping_sub(bytes.len() as Word + 1);
+            for (offset, byte) in bytes.iter().chain(std::iter::once(&0)).enumerate() {
+                self.mem[sp.wrapping_add(offset as Word)] = *byte;
+            }
+            self.write_word(argv, sp);
+            argv = argv.wrapping_add(2);
+        }
+        self.write_word(argv, 0);
+
+        self.set_c_stack_pointer(sp);
+        argc
+    }
+}
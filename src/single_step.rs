@@ -0,0 +1,224 @@
+//! Runner for the per-opcode JSON test vectors of the SingleStepTests project
+//!
+//! Every file (e.g. `a9.json`) holds thousands of cases for one opcode. A case gives
+//! the CPU and RAM before and after one instruction, plus every bus access it made.
+
+use crate::m6502::*;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BusOp {
+    Read,
+    Write,
+}
+
+/** One bus cycle: [address, value, "read" | "write"] */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct BusCycle(pub Word, pub Byte, pub BusOp);
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct State {
+    pub pc: Word,
+    pub s: Byte,
+    pub a: Byte,
+    pub x: Byte,
+    pub y: Byte,
+    pub p: Byte,
+    /** [address, value] pairs, everything else is unspecified */
+    pub ram: Vec<(Word, Byte)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TestCase {
+    pub name: String,
+    pub initial: State,
+    #[serde(rename = "final")]
+    pub expected: State,
+    pub cycles: Vec<BusCycle>,
+}
+
+/** Flat RAM that remembers every access the CPU makes */
+pub struct RecordingBus {
+    pub mem: Mem,
+    pub cycles: Vec<BusCycle>,
+}
+
+impl RecordingBus {
+    pub fn new() -> Self {
+        Self {
+            mem: Mem::new(),
+            cycles: Vec::new(),
+        }
+    }
+}
+
+impl Default for RecordingBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus for RecordingBus {
+    fn read(&mut self, address: Word) -> Byte {
+        let value = self.mem[address];
+        self.cycles.push(BusCycle(address, value, BusOp::Read));
+        value
+    }
+
+    fn write(&mut self, address: Word, value: Byte) {
+        self.mem[address] = value;
+        self.cycles.push(BusCycle(address, value, BusOp::Write));
+    }
+
+    fn peek(&self, address: Word) -> Byte {
+        self.mem[address]
+    }
+}
+
+impl TestCase {
+    /**
+     * Set up the initial state, run one instruction and compare against the final state
+     * @return a description of every difference, empty if the case passed
+     * */
+    pub fn run(&self) -> Vec<String> {
+        let mut cpu = CPU::new();
+        let mut bus = RecordingBus::new();
+        cpu.set_pc(self.initial.pc);
+        cpu.set_sp(self.initial.s);
+        cpu.set_a(self.initial.a);
+        cpu.set_x(self.initial.x);
+        cpu.set_y(self.initial.y);
+        cpu.set_status(self.initial.p);
+        for (address, value) in &self.initial.ram {
+            bus.mem[*address] = *value;
+        }
+
+        let stepped = panic::catch_unwind(AssertUnwindSafe(|| {
+            cpu.step(&mut bus);
+        }));
+        if let Err(cause) = stepped {
+            let message = cause
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| cause.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            return vec![format!("panicked: {}", message)];
+        }
+
+        let mut differences = Vec::new();
+        let mut compare = |name: &str, expected: Word, actual: Word| {
+            if expected != actual {
+                differences.push(format!("{}: expected {:02X}, got {:02X}", name, expected, actual));
+            }
+        };
+        compare("pc", self.expected.pc, cpu.pc());
+        compare("s", self.expected.s as Word, cpu.sp() as Word);
+        compare("a", self.expected.a as Word, cpu.a() as Word);
+        compare("x", self.expected.x as Word, cpu.x() as Word);
+        compare("y", self.expected.y as Word, cpu.y() as Word);
+        //bit 5 doesn't exist in the CPU, it always reads back as 1
+        compare("p", (self.expected.p | 0x20) as Word, cpu.status() as Word);
+        for (address, value) in &self.expected.ram {
+            compare(&format!("ram[{:04X}]", address), *value as Word, bus.mem[*address] as Word);
+        }
+
+        if bus.cycles != self.cycles {
+            differences.push(format!(
+                "cycles: expected {}, got {}",
+                format_cycles(&self.cycles),
+                format_cycles(&bus.cycles)
+            ));
+        }
+
+        differences
+    }
+}
+
+fn format_cycles(cycles: &[BusCycle]) -> String {
+    cycles
+        .iter()
+        .map(|BusCycle(address, value, op)| {
+            let op = match op {
+                BusOp::Read => 'r',
+                BusOp::Write => 'w',
+            };
+            format!("{}{:04X}={:02X}", op, address, value)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/** The result of running every case in one opcode file */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeReport {
+    /** the file name without extension, e.g. "a9" */
+    pub opcode: String,
+    pub total: usize,
+    /** (test name, differences) for every failing case */
+    pub failures: Vec<(String, Vec<String>)>,
+}
+
+impl OpcodeReport {
+    pub fn passed(&self) -> usize {
+        self.total - self.failures.len()
+    }
+}
+
+impl fmt::Display for OpcodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}/{} passed", self.opcode, self.passed(), self.total)?;
+        for (name, differences) in &self.failures {
+            writeln!(f, "  {}: {}", name, differences.join("; "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/** Run every case in `json` */
+pub fn run_cases(opcode: &str, json: &str) -> serde_json::Result<OpcodeReport> {
+    let cases: Vec<TestCase> = serde_json::from_str(json)?;
+    let failures = cases
+        .iter()
+        .map(|case| (case.name.clone(), case.run()))
+        .filter(|(_, differences)| !differences.is_empty())
+        .collect();
+
+    Ok(OpcodeReport {
+        opcode: opcode.to_string(),
+        total: cases.len(),
+        failures,
+    })
+}
+
+/** Run every case in one opcode file, e.g. `v1/a9.json` */
+pub fn run_file<P: AsRef<Path>>(path: P) -> io::Result<OpcodeReport> {
+    let path = path.as_ref();
+    let opcode = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let json = fs::read_to_string(path)?;
+
+    run_cases(&opcode, &json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/** Run every `*.json` file in `directory`, sorted by opcode */
+pub fn run_directory<P: AsRef<Path>>(directory: P) -> io::Result<Vec<OpcodeReport>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    paths.iter().map(run_file).collect()
+}
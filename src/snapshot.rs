@@ -0,0 +1,389 @@
+//! Save states: the CPU, the cycle count and the whole bus in one versioned blob
+//!
+//! Layout (little-endian):
+//! - `6502SNAP` magic, version (2 bytes)
+//! - PC (2 bytes), SP, A, X, Y, P, interrupt inputs (since version 2), halted (since version 3),
+//!   decimal mode enabled (since version 4)
+//! - cycle count (8 bytes), pending cycles (4 bytes, signed, since version 3)
+//! - memory length (4 bytes) and the memory image
+//! - device state flag (1 byte), then its length (4 bytes) and bytes if the flag is set
+
+use crate::m6502::*;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+pub const MAGIC: &[Byte; 8] = b"6502SNAP";
+/** bumped whenever the layout changes */
+pub const VERSION: u16 = 4;
+/** the oldest version this build still reads */
+pub const OLDEST_SUPPORTED_VERSION: u16 = 1;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /** the data doesn't start with the magic */
+    NotASnapshot,
+    /** written by a build with an incompatible layout */
+    UnsupportedVersion { found: u16, supported: (u16, u16) },
+    /** the data ends before the offset (in bytes) where more was expected */
+    Truncated { offset: usize },
+    /** the bus refused the memory image or device state */
+    Bus(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{}", e),
+            SnapshotError::NotASnapshot => write!(f, "not a snapshot"),
+            SnapshotError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "snapshot version {} is not supported, expected {} to {}",
+                found, supported.0, supported.1
+            ),
+            SnapshotError::Truncated { offset } => write!(f, "snapshot truncated at offset {}", offset),
+            SnapshotError::Bus(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+/** A bus whose contents can be saved and restored */
+pub trait SaveState {
+    /** @return everything the CPU can see, one byte per address */
+    fn save_memory(&self) -> Vec<Byte>;
+
+    fn restore_memory(&mut self, memory: &[Byte]) -> Result<(), SnapshotError>;
+
+    /** @return the internal state of the devices on the bus, None if there are none */
+    fn save_devices(&self) -> Option<Vec<Byte>> {
+        None
+    }
+
+    fn restore_devices(&mut self, _state: &[Byte]) -> Result<(), SnapshotError> {
+        Err(SnapshotError::Bus("this bus has no device state to restore".to_string()))
+    }
+}
+
+impl SaveState for Mem {
+    fn save_memory(&self) -> Vec<Byte> {
+        (0..=0xFFFF).map(|address| self[address]).collect()
+    }
+
+    fn restore_memory(&mut self, memory: &[Byte]) -> Result<(), SnapshotError> {
+        if memory.len() != 0x10000 {
+            return Err(SnapshotError::Bus(format!(
+                "expected a 64 KiB memory image, got {} bytes",
+                memory.len()
+            )));
+        }
+        for (address, byte) in memory.iter().enumerate() {
+            self[address as Word] = *byte;
+        }
+
+        Ok(())
+    }
+}
+
+/** Everything needed to resume a run exactly where it was captured */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub pc: Word,
+    pub sp: Byte,
+    pub a: Byte,
+    pub x: Byte,
+    pub y: Byte,
+    /** the status flags packed as NV-BDIZC */
+    pub p: Byte,
+    /** the IRQ/NMI inputs as CPU::interrupt_state packs them */
+    pub interrupts: Byte,
+    /** the CPU stopped on an opcode it doesn't run */
+    pub halted: bool,
+    /** ADC and SBC honour D, false on a 2A03 */
+    pub decimal_enabled: bool,
+    /** cycles run so far, as counted by the caller */
+    pub cycles: u64,
+    /**
+     * cycles left in the slice the caller was running when it captured, negative when the
+     * last instruction ran past its end - carried over so the next slice is as long as it would have been
+     * */
+    pub pending_cycles: s32,
+    pub memory: Vec<Byte>,
+    pub devices: Option<Vec<Byte>>,
+}
+
+/**
+ * Cursor over the bytes of a snapshot or a device's state, reporting where it ran out
+ * - takes back, in the same order, what a StateWriter wrote
+ * */
+pub struct StateReader<'a> {
+    data: &'a [Byte],
+    offset: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [Byte]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    pub fn take(&mut self, length: usize) -> Result<&'a [Byte], SnapshotError> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + length)
+            .ok_or(SnapshotError::Truncated { offset: self.data.len() })?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    pub fn byte(&mut self) -> Result<Byte, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn flag(&mut self) -> Result<bool, SnapshotError> {
+        Ok(self.byte()? != 0)
+    }
+
+    pub fn word(&mut self) -> Result<Word, SnapshotError> {
+        let bytes = self.take(2)?;
+        Ok(Word::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn long(&mut self) -> Result<u32, SnapshotError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /** @return a block of bytes preceded by its length, as StateWriter::block writes it */
+    pub fn block(&mut self) -> Result<Vec<Byte>, SnapshotError> {
+        let length = self.long()?;
+        Ok(self.take(length as usize)?.to_vec())
+    }
+
+    /** Fill `out` with the next `out.len()` bytes, for fixed-size memories */
+    pub fn fill(&mut self, out: &mut [Byte]) -> Result<(), SnapshotError> {
+        out.copy_from_slice(self.take(out.len())?);
+        Ok(())
+    }
+}
+
+fn write_block(out: &mut Vec<Byte>, block: &[Byte]) {
+    out.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    out.extend_from_slice(block);
+}
+
+/** Builds a device's state for a snapshot, little-endian, one field after another */
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    bytes: Vec<Byte>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn byte(mut self, value: Byte) -> Self {
+        self.bytes.push(value);
+        self
+    }
+
+    pub fn flag(self, value: bool) -> Self {
+        self.byte(value as Byte)
+    }
+
+    pub fn word(mut self, value: Word) -> Self {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn long(mut self, value: u32) -> Self {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /** Append `bytes` preceded by their length, for memories whose size isn't fixed */
+    pub fn block(mut self, bytes: &[Byte]) -> Self {
+        write_block(&mut self.bytes, bytes);
+        self
+    }
+
+    /** Append `bytes` as they are, for memories whose size is */
+    pub fn bytes(mut self, bytes: &[Byte]) -> Self {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn into_bytes(self) -> Vec<Byte> {
+        self.bytes
+    }
+}
+
+/** @return Ok if a snapshot written as `version` can be read by this build */
+pub fn check_version(version: u16) -> Result<(), SnapshotError> {
+    if (OLDEST_SUPPORTED_VERSION..=VERSION).contains(&version) {
+        Ok(())
+    } else {
+        Err(SnapshotError::UnsupportedVersion {
+            found: version,
+            supported: (OLDEST_SUPPORTED_VERSION, VERSION),
+        })
+    }
+}
+
+/** @return the version a snapshot was written with, without decoding the rest */
+pub fn version_of(data: &[Byte]) -> Result<u16, SnapshotError> {
+    let mut reader = StateReader::new(data);
+    if reader.take(MAGIC.len()).map_err(|_| SnapshotError::NotASnapshot)? != MAGIC {
+        return Err(SnapshotError::NotASnapshot);
+    }
+    reader.word()
+}
+
+impl Snapshot {
+    /** Capture the CPU and the bus as they are now */
+    pub fn capture<B: SaveState>(cpu: &CPU, cycles: u64, bus: &B) -> Self {
+        Self {
+            pc: cpu.pc(),
+            sp: cpu.sp(),
+            a: cpu.a(),
+            x: cpu.x(),
+            y: cpu.y(),
+            p: cpu.status(),
+            interrupts: cpu.interrupt_state(),
+            halted: cpu.halted(),
+            decimal_enabled: cpu.decimal_enabled(),
+            cycles,
+            pending_cycles: 0,
+            memory: bus.save_memory(),
+            devices: bus.save_devices(),
+        }
+    }
+
+    /** The same snapshot, captured partway through a slice with `pending_cycles` of it left */
+    pub fn with_pending_cycles(self, pending_cycles: s32) -> Self {
+        Self { pending_cycles, ..self }
+    }
+
+    /**
+     * Put the CPU and the bus back the way they were captured
+     * @return the cycle count to carry on from
+     * */
+    pub fn restore<B: SaveState>(&self, cpu: &mut CPU, bus: &mut B) -> Result<u64, SnapshotError> {
+        bus.restore_memory(&self.memory)?;
+        if let Some(devices) = &self.devices {
+            bus.restore_devices(devices)?;
+        }
+
+        cpu.set_pc(self.pc);
+        cpu.set_sp(self.sp);
+        cpu.set_a(self.a);
+        cpu.set_x(self.x);
+        cpu.set_y(self.y);
+        cpu.set_status(self.p);
+        cpu.set_interrupt_state(self.interrupts);
+        cpu.set_halted(self.halted);
+        cpu.set_decimal_enabled(self.decimal_enabled);
+        Ok(self.cycles)
+    }
+
+    pub fn to_bytes(&self) -> Vec<Byte> {
+        let mut out = Vec::with_capacity(self.memory.len() + 64);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.extend_from_slice(&[self.sp, self.a, self.x, self.y, self.p, self.interrupts, self.halted as Byte]);
+        out.push(self.decimal_enabled as Byte);
+        out.extend_from_slice(&self.cycles.to_le_bytes());
+        out.extend_from_slice(&self.pending_cycles.to_le_bytes());
+        write_block(&mut out, &self.memory);
+        match &self.devices {
+            Some(devices) => {
+                out.push(1);
+                write_block(&mut out, devices);
+            }
+            None => out.push(0),
+        }
+
+        out
+    }
+
+    pub fn from_bytes(data: &[Byte]) -> Result<Self, SnapshotError> {
+        let version = version_of(data)?;
+        check_version(version)?;
+
+        let mut reader = StateReader::new(data);
+        reader.take(MAGIC.len() + 2)?;
+        let pc = reader.word()?;
+        let sp = reader.byte()?;
+        let a = reader.byte()?;
+        let x = reader.byte()?;
+        let y = reader.byte()?;
+        let p = reader.byte()?;
+        //version 1 predates the interrupt inputs, they were all released
+        let interrupts = if version >= 2 { reader.byte()? } else { 0 };
+        //before version 3 a CPU couldn't halt, and slices weren't carried over
+        let halted = if version >= 3 { reader.flag()? } else { false };
+        //before version 4 decimal mode always worked
+        let decimal_enabled = if version >= 4 { reader.flag()? } else { true };
+        let mut cycles = [0; 8];
+        reader.fill(&mut cycles)?;
+        let pending_cycles = if version >= 3 { reader.long()? } else { 0 };
+        let memory = reader.block()?;
+        let devices = match reader.byte()? {
+            0 => None,
+            _ => Some(reader.block()?),
+        };
+
+        Ok(Self {
+            pc,
+            sp,
+            a,
+            x,
+            y,
+            p,
+            interrupts,
+            halted,
+            decimal_enabled,
+            cycles: u64::from_le_bytes(cycles),
+            pending_cycles: pending_cycles as s32,
+            memory,
+            devices,
+        })
+    }
+
+    pub fn write_to<W: Write>(&self, mut out: W) -> io::Result<()> {
+        out.write_all(&self.to_bytes())
+    }
+
+    pub fn read_from<R: Read>(mut input: R) -> Result<Self, SnapshotError> {
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+        Self::from_bytes(&data)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SnapshotError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
@@ -0,0 +1,174 @@
+//! ld65 debug info files (`--dbgfile`, format version 2)
+//!
+//! Every line is a record type followed by `key=value` pairs, e.g.
+//! `span id=3,seg=0,start=6,size=3`. Spans are byte ranges within a segment, lines
+//! point at the spans they produced, and symbols carry their address in `val`.
+
+use super::{DebugInfo, Symbols};
+use crate::coverage::LineMap;
+use crate::loader::LoadError;
+use crate::m6502::*;
+use std::collections::HashMap;
+
+/** The `key=value` pairs of one record */
+struct Record<'a> {
+    line: usize,
+    fields: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Record<'a> {
+    fn parse(line: usize, text: &'a str) -> Self {
+        let mut fields = HashMap::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let Some((key, after)) = rest.split_once('=') else {
+                break;
+            };
+            //quoted values may hold commas
+            let (value, after) = match after.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted.find('"').unwrap_or(quoted.len());
+                    let after = quoted.get(end + 1..).unwrap_or("");
+                    (&quoted[..end], after)
+                }
+                None => after.split_once(',').map_or((after, ""), |(value, after)| (value, after)),
+            };
+            fields.insert(key.trim(), value);
+            rest = after.strip_prefix(',').unwrap_or(after);
+        }
+
+        Self { line, fields }
+    }
+
+    fn syntax(&self, message: String) -> LoadError {
+        LoadError::Syntax {
+            line: self.line,
+            message,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a str> {
+        self.fields.get(key).copied()
+    }
+
+    fn number(&self, key: &str) -> Result<u32, LoadError> {
+        let text = self
+            .get(key)
+            .ok_or_else(|| self.syntax(format!("missing {}", key)))?;
+        let number = match text.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => text.parse(),
+        };
+        number.map_err(|_| self.syntax(format!("invalid {}: {}", key, text)))
+    }
+
+    /** @return the ids in a `1+2+3` list */
+    fn ids(&self, key: &str) -> Result<Vec<u32>, LoadError> {
+        match self.get(key) {
+            Some(list) => list
+                .split('+')
+                .map(|id| id.parse().map_err(|_| self.syntax(format!("invalid {}: {}", key, list))))
+                .collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/** A byte range within a segment */
+struct Span {
+    segment: u32,
+    start: u32,
+    size: u32,
+    /** ca65 gives a type to the spans of data directives only */
+    data: bool,
+}
+
+/** `line` records of this type come from inside macros, their invocation is listed too */
+const LINE_TYPE_MACRO: u32 = 2;
+
+pub fn parse(text: &str) -> Result<DebugInfo, LoadError> {
+    let mut files: HashMap<u32, String> = HashMap::new();
+    let mut segments: HashMap<u32, u32> = HashMap::new();
+    let mut spans: HashMap<u32, Span> = HashMap::new();
+    let mut lines = Vec::new();
+    let mut symbols = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let Some((kind, fields)) = line.trim().split_once(char::is_whitespace) else {
+            continue;
+        };
+        let record = Record::parse(index + 1, fields);
+        match kind {
+            "version" => {
+                let major = record.number("major")?;
+                if major != 2 {
+                    return Err(record.syntax(format!("unsupported debug info version {}", major)));
+                }
+            }
+            "file" => {
+                let name = record.get("name").unwrap_or_default().to_string();
+                files.insert(record.number("id")?, name);
+            }
+            "seg" => {
+                segments.insert(record.number("id")?, record.number("start")?);
+            }
+            "span" => {
+                let span = Span {
+                    segment: record.number("seg")?,
+                    start: record.number("start")?,
+                    size: record.number("size")?,
+                    data: record.get("type").is_some(),
+                };
+                spans.insert(record.number("id")?, span);
+            }
+            "line" => lines.push(record),
+            "sym" => symbols.push(record),
+            _ => {}
+        }
+    }
+
+    let mut info = DebugInfo {
+        symbols: Symbols::new(),
+        lines: LineMap::new(),
+    };
+
+    for record in &lines {
+        if record.get("type").map(|_| record.number("type")).transpose()? == Some(LINE_TYPE_MACRO) {
+            continue;
+        }
+        let file = record.number("file")?;
+        let file = files
+            .get(&file)
+            .ok_or_else(|| record.syntax(format!("unknown file {}", file)))?;
+        let line = record.number("line")?;
+        for id in record.ids("span")? {
+            let span = spans
+                .get(&id)
+                .ok_or_else(|| record.syntax(format!("unknown span {}", id)))?;
+            let segment_start = *segments
+                .get(&span.segment)
+                .ok_or_else(|| record.syntax(format!("unknown segment {}", span.segment)))?;
+            let address = (segment_start + span.start) as Word;
+            let size = span.size as Word;
+            if span.data {
+                info.lines.add_data(file, line, address, size);
+            } else {
+                info.lines.add_code(file, line, address, size);
+            }
+        }
+    }
+
+    //labels first, so they win over equates for the same address
+    for wanted in ["lab", "equ"] {
+        for record in symbols.iter().filter(|record| record.get("type") == Some(wanted)) {
+            let (Some(name), Ok(value)) = (record.get("name"), record.number("val")) else {
+                continue;
+            };
+            if value <= 0xFFFF {
+                info.symbols.insert(name, value as Word);
+            }
+        }
+    }
+
+    Ok(info)
+}
@@ -0,0 +1,89 @@
+//! Symbol names and source lines for the addresses of a program
+//!
+//! Read from ld65 `--dbgfile` output or VICE label files, so disassembly, traces and
+//! breakpoints can say `main_loop` instead of `$C012`.
+
+use crate::coverage::LineMap;
+use crate::loader::LoadError;
+use crate::m6502::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub mod ca65;
+pub mod vice;
+
+/** Names for addresses, and addresses for names */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    by_name: BTreeMap<String, Word>,
+    by_address: BTreeMap<Word, String>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Add a symbol - when a name or an address comes up twice, the first one stays */
+    pub fn insert(&mut self, name: &str, address: Word) {
+        self.by_name.entry(name.to_string()).or_insert(address);
+        self.by_address.entry(address).or_insert_with(|| name.to_string());
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<Word> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn name_of(&self, address: Word) -> Option<&str> {
+        self.by_address.get(&address).map(|name| name.as_str())
+    }
+
+    /** @return the name of `address`, or `$XXXX` if it has none */
+    pub fn label(&self, address: Word) -> String {
+        match self.name_of(address) {
+            Some(name) => name.to_string(),
+            None => format!("${:04X}", address),
+        }
+    }
+
+    /** @return the address a user typed: a symbol name, `$C012` or `0xC012` */
+    pub fn resolve(&self, text: &str) -> Option<Word> {
+        let text = text.trim();
+        if let Some(address) = self.address_of(text) {
+            return Some(address);
+        }
+        let hex = text.strip_prefix('$').or_else(|| text.strip_prefix("0x"))?;
+        Word::from_str_radix(hex, 16).ok()
+    }
+
+    /** @return every name and its address, sorted by name */
+    pub fn names(&self) -> &BTreeMap<String, Word> {
+        &self.by_name
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+}
+
+/** What ld65 --dbgfile tells about a program */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    pub symbols: Symbols,
+    pub lines: LineMap,
+}
+
+/** Read an ld65 debug info file */
+pub fn load_dbg<P: AsRef<Path>>(path: P) -> Result<DebugInfo, LoadError> {
+    ca65::parse(&fs::read_to_string(path)?)
+}
+
+/** Read a VICE label file, e.g. from ld65 -Ln */
+pub fn load_vice<P: AsRef<Path>>(path: P) -> Result<Symbols, LoadError> {
+    vice::parse(&fs::read_to_string(path)?)
+}
@@ -0,0 +1,43 @@
+//! VICE monitor label files (.lbl, .vs) as written by ld65 -Ln or VICE itself
+//!
+//! Only `al` (add label) commands matter: `al C:c012 .main_loop` or `al 00C012 .main_loop`.
+
+use super::Symbols;
+use crate::loader::LoadError;
+use crate::m6502::*;
+
+pub fn parse(text: &str) -> Result<Symbols, LoadError> {
+    let mut symbols = Symbols::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let mut fields = line.split_whitespace();
+        if fields.next() != Some("al") {
+            continue;
+        }
+        let syntax = |message: &str| LoadError::Syntax {
+            line: line_number,
+            message: message.to_string(),
+        };
+
+        let (Some(address), Some(name)) = (fields.next(), fields.next()) else {
+            return Err(syntax("expected an address and a name after al"));
+        };
+        //an optional memory space prefix, C: being the CPU's
+        let address = address.rsplit(':').next().unwrap_or(address);
+        let address = u32::from_str_radix(address, 16).map_err(|_| syntax("invalid address"))?;
+        if address > 0xFFFF {
+            return Err(LoadError::OutOfRange {
+                line: line_number,
+                address,
+            });
+        }
+        let name = name.strip_prefix('.').unwrap_or(name);
+        if name.is_empty() {
+            return Err(syntax("empty label"));
+        }
+
+        symbols.insert(name, address as Word);
+    }
+
+    Ok(symbols)
+}
@@ -0,0 +1,192 @@
+use crate::devices::acia::*;
+use crate::devices::serial::*;
+use crate::devices::DeviceBus;
+use crate::m6502::*;
+use std::io::{Cursor, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/** 19200 baud, 8 data bits, 1 stop bit: 10 bits at 1 MHz */
+const CYCLES_PER_BYTE: u32 = 1_000_000 * 10 / 19_200;
+
+fn acia(input: &[Byte], command: Byte) -> Acia<BufferedSerial> {
+    let mut acia = Acia::new(BufferedSerial::new(input));
+    acia.write(CONTROL, 0x1F);
+    acia.write(COMMAND, command);
+    acia
+}
+
+#[test]
+fn transmitting_takes_one_frame_at_the_baud_rate() {
+    //given: DTR, no interrupts
+    let mut acia = acia(b"", 0x0B);
+
+    //when:
+    acia.write(DATA, b'A');
+
+    //then:
+    assert_eq!(acia.cycles_per_byte(), CYCLES_PER_BYTE);
+    assert_eq!(acia.read(STATUS) & STATUS_TDRE, 0);
+    acia.tick(CYCLES_PER_BYTE - 1);
+    assert!(acia.host.output.is_empty());
+    acia.tick(1);
+    assert_eq!(acia.host.output, b"A");
+    assert_ne!(acia.read(STATUS) & STATUS_TDRE, 0);
+}
+
+#[test]
+fn received_bytes_wait_for_the_program_to_read_them() {
+    let mut acia = acia(b"hi", 0x0B);
+
+    acia.tick(1);
+    assert_ne!(acia.read(STATUS) & STATUS_RDRF, 0);
+    acia.tick(10 * CYCLES_PER_BYTE);
+    assert_eq!(acia.read(DATA), b'h');
+    assert_eq!(acia.read(STATUS) & STATUS_RDRF, 0);
+
+    acia.tick(1);
+    assert_eq!(acia.read(DATA), b'i');
+    assert_eq!(acia.read(STATUS) & STATUS_OVERRUN, 0);
+}
+
+#[test]
+fn receiver_is_off_until_dtr() {
+    let mut acia = acia(b"x", 0x0A);
+
+    acia.tick(CYCLES_PER_BYTE);
+    assert_eq!(acia.read(STATUS) & STATUS_RDRF, 0);
+    acia.write(COMMAND, 0x0B);
+    acia.tick(1);
+    assert_ne!(acia.read(STATUS) & STATUS_RDRF, 0);
+}
+
+#[test]
+fn receive_and_transmit_interrupts_are_acknowledged_by_reading_the_status() {
+    //DTR with the receiver interrupt
+    let mut receiver = acia(b"x", 0x09);
+    receiver.tick(1);
+    assert!(receiver.irq());
+    assert_eq!(receiver.read(STATUS) & STATUS_IRQ, STATUS_IRQ);
+    assert!(!receiver.irq());
+
+    //DTR with the transmitter interrupt, receiver interrupt off
+    let mut transmitter = acia(b"", 0x07);
+    transmitter.write(DATA, b'y');
+    transmitter.tick(CYCLES_PER_BYTE);
+    assert!(transmitter.irq());
+    transmitter.read(STATUS);
+    assert!(!transmitter.irq());
+}
+
+#[test]
+fn echo_mode_sends_received_bytes_back() {
+    let mut acia = acia(b"e", 0x13);
+
+    acia.tick(1);
+
+    assert_eq!(acia.host.output, b"e");
+    assert_eq!(acia.read(DATA), b'e');
+}
+
+#[test]
+fn programmed_reset_clears_the_low_command_bits() {
+    let mut acia = acia(b"", 0xEB);
+
+    acia.write(STATUS, 0x00);
+
+    assert_eq!(acia.read(COMMAND), 0xE0);
+    assert_eq!(acia.read(CONTROL), 0x1F);
+}
+
+#[test]
+fn acia_on_the_bus_is_read_and_written_by_the_cpu() {
+    //given: copy the received byte to the transmitter, then idle
+    let mut bus = DeviceBus::new(Mem::new());
+    let port = bus.attach(0x5000, 4, acia(b"O", 0x0B));
+    let mut cpu = CPU::new();
+    cpu.reset(0x0300, &mut bus.bus);
+    let code = [
+        CPU::INS_LDA_ABS, 0x00, 0x50,
+        CPU::INS_STA_ABS, 0x00, 0x50,
+        CPU::INS_JMP_ABS, 0x00, 0x03,
+    ];
+    for (offset, byte) in code.iter().enumerate() {
+        bus.bus[0x0200 + offset as Word] = *byte;
+    }
+    bus.bus[0x0300] = CPU::INS_JMP_ABS;
+    bus.bus[0x0301] = 0x00;
+    bus.bus[0x0302] = 0x03;
+
+    //when: idle until the byte is in, then run the copy
+    bus.execute(10, &mut cpu);
+    cpu.set_pc(0x0200);
+    bus.execute(CYCLES_PER_BYTE as s32 + 20, &mut cpu);
+
+    //then:
+    assert_eq!(bus.device(port).host.output, b"O");
+    assert_eq!(bus.device(port).peek(STATUS) & (STATUS_RDRF | STATUS_TDRE), STATUS_TDRE);
+}
+
+/** A writer the test can still look at after handing it over */
+#[derive(Clone, Default)]
+struct SharedOutput(Arc<Mutex<Vec<Byte>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/** Tick until a byte arrives from a host reading on another thread */
+fn receive_within_a_second<H: SerialHost>(acia: &mut Acia<H>) -> Option<Byte> {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) {
+        acia.tick(CYCLES_PER_BYTE);
+        if acia.read(STATUS) & STATUS_RDRF != 0 {
+            return Some(acia.read(DATA));
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    None
+}
+
+#[test]
+fn stream_serial_bridges_a_reader_and_a_writer() {
+    let output = SharedOutput::default();
+    let mut acia = Acia::new(StreamSerial::new(Cursor::new(b"z".to_vec()), output.clone()));
+    acia.write(CONTROL, 0x1F);
+    acia.write(COMMAND, 0x0B);
+
+    assert_eq!(receive_within_a_second(&mut acia), Some(b'z'));
+    acia.write(DATA, b'!');
+    acia.tick(CYCLES_PER_BYTE);
+    assert_eq!(*output.0.lock().unwrap(), b"!");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn pty_serial_talks_to_whoever_opens_the_terminal() {
+    use std::io::Read;
+
+    //some sandboxes have no /dev/ptmx
+    let Ok(pty) = PtySerial::open() else {
+        return;
+    };
+    let mut terminal = std::fs::File::options().read(true).write(true).open(pty.path()).unwrap();
+    let mut acia = Acia::new(pty);
+    acia.write(CONTROL, 0x1F);
+    acia.write(COMMAND, 0x0B);
+
+    terminal.write_all(b"p").unwrap();
+    assert_eq!(receive_within_a_second(&mut acia), Some(b'p'));
+    acia.write(DATA, b'q');
+    acia.tick(CYCLES_PER_BYTE);
+    let mut received = [0; 1];
+    terminal.read_exact(&mut received).unwrap();
+    assert_eq!(&received, b"q");
+}
@@ -0,0 +1,128 @@
+use crate::m6502::*;
+
+struct Expected {
+    a: Byte,
+    c: Byte,
+    z: Byte,
+    n: Byte,
+    v: Byte,
+}
+
+/** Run `opcode_to_test` #operand with A = `a` and C = `carry` */
+fn test_arithmetic_immediate(opcode_to_test: Byte, a: Byte, operand: Byte, carry: Byte, decimal: Byte, expected: Expected) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_a(a);
+    cpu.set_c(carry);
+    cpu.set_d(decimal);
+    mem[0xFFFC] = opcode_to_test;
+    mem[0xFFFD] = operand;
+
+    //when:
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(cpu.a(), expected.a, "A");
+    assert_eq!(cpu.c(), expected.c, "C");
+    assert_eq!(cpu.z(), expected.z, "Z");
+    assert_eq!(cpu.n(), expected.n, "N");
+    assert_eq!(cpu.v(), expected.v, "V");
+}
+
+#[test]
+fn adc_can_add_two_unsigned_numbers_with_the_carry() {
+    test_arithmetic_immediate(CPU::INS_ADC_IM, 0x10, 0x20, 1, 0, Expected { a: 0x31, c: 0, z: 0, n: 0, v: 0 });
+}
+
+#[test]
+fn adc_sets_the_carry_and_zero_flags_when_it_wraps() {
+    test_arithmetic_immediate(CPU::INS_ADC_IM, 0xFF, 0x01, 0, 0, Expected { a: 0x00, c: 1, z: 1, n: 0, v: 0 });
+}
+
+#[test]
+fn adc_sets_the_overflow_flag_when_two_positives_make_a_negative() {
+    test_arithmetic_immediate(CPU::INS_ADC_IM, 0x7F, 0x01, 0, 0, Expected { a: 0x80, c: 0, z: 0, n: 1, v: 1 });
+}
+
+#[test]
+fn adc_sets_the_overflow_flag_when_two_negatives_make_a_positive() {
+    test_arithmetic_immediate(CPU::INS_ADC_IM, 0x80, 0xFF, 0, 0, Expected { a: 0x7F, c: 1, z: 0, n: 0, v: 1 });
+}
+
+/** N and V come from the sum before the high digit is adjusted, as on the NMOS 6502 */
+#[test]
+fn adc_adds_binary_coded_decimal_when_the_decimal_flag_is_set() {
+    test_arithmetic_immediate(CPU::INS_ADC_IM, 0x58, 0x46, 1, 1, Expected { a: 0x05, c: 1, z: 0, n: 1, v: 1 });
+}
+
+#[test]
+fn adc_in_decimal_mode_takes_z_from_the_binary_sum_as_the_nmos_6502_does() {
+    test_arithmetic_immediate(CPU::INS_ADC_IM, 0x99, 0x01, 0, 1, Expected { a: 0x00, c: 1, z: 0, n: 1, v: 0 });
+}
+
+#[test]
+fn sbc_can_subtract_with_the_borrow() {
+    test_arithmetic_immediate(CPU::INS_SBC_IM, 0x50, 0x20, 0, 0, Expected { a: 0x2F, c: 1, z: 0, n: 0, v: 0 });
+}
+
+#[test]
+fn sbc_clears_the_carry_when_it_borrows() {
+    test_arithmetic_immediate(CPU::INS_SBC_IM, 0x00, 0x01, 1, 0, Expected { a: 0xFF, c: 0, z: 0, n: 1, v: 0 });
+}
+
+#[test]
+fn sbc_sets_the_overflow_flag_when_a_negative_minus_a_positive_is_positive() {
+    test_arithmetic_immediate(CPU::INS_SBC_IM, 0x80, 0x01, 1, 0, Expected { a: 0x7F, c: 1, z: 0, n: 0, v: 1 });
+}
+
+#[test]
+fn sbc_subtracts_binary_coded_decimal_when_the_decimal_flag_is_set() {
+    test_arithmetic_immediate(CPU::INS_SBC_IM, 0x12, 0x21, 1, 1, Expected { a: 0x91, c: 0, z: 0, n: 1, v: 0 });
+}
+
+#[test]
+fn adc_ignores_the_decimal_flag_when_decimal_mode_is_disabled() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_decimal_enabled(false);
+    cpu.set_d(1);
+    cpu.set_a(0x09);
+    mem[0xFFFC] = CPU::INS_ADC_IM;
+    mem[0xFFFD] = 0x01;
+
+    //when:
+    cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cpu.a(), 0x0A);
+    assert_eq!(cpu.d(), 1);
+}
+
+#[test]
+fn adc_absolute_y_adds_a_value_from_memory() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_a(0x01);
+    cpu.set_y(0x02);
+    mem[0xFFFC] = CPU::INS_ADC_ABSY;
+    mem[0xFFFD] = 0x00;
+    mem[0xFFFE] = 0x80;
+    mem[0x8002] = 0x41;
+
+    //when:
+    let cycles_used = cpu.execute(4, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 4);
+    assert_eq!(cpu.a(), 0x42);
+}
@@ -0,0 +1,140 @@
+use crate::devices::serial::BufferedSerial;
+use crate::loader::LoadError;
+use crate::m6502::*;
+use crate::machines::apple1::*;
+use super::test_rom_path;
+use std::fs;
+
+/** An Apple I with 4 KiB of RAM, `code` at $0200 and the CPU about to run it */
+fn apple1(input: &[Byte], code: &[Byte]) -> Apple1<BufferedSerial> {
+    let mut machine = Apple1::new(4096, BufferedSerial::new(input));
+    machine.reset();
+    machine.bus.bus.ram[0x0200..0x0200 + code.len()].copy_from_slice(code);
+    machine.cpu.set_pc(0x0200);
+    machine
+}
+
+/** Set up the display port as the Woz Monitor does */
+const DISPLAY_SETUP: [Byte; 10] = [
+    CPU::INS_LDA_IM, 0x7F,
+    CPU::INS_STA_ABS, 0x12, 0xD0,
+    CPU::INS_LDA_IM, 0xA7,
+    CPU::INS_STA_ABS, 0x13, 0xD0,
+];
+
+#[test]
+fn typed_keys_are_upper_cased_and_strobed_into_the_pia() {
+    //given:
+    let code = [
+        CPU::INS_LDA_IM, 0x07,
+        CPU::INS_STA_ABS, 0x11, 0xD0,
+        CPU::INS_LDA_ABS, 0x11, 0xD0,
+        CPU::INS_STA_ZP, 0x10,
+        CPU::INS_LDA_ABS, 0x10, 0xD0,
+        CPU::INS_STA_ZP, 0x11,
+        CPU::INS_LDA_ABS, 0x11, 0xD0,
+        CPU::INS_STA_ZP, 0x12,
+        CPU::INS_JMP_ABS, 0x14, 0x02,
+    ];
+    let mut machine = apple1(b"ab", &code);
+
+    //when:
+    machine.execute(50);
+
+    //then: the second key waits until the first one is read
+    assert_eq!(machine.bus.bus.ram[0x10], 0x87);
+    assert_eq!(machine.bus.bus.ram[0x11], 0xC1);
+    assert_eq!(machine.bus.bus.ram[0x12], 0x87);
+    assert_eq!(machine.pia().port_a(), 0xC2);
+    assert!(machine.terminal.input.is_empty());
+}
+
+#[test]
+fn display_shows_upper_case_and_turns_cr_into_newlines() {
+    //given:
+    let mut code = DISPLAY_SETUP.to_vec();
+    for character in [0xC8, 0x8D, 0xE9, 0x87] {
+        code.extend([CPU::INS_LDA_IM, character, CPU::INS_STA_ABS, 0x12, 0xD0]);
+    }
+    code.extend([CPU::INS_JMP_ABS, code.len() as Byte, 0x02]);
+    let mut machine = apple1(b"", &code);
+
+    //when:
+    machine.execute(100);
+
+    //then:
+    assert_eq!(machine.terminal.output, b"H\nI");
+    assert!(machine.pia().cb2());
+}
+
+#[test]
+fn display_wraps_after_40_columns() {
+    let mut code = DISPLAY_SETUP.to_vec();
+    code.extend([CPU::INS_LDA_IM, b'*' | 0x80]);
+    for _ in 0..COLUMNS + 1 {
+        code.extend([CPU::INS_STA_ABS, 0x12, 0xD0]);
+    }
+    code.extend([CPU::INS_JMP_ABS, code.len() as Byte, 0x02]);
+    let mut machine = apple1(b"", &code);
+
+    machine.execute(300);
+
+    let mut expected = vec![b'*'; COLUMNS];
+    expected.extend(b"\n*");
+    assert_eq!(machine.terminal.output, expected);
+}
+
+#[test]
+fn rom_sits_at_the_top_and_ram_ends_where_it_ends() {
+    //given:
+    let mut rom = [0xEA; ROM_SIZE];
+    rom[0xFC] = 0x00;
+    rom[0xFD] = 0x03;
+    let mut machine = Apple1::new(4096, BufferedSerial::default());
+
+    //when:
+    machine.load_rom(&rom).unwrap();
+    machine.reset();
+    machine.bus.write(0xFF00, 0x00);
+    machine.bus.write(0x0FFF, 0x12);
+    machine.bus.write(0x1000, 0x34);
+
+    //then:
+    assert_eq!(machine.cpu.pc(), 0x0300);
+    assert_eq!(machine.bus.peek(0xFF00), 0xEA);
+    assert_eq!(machine.bus.peek(0x0FFF), 0x12);
+    assert_eq!(machine.bus.peek(0x1000), 0xFF);
+    assert!(matches!(machine.load_rom(&[0; ROM_SIZE + 1]), Err(LoadError::TooLarge { .. })));
+}
+
+#[test]
+fn character_sets_are_converted_both_ways() {
+    assert_eq!(keyboard_code(b'a'), Some(0xC1));
+    assert_eq!(keyboard_code(b'\n'), Some(0x8D));
+    assert_eq!(keyboard_code(0x7F), Some(b'_' | 0x80));
+    assert_eq!(keyboard_code(0xE9), None);
+    assert_eq!(display_character(0xC1), Some(b'A'));
+    assert_eq!(display_character(b'z'), Some(b'Z'));
+    assert_eq!(display_character(0x07), None);
+}
+
+/** The 256-byte Woz Monitor, e.g. the $FF00-$FFFF dump from apple1.rom */
+#[test]
+#[ignore = "needs wozmon.bin in test_roms/ or WOZMON_ROM"]
+fn woz_monitor_boots_and_examines_memory() {
+    let path = test_rom_path("WOZMON_ROM", "wozmon.bin");
+
+    //given:
+    let mut machine = Apple1::new(4096, BufferedSerial::new(b"FF00\n"));
+    machine.load_rom(&fs::read(path).unwrap()).unwrap();
+    machine.reset();
+
+    //when:
+    machine.execute(200_000);
+
+    //then: the prompt, the echoed line and the first byte of the monitor itself, CLD
+    assert!(!machine.cpu.halted(), "halted at ${:04X}", machine.cpu.pc());
+    let output = String::from_utf8_lossy(&machine.terminal.output).into_owned();
+    assert!(output.starts_with("\\\nFF00\n"), "{:?}", output);
+    assert!(output.contains("FF00: D8"), "{:?}", output);
+}
@@ -0,0 +1,181 @@
+use crate::devices::via;
+use crate::m6502::*;
+use crate::machines::ben_eater::*;
+
+const PORTB: Word = VIA_BASE + via::ORB as Word;
+const PORTA: Word = VIA_BASE + via::ORA as Word;
+const DDRB: Word = VIA_BASE + via::DDRB as Word;
+const DDRA: Word = VIA_BASE + via::DDRA as Word;
+
+/** Append `LDA #value / STA address` */
+fn store(code: &mut Vec<Byte>, address: Word, value: Byte) {
+    let [lo, hi] = address.to_le_bytes();
+    code.extend([CPU::INS_LDA_IM, value, CPU::INS_STA_ABS, lo, hi]);
+}
+
+/** A machine with `code` in ROM at $8000, reset and ready to run it */
+fn machine(wiring: LcdWiring, mut code: Vec<Byte>) -> BenEater {
+    let idle = ROM_BASE + code.len() as Word;
+    let [lo, hi] = idle.to_le_bytes();
+    code.extend([CPU::INS_JMP_ABS, lo, hi]);
+    let mut rom = vec![0xEA; ROM_SIZE];
+    rom[..code.len()].copy_from_slice(&code);
+    rom[0x7FFC] = 0x00;
+    rom[0x7FFD] = 0x80;
+
+    let mut machine = BenEater::new(wiring, 1_000_000);
+    machine.load_rom(&rom).unwrap();
+    machine.reset();
+    machine
+}
+
+#[test]
+fn memory_map_matches_the_address_decoding() {
+    //given:
+    let mut machine = machine(LcdWiring::EightBit, Vec::new());
+
+    //when:
+    machine.bus.write(0x3FFF, 0x12);
+    machine.bus.write(0x4000, 0x34);
+    machine.bus.write(0x8000, 0x56);
+    machine.bus.write(0x7FF3, 0xE0);
+
+    //then:
+    assert_eq!(machine.cpu.pc(), 0x8000);
+    assert_eq!(machine.bus.peek(0x3FFF), 0x12);
+    assert_eq!(machine.bus.peek(0x4000), 0xFF);
+    assert_eq!(machine.bus.peek(0x8000), CPU::INS_JMP_ABS);
+    assert_eq!(machine.via().peek(via::DDRA), 0xE0);
+    assert_eq!(machine.bus.peek(DDRA), 0xE0);
+}
+
+#[test]
+fn hello_world_on_the_eight_bit_wiring() {
+    //given: E, RW and RS on PA7, PA6 and PA5, as in the first video
+    let mut code = Vec::new();
+    store(&mut code, DDRB, 0xFF);
+    store(&mut code, DDRA, 0xE0);
+    for instruction in [0x38, 0x0E, 0x06] {
+        store(&mut code, PORTB, instruction);
+        store(&mut code, PORTA, 0x00);
+        store(&mut code, PORTA, 0x80);
+        store(&mut code, PORTA, 0x00);
+    }
+    for character in *b"Hi" {
+        store(&mut code, PORTB, character);
+        store(&mut code, PORTA, 0x20);
+        store(&mut code, PORTA, 0xA0);
+        store(&mut code, PORTA, 0x20);
+    }
+    let mut machine = machine(LcdWiring::EightBit, code);
+
+    //when:
+    machine.execute(300);
+
+    //then:
+    assert_eq!(machine.lcd.text(), ["Hi              ", "                "]);
+    assert!(machine.lcd.cursor_on());
+}
+
+#[test]
+fn four_bit_wiring_sends_nibbles_and_reads_the_busy_flag() {
+    //given: everything on port B, E, RW and RS on PB6, PB5 and PB4
+    let mut code = Vec::new();
+    store(&mut code, DDRB, 0xFF);
+    fn send(code: &mut Vec<Byte>, nibble: Byte) {
+        store(code, PORTB, nibble);
+        store(code, PORTB, nibble | 0x40);
+        store(code, PORTB, nibble);
+    }
+    send(&mut code, 0x02);
+    for byte in [0x28, 0x0C] {
+        send(&mut code, byte >> 4);
+        send(&mut code, byte & 0x0F);
+    }
+    for byte in *b"OK" {
+        send(&mut code, 0x10 | byte >> 4);
+        send(&mut code, 0x10 | (byte & 0x0F));
+    }
+    //read the high nibble of the status right after the last write
+    store(&mut code, DDRB, 0xF0);
+    store(&mut code, PORTB, 0x20);
+    store(&mut code, PORTB, 0x60);
+    code.extend([CPU::INS_LDA_ABS, 0x00, 0x60, CPU::INS_STA_ZP, 0x10]);
+    store(&mut code, PORTB, 0x20);
+    let mut machine = machine(LcdWiring::FourBit, code);
+
+    //when:
+    machine.execute(600);
+
+    //then:
+    assert_eq!(machine.lcd.text()[0], "OK              ");
+    assert_eq!(machine.bus.bus.ram[0x10], 0x60 | 0x08);
+}
+
+/** Ben's hello-world.s, the version that waits on the busy flag, assembled by hand */
+#[rustfmt::skip]
+const HELLO_WORLD: &[Byte] = &[
+    //$8000 reset:
+    CPU::INS_LDX_IM, 0xFF,
+    CPU::INS_TXS,
+    CPU::INS_LDA_IM, 0xFF, CPU::INS_STA_ABS, 0x02, 0x60,    //all of port B is output
+    CPU::INS_LDA_IM, 0xE0, CPU::INS_STA_ABS, 0x03, 0x60,    //E, RW and RS on port A
+    CPU::INS_LDA_IM, 0x38, CPU::INS_JSR, 0x63, 0x80,        //8 bits, 2 lines, 5x8 font
+    CPU::INS_LDA_IM, 0x0E, CPU::INS_JSR, 0x63, 0x80,        //display and cursor on
+    CPU::INS_LDA_IM, 0x06, CPU::INS_JSR, 0x63, 0x80,        //move the cursor right
+    CPU::INS_LDA_IM, 0x01, CPU::INS_JSR, 0x63, 0x80,        //clear
+    CPU::INS_LDX_IM, 0x00,
+    //$8023 print:
+    CPU::INS_LDA_ABSX, 0x32, 0x80,
+    CPU::INS_BEQ, 0x07,
+    CPU::INS_JSR, 0x79, 0x80,
+    CPU::INS_INX,
+    CPU::INS_JMP_ABS, 0x23, 0x80,
+    //$802F loop:
+    CPU::INS_JMP_ABS, 0x2F, 0x80,
+    //$8032 message:
+    b'H', b'e', b'l', b'l', b'o', b',', b' ', b'w', b'o', b'r', b'l', b'd', b'!', 0,
+    //$8040 lcd_wait:
+    CPU::INS_PHA,
+    CPU::INS_LDA_IM, 0x00, CPU::INS_STA_ABS, 0x02, 0x60,    //port B is input
+    //$8046 lcdbusy:
+    CPU::INS_LDA_IM, 0x40, CPU::INS_STA_ABS, 0x01, 0x60,
+    CPU::INS_LDA_IM, 0xC0, CPU::INS_STA_ABS, 0x01, 0x60,
+    CPU::INS_LDA_ABS, 0x00, 0x60,
+    CPU::INS_AND_IM, 0x80,
+    CPU::INS_BNE, 0xEF,
+    CPU::INS_LDA_IM, 0x40, CPU::INS_STA_ABS, 0x01, 0x60,
+    CPU::INS_LDA_IM, 0xFF, CPU::INS_STA_ABS, 0x02, 0x60,    //port B is output
+    CPU::INS_PLA,
+    CPU::INS_RTS,
+    //$8063 lcd_instruction:
+    CPU::INS_JSR, 0x40, 0x80,
+    CPU::INS_STA_ABS, 0x00, 0x60,
+    CPU::INS_LDA_IM, 0x00, CPU::INS_STA_ABS, 0x01, 0x60,
+    CPU::INS_LDA_IM, 0x80, CPU::INS_STA_ABS, 0x01, 0x60,
+    CPU::INS_LDA_IM, 0x00, CPU::INS_STA_ABS, 0x01, 0x60,
+    CPU::INS_RTS,
+    //$8079 print_char:
+    CPU::INS_JSR, 0x40, 0x80,
+    CPU::INS_STA_ABS, 0x00, 0x60,
+    CPU::INS_LDA_IM, 0x20, CPU::INS_STA_ABS, 0x01, 0x60,
+    CPU::INS_LDA_IM, 0xA0, CPU::INS_STA_ABS, 0x01, 0x60,
+    CPU::INS_LDA_IM, 0x20, CPU::INS_STA_ABS, 0x01, 0x60,
+    CPU::INS_RTS,
+];
+
+#[test]
+fn runs_the_hello_world_from_the_videos() {
+    //given:
+    let mut machine = machine(LcdWiring::EightBit, HELLO_WORLD.to_vec());
+
+    //when: long enough for the clear and thirteen characters, waiting on the busy flag
+    machine.execute(20_000);
+
+    //then:
+    assert!(!machine.cpu.halted());
+    assert_eq!(machine.cpu.pc(), 0x802F);
+    assert_eq!(machine.cpu.sp(), 0xFF);
+    assert_eq!(machine.lcd.text(), ["Hello, world!   ", "                "]);
+    assert!(!machine.lcd.busy());
+}
@@ -0,0 +1,74 @@
+use crate::m6502::*;
+
+type SetFlag = fn(&mut CPU);
+
+/** Run a branch at `pc` with an offset, after `set_flag` */
+fn run_branch(opcode_to_test: Byte, set_flag: SetFlag, pc: Word, offset: Byte) -> (CPU, s32) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(pc, &mut mem);
+
+    //given:
+    set_flag(&mut cpu);
+    mem[pc] = opcode_to_test;
+    mem[pc.wrapping_add(1)] = offset;
+
+    //when:
+    let cycles_used = cpu.step(&mut mem);
+
+    (cpu, cycles_used)
+}
+
+#[test]
+fn beq_can_branch_forward_when_the_zero_flag_is_set() {
+    let (cpu, cycles_used) = run_branch(CPU::INS_BEQ, |cpu| cpu.set_z(1), 0xFF00, 0x01);
+
+    //then:
+    assert_eq!(cycles_used, 3);
+    assert_eq!(cpu.pc(), 0xFF03);
+}
+
+#[test]
+fn beq_does_not_branch_when_the_zero_flag_is_clear() {
+    let (cpu, cycles_used) = run_branch(CPU::INS_BEQ, |cpu| cpu.set_z(0), 0xFF00, 0x01);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(cpu.pc(), 0xFF02);
+}
+
+#[test]
+fn beq_takes_a_cycle_more_when_it_branches_into_another_page() {
+    let (cpu, cycles_used) = run_branch(CPU::INS_BEQ, |cpu| cpu.set_z(1), 0xFEFD, 0x01);
+
+    //then:
+    assert_eq!(cycles_used, 4);
+    assert_eq!(cpu.pc(), 0xFF00);
+}
+
+#[test]
+fn bne_can_branch_backwards() {
+    let (cpu, cycles_used) = run_branch(CPU::INS_BNE, |cpu| cpu.set_z(0), 0xFFCC, 0xCC);
+
+    //then:
+    assert_eq!(cycles_used, 3);
+    assert_eq!(cpu.pc(), 0xFFCC + 2 - 0x34);
+}
+
+#[test]
+fn every_branch_tests_its_own_flag() {
+    let branches: [(Byte, SetFlag); 8] = [
+        (CPU::INS_BCC, |cpu| cpu.set_c(0)),
+        (CPU::INS_BCS, |cpu| cpu.set_c(1)),
+        (CPU::INS_BNE, |cpu| cpu.set_z(0)),
+        (CPU::INS_BEQ, |cpu| cpu.set_z(1)),
+        (CPU::INS_BPL, |cpu| cpu.set_n(0)),
+        (CPU::INS_BMI, |cpu| cpu.set_n(1)),
+        (CPU::INS_BVC, |cpu| cpu.set_v(0)),
+        (CPU::INS_BVS, |cpu| cpu.set_v(1)),
+    ];
+    for (opcode, set_flag) in branches {
+        let (cpu, _) = run_branch(opcode, set_flag, 0x0200, 0x10);
+        assert_eq!(cpu.pc(), 0x0212, "opcode {:02X}", opcode);
+    }
+}
@@ -0,0 +1,347 @@
+use crate::devices::cia;
+use crate::devices::vic_ii;
+use crate::devices::DeviceBus;
+use crate::m6502::*;
+use crate::m6510::{self, IoPort};
+use crate::machines::c64::*;
+use super::{idle_at, test_rom_path};
+use std::fs;
+
+/** where the tests' code goes in RAM */
+const ORIGIN: Word = 0x1000;
+
+/**
+ * A C64 with marked BASIC and character ROMs and a stand-in KERNAL whose reset vector
+ * points at `code` in RAM at ORIGIN
+ * */
+fn c64(code: &[Byte]) -> C64 {
+    let mut machine = C64::new();
+    machine.load_basic(&[0xBA; BASIC_SIZE]).unwrap();
+    machine.load_char_rom(&[0xC6; CHAR_SIZE]).unwrap();
+    let [lo, hi] = ORIGIN.to_le_bytes();
+    machine.load_kernal(&[0x00, 0x11, lo, hi, 0x00, 0x12]).unwrap();
+    for (offset, byte) in code.iter().enumerate() {
+        machine.bus.ram[ORIGIN + offset as Word] = *byte;
+    }
+    machine.reset();
+    machine
+}
+
+#[test]
+fn roms_and_io_are_banked_in_after_reset() {
+    //given:
+    let machine = c64(&[]);
+
+    //then: the port's pins are inputs, pulled up
+    assert_eq!(machine.cpu.pc(), ORIGIN);
+    assert_eq!(machine.bus.port.pins() & 0x07, 0x07);
+    assert_eq!(machine.bus.bank(0x9FFF), Bank::Ram);
+    assert_eq!(machine.bus.bank(0xA000), Bank::Basic);
+    assert_eq!(machine.bus.bank(0xC000), Bank::Ram);
+    assert_eq!(machine.bus.bank(0xD000), Bank::Io);
+    assert_eq!(machine.bus.bank(0xE000), Bank::Kernal);
+    assert_eq!(machine.bus.peek(0xA000), 0xBA);
+    assert_eq!(machine.bus.peek(0xE000), 0xFF);
+    assert_eq!(machine.bus.peek(0xFFFC), 0x00);
+    assert_eq!(machine.bus.peek(0xFFFD), 0x10);
+}
+
+#[test]
+fn port_banks_the_roms_out() {
+    //given: LORAM low, HIRAM and CHAREN high
+    let mut code = vec![
+        CPU::INS_LDA_IM, 0x2F,
+        CPU::INS_STA_ZP, 0x00,
+        CPU::INS_LDA_IM, 0x36,
+        CPU::INS_STA_ZP, 0x01,
+        CPU::INS_LDA_ABS, 0x00, 0xA0,
+        CPU::INS_STA_ZP, 0x10,
+    ];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = c64(&code);
+    machine.bus.ram[0xA000] = 0x42;
+
+    //when:
+    machine.execute(30);
+
+    //then: the write also went to the RAM under the port
+    assert_eq!(machine.bus.peek(m6510::DDR), 0x2F);
+    assert_eq!(machine.bus.peek(m6510::PORT) & 0x3F, 0x36);
+    assert_eq!(machine.bus.ram[0x0001], 0x36);
+    assert_eq!(machine.bus.ram[0x0010], 0x42);
+    assert_eq!(machine.bus.bank(0xA000), Bank::Ram);
+    assert_eq!(machine.bus.bank(0xD000), Bank::Io);
+    assert_eq!(machine.bus.bank(0xE000), Bank::Kernal);
+}
+
+#[test]
+fn charen_and_hiram_select_the_rest() {
+    //given:
+    let mut machine = c64(&[]);
+    machine.bus.write(m6510::DDR, 0x07);
+
+    //when: CHAREN low
+    machine.bus.write(m6510::PORT, 0x03);
+
+    //then: writes go under the character ROM, not to the VIC
+    machine.bus.write(0xD020, 0x05);
+    assert_eq!(machine.bus.bank(0xD000), Bank::CharRom);
+    assert_eq!(machine.bus.peek(0xD020), 0xC6);
+    assert_eq!(machine.bus.ram[0xD020], 0x05);
+    assert_eq!(machine.bus.vic().peek(vic_ii::BORDER_COLOR), 0xF0);
+
+    //when: HIRAM low
+    machine.bus.write(m6510::PORT, 0x05);
+
+    //then: BASIC goes with the KERNAL
+    assert_eq!(machine.bus.bank(0xA000), Bank::Ram);
+    assert_eq!(machine.bus.bank(0xD000), Bank::Io);
+    assert_eq!(machine.bus.bank(0xE000), Bank::Ram);
+
+    //when: LORAM and HIRAM low
+    machine.bus.write(m6510::PORT, 0x04);
+
+    //then:
+    assert_eq!(machine.bus.bank(0xD000), Bank::Ram);
+    assert_eq!(machine.bus.peek(0xD020), 0x05);
+}
+
+#[test]
+fn io_area_decodes_the_chips() {
+    //given:
+    let mut machine = c64(&[]);
+
+    //when:
+    machine.bus.write(0xD020, 0x0E);
+    machine.bus.write(0xD860, 0xF3);
+    machine.bus.write(0xDC04, 0x25);
+    machine.bus.write(0xDC05, 0x40);
+
+    //then: the VIC repeats every 64 bytes and the CIA every 16
+    assert_eq!(machine.bus.peek(0xD020), 0xFE);
+    assert_eq!(machine.bus.peek(0xD060), 0xFE);
+    assert_eq!(machine.bus.peek(0xD02F), 0xFF);
+    assert_eq!(machine.bus.peek(0xD860), 0x03);
+    assert_eq!(machine.bus.peek(0xDC14), 0x25);
+    assert_eq!(machine.bus.peek(0xDC15), 0x40);
+    assert_eq!(machine.bus.peek(0xD419), 0xFF);
+    assert_eq!(machine.bus.peek(0xDE00), 0xFF);
+    assert_eq!(machine.bus.ram[0xD020], 0x00);
+}
+
+#[test]
+fn raster_counts_lines_and_interrupts_on_compare() {
+    //given:
+    let mut machine = c64(&[]);
+    machine.bus.write(0xD012, 0x02);
+    machine.bus.write(0xD01A, vic_ii::IRQ_RASTER);
+
+    //when:
+    machine.bus.io.tick(vic_ii::PAL_CYCLES_PER_LINE, &mut machine.cpu);
+
+    //then:
+    assert_eq!(machine.bus.peek(0xD012), 0x01);
+    assert!(!machine.bus.io.irq());
+
+    //when:
+    machine.bus.io.tick(vic_ii::PAL_CYCLES_PER_LINE, &mut machine.cpu);
+
+    //then:
+    assert_eq!(machine.bus.peek(0xD019), 0xF1);
+    assert!(machine.bus.io.irq());
+
+    //when: acknowledged, and around to line 256
+    machine.bus.write(0xD019, vic_ii::IRQ_RASTER);
+    machine.bus.io.tick(254 * vic_ii::PAL_CYCLES_PER_LINE, &mut machine.cpu);
+
+    //then:
+    assert!(!machine.bus.io.irq());
+    assert_eq!(machine.bus.peek(0xD012), 0x00);
+    assert_eq!(machine.bus.peek(0xD011) & 0x80, 0x80);
+}
+
+#[test]
+fn cia_timers_interrupt_through_irq_and_nmi() {
+    //given: timer A of each CIA running continuously from 9
+    let mut machine = c64(&[]);
+    for base in [CIA_1, CIA_2] {
+        machine.bus.write(base + cia::TA_LO as Word, 0x09);
+        machine.bus.write(base + cia::TA_HI as Word, 0x00);
+        machine.bus.write(base + cia::ICR as Word, cia::IRQ_SET | cia::IRQ_TA);
+        machine.bus.write(base + cia::CRA as Word, cia::CR_START);
+    }
+
+    //when:
+    machine.bus.io.tick(9, &mut machine.cpu);
+
+    //then:
+    assert!(!machine.bus.io.irq());
+    assert_eq!(machine.bus.peek(CIA_1 + cia::TA_LO as Word), 0x00);
+
+    //when:
+    machine.bus.io.tick(1, &mut machine.cpu);
+
+    //then: reloaded from the latch
+    assert!(machine.bus.io.irq());
+    assert!(machine.bus.io.nmi());
+    assert_eq!(machine.bus.peek(CIA_1 + cia::TA_LO as Word), 0x09);
+    assert_eq!(machine.bus.read(CIA_1 + cia::ICR as Word), 0x81);
+    assert!(!machine.bus.io.irq());
+    assert!(machine.bus.io.nmi());
+}
+
+#[test]
+fn chained_one_shot_timer_b_counts_timer_a_underflows() {
+    //given:
+    let mut chip = cia::Cia::new();
+    chip.write(cia::TA_LO, 0x01);
+    chip.write(cia::TA_HI, 0x00);
+    chip.write(cia::TB_LO, 0x02);
+    chip.write(cia::TB_HI, 0x00);
+    chip.write(cia::ICR, cia::IRQ_SET | cia::IRQ_TB);
+    chip.write(cia::CRB, cia::CR_START | cia::CR_ONE_SHOT | cia::CRB_COUNT_TA);
+    chip.write(cia::CRA, cia::CR_START);
+
+    //when: timer A underflows every other cycle
+    chip.tick(5);
+
+    //then:
+    assert!(!chip.irq());
+
+    //when:
+    chip.tick(1);
+
+    //then: stopped and reloaded
+    assert!(chip.irq());
+    assert_eq!(chip.peek(cia::CRB) & cia::CR_START, 0);
+    assert_eq!(chip.peek(cia::TB_LO), 0x02);
+}
+
+#[test]
+fn typed_keys_go_into_the_keyboard_buffer() {
+    //given:
+    let mut code = vec![];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = c64(&code);
+    machine.type_text("print 1\nrun:list\n");
+
+    //when:
+    machine.step();
+
+    //then: ten at a time, once the KERNAL has taken them
+    assert_eq!(machine.bus.ram[KEYBOARD_COUNT], 10);
+    assert_eq!(machine.bus.ram[KEYBOARD_BUFFER], b'P');
+    assert_eq!(machine.bus.ram[KEYBOARD_BUFFER + 7], 0x0D);
+    assert!(machine.typing());
+
+    //when:
+    machine.step();
+    machine.bus.ram[KEYBOARD_COUNT] = 0;
+    machine.step();
+
+    //then:
+    assert_eq!(machine.bus.ram[KEYBOARD_COUNT], 7);
+    assert_eq!(machine.bus.ram[KEYBOARD_BUFFER], b'N');
+    assert!(!machine.typing());
+}
+
+#[test]
+fn screen_is_read_from_the_video_matrix() {
+    //given: READY. on the second line, and a reversed space for the cursor
+    let mut machine = c64(&[]);
+    for offset in 0..(COLUMNS * ROWS) as Word {
+        machine.bus.ram[0x0400 + offset] = 0x20;
+    }
+    for (offset, code) in [0x12, 0x05, 0x01, 0x04, 0x19, 0x2E, 0xA0].iter().enumerate() {
+        machine.bus.ram[0x0400 + COLUMNS as Word + offset as Word] = *code;
+    }
+    machine.bus.write(0xD018, 0x14);
+
+    //when:
+    let text = machine.screen_text();
+
+    //then:
+    assert_eq!(text.len(), ROWS);
+    assert_eq!(text[1], format!("{:<40}", "READY."));
+
+    //when: the lower case set, and the screen at $0800
+    machine.bus.ram[0x0800] = 0x08;
+    machine.bus.ram[0x0801] = 0x49;
+    machine.bus.write(0xD018, 0x26);
+
+    //then:
+    assert_eq!(&machine.screen_text()[0][..2], "hI");
+}
+
+#[test]
+fn basic_programs_get_their_end_pointers() {
+    //given: 10 PRINT
+    let prg = [0x01, 0x08, 0x07, 0x08, 0x0A, 0x00, 0x99, 0x00, 0x00, 0x00];
+    let mut machine = c64(&[]);
+
+    //when:
+    let address = machine.load_prg(&prg).unwrap();
+
+    //then:
+    assert_eq!(address, BASIC_START);
+    assert_eq!(machine.bus.ram[0x0807], 0x00);
+    for pointer in BASIC_END_POINTERS {
+        assert_eq!(machine.bus.ram[pointer], 0x09);
+        assert_eq!(machine.bus.ram[pointer + 1], 0x08);
+    }
+}
+
+#[test]
+fn io_port_on_a_device_bus() {
+    //given: a bare 6510 with P3 driven low from outside
+    let mut bus = DeviceBus::new(Mem::new());
+    let port = bus.attach(m6510::DDR, 2, IoPort::new());
+    bus.device_mut(port).set_input(!0x08);
+
+    //when: P0-P1 outputs
+    bus.write(m6510::DDR, 0x03);
+    bus.write(m6510::PORT, 0xC1);
+
+    //then: the inputs float high but for P3, and P6-P7 keep what was written
+    assert_eq!(bus.read(m6510::PORT), 0xC1 | 0x34);
+    assert_eq!(bus.device(port).pins(), 0x35);
+}
+
+/** @return the three ROM images, basic.bin, kernal.bin and chargen.bin as VICE names them */
+fn c64_roms() -> [Vec<Byte>; 3] {
+    let basic = test_rom_path("C64_BASIC_ROM", "basic.bin");
+    let kernal = test_rom_path("C64_KERNAL_ROM", "kernal.bin");
+    let chargen = test_rom_path("C64_CHARGEN_ROM", "chargen.bin");
+    [basic, kernal, chargen].map(|path| fs::read(path).unwrap())
+}
+
+#[test]
+#[ignore = "needs basic.bin, kernal.bin and chargen.bin in test_roms/ or C64_BASIC_ROM, C64_KERNAL_ROM and C64_CHARGEN_ROM"]
+fn kernal_boots_to_the_basic_prompt() {
+    let [basic, kernal, chargen] = c64_roms();
+
+    //given:
+    let mut machine = C64::new();
+    machine.load_basic(&basic).unwrap();
+    machine.load_kernal(&kernal).unwrap();
+    machine.load_char_rom(&chargen).unwrap();
+    machine.reset();
+
+    //when: the RAM test alone takes over a second
+    machine.execute(4_000_000);
+
+    //then:
+    assert!(!machine.cpu.halted(), "halted at ${:04X}", machine.cpu.pc());
+    let text = machine.screen_text();
+    assert_eq!(text[1].trim(), "**** COMMODORE 64 BASIC V2 ****", "{:#?}", text);
+    assert_eq!(text[3].trim(), "64K RAM SYSTEM  38911 BASIC BYTES FREE", "{:#?}", text);
+    assert_eq!(text[5].trim(), "READY.", "{:#?}", text);
+
+    //when: a line typed at the prompt
+    machine.type_text("print 6*7\n");
+    machine.execute(500_000);
+
+    //then:
+    let text = machine.screen_text();
+    assert_eq!(text[7].trim(), "42", "{:#?}", text);
+}
@@ -0,0 +1,80 @@
+use crate::m6502::*;
+
+/** Compare `register` set by `set_register` against `operand`, checking Z, N and C */
+fn test_compare_immediate(
+    opcode_to_test: Byte,
+    set_register: fn(&mut CPU, Byte),
+    register: Byte,
+    operand: Byte,
+    expected_znc: (Byte, Byte, Byte),
+) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    set_register(&mut cpu, register);
+    mem[0xFFFC] = opcode_to_test;
+    mem[0xFFFD] = operand;
+
+    //when:
+    let cpu_copy = cpu.clone();
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!((cpu.z(), cpu.n(), cpu.c()), expected_znc);
+    assert_eq!(cpu.a(), cpu_copy.a());
+    assert_eq!(cpu.x(), cpu_copy.x());
+    assert_eq!(cpu.y(), cpu_copy.y());
+    assert_eq!(cpu.v(), cpu_copy.v());
+}
+
+#[test]
+fn cmp_immediate_can_compare_two_identical_values() {
+    test_compare_immediate(CPU::INS_CMP_IM, CPU::set_a, 0x1A, 0x1A, (1, 0, 1));
+}
+
+#[test]
+fn cmp_immediate_can_compare_a_large_positive_to_a_positive() {
+    test_compare_immediate(CPU::INS_CMP_IM, CPU::set_a, 0x30, 0x1A, (0, 0, 1));
+}
+
+#[test]
+fn cmp_immediate_can_compare_a_smaller_value_and_borrows() {
+    test_compare_immediate(CPU::INS_CMP_IM, CPU::set_a, 0x08, 0x1A, (0, 1, 0));
+}
+
+#[test]
+fn cpx_immediate_can_compare_against_the_x_register() {
+    test_compare_immediate(CPU::INS_CPX_IM, CPU::set_x, 0x42, 0x42, (1, 0, 1));
+}
+
+#[test]
+fn cpy_immediate_can_compare_against_the_y_register() {
+    test_compare_immediate(CPU::INS_CPY_IM, CPU::set_y, 0x00, 0x01, (0, 1, 0));
+}
+
+#[test]
+fn cmp_indirect_y_compares_a_value_from_memory() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_a(0x42);
+    cpu.set_y(0x04);
+    mem[0xFFFC] = CPU::INS_CMP_INDY;
+    mem[0xFFFD] = 0x02;
+    mem[0x0002] = 0x00;
+    mem[0x0003] = 0x80;
+    mem[0x8004] = 0x42;
+
+    //when:
+    let cycles_used = cpu.execute(5, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 5);
+    assert_eq!(cpu.z(), 1);
+    assert_eq!(cpu.c(), 1);
+}
@@ -0,0 +1,114 @@
+use crate::coverage::*;
+use crate::m6502::*;
+use std::collections::BTreeMap;
+
+const BNE: Byte = 0xD0;
+
+/**
+ * main.s
+ *  1 main:   LDA $0300
+ *  2         STA $0301
+ *  3         JSR sub
+ *  4 trap:   JMP trap
+ *  5         BNE trap
+ *  7 table:  .byte $42
+ *  9 sub:    LDX #$01
+ * 10         RTS
+ * 11 unused: LDY #$02
+ * */
+fn program() -> (CPU, Mem, LineMap, BTreeMap<String, Word>) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut mem);
+
+    let main = [
+        CPU::INS_LDA_ABS, 0x00, 0x03,
+        CPU::INS_STA_ABS, 0x01, 0x03,
+        CPU::INS_JSR, 0x00, 0x04,
+        CPU::INS_JMP_ABS, 0x09, 0x02,
+        BNE, 0xFB,
+    ];
+    let sub = [CPU::INS_LDX_IM, 0x01, CPU::INS_RTS, CPU::INS_LDY_IM, 0x02];
+    for (address, code) in [(0x0200, &main[..]), (0x0300, &[0x42][..]), (0x0400, &sub[..])] {
+        for (offset, byte) in code.iter().enumerate() {
+            mem[address + offset as Word] = *byte;
+        }
+    }
+
+    let mut lines = LineMap::new();
+    lines.add_code("main.s", 1, 0x0200, 3);
+    lines.add_code("main.s", 2, 0x0203, 3);
+    lines.add_code("main.s", 3, 0x0206, 3);
+    lines.add_code("main.s", 4, 0x0209, 3);
+    lines.add_code("main.s", 5, 0x020C, 2);
+    lines.add_data("main.s", 7, 0x0300, 1);
+    lines.add_code("main.s", 9, 0x0400, 2);
+    lines.add_code("main.s", 10, 0x0402, 1);
+    lines.add_code("main.s", 11, 0x0403, 2);
+
+    let symbols = BTreeMap::from([
+        ("main".to_string(), 0x0200),
+        ("sub".to_string(), 0x0400),
+        ("unused".to_string(), 0x0403),
+    ]);
+
+    (cpu, mem, lines, symbols)
+}
+
+fn run(cpu: &mut CPU, mem: &mut Mem) -> Coverage {
+    let mut coverage = Coverage::new();
+    while cpu.pc() != 0x0209 {
+        coverage.step(cpu, mem);
+    }
+    coverage.step(cpu, mem);
+    coverage
+}
+
+#[test]
+fn coverage_tells_opcodes_operands_reads_and_writes_apart() {
+    //given:
+    let (mut cpu, mut mem, _, _) = program();
+
+    //when:
+    let coverage = run(&mut cpu, &mut mem);
+
+    //then:
+    assert_eq!(coverage.flags(0x0200), EXECUTED_OPCODE);
+    assert_eq!(coverage.flags(0x0201), EXECUTED_OPERAND);
+    assert_eq!(coverage.flags(0x0202), EXECUTED_OPERAND);
+    assert_eq!(coverage.flags(0x0300), READ);
+    assert_eq!(coverage.flags(0x0301), WRITTEN);
+    //the return address JSR pushed and RTS pulled
+    assert_eq!(coverage.flags(0x01FF), WRITTEN | READ);
+    assert_eq!(coverage.flags(0x020C), 0);
+    assert_eq!(coverage.flags(0x0403), 0);
+    assert_eq!(coverage.executions(0x0209), 1);
+}
+
+#[test]
+fn lcov_report_maps_coverage_to_source_lines() {
+    let (mut cpu, mut mem, lines, symbols) = program();
+    let coverage = run(&mut cpu, &mut mem);
+    let mut out = Vec::new();
+
+    coverage.write_lcov(&mut out, &mem, &lines, &symbols).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "TN:\nSF:main.s\n\
+         FN:1,main\nFN:9,sub\nFN:11,unused\n\
+         FNDA:1,main\nFNDA:1,sub\nFNDA:0,unused\nFNF:3\nFNH:2\n\
+         BRDA:5,0,0,-\nBRDA:5,0,1,-\nBRF:2\nBRH:0\n\
+         DA:1,1\nDA:2,1\nDA:3,1\nDA:4,1\nDA:5,0\nDA:9,1\nDA:10,1\nDA:11,0\n\
+         LF:8\nLH:6\nend_of_record\n"
+    );
+}
+
+#[test]
+fn line_map_finds_the_line_of_any_byte() {
+    let (_, _, lines, _) = program();
+
+    assert_eq!(lines.line_of(0x0205).map(|span| span.line), Some(2));
+    assert_eq!(lines.line_of(0x0300).map(|span| span.code), Some(false));
+    assert_eq!(lines.line_of(0x0500), None);
+}
@@ -0,0 +1,143 @@
+use crate::devices::*;
+use crate::m6502::*;
+
+/** Four registers, a cycle counter and interrupt lines the test drives by hand */
+#[derive(Default)]
+struct Latch {
+    registers: [Byte; 4],
+    cycles: u64,
+    irq: bool,
+    nmi: bool,
+}
+
+impl Device for Latch {
+    fn read(&mut self, offset: Word) -> Byte {
+        self.registers[offset as usize & 3]
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        self.registers[offset as usize & 3] = value;
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        self.registers[offset as usize & 3]
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+
+    fn nmi(&self) -> bool {
+        self.nmi
+    }
+}
+
+#[test]
+fn addresses_decode_to_devices_mirrors_or_the_bus_below() {
+    //given:
+    let mut bus = DeviceBus::new(Mem::new());
+    let first = bus.attach(0x4000, 4, Latch::default());
+    let second = bus.attach(0x4002, 4, Latch::default());
+    bus.map(first, 0x5000, 0x100, 0);
+
+    //when:
+    bus.write(0x4001, 0x11);
+    bus.write(0x4003, 0x33);
+    bus.write(0x4005, 0x55);
+    bus.write(0x50FE, 0xEE);
+    bus.write(0x3FFF, 0x77);
+
+    //then:
+    assert_eq!(bus.device(first).registers, [0x00, 0x11, 0xEE, 0x33]);
+    assert_eq!(bus.device(second).registers, [0x00, 0x00, 0x00, 0x55]);
+    assert_eq!(bus.peek(0x5001), 0x11);
+    assert_eq!(bus.bus[0x3FFF], 0x77);
+    assert_eq!(bus.bus[0x4001], 0x00);
+}
+
+#[test]
+fn interrupt_lines_are_wire_ored_onto_the_cpu() {
+    let mut bus = DeviceBus::new(Mem::new());
+    let mut cpu = CPU::new();
+    let first = bus.attach(0x4000, 4, Latch::default());
+    let second = bus.attach(0x4004, 4, Latch::default());
+
+    bus.device_mut(first).irq = true;
+    bus.device_mut(second).irq = true;
+    bus.tick(0, &mut cpu);
+    assert!(cpu.irq());
+
+    bus.device_mut(first).irq = false;
+    bus.tick(0, &mut cpu);
+    assert!(cpu.irq());
+
+    bus.device_mut(second).irq = false;
+    bus.device_mut(second).nmi = true;
+    bus.tick(0, &mut cpu);
+    assert!(!cpu.irq());
+    assert!(cpu.nmi());
+
+    bus.disconnect_interrupts(second);
+    bus.tick(0, &mut cpu);
+    assert!(!cpu.nmi());
+}
+
+#[test]
+fn devices_are_clocked_by_the_cycles_the_cpu_used() {
+    //given:
+    let mut bus = DeviceBus::new(Mem::new());
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut bus.bus);
+    let code = [
+        CPU::INS_LDA_IM, 0x42,
+        CPU::INS_STA_ABS, 0x01, 0x40,
+        CPU::INS_JMP_ABS, 0x05, 0x02,
+    ];
+    for (offset, byte) in code.iter().enumerate() {
+        bus.bus[0x0200 + offset as Word] = *byte;
+    }
+    let latch = bus.attach(0x4000, 4, Latch::default());
+
+    //when:
+    let cycles = bus.execute(20, &mut cpu);
+
+    //then:
+    assert_eq!(bus.device(latch).cycles, cycles as u64);
+    assert_eq!(bus.device(latch).registers[1], 0x42);
+}
+
+#[test]
+fn device_nmi_reaches_the_cpu() {
+    //given:
+    let mut bus = DeviceBus::new(Mem::new());
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut bus.bus);
+    let main = [CPU::INS_JMP_ABS, 0x00, 0x02];
+    let handler = [
+        CPU::INS_LDY_IM, 0x01,
+        CPU::INS_STY_ZP, 0x10,
+        CPU::INS_RTI,
+    ];
+    for (offset, byte) in main.iter().enumerate() {
+        bus.bus[0x0200 + offset as Word] = *byte;
+    }
+    for (offset, byte) in handler.iter().enumerate() {
+        bus.bus[0x0600 + offset as Word] = *byte;
+    }
+    bus.bus[0xFFFA] = 0x00;
+    bus.bus[0xFFFB] = 0x06;
+    let latch = bus.attach(0x4000, 4, Latch::default());
+
+    //when: the line stays low, but NMI is edge triggered
+    bus.device_mut(latch).nmi = true;
+    bus.execute(60, &mut cpu);
+
+    //then:
+    assert_eq!(bus.bus[0x0010], 0x01);
+    assert_eq!(cpu.sp(), 0xFF);
+    assert_eq!(cpu.pc(), 0x0200);
+}
@@ -0,0 +1,67 @@
+use crate::disassembler::*;
+use crate::m6502::*;
+
+#[test]
+fn every_opcode_has_an_entry() {
+    let legal = OPCODES.iter().filter(|opcode| !opcode.illegal).count();
+    assert_eq!(legal, 151);
+    assert_eq!(OPCODES[CPU::INS_LDA_INDY as usize].mnemonic, "LDA");
+    assert_eq!(OPCODES[CPU::INS_LDA_INDY as usize].mode, AddressingMode::IndirectY);
+    assert_eq!(OPCODES[CPU::INS_JSR as usize].size(), 3);
+    assert_eq!(OPCODES[CPU::INS_RTS as usize].size(), 1);
+}
+
+#[test]
+fn can_disassemble_a_little_program() {
+    let mut mem: Mem = Mem::new();
+
+    //given:
+    mem[0x8000] = CPU::INS_LDA_IM;
+    mem[0x8001] = 0x42;
+    mem[0x8002] = CPU::INS_STA_ABSX;
+    mem[0x8003] = 0x00;
+    mem[0x8004] = 0x02;
+    mem[0x8005] = CPU::INS_LDY_ZPX;
+    mem[0x8006] = 0x80;
+    mem[0x8007] = CPU::INS_LDA_INDX;
+    mem[0x8008] = 0x20;
+    mem[0x8009] = 0xD0; //BNE
+    mem[0x800A] = 0xF5;
+    mem[0x800B] = 0x6C; //JMP (ind)
+    mem[0x800C] = 0xFC;
+    mem[0x800D] = 0xFF;
+    mem[0x800E] = CPU::INS_RTS;
+
+    //when:
+    let lines = disassemble(&mem, 0x8000, 7);
+
+    //then:
+    assert_eq!(
+        lines,
+        vec![
+            "8000  A9 42     LDA #$42",
+            "8002  9D 00 02  STA $0200,X",
+            "8005  B4 80     LDY $80,X",
+            "8007  A1 20     LDA ($20,X)",
+            "8009  D0 F5     BNE $8000",
+            "800B  6C FC FF  JMP ($FFFC)",
+            "800E  60        RTS",
+        ]
+    );
+}
+
+#[test]
+fn can_render_addresses_with_custom_names() {
+    let mut mem: Mem = Mem::new();
+    mem[0x8000] = CPU::INS_JSR;
+    mem[0x8001] = 0xD2;
+    mem[0x8002] = 0xFF;
+
+    let instruction = decode(&mem, 0x8000);
+    let text = instruction.text_with(&|address| match address {
+        0xFFD2 => "CHROUT".to_string(),
+        _ => format!("${:04X}", address),
+    });
+
+    assert_eq!(text, "JSR CHROUT");
+}
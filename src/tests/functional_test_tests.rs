@@ -0,0 +1,118 @@
+use crate::functional_test::*;
+use crate::m6502::*;
+use super::test_rom_path;
+
+#[test]
+#[ignore = "needs 6502_functional_test.bin in test_roms/ or KLAUS_FUNCTIONAL_TEST"]
+fn klaus_dormann_functional_test() {
+    let path = test_rom_path("KLAUS_FUNCTIONAL_TEST", "6502_functional_test.bin");
+
+    let outcome = TestRom::functional_test().run_file(path).unwrap();
+
+    assert!(outcome.is_success(), "{:?}", outcome);
+}
+
+#[test]
+#[ignore = "needs 6502_decimal_test.bin in test_roms/ or KLAUS_DECIMAL_TEST, and KLAUS_DECIMAL_TEST_SUCCESS"]
+fn klaus_dormann_decimal_test() {
+    let path = test_rom_path("KLAUS_DECIMAL_TEST", "6502_decimal_test.bin");
+    let success = std::env::var("KLAUS_DECIMAL_TEST_SUCCESS")
+        .ok()
+        .and_then(|address| u16::from_str_radix(address.trim_start_matches('$'), 16).ok())
+        .expect("set KLAUS_DECIMAL_TEST_SUCCESS to the hex address of DONE in the listing");
+
+    let outcome = TestRom::decimal_test(success).run_file(path).unwrap();
+
+    assert!(outcome.is_success(), "{:?}", outcome);
+}
+
+/** LDA #error / STA $0B / JMP $0210 ... $0210: JMP $0210 */
+fn trapping_program(error: Byte) -> Vec<Byte> {
+    let mut image = vec![
+        CPU::INS_LDA_IM,
+        error,
+        CPU::INS_STA_ZP,
+        0x0B,
+        CPU::INS_JMP_ABS,
+        0x10,
+        0x02,
+    ];
+    image.resize(0x10, 0x00);
+    image.extend_from_slice(&[CPU::INS_JMP_ABS, 0x10, 0x02]);
+    image
+}
+
+#[test]
+fn trap_at_the_success_address_is_a_success() {
+    let rom = TestRom {
+        error_flag: None,
+        ..TestRom::decimal_test(0x0210)
+    };
+
+    let outcome = rom.run(&trapping_program(0));
+
+    //2 + 3 + 3 to get there, 3 more to notice the trap
+    assert_eq!(outcome, Outcome::Success { cycles: 11 });
+}
+
+#[test]
+fn trap_anywhere_else_is_a_failure() {
+    let rom = TestRom::decimal_test(0x0300);
+
+    let outcome = rom.run(&trapping_program(0));
+
+    assert_eq!(outcome, Outcome::Trapped { pc: 0x0210, cycles: 11 });
+}
+
+#[test]
+fn error_flag_is_checked_at_the_success_address() {
+    let rom = TestRom::decimal_test(0x0210);
+
+    let outcome = rom.run(&trapping_program(1));
+
+    assert_eq!(
+        outcome,
+        Outcome::Failed {
+            pc: 0x0210,
+            error: 1,
+            cycles: 11
+        }
+    );
+}
+
+#[test]
+fn running_out_of_cycles_is_reported() {
+    let rom = TestRom {
+        max_cycles: 4,
+        ..TestRom::decimal_test(0x0210)
+    };
+
+    let outcome = rom.run(&trapping_program(0));
+
+    assert_eq!(outcome, Outcome::TimedOut { pc: 0x0204, cycles: 5 });
+}
+
+#[test]
+fn an_opcode_the_core_does_not_run_is_not_mistaken_for_a_trap() {
+    let rom = TestRom::decimal_test(0x0210);
+    let mut image = trapping_program(0);
+    image[4] = 0x02;
+
+    let outcome = rom.run(&image);
+
+    assert_eq!(outcome, Outcome::Halted { pc: 0x0204, cycles: 5 });
+}
+
+#[test]
+fn halting_at_the_success_address_is_not_a_success() {
+    let rom = TestRom {
+        error_flag: None,
+        ..TestRom::decimal_test(0x0210)
+    };
+    let mut image = trapping_program(0);
+    image[0x10] = 0x02;
+
+    let outcome = rom.run(&image);
+
+    assert_eq!(outcome, Outcome::Halted { pc: 0x0210, cycles: 8 });
+}
@@ -0,0 +1,247 @@
+use crate::gdb::*;
+use crate::m6502::*;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+
+/** A scripted front-end talking to the stub over localhost */
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn send(&mut self, payload: &str) -> String {
+        let packet = format!("${}#{:02x}", payload, checksum_of(payload.as_bytes()));
+        self.stream.write_all(packet.as_bytes()).unwrap();
+
+        let mut byte = [0u8; 1];
+        self.stream.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], b'+');
+
+        self.receive()
+    }
+
+    fn receive(&mut self) -> String {
+        let mut byte = [0u8; 1];
+        let mut reply = Vec::new();
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            reply.push(byte[0]);
+        }
+        let mut checksum = [0u8; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        assert_eq!(
+            u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap(),
+            checksum_of(&reply)
+        );
+        self.stream.write_all(b"+").unwrap();
+
+        String::from_utf8(reply).unwrap()
+    }
+}
+
+fn start_stub(cpu: CPU, mem: Mem) -> (Client, JoinHandle<GdbStub<Mem>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let mut stub = GdbStub::new(cpu, mem);
+        let (stream, _) = listener.accept().unwrap();
+        stub.serve(stream).unwrap();
+        stub
+    });
+
+    let client = Client {
+        stream: TcpStream::connect(address).unwrap(),
+    };
+    (client, handle)
+}
+
+/** LDA #$42 / LDX #$07 / STA $0200 / JSR $8000 ... $8000: RTS */
+fn little_program() -> (CPU, Mem) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x1000, &mut mem);
+
+    mem[0x1000] = CPU::INS_LDA_IM;
+    mem[0x1001] = 0x42;
+    mem[0x1002] = CPU::INS_LDX_IM;
+    mem[0x1003] = 0x07;
+    mem[0x1004] = CPU::INS_STA_ABS;
+    mem[0x1005] = 0x00;
+    mem[0x1006] = 0x02;
+    mem[0x1007] = CPU::INS_JSR;
+    mem[0x1008] = 0x00;
+    mem[0x1009] = 0x80;
+    mem[0x8000] = CPU::INS_RTS;
+
+    (cpu, mem)
+}
+
+#[test]
+fn gdb_can_read_and_write_registers() {
+    let (mut cpu, mem) = little_program();
+    cpu.set_a(0x11);
+    cpu.set_x(0x22);
+    cpu.set_y(0x33);
+    cpu.set_c(1);
+    let (mut client, handle) = start_stub(cpu, mem);
+
+    //A X Y SP PC(lo hi) P
+    assert_eq!(client.send("g"), "112233ff001021");
+    assert_eq!(client.send("p4"), "0010");
+    assert_eq!(client.send("P0=99"), "OK");
+    assert_eq!(client.send("P4=3412"), "OK");
+    assert_eq!(client.send("G0102030405068a"), "OK");
+    assert_eq!(client.send("p5"), "aa");
+    client.send("D");
+
+    let stub = handle.join().unwrap();
+    assert_eq!(stub.cpu.a(), 0x01);
+    assert_eq!(stub.cpu.x(), 0x02);
+    assert_eq!(stub.cpu.y(), 0x03);
+    assert_eq!(stub.cpu.sp(), 0x04);
+    assert_eq!(stub.cpu.pc(), 0x0605);
+    assert_eq!(stub.cpu.n(), 1);
+    assert_eq!(stub.cpu.d(), 1);
+    assert_eq!(stub.cpu.z(), 1);
+    assert_eq!(stub.cpu.c(), 0);
+}
+
+#[test]
+fn gdb_can_read_and_write_memory_through_the_bus() {
+    let (cpu, mem) = little_program();
+    let (mut client, handle) = start_stub(cpu, mem);
+
+    assert_eq!(client.send("m1000,4"), "a942a207");
+    assert_eq!(client.send("M0300,3:deadbe"), "OK");
+    assert_eq!(client.send("m0300,3"), "deadbe");
+    assert_eq!(client.send("M0300,2:de"), "E01");
+    assert_eq!(client.send("m0000,800").len(), 0x1000);
+    assert_eq!(client.send("m0000,801"), "E01");
+    assert_eq!(client.send("m0000,ffffffffff"), "E01");
+    client.send("D");
+
+    let stub = handle.join().unwrap();
+    assert_eq!(stub.bus[0x0300], 0xDE);
+    assert_eq!(stub.bus[0x0302], 0xBE);
+}
+
+#[test]
+fn gdb_can_single_step() {
+    let (cpu, mem) = little_program();
+    let (mut client, handle) = start_stub(cpu, mem);
+
+    assert_eq!(client.send("s"), "S05");
+    assert_eq!(client.send("p4"), "0210");
+    assert_eq!(client.send("p0"), "42");
+    assert_eq!(client.send("vCont;s:1"), "S05");
+    assert_eq!(client.send("p1"), "07");
+    client.send("D");
+
+    handle.join().unwrap();
+}
+
+#[test]
+fn gdb_continue_stops_at_software_and_hardware_breakpoints() {
+    let (cpu, mem) = little_program();
+    let (mut client, handle) = start_stub(cpu, mem);
+
+    assert_eq!(client.send("Z0,1004,1"), "OK");
+    assert_eq!(client.send("Z1,8000,1"), "OK");
+    assert_eq!(client.send("c"), "T05swbreak:;");
+    assert_eq!(client.send("p4"), "0410");
+    assert_eq!(client.send("c"), "T05hwbreak:;");
+    assert_eq!(client.send("p4"), "0080");
+    assert_eq!(client.send("m0200,1"), "42");
+
+    assert_eq!(client.send("z1,8000,1"), "OK");
+    assert_eq!(client.send("Z0,100a,1"), "OK");
+    assert_eq!(client.send("c"), "T05swbreak:;");
+    assert_eq!(client.send("p4"), "0a10");
+    client.send("D");
+
+    handle.join().unwrap();
+}
+
+#[test]
+fn gdb_can_interrupt_a_running_target() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x3000, &mut mem);
+
+    //given: a subroutine that rewrites its own return address so it is called forever
+    mem[0x3000] = CPU::INS_JSR;
+    mem[0x3001] = 0x10;
+    mem[0x3002] = 0x30;
+    mem[0x3010] = CPU::INS_LDA_IM;
+    mem[0x3011] = 0xFF;
+    mem[0x3012] = CPU::INS_STA_ABS;
+    mem[0x3013] = 0xFE;
+    mem[0x3014] = 0x01;
+    mem[0x3015] = CPU::INS_LDA_IM;
+    mem[0x3016] = 0x2F;
+    mem[0x3017] = CPU::INS_STA_ABS;
+    mem[0x3018] = 0xFF;
+    mem[0x3019] = 0x01;
+    mem[0x301A] = CPU::INS_RTS;
+    let (mut client, handle) = start_stub(cpu, mem);
+
+    //when:
+    let packet = format!("$c#{:02x}", checksum_of(b"c"));
+    client.stream.write_all(packet.as_bytes()).unwrap();
+    let mut ack = [0u8; 1];
+    client.stream.read_exact(&mut ack).unwrap();
+    client.stream.write_all(&[0x03]).unwrap();
+
+    //then:
+    assert_eq!(client.receive(), "S02");
+    let packet = format!("$k#{:02x}", checksum_of(b"k"));
+    client.stream.write_all(packet.as_bytes()).unwrap();
+
+    let stub = handle.join().unwrap();
+    assert!((0x3000..=0x301A).contains(&stub.cpu.pc()));
+}
+
+#[test]
+fn gdb_answers_queries() {
+    let (cpu, mem) = little_program();
+    let (mut client, handle) = start_stub(cpu, mem);
+
+    assert!(client.send("qSupported:swbreak+").contains("qXfer:features:read+"));
+    assert_eq!(client.send("?"), "S05");
+    assert_eq!(client.send("qAttached"), "1");
+    assert!(client.send("qXfer:features:read:target.xml:0,fff").starts_with("l<?xml"));
+    assert_eq!(client.send("qUnknownPacket"), "");
+    assert_eq!(client.send("\u{e9}1"), "");
+    client.send("D");
+
+    handle.join().unwrap();
+}
+
+#[test]
+fn gdb_reports_a_halted_cpu_instead_of_running_on() {
+    let (cpu, mut mem) = little_program();
+    mem[0x1004] = 0x02;
+    let (mut client, handle) = start_stub(cpu, mem);
+
+    //when: continuing into an opcode the core doesn't run
+    assert_eq!(client.send("c"), "S04");
+
+    //then: it stays put and says so
+    assert_eq!(client.send("p4"), "0410");
+    assert_eq!(client.send("s"), "S04");
+    assert_eq!(client.send("?"), "S04");
+    client.send("D");
+
+    let stub = handle.join().unwrap();
+    assert!(stub.cpu.halted());
+}
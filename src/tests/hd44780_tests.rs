@@ -0,0 +1,145 @@
+use crate::devices::hd44780::*;
+
+/** Pulse E with RS and D0-D7 set, as an 8-bit interface does */
+fn write(lcd: &mut Hd44780, rs: bool, byte: u8) {
+    lcd.set_pins(true, rs, false, byte);
+    lcd.set_pins(false, rs, false, byte);
+}
+
+/** Send a byte as two nibbles on D4-D7, as a 4-bit interface does */
+fn write_nibbles(lcd: &mut Hd44780, rs: bool, byte: u8) {
+    write(lcd, rs, byte & 0xF0);
+    write(lcd, rs, byte << 4);
+}
+
+fn read(lcd: &mut Hd44780, rs: bool) -> Option<u8> {
+    lcd.set_pins(true, rs, true, 0);
+    let data = lcd.output();
+    lcd.set_pins(false, rs, true, 0);
+    data
+}
+
+/** 8-bit, two lines, display on, cursor moving right */
+fn lcd() -> Hd44780 {
+    let mut lcd = Hd44780::new(16, 2);
+    for command in [FUNCTION_SET | EIGHT_BIT | TWO_LINES, DISPLAY_CONTROL | DISPLAY_ON, ENTRY_MODE_SET | ENTRY_INCREMENT] {
+        write(&mut lcd, false, command);
+    }
+    lcd
+}
+
+#[test]
+fn characters_written_show_on_the_first_line() {
+    //given:
+    let mut lcd = lcd();
+
+    //when:
+    for byte in b"Hello" {
+        write(&mut lcd, true, *byte);
+    }
+
+    //then:
+    assert_eq!(lcd.text(), ["Hello           ", "                "]);
+    assert_eq!(lcd.address(), 5);
+}
+
+#[test]
+fn nothing_shows_while_the_display_is_off() {
+    let mut lcd = Hd44780::new(16, 2);
+    write(&mut lcd, true, b'X');
+    assert_eq!(lcd.text()[0], "                ");
+
+    write(&mut lcd, false, DISPLAY_CONTROL | DISPLAY_ON);
+    assert_eq!(lcd.text()[0], "X               ");
+}
+
+#[test]
+fn end_of_the_first_line_runs_into_the_second() {
+    //given:
+    let mut lcd = lcd();
+
+    //when:
+    write(&mut lcd, false, SET_DDRAM_ADDRESS | 0x27);
+    write(&mut lcd, true, b'x');
+    write(&mut lcd, true, b'y');
+    write(&mut lcd, false, SET_DDRAM_ADDRESS | 0x4F);
+    write(&mut lcd, true, b'z');
+
+    //then:
+    assert_eq!(lcd.text(), ["                ", "y              z"]);
+    assert_eq!(lcd.address(), 0x50);
+}
+
+#[test]
+fn four_bit_interface_takes_two_nibbles() {
+    //given: powered up in 8-bit mode with D0-D3 not connected
+    let mut lcd = Hd44780::new(16, 2);
+    write(&mut lcd, false, FUNCTION_SET);
+    assert!(!lcd.eight_bit());
+
+    //when:
+    write_nibbles(&mut lcd, false, FUNCTION_SET | TWO_LINES);
+    write_nibbles(&mut lcd, false, DISPLAY_CONTROL | DISPLAY_ON);
+    write_nibbles(&mut lcd, true, b'O');
+    write_nibbles(&mut lcd, true, b'K');
+
+    //then:
+    assert_eq!(lcd.text()[0], "OK              ");
+    lcd.tick(100);
+    assert_eq!(read(&mut lcd, false), Some(0x00));
+    assert_eq!(read(&mut lcd, false), Some(0x20));
+}
+
+#[test]
+fn busy_flag_is_set_for_as_long_as_a_command_takes() {
+    let mut lcd = lcd();
+    write(&mut lcd, true, b'A');
+    write(&mut lcd, false, CLEAR_DISPLAY);
+
+    assert_eq!(read(&mut lcd, false), Some(BUSY_FLAG));
+    lcd.tick(1519);
+    assert!(lcd.busy());
+    lcd.tick(1);
+    assert_eq!(read(&mut lcd, false), Some(0x00));
+    assert_eq!(lcd.text()[0], "                ");
+}
+
+#[test]
+fn data_reads_back_and_the_display_shifts() {
+    //given:
+    let mut lcd = lcd();
+    for byte in b"ABC" {
+        write(&mut lcd, true, *byte);
+    }
+
+    //when:
+    write(&mut lcd, false, CURSOR_SHIFT | SHIFT_DISPLAY);
+    write(&mut lcd, false, SET_DDRAM_ADDRESS);
+
+    //then:
+    assert_eq!(lcd.text()[0], "BC              ");
+    assert_eq!(read(&mut lcd, true), Some(b'A'));
+    assert_eq!(read(&mut lcd, true), Some(b'B'));
+    assert_eq!(lcd.address(), 2);
+}
+
+#[test]
+fn custom_characters_are_kept_in_cgram() {
+    //given:
+    let mut lcd = lcd();
+    let heart = [0x00, 0x0A, 0x1F, 0x1F, 0x0E, 0x04, 0x00, 0x00];
+
+    //when:
+    write(&mut lcd, false, SET_CGRAM_ADDRESS | 0x08);
+    for row in heart {
+        write(&mut lcd, true, row | 0xE0);
+    }
+    write(&mut lcd, false, SET_DDRAM_ADDRESS);
+    for byte in [0x01, b'\\', 0x7E] {
+        write(&mut lcd, true, byte);
+    }
+
+    //then:
+    assert_eq!(lcd.glyph(1), heart);
+    assert_eq!(lcd.text()[0], "▒¥→             ");
+}
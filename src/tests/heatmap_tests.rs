@@ -0,0 +1,98 @@
+use crate::heatmap::*;
+use crate::m6502::*;
+
+/**
+ * $0200: LDA $0300 / STA $0301 / JSR $0400 / JMP $0200
+ * $0400: RTS
+ * run twice around the loop
+ * */
+fn heatmap() -> Heatmap {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut mem);
+
+    let code = [
+        CPU::INS_LDA_ABS, 0x00, 0x03,
+        CPU::INS_STA_ABS, 0x01, 0x03,
+        CPU::INS_JSR, 0x00, 0x04,
+        CPU::INS_JMP_ABS, 0x00, 0x02,
+    ];
+    for (offset, byte) in code.iter().enumerate() {
+        mem[0x0200 + offset as Word] = *byte;
+    }
+    mem[0x0400] = CPU::INS_RTS;
+
+    let mut heatmap = Heatmap::new();
+    for _ in 0..10 {
+        heatmap.step(&mut cpu, &mut mem);
+    }
+    heatmap
+}
+
+#[test]
+fn accesses_are_counted_by_kind() {
+    //when:
+    let heatmap = heatmap();
+
+    //then:
+    assert_eq!(heatmap.executes(0x0200), 2);
+    assert_eq!(heatmap.executes(0x0202), 2);
+    assert_eq!(heatmap.executes(0x0400), 2);
+    //fetching operands isn't reading data
+    assert_eq!(heatmap.reads(0x0201), 0);
+    assert_eq!(heatmap.reads(0x0300), 2);
+    assert_eq!(heatmap.writes(0x0301), 2);
+    assert_eq!(heatmap.writes(0x01FF), 2);
+    assert_eq!(heatmap.reads(0x01FF), 2);
+}
+
+#[test]
+fn pixels_are_coloured_by_access_type() {
+    let heatmap = heatmap();
+
+    assert_eq!(heatmap.pixel(0x0300), [0, 255, 0]);
+    assert_eq!(heatmap.pixel(0x0301), [255, 0, 0]);
+    assert_eq!(heatmap.pixel(0x0200), [0, 0, 255]);
+    assert_eq!(heatmap.pixel(0x01FF), [255, 255, 0]);
+    assert_eq!(heatmap.pixel(0x0500), [0, 0, 0]);
+}
+
+#[test]
+fn ppm_has_one_pixel_per_byte() {
+    let ppm = heatmap().to_ppm();
+
+    let header = b"P6\n256 256\n255\n";
+    assert_eq!(&ppm[..header.len()], header);
+    assert_eq!(ppm.len(), header.len() + 256 * 256 * 3);
+    let pixel = header.len() + 0x0301 * 3;
+    assert_eq!(&ppm[pixel..pixel + 3], &[255, 0, 0]);
+}
+
+#[test]
+fn png_is_well_formed() {
+    let png = heatmap().to_png();
+
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[8..16], b"\0\0\0\x0dIHDR");
+    assert_eq!(&png[16..29], &[0, 0, 1, 0, 0, 0, 1, 0, 8, 2, 0, 0, 0]);
+    //256 rows of a filter byte and 768 colour bytes, in 4 stored deflate blocks
+    assert_eq!(&png[33..37], &(2 + 4 * 5 + 256 * 769 + 4u32).to_be_bytes());
+    assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+}
+
+#[test]
+fn summary_lists_the_pages_in_use() {
+    let mut out = Vec::new();
+
+    heatmap().write_summary(&mut out).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "page        reads     writes   executes  touched
+$01             4          4          0    2/256
+$02             0          0         24   12/256
+$03             2          2          0    2/256
+$04             0          0          2    1/256
+"
+    );
+}
@@ -0,0 +1,102 @@
+use crate::host_calls::*;
+use crate::m6502::*;
+
+const CHROUT: Word = 0xFFD2;
+
+/** LDA #'H' / JSR CHROUT / LDA #'I' / JSR CHROUT / LDY #$01 */
+fn hello_program() -> (CPU, Mem) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut mem);
+
+    mem[0x0200] = CPU::INS_LDA_IM;
+    mem[0x0201] = b'H';
+    mem[0x0202] = CPU::INS_JSR;
+    mem[0x0203] = 0xD2;
+    mem[0x0204] = 0xFF;
+    mem[0x0205] = CPU::INS_LDA_IM;
+    mem[0x0206] = b'I';
+    mem[0x0207] = CPU::INS_JSR;
+    mem[0x0208] = 0xD2;
+    mem[0x0209] = 0xFF;
+    mem[0x020A] = CPU::INS_LDY_IM;
+    mem[0x020B] = 0x01;
+
+    (cpu, mem)
+}
+
+#[test]
+fn jsr_to_a_hooked_address_runs_the_closure_and_returns() {
+    let (mut cpu, mut mem) = hello_program();
+    let mut output = Vec::new();
+
+    //when:
+    {
+        let mut calls = HostCalls::new();
+        calls.register(CHROUT, |cpu: &mut CPU, _: &mut Mem| {
+            output.push(cpu.a());
+            Flow::Return
+        });
+        //2 + 6 + 6 for each character, 2 for LDY
+        let cycles_used = calls.execute(2 * (2 + 6 + 6) + 2, &mut cpu, &mut mem).unwrap();
+        assert_eq!(cycles_used, 30);
+    }
+
+    //then:
+    assert_eq!(output, b"HI");
+    assert_eq!(cpu.y(), 0x01);
+    assert_eq!(cpu.sp(), 0xFF);
+    assert_eq!(cpu.pc(), 0x020C);
+}
+
+#[test]
+fn host_call_can_change_registers_and_memory() {
+    let (mut cpu, mut mem) = hello_program();
+    let mut calls = HostCalls::new();
+    calls.register(CHROUT, |cpu: &mut CPU, mem: &mut Mem| {
+        mem.write(0x0400, cpu.a());
+        cpu.set_x(0x99);
+        cpu.set_c(1);
+        Flow::Return
+    });
+
+    calls.execute(2 + 6 + 6, &mut cpu, &mut mem).unwrap();
+
+    assert_eq!(mem[0x0400], b'H');
+    assert_eq!(cpu.x(), 0x99);
+    assert_eq!(cpu.c(), 1);
+    assert_eq!(cpu.pc(), 0x0205);
+}
+
+#[test]
+fn host_call_can_stop_execution() {
+    let (mut cpu, mut mem) = hello_program();
+    let mut calls = HostCalls::new();
+    calls.register(CHROUT, |_: &mut CPU, _: &mut Mem| Flow::Stop);
+
+    let stopped = calls.execute(1000, &mut cpu, &mut mem).unwrap_err();
+
+    assert_eq!(
+        stopped,
+        Stopped {
+            address: CHROUT,
+            cycles: 8
+        }
+    );
+    assert_eq!(cpu.pc(), CHROUT);
+}
+
+#[test]
+fn unregistered_addresses_run_normally() {
+    let (mut cpu, mut mem) = hello_program();
+    mem[CHROUT] = CPU::INS_RTS;
+    let mut calls = HostCalls::new();
+    calls.register(CHROUT, |_: &mut CPU, _: &mut Mem| Flow::Stop);
+
+    assert!(calls.unregister(CHROUT));
+    assert!(!calls.is_registered(CHROUT));
+    let cycles_used = calls.execute(30, &mut cpu, &mut mem).unwrap();
+
+    assert_eq!(cycles_used, 30);
+    assert_eq!(cpu.a(), b'I');
+}
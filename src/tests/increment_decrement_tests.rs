@@ -0,0 +1,81 @@
+use crate::m6502::*;
+
+fn test_register_step(opcode_to_test: Byte, set_register: fn(&mut CPU, Byte), register: fn(&CPU) -> Byte, from: Byte, to: Byte) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    set_register(&mut cpu, from);
+    mem[0xFFFC] = opcode_to_test;
+
+    //when:
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(register(&cpu), to);
+    assert_eq!(cpu.z(), (to == 0) as Byte);
+    assert_eq!(cpu.n(), to >> 7);
+}
+
+#[test]
+fn inx_can_increment_the_x_register_and_wraps_to_zero() {
+    test_register_step(CPU::INS_INX, CPU::set_x, CPU::x, 0xFF, 0x00);
+}
+
+#[test]
+fn iny_can_increment_the_y_register() {
+    test_register_step(CPU::INS_INY, CPU::set_y, CPU::y, 0x7F, 0x80);
+}
+
+#[test]
+fn dex_can_decrement_the_x_register_and_wraps_to_ff() {
+    test_register_step(CPU::INS_DEX, CPU::set_x, CPU::x, 0x00, 0xFF);
+}
+
+#[test]
+fn dey_can_decrement_the_y_register() {
+    test_register_step(CPU::INS_DEY, CPU::set_y, CPU::y, 0x01, 0x00);
+}
+
+#[test]
+fn inc_zero_page_x_can_increment_a_value_in_memory() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_x(0x10);
+    mem[0xFFFC] = CPU::INS_INC_ZPX;
+    mem[0xFFFD] = 0xF8;
+    mem[0x0008] = 0x57;
+
+    //when:
+    let cycles_used = cpu.execute(6, &mut mem);
+
+    //then: the address wraps around in the zero page
+    assert_eq!(cycles_used, 6);
+    assert_eq!(mem[0x0008], 0x58);
+}
+
+#[test]
+fn dec_absolute_can_decrement_a_value_in_memory() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    mem[0xFFFC] = CPU::INS_DEC_ABS;
+    mem[0xFFFD] = 0x00;
+    mem[0xFFFE] = 0x80;
+    mem[0x8000] = 0x01;
+
+    //when:
+    let cycles_used = cpu.execute(6, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 6);
+    assert_eq!(mem[0x8000], 0x00);
+    assert_eq!(cpu.z(), 1);
+}
@@ -0,0 +1,86 @@
+use crate::m6502::*;
+
+fn set_up() -> (CPU, Mem) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut mem);
+
+    mem[0x0200] = CPU::INS_LDA_IM;
+    mem[0x0201] = 0x01;
+    mem[0xFFFE] = 0x00;
+    mem[0xFFFF] = 0x05;
+    mem[0xFFFA] = 0x00;
+    mem[0xFFFB] = 0x06;
+    mem[0x0500] = CPU::INS_RTI;
+    mem[0x0600] = CPU::INS_LDX_IM;
+    mem[0x0601] = 0x02;
+
+    (cpu, mem)
+}
+
+#[test]
+fn irq_pushes_pc_and_status_and_jumps_through_the_vector() {
+    //given:
+    let (mut cpu, mut mem) = set_up();
+    cpu.set_c(1);
+    cpu.set_irq(true);
+
+    //when:
+    let cycles_used = cpu.step(&mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 7);
+    assert_eq!(cpu.pc(), 0x0500);
+    assert_eq!(cpu.sp(), 0xFC);
+    assert_eq!(mem[0x01FF], 0x02);
+    assert_eq!(mem[0x01FE], 0x00);
+    assert_eq!(mem[0x01FD], 0b0010_0001);
+    assert_eq!(cpu.i(), 1);
+}
+
+#[test]
+fn irq_is_ignored_while_interrupts_are_disabled() {
+    let (mut cpu, mut mem) = set_up();
+    cpu.set_i(1);
+    cpu.set_irq(true);
+
+    let cycles_used = cpu.step(&mut mem);
+
+    assert_eq!(cycles_used, 2);
+    assert_eq!(cpu.a(), 0x01);
+    assert_eq!(cpu.sp(), 0xFF);
+}
+
+#[test]
+fn rti_returns_to_the_interrupted_instruction() {
+    let (mut cpu, mut mem) = set_up();
+    cpu.set_irq(true);
+    cpu.step(&mut mem);
+    cpu.set_irq(false);
+
+    let cycles_used = cpu.step(&mut mem);
+
+    assert_eq!(cycles_used, 6);
+    assert_eq!(cpu.pc(), 0x0200);
+    assert_eq!(cpu.sp(), 0xFF);
+    assert_eq!(cpu.i(), 0);
+    assert_eq!(cpu.c(), 0);
+}
+
+#[test]
+fn nmi_is_taken_once_per_assertion_even_with_interrupts_disabled() {
+    let (mut cpu, mut mem) = set_up();
+    cpu.set_i(1);
+    cpu.set_nmi(true);
+
+    let nmi_cycles = cpu.step(&mut mem);
+    //still asserted, but there is no new edge
+    cpu.set_nmi(true);
+    let handler_cycles = cpu.step(&mut mem);
+
+    assert_eq!(nmi_cycles, 7);
+    assert_eq!(handler_cycles, 2);
+    assert_eq!(cpu.x(), 0x02);
+    assert_eq!(cpu.pc(), 0x0602);
+    assert!(cpu.nmi());
+}
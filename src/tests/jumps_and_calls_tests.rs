@@ -26,3 +26,70 @@ fn can_jump_to_subroutine_and_jump_back_again() {
     assert_eq!(actual_cycles, EXPECTED_CYCLES);
     assert_eq!(cpu.a(), 0x42);
 }
+
+#[test]
+fn jmp_absolute_can_jump_to_a_new_location_in_the_program() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    mem[0xFF00] = CPU::INS_JMP_ABS;
+    mem[0xFF01] = 0x00;
+    mem[0xFF02] = 0x80;
+    const EXPECTED_CYCLES: s32 = 3;
+    let cpu_copy = cpu.clone();
+
+    //when:
+    let actual_cycles = cpu.execute(EXPECTED_CYCLES, &mut mem);
+
+    //then:
+    assert_eq!(actual_cycles, EXPECTED_CYCLES);
+    assert_eq!(cpu.sp(), cpu_copy.sp());
+    assert_eq!(cpu.pc(), 0x8000);
+}
+
+#[test]
+fn jmp_indirect_can_jump_to_a_new_location_in_the_program() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    mem[0xFF00] = CPU::INS_JMP_IND;
+    mem[0xFF01] = 0x20;
+    mem[0xFF02] = 0x01;
+    mem[0x0120] = 0x00;
+    mem[0x0121] = 0x80;
+    const EXPECTED_CYCLES: s32 = 5;
+    let cpu_copy = cpu.clone();
+
+    //when:
+    let actual_cycles = cpu.execute(EXPECTED_CYCLES, &mut mem);
+
+    //then:
+    assert_eq!(actual_cycles, EXPECTED_CYCLES);
+    assert_eq!(cpu.sp(), cpu_copy.sp());
+    assert_eq!(cpu.pc(), 0x8000);
+}
+
+#[test]
+fn jmp_indirect_does_not_carry_into_the_next_page() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    mem[0xFF00] = CPU::INS_JMP_IND;
+    mem[0xFF01] = 0xFF;
+    mem[0xFF02] = 0x10;
+    mem[0x10FF] = 0x34;
+    mem[0x1000] = 0x12;
+    mem[0x1100] = 0x56;
+
+    //when:
+    cpu.execute(5, &mut mem);
+
+    //then:
+    assert_eq!(cpu.pc(), 0x1234);
+}
@@ -0,0 +1,191 @@
+use crate::devices::rriot;
+use crate::devices::serial::BufferedSerial;
+use crate::m6502::*;
+use crate::machines::kim1::*;
+use super::{idle_at, test_rom_path};
+use std::fs;
+
+/** where the tests' code goes in RAM */
+const ORIGIN: Word = 0x0200;
+
+/**
+ * A KIM-1 with a stand-in monitor ROM whose vectors point at `reset` and `nmi`,
+ * with `code` at ORIGIN in RAM
+ * */
+fn kim1(console: Console, input: &[Byte], code: &[Byte]) -> Kim1<BufferedSerial> {
+    let mut machine = Kim1::new(RAM_SIZE, console, BufferedSerial::new(input));
+    let mut rom = [0xEA; rriot::ROM_SIZE];
+    rom[0x3FA..].copy_from_slice(&[0x00, 0x03, 0x00, 0x02, 0x00, 0x02]);
+    machine.load_rom_002(&rom).unwrap();
+    let origin = ORIGIN as usize;
+    machine.bus.bus.ram[origin..origin + code.len()].copy_from_slice(code);
+    machine.reset();
+    machine
+}
+
+#[test]
+fn memory_map_repeats_every_8k() {
+    //given:
+    let mut machine = kim1(Console::Keypad, b"", &[]);
+
+    //when:
+    machine.bus.write(0x2010, 0x12);
+    machine.bus.write(0xF7FF, 0x34);
+    machine.bus.write(0x0400, 0x56);
+    machine.bus.write(0x1C00, 0x00);
+
+    //then:
+    assert_eq!(machine.cpu.pc(), 0x0200);
+    assert_eq!(machine.bus.peek(0x0010), 0x12);
+    assert_eq!(machine.bus.peek(0xE010), 0x12);
+    assert_eq!(machine.rriot_002().ram[63], 0x34);
+    assert_eq!(machine.bus.peek(0x0400), 0xFF);
+    assert_eq!(machine.bus.peek(0xFC00), 0xEA);
+    assert_eq!(machine.bus.peek(0xFFFC), 0x00);
+    assert_eq!(machine.bus.peek(0x1800), 0xFF);
+}
+
+#[test]
+fn tty_routines_are_trapped() {
+    //given:
+    let mut code = vec![
+        CPU::INS_JSR, 0x5A, 0x1E,
+        CPU::INS_STA_ZP, 0x10,
+        CPU::INS_LDA_IM, b'K',
+        CPU::INS_JSR, 0xA0, 0x1E,
+        CPU::INS_STY_ZP, 0x11,
+    ];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = kim1(Console::Tty, b"\n", &code);
+
+    //when:
+    machine.execute(100);
+
+    //then: the key was echoed
+    assert_eq!(machine.bus.bus.ram[0x10], b'\r');
+    assert_eq!(machine.bus.bus.ram[0x11], 0xFF);
+    assert_eq!(machine.terminal.output, b"\rK");
+    //the trap returned like RTS would, leaving the stack where reset put it
+    assert_eq!(machine.cpu.sp(), 0xFD);
+}
+
+#[test]
+fn getch_waits_for_a_key() {
+    let mut machine = kim1(Console::Tty, b"", &[CPU::INS_JSR, 0x5A, 0x1E]);
+
+    machine.execute(100);
+    assert_eq!(machine.cpu.pc(), GETCH);
+
+    machine.terminal.input.push_back(b'7');
+    machine.step();
+    assert_eq!(machine.cpu.a(), b'7');
+    assert_eq!(machine.cpu.pc(), 0x0203);
+}
+
+#[test]
+fn baud_rate_detection_is_skipped() {
+    let mut machine = kim1(Console::Tty, b"", &[]);
+    machine.cpu.set_pc(DETCPS);
+
+    machine.step();
+
+    assert_eq!(machine.cpu.pc(), START);
+    assert_ne!(machine.bus.peek(CNTL30), 0x00);
+    assert_eq!(machine.rriot_002().port_a() & 0x01, 0x00);
+}
+
+#[test]
+fn keypad_rows_are_read_on_port_a() {
+    //given: row 1 selected, which has 7 8 9 A B C D
+    let mut code = vec![
+        CPU::INS_LDA_IM, 0x1E,
+        CPU::INS_STA_ABS, 0x43, 0x17,
+        CPU::INS_LDA_IM, 0x02,
+        CPU::INS_STA_ABS, 0x42, 0x17,
+        CPU::INS_LDA_ABS, 0x40, 0x17,
+        CPU::INS_STA_ZP, 0x10,
+    ];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = kim1(Console::Keypad, b"", &code);
+
+    //when:
+    machine.press(Key::from_char('9').unwrap());
+    machine.execute(50);
+
+    //then:
+    assert_eq!(machine.bus.bus.ram[0x10], 0xFB);
+    assert!(machine.typing());
+    machine.execute(2 * 50_000);
+    assert!(!machine.typing());
+}
+
+#[test]
+fn display_shows_what_the_digits_were_lit_with() {
+    //given: 1 on the leftmost digit and F on the rightmost
+    let mut code = vec![
+        CPU::INS_LDA_IM, 0x7F,
+        CPU::INS_STA_ABS, 0x41, 0x17,
+        CPU::INS_LDA_IM, 0x1E,
+        CPU::INS_STA_ABS, 0x43, 0x17,
+    ];
+    for (digit, segments) in [(4, 0x06), (9, 0x71)] {
+        code.extend([
+            CPU::INS_LDA_IM, digit << 1,
+            CPU::INS_STA_ABS, 0x42, 0x17,
+            CPU::INS_LDA_IM, segments,
+            CPU::INS_STA_ABS, 0x40, 0x17,
+            CPU::INS_LDA_IM, 0x00,
+            CPU::INS_STA_ABS, 0x40, 0x17,
+        ]);
+    }
+    idle_at(&mut code, ORIGIN);
+    let mut machine = kim1(Console::Keypad, b"", &code);
+
+    //when:
+    machine.execute(20_100);
+
+    //then:
+    assert_eq!(display_text(&machine.display()), "1    F");
+    let art = display_art(&machine.display());
+    assert_eq!(art[1], format!("  |{}|_ ", " ".repeat(14)));
+}
+
+#[test]
+fn st_key_interrupts_through_nmi() {
+    //given: the NMI vector points at $0300
+    let mut code = vec![];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = kim1(Console::Keypad, b"", &code);
+    machine.bus.bus.ram[0x0300..0x0305].copy_from_slice(&[CPU::INS_LDY_IM, 0x01, CPU::INS_STY_ZP, 0x10, CPU::INS_RTI]);
+
+    //when:
+    machine.press(Key::Stop);
+    machine.execute(40);
+
+    //then:
+    assert_eq!(machine.bus.bus.ram[0x10], 0x01);
+    assert_eq!(Key::from_char('m'), Some(Key::Address));
+    assert_eq!(Key::from_char('x'), None);
+}
+
+/** The monitor from the 6530-002, e.g. 6530-002.bin from the KIM-1 ROM dumps */
+#[test]
+#[ignore = "needs 6530-002.bin in test_roms/ or KIM1_ROM_002"]
+fn monitor_boots_and_examines_memory_on_the_tty() {
+    let path = test_rom_path("KIM1_ROM_002", "6530-002.bin");
+
+    //given: the first location of the monitor opened with a space
+    let mut machine = Kim1::new(RAM_SIZE, Console::Tty, BufferedSerial::new(b"1C00 "));
+    machine.load_rom_002(&fs::read(path).unwrap()).unwrap();
+    machine.reset();
+
+    //when:
+    machine.execute(500_000);
+
+    //then: the prompt, and the address with SAVE's STA ACC
+    assert!(!machine.cpu.halted(), "halted at ${:04X}", machine.cpu.pc());
+    assert_eq!(machine.cpu.pc(), GETCH);
+    let output = String::from_utf8_lossy(&machine.terminal.output).into_owned();
+    assert!(output.contains("KIM"), "{:?}", output);
+    assert!(output.contains("1C00 85"), "{:?}", output);
+}
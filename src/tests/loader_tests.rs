@@ -0,0 +1,250 @@
+use crate::loader::*;
+use crate::m6502::*;
+
+#[test]
+fn can_load_a_raw_binary() {
+    let mut mem: Mem = Mem::new();
+
+    let image = load_raw(&mut mem, 0x8000, &[CPU::INS_LDA_IM, 0x42]).unwrap();
+
+    assert_eq!(mem[0x8000], CPU::INS_LDA_IM);
+    assert_eq!(mem[0x8001], 0x42);
+    assert_eq!(image.start, None);
+    assert!(matches!(
+        load_raw(&mut mem, 0xFFFF, &[0x00, 0x00]),
+        Err(LoadError::TooLarge { address: 0xFFFF, length: 2 })
+    ));
+}
+
+#[test]
+fn can_load_intel_hex_with_a_start_address() {
+    let mut mem: Mem = Mem::new();
+    let text = "\
+:03800000A942EAA8
+:0280030060001B
+:040000050000800077
+:00000001FF
+";
+
+    let image = load_ihex(&mut mem, text).unwrap();
+
+    assert_eq!(image.segments, vec![(0x8000, vec![0xA9, 0x42, 0xEA, 0x60, 0x00])]);
+    assert_eq!(image.start, Some(0x8000));
+    assert_eq!(mem[0x8001], 0x42);
+    assert_eq!(mem[0x8003], 0x60);
+    assert_eq!(mem[RESET_VECTOR], 0x00);
+    assert_eq!(mem[RESET_VECTOR + 1], 0x80);
+}
+
+#[test]
+fn intel_hex_errors_carry_line_numbers() {
+    let mut mem: Mem = Mem::new();
+
+    let bad_checksum = ":03800000A942EAA8\n:0280030060001C\n:00000001FF\n";
+    let error = load_ihex(&mut mem, bad_checksum).unwrap_err();
+    assert!(matches!(error, LoadError::Checksum { line: 2, expected: 0x1B, actual: 0x1C }));
+    assert_eq!(error.to_string(), "line 2: checksum is 1C, expected 1B");
+
+    let beyond_64k = ":020000040001F9\n:0100000042BD\n:00000001FF\n";
+    assert!(matches!(
+        load_ihex(&mut mem, beyond_64k),
+        Err(LoadError::OutOfRange { line: 2, address: 0x10000 })
+    ));
+
+    //nothing after the end of file record is looked at
+    let not_a_record = ":00000001FF\nhello\n";
+    assert!(load_ihex(&mut mem, not_a_record).is_ok());
+    assert!(matches!(
+        load_ihex(&mut mem, "hello\n"),
+        Err(LoadError::Syntax { line: 1, .. })
+    ));
+}
+
+#[test]
+fn can_load_motorola_s_records_with_a_start_address() {
+    let mut mem: Mem = Mem::new();
+    let text = "\
+S00600004844521B
+S1068000A942EAA4
+S1058003600017
+S5030002FA
+S90380007C
+";
+
+    let image = load_srec(&mut mem, text).unwrap();
+
+    assert_eq!(image.segments, vec![(0x8000, vec![0xA9, 0x42, 0xEA, 0x60, 0x00])]);
+    assert_eq!(image.start, Some(0x8000));
+    assert_eq!(mem[0x8002], 0xEA);
+    assert_eq!(mem[RESET_VECTOR + 1], 0x80);
+}
+
+#[test]
+fn s_record_errors_carry_line_numbers() {
+    let mut mem: Mem = Mem::new();
+
+    let bad_checksum = "S1068000A942EAA4\nS1058003600018\n";
+    assert!(matches!(
+        load_srec(&mut mem, bad_checksum),
+        Err(LoadError::Checksum { line: 2, expected: 0x17, actual: 0x18 })
+    ));
+
+    let beyond_64k = "S20801000001020304EC\n";
+    assert!(matches!(
+        load_srec(&mut mem, beyond_64k),
+        Err(LoadError::OutOfRange { line: 1, .. })
+    ));
+
+    assert!(matches!(
+        load_srec(&mut mem, "S1068000A942EAA4\nS4030000FC\n"),
+        Err(LoadError::Syntax { line: 2, .. })
+    ));
+}
+
+#[test]
+fn can_load_a_commodore_prg() {
+    let mut mem: Mem = Mem::new();
+
+    let image = load_prg(&mut mem, &[0x01, 0x08, 0x0B, 0x08, 0x0A, 0x00]).unwrap();
+
+    assert_eq!(image.segments, vec![(0x0801, vec![0x0B, 0x08, 0x0A, 0x00])]);
+    assert_eq!(mem[0x0801], 0x0B);
+    assert_eq!(mem[0x0804], 0x00);
+    assert!(matches!(load_prg(&mut mem, &[0x01]), Err(LoadError::Malformed { offset: 0, .. })));
+}
+
+/** Header for a 16-bit o65 object with no header options */
+fn o65_header(mode: Word, text: (Word, Word), data: (Word, Word), bss: (Word, Word)) -> Vec<Byte> {
+    let mut header = vec![0x01, 0x00, b'o', b'6', b'5', 0x00];
+    for word in [mode, text.0, text.1, data.0, data.1, bss.0, bss.1, 0x0000, 0x0000, 0x0000] {
+        header.extend_from_slice(&word.to_le_bytes());
+    }
+    //a header option (an assembler name) that must be skipped
+    header.extend_from_slice(&[5, 2, b'x', b'a', 0]);
+    header.push(0);
+    header
+}
+
+#[test]
+fn can_relocate_an_o65_object_and_return_its_exports() {
+    let mut mem: Mem = Mem::new();
+
+    //given: assembled for text at $1000 and data at $2000
+    let mut object = o65_header(0x0200, (0x1000, 11), (0x2000, 2), (0x2002, 4));
+    object.extend_from_slice(&[
+        0xAD, 0x09, 0x10, //LDA $1009
+        0xA9, 0x00, //LDA #<$2000
+        0xA2, 0x20, //LDX #>$2000
+        0x4C, 0x00, 0x10, //JMP $1000
+        0x60, //RTS
+    ]);
+    object.extend_from_slice(&[0x34, 0x12]);
+    object.extend_from_slice(&[0x00, 0x00]); //no undefined references
+    object.extend_from_slice(&[
+        2, 0x82, //word, text
+        3, 0x23, //low, data
+        2, 0x43, 0x00, //high, data, with the low byte that went with it
+        2, 0x82, //word, text
+        0,
+    ]);
+    object.push(0); //nothing to relocate in data
+    object.extend_from_slice(&[0x02, 0x00]);
+    object.extend_from_slice(b"start\0\x02\x00\x10");
+    object.extend_from_slice(b"table\0\x03\x00\x20");
+    mem[0x300D] = 0xFF;
+
+    //when:
+    let placement = o65::Placement {
+        text: 0x3000,
+        ..Default::default()
+    };
+    let loaded = load_o65(&mut mem, &object, &placement, &Default::default()).unwrap();
+
+    //then:
+    let text: Vec<Byte> = (0x3000..0x300B).map(|address| mem[address]).collect();
+    assert_eq!(text, vec![0xAD, 0x09, 0x30, 0xA9, 0x0B, 0xA2, 0x30, 0x4C, 0x00, 0x30, 0x60]);
+    assert_eq!(mem[0x300B], 0x34);
+    assert_eq!(mem[0x300C], 0x12);
+    assert_eq!(mem[0x300D], 0x00, "bss is cleared");
+    assert_eq!(loaded.data, (0x300B, 2));
+    assert_eq!(loaded.bss, (0x300D, 4));
+    assert_eq!(loaded.exports.get("start"), Some(&0x3000));
+    assert_eq!(loaded.exports.get("table"), Some(&0x300B));
+}
+
+#[test]
+fn o65_undefined_references_are_resolved_from_imports() {
+    let mut mem: Mem = Mem::new();
+
+    //given: JSR chrout
+    let mut object = o65_header(0x0000, (0x0400, 3), (0x0403, 0), (0x0403, 0));
+    object.extend_from_slice(&[CPU::INS_JSR, 0x00, 0x00]);
+    object.extend_from_slice(&[0x01, 0x00]);
+    object.extend_from_slice(b"chrout\0");
+    object.extend_from_slice(&[2, 0x80, 0x00, 0x00, 0]);
+    object.push(0);
+    object.extend_from_slice(&[0x00, 0x00]);
+    let placement = o65::Placement {
+        text: 0xC000,
+        ..Default::default()
+    };
+
+    //when:
+    let unresolved = load_o65(&mut mem, &object, &placement, &Default::default());
+    let imports = [("chrout".to_string(), 0xFFD2)].into_iter().collect();
+    let loaded = load_o65(&mut mem, &object, &placement, &imports).unwrap();
+
+    //then:
+    assert!(matches!(unresolved, Err(LoadError::Unresolved(name)) if name == "chrout"));
+    assert_eq!(loaded.image.segments, vec![(0xC000, vec![CPU::INS_JSR, 0xD2, 0xFF])]);
+    assert_eq!(mem[0xC002], 0xFF);
+}
+
+#[test]
+fn not_an_o65_object_is_rejected() {
+    let mut mem: Mem = Mem::new();
+    let placement = o65::Placement::default();
+
+    let error = load_o65(&mut mem, b"\x01\x00o64\x00", &placement, &Default::default()).unwrap_err();
+
+    assert_eq!(error.to_string(), "offset 0: not an o65 object");
+}
+
+#[test]
+fn the_format_comes_from_the_file_extension() {
+    assert_eq!(Format::from_path("rom.hex"), Format::IntelHex);
+    assert_eq!(Format::from_path("ROM.IHX"), Format::IntelHex);
+    assert_eq!(Format::from_path("rom.s19"), Format::SRecord);
+    assert_eq!(Format::from_path("rom.srec"), Format::SRecord);
+    assert_eq!(Format::from_path("game.prg"), Format::Prg);
+    assert_eq!(Format::from_path("rom.bin"), Format::Raw);
+    assert_eq!(Format::from_path("rom"), Format::Raw);
+}
+
+#[test]
+fn a_raw_binary_is_not_mistaken_for_text() {
+    //given: a raw image that happens to read as an S-record
+    let path = std::env::temp_dir().join(format!("loader_test_{}.bin", std::process::id()));
+    std::fs::write(&path, b"S0030000FC\n").unwrap();
+    let mut mem: Mem = Mem::new();
+
+    //when:
+    let image = load_file(&mut mem, &path, 0x8000);
+    std::fs::remove_file(&path).unwrap();
+
+    //then:
+    assert_eq!(image.unwrap().segments, vec![(0x8000, b"S0030000FC\n".to_vec())]);
+    assert_eq!(mem[0x8000], b'S');
+}
+
+#[test]
+fn an_explicit_format_wins_over_the_extension() {
+    let path = std::env::temp_dir().join(format!("loader_test_{}.bin", std::process::id() + 1));
+    std::fs::write(&path, ":0280000060EA34\n:00000001FF\n").unwrap();
+    let mut mem: Mem = Mem::new();
+
+    let image = load_file_as(&mut mem, &path, Format::IntelHex, 0x0000);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(image.unwrap().segments, vec![(0x8000, vec![0x60, 0xEA])]);
+}
@@ -0,0 +1,136 @@
+use crate::m6502::*;
+
+fn test_logical_op_immediate(opcode_to_test: Byte, a: Byte, operand: Byte, expected: Byte) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_a(a);
+    cpu.set_z(1);
+    cpu.set_n(0);
+    mem[0xFFFC] = opcode_to_test;
+    mem[0xFFFD] = operand;
+
+    //when:
+    let cpu_copy = cpu.clone();
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(cpu.a(), expected);
+    assert_eq!(cpu.z(), 0);
+    assert_eq!(cpu.n(), 1);
+    assert_eq!(cpu.c(), cpu_copy.c());
+    assert_eq!(cpu.v(), cpu_copy.v());
+}
+
+#[test]
+fn and_immediate_can_and_a_value_into_the_a_register() {
+    test_logical_op_immediate(CPU::INS_AND_IM, 0xCC, 0x84, 0x84);
+}
+
+#[test]
+fn ora_immediate_can_or_a_value_into_the_a_register() {
+    test_logical_op_immediate(CPU::INS_ORA_IM, 0x80, 0x04, 0x84);
+}
+
+#[test]
+fn eor_immediate_can_exclusive_or_a_value_into_the_a_register() {
+    test_logical_op_immediate(CPU::INS_EOR_IM, 0xCC, 0x48, 0x84);
+}
+
+#[test]
+fn and_absolute_x_takes_a_cycle_more_when_it_crosses_a_page_boundary() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_a(0x0F);
+    cpu.set_x(0x01);
+    mem[0xFFFC] = CPU::INS_AND_ABSX;
+    mem[0xFFFD] = 0xFF;
+    mem[0xFFFE] = 0x44;
+    mem[0x4500] = 0x37;
+
+    //when:
+    let cycles_used = cpu.execute(5, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 5);
+    assert_eq!(cpu.a(), 0x07);
+}
+
+#[test]
+fn eor_indirect_x_wraps_around_in_the_zero_page() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_a(0xFF);
+    cpu.set_x(0x04);
+    mem[0xFFFC] = CPU::INS_EOR_INDX;
+    mem[0xFFFD] = 0xFB;
+    mem[0x00FF] = 0x00;
+    mem[0x0000] = 0x80;
+    mem[0x8000] = 0xFF;
+
+    //when:
+    let cycles_used = cpu.execute(6, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 6);
+    assert_eq!(cpu.a(), 0x00);
+    assert_eq!(cpu.z(), 1);
+}
+
+#[test]
+fn bit_zero_page_copies_bits_7_and_6_and_tests_against_the_a_register() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_a(0x0F);
+    mem[0xFFFC] = CPU::INS_BIT_ZP;
+    mem[0xFFFD] = 0x42;
+    mem[0x0042] = 0xF0;
+
+    //when:
+    let cpu_copy = cpu.clone();
+    let cycles_used = cpu.execute(3, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 3);
+    assert_eq!(cpu.a(), cpu_copy.a());
+    assert_eq!(cpu.z(), 1);
+    assert_eq!(cpu.n(), 1);
+    assert_eq!(cpu.v(), 1);
+}
+
+#[test]
+fn bit_absolute_clears_n_and_v_from_the_operand() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_a(0x01);
+    cpu.set_n(1);
+    cpu.set_v(1);
+    mem[0xFFFC] = CPU::INS_BIT_ABS;
+    mem[0xFFFD] = 0x00;
+    mem[0xFFFE] = 0x80;
+    mem[0x8000] = 0x01;
+
+    //when:
+    let cycles_used = cpu.execute(4, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 4);
+    assert_eq!(cpu.z(), 0);
+    assert_eq!(cpu.n(), 0);
+    assert_eq!(cpu.v(), 0);
+}
@@ -1,3 +1,66 @@
 mod load_register_tests; 
 mod store_register_tests; 
 mod jumps_and_calls_tests; 
+mod stack_operations_tests; 
+mod logical_ops_tests; 
+mod transfer_register_tests; 
+mod increment_decrement_tests; 
+mod branch_tests; 
+mod status_flag_change_tests; 
+mod add_with_carry_tests; 
+mod compare_register_tests; 
+mod shifts_tests; 
+mod system_functions_tests; 
+mod gdb_tests; 
+mod disassembler_tests; 
+mod trace_tests; 
+mod trace_diff_tests; 
+mod functional_test_tests; 
+mod single_step_tests; 
+mod loader_tests; 
+mod sim65_tests; 
+mod host_calls_tests; 
+mod snapshot_tests; 
+mod interrupts_tests; 
+mod rewind_tests; 
+mod profiler_tests; 
+mod coverage_tests; 
+mod symbols_tests; 
+mod heatmap_tests; 
+mod via_tests; 
+mod acia_tests; 
+mod riot_tests; 
+mod pia_tests; 
+mod devices_tests; 
+mod apple1_tests; 
+mod hd44780_tests; 
+mod ben_eater_tests; 
+mod rriot_tests; 
+mod kim1_tests; 
+mod c64_tests; 
+mod nes_tests; 
+mod ines_tests; 
+
+use crate::m6502::*;
+use std::path::PathBuf;
+
+/** Append a jump to itself to `code`, which will be run from `origin`, so the CPU idles once it's done */
+fn idle_at(code: &mut Vec<Byte>, origin: Word) {
+    let [lo, hi] = (origin + code.len() as Word).to_le_bytes();
+    code.extend([CPU::INS_JMP_ABS, lo, hi]);
+}
+
+/**
+ * The test binaries and ROMs are not part of the crate, so the tests using them are ignored.
+ * Vendor them into test_roms/ or point the environment variables at them, then run
+ * `cargo test -- --ignored`.
+ * @return the path, panics if there's nothing there
+ * */
+fn test_rom_path(variable: &str, file_name: &str) -> PathBuf {
+    let path = match std::env::var_os(variable) {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_roms").join(file_name),
+    };
+    assert!(path.exists(), "{} not found, vendor it into test_roms/ or set {}", path.display(), variable);
+    path
+}
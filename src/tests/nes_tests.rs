@@ -0,0 +1,288 @@
+use crate::devices::ppu;
+use crate::devices::Device;
+use crate::m2a03::*;
+use crate::m6502::*;
+use crate::machines::nes::*;
+use super::idle_at;
+
+/** where RESET goes */
+const ORIGIN: Word = 0x8000;
+
+/** RAM from $4020 up, with an IRQ output the test can pull */
+struct Board {
+    memory: Vec<Byte>,
+    irq: bool,
+}
+
+impl Device for Board {
+    fn read(&mut self, offset: Word) -> Byte {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: Word, value: Byte) {
+        self.memory[offset as usize] = value;
+    }
+
+    fn peek(&self, offset: Word) -> Byte {
+        self.memory[offset as usize]
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+}
+
+/** A NES with `code` at ORIGIN, and NMI and IRQ handlers idling at $9000 and $9100 */
+fn nes(code: &[Byte]) -> Nes<Board> {
+    let mut memory = vec![0; 0x10000];
+    let origin = ORIGIN as usize;
+    memory[origin..origin + code.len()].copy_from_slice(code);
+    memory[0x9000..0x9003].copy_from_slice(&[CPU::INS_JMP_ABS, 0x00, 0x90]);
+    memory[0x9100..0x9103].copy_from_slice(&[CPU::INS_JMP_ABS, 0x00, 0x91]);
+    let [lo, hi] = ORIGIN.to_le_bytes();
+    memory[0xFFFA..].copy_from_slice(&[0x00, 0x90, lo, hi, 0x00, 0x91]);
+    let mut machine = Nes::new(Board { memory, irq: false });
+    machine.reset();
+    machine
+}
+
+#[test]
+fn ram_and_ppu_registers_repeat() {
+    //given:
+    let mut machine = nes(&[]);
+
+    //when:
+    machine.bus.write(0x0801, 0x12);
+    machine.bus.write(0x1FFF, 0x34);
+    machine.bus.write(0x3FFB, 0x10);
+    machine.bus.write(0x200C, 0x56);
+
+    //then:
+    assert_eq!(machine.cpu.pc(), ORIGIN);
+    assert_eq!(machine.cpu.sp(), 0xFD);
+    assert_eq!(machine.bus.peek(0x0001), 0x12);
+    assert_eq!(machine.bus.ram[0x07FF], 0x34);
+    assert_eq!(machine.bus.ppu.oam()[0x10], 0x56);
+    assert_eq!(machine.bus.peek(0x4018), 0x40);
+    assert_eq!(machine.bus.peek(0x9000), CPU::INS_JMP_ABS);
+}
+
+#[test]
+fn vblank_raises_nmi() {
+    //given:
+    let mut code = vec![
+        CPU::INS_LDA_IM, ppu::CTRL_NMI_ENABLE,
+        CPU::INS_STA_ABS, 0x00, 0x20,
+    ];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = nes(&code);
+
+    //when: not quite to line 241
+    machine.execute(27_300);
+
+    //then:
+    assert_eq!(machine.cpu.pc() & 0xFF00, ORIGIN);
+    assert_eq!(machine.bus.ppu.scanline(), 240);
+
+    //when:
+    machine.execute(200);
+
+    //then:
+    assert_eq!(machine.cpu.pc(), 0x9000);
+    assert_eq!(machine.bus.peek(0x2002) & ppu::STATUS_VBLANK, ppu::STATUS_VBLANK);
+    machine.bus.read(0x2002);
+    assert_eq!(machine.bus.peek(0x2002) & ppu::STATUS_VBLANK, 0);
+}
+
+#[test]
+fn oam_dma_copies_a_page_and_halts_the_cpu() {
+    //given: an even number of cycles before the write
+    let mut code = vec![
+        CPU::INS_LDA_IM, 0x02,
+        CPU::INS_STA_ABS, 0x14, 0x40,
+    ];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = nes(&code);
+    for offset in 0..0x100 {
+        machine.bus.ram[0x200 + offset] = offset as Byte ^ 0xFF;
+    }
+
+    //when:
+    machine.step();
+    let cycles = machine.step();
+
+    //then:
+    assert_eq!(cycles, 4 + 513);
+    assert_eq!(machine.cycles(), 2 + 4 + 513);
+    assert_eq!(machine.bus.ppu.oam()[0], 0xFF);
+    assert_eq!(machine.bus.ppu.oam()[0xFF], 0x00);
+}
+
+#[test]
+fn oam_dma_on_an_odd_cycle_takes_one_more() {
+    //given:
+    let mut code = vec![
+        CPU::INS_LDA_ZP, 0x00,
+        CPU::INS_LDA_IM, 0x03,
+        CPU::INS_STA_ABS, 0x14, 0x40,
+    ];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = nes(&code);
+
+    //when:
+    machine.step();
+    machine.step();
+    let cycles = machine.step();
+
+    //then:
+    assert_eq!(cycles, 4 + 514);
+}
+
+#[test]
+fn frame_counter_flags_its_irq_every_frame() {
+    //given:
+    let mut machine = nes(&[]);
+    machine.bus.write(FRAME_COUNTER, 0x00);
+
+    //when:
+    machine.bus.tick(29_827);
+
+    //then:
+    assert!(!machine.bus.irq());
+
+    //when:
+    machine.bus.tick(1);
+
+    //then: reading the status acknowledges it
+    assert!(machine.bus.irq());
+    assert_eq!(machine.bus.read(APU_STATUS), STATUS_FRAME_IRQ);
+    assert!(!machine.bus.irq());
+
+    //when: set again for the other two cycles, then inhibited
+    machine.bus.tick(1);
+    assert!(machine.bus.irq());
+    machine.bus.write(FRAME_COUNTER, FRAME_IRQ_INHIBIT);
+
+    //then:
+    assert!(!machine.bus.irq());
+    machine.bus.tick(40_000);
+    assert!(!machine.bus.irq());
+
+    //when: the 5-step sequence never interrupts
+    machine.bus.write(FRAME_COUNTER, FRAME_FIVE_STEP);
+    machine.bus.tick(40_000);
+
+    //then:
+    assert!(!machine.bus.irq());
+}
+
+#[test]
+fn frame_irq_reaches_the_cpu() {
+    //given: CLI isn't there, so the IRQ handler is found through the I flag
+    let mut code = vec![];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = nes(&code);
+    machine.cpu.set_i(0);
+
+    //when:
+    machine.execute(29_840);
+
+    //then:
+    assert_eq!(machine.cpu.pc(), 0x9100);
+}
+
+#[test]
+fn length_counters_count_half_frames() {
+    //given: pulse 1 on, with the shortest length and no halt
+    let mut machine = nes(&[]);
+    machine.bus.write(APU_STATUS, 0x01);
+    machine.bus.write(0x4003, 0x18);
+    machine.bus.write(0x4007, 0x18);
+
+    //then: pulse 2 is off so its length isn't loaded
+    assert_eq!(machine.bus.peek(APU_STATUS), 0x01);
+
+    //when: two half frames
+    machine.bus.write(FRAME_COUNTER, FRAME_IRQ_INHIBIT);
+    machine.bus.tick(29_829);
+
+    //then:
+    assert_eq!(machine.bus.peek(APU_STATUS), 0x00);
+}
+
+#[test]
+fn dmc_fetches_steal_cycles_and_flag_the_end_of_the_sample() {
+    //given: 17 bytes from $C040 at the fastest rate, then an IRQ
+    let mut machine = nes(&[]);
+    machine.bus.write(FRAME_COUNTER, FRAME_IRQ_INHIBIT);
+    machine.bus.write(DMC_FREQUENCY, DMC_IRQ_ENABLE | 0x0F);
+    machine.bus.write(DMC_ADDRESS, 0x01);
+    machine.bus.write(DMC_LENGTH, 0x01);
+
+    //when: the first byte is fetched straight away
+    machine.bus.write(APU_STATUS, STATUS_DMC);
+    let stolen = machine.bus.tick(1);
+
+    //then:
+    assert_eq!(stolen, DMC_STALL_CYCLES);
+    assert_eq!(machine.bus.peek(APU_STATUS), STATUS_DMC);
+
+    //when: a byte goes every 8 bits of 54 cycles, once the 428 cycle bit the DMC started with is out
+    let stolen = machine.bus.tick(10_000);
+
+    //then:
+    assert_eq!(stolen, 16 * DMC_STALL_CYCLES);
+    assert_eq!(machine.bus.peek(APU_STATUS), STATUS_DMC_IRQ);
+    assert!(machine.bus.irq());
+
+    //when: writing the status acknowledges it
+    machine.bus.write(APU_STATUS, 0x00);
+
+    //then:
+    assert!(!machine.bus.irq());
+}
+
+#[test]
+fn cartridge_irq_reaches_the_cpu() {
+    //given:
+    let mut code = vec![];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = nes(&code);
+    machine.cpu.set_i(0);
+
+    //when:
+    machine.bus.cartridge.irq = true;
+    machine.step();
+    machine.step();
+
+    //then:
+    assert_eq!(machine.cpu.pc(), 0x9100);
+}
+
+#[test]
+fn decimal_flag_is_ignored_by_arithmetic() {
+    //given: 9 + 1 with D set
+    let mut code = vec![
+        CPU::INS_SED,
+        CPU::INS_CLC,
+        CPU::INS_LDA_IM, 0x09,
+        CPU::INS_ADC_IM, 0x01,
+        CPU::INS_PHP,
+    ];
+    idle_at(&mut code, ORIGIN);
+    let mut machine = nes(&code);
+
+    //when:
+    machine.execute(13);
+
+    //then: binary, and D still there to push
+    assert_eq!(machine.cpu.a(), 0x0A);
+    assert_eq!(machine.cpu.d(), 1);
+    assert_eq!(machine.bus.ram[0x01FD] & 0x08, 0x08);
+
+    //when: RESET doesn't give the 2A03 its decimal mode back
+    machine.reset();
+
+    //then:
+    assert!(!machine.cpu.decimal_enabled());
+}
@@ -0,0 +1,128 @@
+use crate::devices::pia::*;
+use crate::devices::DeviceBus;
+use crate::m6502::*;
+
+#[test]
+fn ddr_and_output_register_share_an_address() {
+    //given:
+    let mut pia = Pia::new();
+    pia.set_port_a_input(0x05);
+
+    //when:
+    pia.write(PORT_A, 0xF0);
+    pia.write(CRA, CR_OUTPUT_REGISTER);
+    pia.write(PORT_A, 0xAB);
+
+    //then:
+    assert_eq!(pia.read(PORT_A), 0xA5);
+    assert_eq!(pia.port_a(), 0xA5);
+    pia.write(CRA, 0);
+    assert_eq!(pia.read(PORT_A), 0xF0);
+}
+
+#[test]
+fn ca1_edge_interrupts_until_port_a_is_read() {
+    //given: falling edges, interrupt enabled
+    let mut pia = Pia::new();
+    pia.write(CRA, CR_OUTPUT_REGISTER | CR_C1_IRQ_ENABLE);
+
+    //when:
+    pia.set_ca1(false);
+
+    //then:
+    assert!(pia.irq_a());
+    assert!(pia.irq());
+    assert_eq!(pia.read(CRA) & CR_C1_IRQ_FLAG, CR_C1_IRQ_FLAG);
+    pia.read(PORT_A);
+    assert!(!pia.irq());
+    pia.set_ca1(true);
+    assert_eq!(pia.read(CRA) & CR_C1_IRQ_FLAG, 0);
+}
+
+#[test]
+fn cb2_input_interrupts_on_the_selected_edge() {
+    let mut pia = Pia::new();
+    pia.write(CRB, CR_OUTPUT_REGISTER | 0x18);
+
+    pia.set_cb2(false);
+    assert!(!pia.irq_b());
+    pia.set_cb2(true);
+    assert!(pia.irq_b());
+    assert!(!pia.irq_a());
+    assert_eq!(pia.read(CRB), CR_C2_IRQ_FLAG | CR_OUTPUT_REGISTER | 0x18);
+
+    pia.read(PORT_B);
+    assert!(!pia.irq_b());
+}
+
+#[test]
+fn control_flags_are_read_only() {
+    let mut pia = Pia::new();
+    pia.set_ca1(false);
+
+    pia.write(CRA, 0xFF);
+    assert_eq!(pia.read(CRA), CR_C1_IRQ_FLAG | 0x3F);
+    pia.write(CRA, 0x00);
+    assert_eq!(pia.read(CRA), CR_C1_IRQ_FLAG);
+}
+
+#[test]
+fn c2_outputs_handshake_pulse_and_follow_the_control_register() {
+    let mut pia = Pia::new();
+
+    //CA2 goes low on a read of port A and back high on the CA1 edge
+    pia.write(CRA, CR_OUTPUT_REGISTER | 0x20);
+    assert!(pia.ca2());
+    pia.read(PORT_A);
+    assert!(!pia.ca2());
+    pia.set_ca1(false);
+    assert!(pia.ca2());
+
+    //CB2 goes low for one cycle after a write of port B
+    pia.write(CRB, CR_OUTPUT_REGISTER | 0x28);
+    pia.write(PORT_B, 0x12);
+    assert!(!pia.cb2());
+    pia.tick(1);
+    assert!(pia.cb2());
+
+    //CB2 set by hand
+    pia.write(CRB, 0x30);
+    assert!(!pia.cb2());
+    pia.write(CRB, 0x38);
+    assert!(pia.cb2());
+}
+
+#[test]
+fn pia_on_the_bus_is_read_by_the_cpu() {
+    //given: a key waiting on port A, strobed on CA1, as on the Apple I
+    let mut bus = DeviceBus::new(Mem::new());
+    let pia = bus.attach(0xD010, 4, Pia::new());
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut bus.bus);
+    let code = [
+        CPU::INS_LDA_IM, CR_OUTPUT_REGISTER,
+        CPU::INS_STA_ABS, 0x11, 0xD0,
+        CPU::INS_LDA_ABS, 0x11, 0xD0,
+        CPU::INS_STA_ZP, 0x10,
+        CPU::INS_LDA_ABS, 0x10, 0xD0,
+        CPU::INS_STA_ZP, 0x11,
+        CPU::INS_LDA_ABS, 0x11, 0xD0,
+        CPU::INS_STA_ZP, 0x12,
+        CPU::INS_JMP_ABS, 0x14, 0x02,
+    ];
+    for (offset, byte) in code.iter().enumerate() {
+        bus.bus[0x0200 + offset as Word] = *byte;
+    }
+    bus.device_mut(pia).set_port_a_input(0xC1);
+    bus.device_mut(pia).set_ca1(false);
+
+    //when:
+    bus.execute(40, &mut cpu);
+
+    //then:
+    assert_eq!(bus.bus[0x0010], CR_C1_IRQ_FLAG | CR_OUTPUT_REGISTER);
+    assert_eq!(bus.bus[0x0011], 0xC1);
+    assert_eq!(bus.bus[0x0012], CR_OUTPUT_REGISTER);
+    assert_eq!(bus.bus[0xD010], 0x00);
+    assert_eq!(cpu.pc(), 0x0214);
+}
@@ -0,0 +1,193 @@
+use crate::m6502::*;
+use crate::profiler::*;
+
+const TRAP: Word = 0x0209;
+
+/**
+ * $0200: JSR $0300 / JSR $0300 / JSR $0400 / JMP $0209
+ * $0300: LDA #$01 / JSR $0400 / RTS
+ * $0400: LDX #$02 / RTS
+ * */
+fn profiled() -> Profiler {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut mem);
+
+    let main = [
+        CPU::INS_JSR, 0x00, 0x03,
+        CPU::INS_JSR, 0x00, 0x03,
+        CPU::INS_JSR, 0x00, 0x04,
+        CPU::INS_JMP_ABS, 0x09, 0x02,
+    ];
+    let sub = [CPU::INS_LDA_IM, 0x01, CPU::INS_JSR, 0x00, 0x04, CPU::INS_RTS];
+    let leaf = [CPU::INS_LDX_IM, 0x02, CPU::INS_RTS];
+    for (address, code) in [(0x0200, &main[..]), (0x0300, &sub[..]), (0x0400, &leaf[..])] {
+        for (offset, byte) in code.iter().enumerate() {
+            mem[address + offset as Word] = *byte;
+        }
+    }
+
+    let mut profiler = Profiler::new();
+    while cpu.pc() != TRAP {
+        profiler.step(&mut cpu, &mut mem);
+    }
+    profiler
+}
+
+#[test]
+fn cycles_are_attributed_to_subroutines_exclusively_and_inclusively() {
+    //when:
+    let profiler = profiled();
+
+    //then:
+    assert_eq!(profiler.cycles(), 70);
+    assert_eq!(
+        profiler.subroutines(),
+        vec![
+            Subroutine {
+                address: 0x0200,
+                calls: 1,
+                exclusive_instructions: 3,
+                exclusive_cycles: 18,
+                inclusive_instructions: 15,
+                inclusive_cycles: 70,
+            },
+            Subroutine {
+                address: 0x0300,
+                calls: 2,
+                exclusive_instructions: 6,
+                exclusive_cycles: 28,
+                inclusive_instructions: 10,
+                inclusive_cycles: 44,
+            },
+            Subroutine {
+                address: 0x0400,
+                calls: 3,
+                exclusive_instructions: 6,
+                exclusive_cycles: 24,
+                inclusive_instructions: 6,
+                inclusive_cycles: 24,
+            },
+        ]
+    );
+}
+
+#[test]
+fn every_address_counts_its_hits_and_cycles() {
+    let profiler = profiled();
+
+    assert_eq!(profiler.address(0x0400), AddressStats { hits: 3, cycles: 6 });
+    assert_eq!(profiler.address(0x0402), AddressStats { hits: 3, cycles: 18 });
+    assert_eq!(profiler.address(0x0203), AddressStats { hits: 1, cycles: 6 });
+    assert_eq!(profiler.address(0x0401), AddressStats::default());
+    assert_eq!(profiler.hot_addresses()[0], (0x0402, AddressStats { hits: 3, cycles: 18 }));
+}
+
+#[test]
+fn report_lists_subroutines_by_inclusive_cycles() {
+    let profiler = profiled();
+    let mut out = Vec::new();
+
+    profiler.write_report(&mut out, 2, &hex_name).unwrap();
+
+    let report = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], "15 instructions, 70 cycles");
+    assert_eq!(lines[3], "          70 100.00%           18  25.71%        1  $0200");
+    assert_eq!(lines[4], "          44  62.86%           28  40.00%        2  $0300");
+    assert_eq!(lines[5], "          24  34.29%           24  34.29%        3  $0400");
+    assert_eq!(lines[8], "          18  25.71%          3  $0402");
+    assert_eq!(lines.len(), 10);
+}
+
+#[test]
+fn callgrind_output_has_costs_and_call_edges() {
+    let profiler = profiled();
+    let mut out = Vec::new();
+
+    profiler.write_callgrind(&mut out, &hex_name).unwrap();
+
+    let callgrind = String::from_utf8(out).unwrap();
+    assert!(callgrind.starts_with("# callgrind format\nversion: 1\n"));
+    assert!(callgrind.contains("events: Instructions Cycles\nsummary: 15 70\n"));
+    assert!(callgrind.contains(
+        "fn=$0200\n0x0200 1 6\n0x0203 1 6\n0x0206 1 6\n\
+         cfn=$0300\ncalls=1 0x0300\n0x0200 5 22\n\
+         cfn=$0300\ncalls=1 0x0300\n0x0203 5 22\n\
+         cfn=$0400\ncalls=1 0x0400\n0x0206 2 8\n"
+    ));
+    assert!(callgrind.contains("fn=$0300\n0x0300 2 4\n0x0302 2 12\n0x0305 2 12\ncfn=$0400\ncalls=2 0x0400\n0x0302 4 16\n"));
+}
+
+/**
+ * $0200: LDX #$03 / JSR $0300 / JMP $0205
+ * $0300: DEX / BEQ $0306 / JSR $0300 / RTS
+ * */
+#[test]
+fn recursive_calls_count_their_inclusive_cycles_once() {
+    //given:
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut mem);
+    let main = [CPU::INS_LDX_IM, 0x03, CPU::INS_JSR, 0x00, 0x03, CPU::INS_JMP_ABS, 0x05, 0x02];
+    let countdown = [CPU::INS_DEX, CPU::INS_BEQ, 0x03, CPU::INS_JSR, 0x00, 0x03, CPU::INS_RTS];
+    for (address, code) in [(0x0200, &main[..]), (0x0300, &countdown[..])] {
+        for (offset, byte) in code.iter().enumerate() {
+            mem[address + offset as Word] = *byte;
+        }
+    }
+
+    //when:
+    let mut profiler = Profiler::new();
+    while cpu.pc() != 0x0205 {
+        profiler.step(&mut cpu, &mut mem);
+    }
+
+    //then:
+    assert_eq!(profiler.cycles(), 51);
+    assert_eq!(
+        profiler.subroutines()[1],
+        Subroutine {
+            address: 0x0300,
+            calls: 3,
+            exclusive_instructions: 11,
+            exclusive_cycles: 43,
+            inclusive_instructions: 11,
+            inclusive_cycles: 43,
+        }
+    );
+}
+
+#[test]
+fn taking_an_interrupt_costs_the_handler() {
+    //given:
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut mem);
+    mem[0xFFFA] = 0x00;
+    mem[0xFFFB] = 0x06;
+    mem[0x0600] = CPU::INS_LDX_IM;
+    mem[0x0601] = 0x02;
+    mem[0x0602] = CPU::INS_RTI;
+    cpu.set_nmi(true);
+
+    //when:
+    let mut profiler = Profiler::new();
+    profiler.execute(7 + 2 + 6, &mut cpu, &mut mem);
+
+    //then:
+    assert_eq!(profiler.cycles(), 15);
+    assert_eq!(profiler.address(0x0600), AddressStats { hits: 1, cycles: 9 });
+    //after $0200, where profiling started, which includes it
+    assert_eq!(
+        profiler.subroutines()[1],
+        Subroutine {
+            address: 0x0600,
+            calls: 1,
+            exclusive_instructions: 2,
+            exclusive_cycles: 15,
+            inclusive_instructions: 2,
+            inclusive_cycles: 15,
+        }
+    );
+}
@@ -0,0 +1,183 @@
+use crate::devices::via::{self, Via};
+use crate::devices::DeviceBus;
+use crate::m6502::*;
+use crate::rewind::*;
+use crate::snapshot::Snapshot;
+
+const INPUT: Word = 0x0300;
+
+/**
+ * loop: LDA INPUT / STA $0400 / LDX $0400 / JMP loop
+ * irq:  LDY #$99 / STY $0401 / RTI
+ * */
+fn program() -> (CPU, Mem) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut mem);
+
+    let code = [
+        CPU::INS_LDA_ABS, 0x00, 0x03,
+        CPU::INS_STA_ABS, 0x00, 0x04,
+        CPU::INS_LDX_ABS, 0x00, 0x04,
+        CPU::INS_JMP_ABS, 0x00, 0x02,
+    ];
+    for (offset, byte) in code.iter().enumerate() {
+        mem[0x0200 + offset as Word] = *byte;
+    }
+    let irq = [CPU::INS_LDY_IM, 0x99, CPU::INS_STY_ABS, 0x01, 0x04, CPU::INS_RTI];
+    for (offset, byte) in irq.iter().enumerate() {
+        mem[0x0500 + offset as Word] = *byte;
+    }
+    mem[0xFFFE] = 0x00;
+    mem[0xFFFF] = 0x05;
+
+    (cpu, mem)
+}
+
+/** Run 100 instructions with a few inputs along the way, snapshotting after each one */
+fn run(rewind: &mut Rewind, cpu: &mut CPU, mem: &mut Mem) -> Vec<Snapshot> {
+    let mut history = vec![Snapshot::capture(cpu, 0, mem)];
+    for instruction in 0..100 {
+        match instruction {
+            10 => rewind.input(InputEvent::Write { address: INPUT, value: 0x11 }, cpu, mem),
+            25 => rewind.input(InputEvent::Irq(true), cpu, mem),
+            26 => rewind.input(InputEvent::Irq(false), cpu, mem),
+            60 => rewind.input(InputEvent::Write { address: INPUT, value: 0x22 }, cpu, mem),
+            _ => {}
+        }
+        rewind.step(cpu, mem);
+        history.push(Snapshot::capture(cpu, rewind.cycles(), mem));
+    }
+
+    history
+}
+
+#[test]
+fn execution_is_deterministic() {
+    //given:
+    let (mut first_cpu, mut first_mem) = program();
+    let (mut second_cpu, mut second_mem) = program();
+    let mut first = Rewind::new(16, 8, &first_cpu, &first_mem);
+    let mut second = Rewind::new(16, 8, &second_cpu, &second_mem);
+
+    //when:
+    let first_history = run(&mut first, &mut first_cpu, &mut first_mem);
+    let second_history = run(&mut second, &mut second_cpu, &mut second_mem);
+
+    //then:
+    assert_eq!(first_history, second_history);
+}
+
+/** A recorded 100 instruction run and the state after each instruction */
+fn recorded(interval: u64, capacity: usize) -> (Rewind, CPU, Mem, Vec<Snapshot>) {
+    let (mut cpu, mut mem) = program();
+    let mut rewind = Rewind::new(interval, capacity, &cpu, &mem);
+    let history = run(&mut rewind, &mut cpu, &mut mem);
+
+    (rewind, cpu, mem, history)
+}
+
+#[test]
+fn step_back_reaches_the_same_state_as_the_original_run() {
+    for count in [1, 5, 16, 40, 75, 99] {
+        let (mut rewind, mut cpu, mut mem, history) = recorded(16, 8);
+
+        let reached = rewind.step_back(count, &mut cpu, &mut mem).unwrap();
+
+        assert_eq!(reached, 100 - count);
+        assert_eq!(rewind.instructions(), reached);
+        assert_eq!(
+            Snapshot::capture(&cpu, rewind.cycles(), &mem),
+            history[reached as usize],
+            "stepping back {} instructions",
+            count
+        );
+    }
+}
+
+#[test]
+fn inputs_before_the_target_are_replayed() {
+    let (mut rewind, mut cpu, mut mem, history) = recorded(16, 8);
+
+    //the write at instruction 10 has to come back from the log, not from a snapshot
+    rewind.step_back(80, &mut cpu, &mut mem).unwrap();
+    rewind.rewind_to(15, &mut cpu, &mut mem).unwrap();
+    for _ in 15..20 {
+        rewind.step(&mut cpu, &mut mem);
+    }
+
+    assert_eq!(Snapshot::capture(&cpu, rewind.cycles(), &mem), history[20]);
+    assert_eq!(mem[0x0400], 0x11);
+}
+
+#[test]
+fn step_back_past_the_oldest_snapshot_fails() {
+    let (mut rewind, mut cpu, mut mem, _) = recorded(16, 2);
+
+    let error = rewind.step_back(50, &mut cpu, &mut mem).unwrap_err();
+
+    assert_eq!(rewind.oldest(), 80);
+    assert!(matches!(error, RewindError::BeyondHistory { target: 50, oldest: 80 }));
+    assert_eq!(rewind.instructions(), 100);
+}
+
+const VIA_BASE: Word = 0x6000;
+
+/**
+ * A VIA whose free-running T1 interrupts every 0x42 cycles
+ * loop: CLI / INX / JMP loop+1
+ * irq:  INC $10 / LDA T1C_L / RTI
+ * */
+fn via_machine() -> (CPU, DeviceBus<Mem>) {
+    let mut bus = DeviceBus::new(Mem::new());
+    bus.attach(VIA_BASE, 0x10, Via::new());
+    let code = [CPU::INS_CLI, CPU::INS_INX, CPU::INS_JMP_ABS, 0x01, 0x02];
+    for (offset, byte) in code.iter().enumerate() {
+        bus.bus[0x0200 + offset as Word] = *byte;
+    }
+    let irq = [CPU::INS_INC_ZP, 0x10, CPU::INS_LDA_ABS, via::T1C_L, 0x60, CPU::INS_RTI];
+    for (offset, byte) in irq.iter().enumerate() {
+        bus.bus[0x0500 + offset as Word] = *byte;
+    }
+    bus.bus[0xFFFE] = 0x00;
+    bus.bus[0xFFFF] = 0x05;
+
+    bus.write(VIA_BASE + via::ACR as Word, 0x40);
+    bus.write(VIA_BASE + via::T1C_L as Word, 0x40);
+    bus.write(VIA_BASE + via::T1C_H as Word, 0x00);
+    bus.write(VIA_BASE + via::IER as Word, 0x80 | via::IRQ_T1);
+
+    let mut cpu = CPU::new();
+    cpu.set_pc(0x0200);
+    cpu.set_sp(0xFF);
+    (cpu, bus)
+}
+
+#[test]
+fn a_run_with_a_via_timer_replays_the_same_after_stepping_back() {
+    //given:
+    let (mut cpu, mut bus) = via_machine();
+    let mut rewind = Rewind::new(16, 8, &cpu, &bus);
+    let mut history = vec![Snapshot::capture(&cpu, 0, &bus)];
+    for _ in 0..100 {
+        rewind.step(&mut cpu, &mut bus);
+        history.push(Snapshot::capture(&cpu, rewind.cycles(), &bus));
+    }
+    let interrupts = bus.bus[0x0010];
+
+    for count in [1, 7, 16, 45, 99] {
+        //when:
+        rewind.step_back(count, &mut cpu, &mut bus).unwrap();
+        let reached = rewind.instructions();
+        let stepped_back = Snapshot::capture(&cpu, rewind.cycles(), &bus);
+        while rewind.instructions() < 100 {
+            rewind.step(&mut cpu, &mut bus);
+        }
+
+        //then:
+        assert_eq!(stepped_back, history[reached as usize], "stepping back {} instructions", count);
+        assert_eq!(Snapshot::capture(&cpu, rewind.cycles(), &bus), history[100], "replaying {} instructions", count);
+    }
+    //the timer really did interrupt along the way
+    assert!(interrupts >= 2);
+}
@@ -0,0 +1,139 @@
+use crate::devices::riot::*;
+use crate::devices::DeviceBus;
+use crate::m6502::*;
+
+#[test]
+fn ram_wraps_every_128_bytes() {
+    //given:
+    let mut riot = Riot::new();
+
+    //when:
+    riot.write_ram(0x05, 0x42);
+    riot.write_ram(0xFF, 0x99);
+
+    //then:
+    assert_eq!(riot.read_ram(0x85), 0x42);
+    assert_eq!(riot.read_ram(0x7F), 0x99);
+    assert_eq!(riot.peek(ORA), 0xFF);
+}
+
+#[test]
+fn ports_mix_outputs_and_inputs_by_ddr() {
+    //given:
+    let mut riot = Riot::new();
+    riot.set_port_a_input(0x05);
+    riot.set_port_b_input(0x3C);
+
+    //when:
+    riot.write(DDRA, 0xF0);
+    riot.write(ORA, 0xAB);
+    riot.write(DDRB, 0xFF);
+    riot.write(ORB, 0x81);
+
+    //then:
+    assert_eq!(riot.read(ORA), 0xA5);
+    assert_eq!(riot.read(ORB), 0x81);
+    assert_eq!(riot.read(DDRA), 0xF0);
+}
+
+#[test]
+fn timer_counts_at_the_prescaler_rate_then_every_cycle() {
+    let mut riot = Riot::new();
+    riot.write(TIMER_8, 10);
+
+    riot.tick(8);
+    assert_eq!(riot.read(TIMER), 9);
+    riot.tick(72);
+    assert_eq!(riot.read(TIMER), 0);
+    assert_eq!(riot.peek(INTERRUPT_FLAGS) & FLAG_TIMER, 0);
+
+    riot.tick(8);
+    assert_eq!(riot.peek(INTERRUPT_FLAGS) & FLAG_TIMER, FLAG_TIMER);
+    assert_eq!(riot.peek(TIMER), 0xFF);
+    riot.tick(1);
+    assert_eq!(riot.peek(TIMER), 0xFE);
+    assert!(!riot.irq());
+}
+
+#[test]
+fn timer_interrupt_is_acknowledged_by_reading_the_timer() {
+    let mut riot = Riot::new();
+    riot.write(TIMER_1 | TIMER_IRQ_ENABLE, 5);
+
+    riot.tick(5);
+    assert!(!riot.irq());
+    riot.tick(1);
+    assert!(riot.irq());
+
+    riot.read(TIMER | TIMER_IRQ_ENABLE);
+    assert!(!riot.irq());
+    riot.tick(256);
+    assert!(riot.irq());
+}
+
+#[test]
+fn pa7_edge_is_flagged_and_cleared_by_reading_the_flags() {
+    //given: falling edges, interrupt enabled
+    let mut riot = Riot::new();
+    riot.write(EDGE_CONTROL | EDGE_IRQ_ENABLE, 0);
+
+    //when:
+    riot.set_port_a_input(0xFF);
+    assert!(!riot.irq());
+    riot.set_port_a_input(0x7F);
+
+    //then:
+    assert!(riot.irq());
+    assert_eq!(riot.read(INTERRUPT_FLAGS), FLAG_PA7);
+    assert!(!riot.irq());
+
+    //when: watching for rising edges, PA7 driven as an output
+    riot.write(EDGE_CONTROL | EDGE_POSITIVE | EDGE_IRQ_ENABLE, 0);
+    riot.write(DDRA, 0x80);
+    riot.write(ORA, 0x80);
+
+    //then:
+    assert!(riot.irq());
+}
+
+#[test]
+fn riot_on_the_bus_serves_ram_and_interrupts_the_cpu() {
+    //given: RAM at $80 and I/O at $280, as in the Atari 2600
+    let mut bus = DeviceBus::new(Mem::new());
+    let riot = bus.attach(0x0080, RAM_SIZE as u32, Riot::new());
+    bus.map(riot, 0x0280, 32, IO_OFFSET);
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut bus.bus);
+    let main = [
+        CPU::INS_LDA_IM, 0x42,
+        CPU::INS_STA_ZP, 0x80,
+        CPU::INS_LDA_IM, 0x0A,
+        CPU::INS_STA_ABS, 0x9C, 0x02,
+        CPU::INS_JMP_ABS, 0x09, 0x02,
+    ];
+    let handler = [
+        CPU::INS_LDY_IM, 0x01,
+        CPU::INS_STY_ZP, 0x10,
+        CPU::INS_LDA_ABS, 0x8C, 0x02,
+        CPU::INS_RTI,
+    ];
+    for (offset, byte) in main.iter().enumerate() {
+        bus.bus[0x0200 + offset as Word] = *byte;
+    }
+    for (offset, byte) in handler.iter().enumerate() {
+        bus.bus[0x0500 + offset as Word] = *byte;
+    }
+    bus.bus[0xFFFE] = 0x00;
+    bus.bus[0xFFFF] = 0x05;
+
+    //when:
+    bus.execute(100, &mut cpu);
+
+    //then:
+    assert_eq!(bus.device(riot).ram[0], 0x42);
+    assert_eq!(bus.bus[0x0080], 0x00);
+    assert_eq!(bus.bus[0x0010], 0x01);
+    assert!(!bus.device(riot).irq());
+    assert_eq!(cpu.sp(), 0xFF);
+    assert_eq!(cpu.pc(), 0x0209);
+}
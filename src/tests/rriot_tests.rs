@@ -0,0 +1,62 @@
+use crate::devices::rriot::*;
+use crate::devices::{Device, DeviceBus};
+use crate::m6502::*;
+
+#[test]
+fn timer_is_started_by_any_write_with_a2_set() {
+    //given:
+    let mut rriot = Rriot::new();
+
+    //when:
+    rriot.write(TIMER_8 | TIMER_IRQ_ENABLE, 2);
+    rriot.tick(16);
+
+    //then:
+    assert_eq!(rriot.read(TIMER_1 | TIMER_IRQ_ENABLE), 0);
+    assert!(!rriot.irq());
+    rriot.tick(8);
+    assert!(rriot.irq());
+    assert_eq!(rriot.peek(INTERRUPT_FLAG), 0x80);
+    //reading the timer with A3 clear acknowledges and disables the interrupt
+    rriot.read(TIMER_64);
+    assert_eq!(rriot.read(INTERRUPT_FLAG), 0x00);
+    rriot.tick(0x100);
+    assert!(!rriot.irq());
+}
+
+#[test]
+fn ports_have_no_edge_detector_flag() {
+    let mut rriot = Rriot::new();
+    rriot.write(PADD, 0x0F);
+    rriot.write(PAD, 0x05);
+    rriot.set_port_a_input(0x70);
+
+    assert_eq!(rriot.read(PAD), 0x75);
+    rriot.set_port_a_input(0x00);
+    assert_eq!(rriot.read(INTERRUPT_FLAG), 0x00);
+}
+
+#[test]
+fn rom_ram_and_io_are_mapped_separately() {
+    //given:
+    let mut rriot = Rriot::new();
+    rriot.load_rom(&[0x4C, 0x00, 0x1C]).unwrap();
+    let mut bus = DeviceBus::new(Mem::new());
+    let id = bus.attach(0x1C00, ROM_SIZE as u32, rriot);
+    bus.map(id, 0x17C0, RAM_SIZE as u32, RAM_OFFSET);
+    bus.map(id, 0x1740, 0x40, IO_OFFSET);
+
+    //when:
+    bus.write(0x1C00, 0x00);
+    bus.write(0x17FF, 0x42);
+    bus.write(0x1743, 0xFF);
+    bus.write(0x1752, 0x81);
+
+    //then:
+    assert_eq!(bus.peek(0x1C00), 0x4C);
+    assert_eq!(bus.peek(0x1FFF), 0xFF);
+    assert_eq!(bus.device(id).ram[RAM_SIZE - 1], 0x42);
+    assert_eq!(bus.device(id).port_b(), 0x81);
+    assert_eq!(Device::peek(bus.device(id), IO_OFFSET + 0x23), 0xFF);
+    assert!(Rriot::new().load_rom(&[0; ROM_SIZE + 1]).is_err());
+}
@@ -0,0 +1,114 @@
+use crate::m6502::*;
+use crate::single_step::{BusOp, RecordingBus};
+
+#[test]
+fn asl_can_shift_the_a_register_into_the_carry() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_a(0b1100_0001);
+    mem[0xFFFC] = CPU::INS_ASL;
+
+    //when:
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(cpu.a(), 0b1000_0010);
+    assert_eq!(cpu.c(), 1);
+    assert_eq!(cpu.n(), 1);
+    assert_eq!(cpu.z(), 0);
+}
+
+#[test]
+fn lsr_can_shift_the_a_register_into_the_carry() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_a(0b0000_0001);
+    cpu.set_n(1);
+    mem[0xFFFC] = CPU::INS_LSR;
+
+    //when:
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(cpu.a(), 0);
+    assert_eq!(cpu.c(), 1);
+    assert_eq!(cpu.n(), 0);
+    assert_eq!(cpu.z(), 1);
+}
+
+#[test]
+fn rol_zero_page_rotates_the_carry_into_bit_0() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_c(1);
+    mem[0xFFFC] = CPU::INS_ROL_ZP;
+    mem[0xFFFD] = 0x42;
+    mem[0x0042] = 0b1000_0000;
+
+    //when:
+    let cycles_used = cpu.execute(5, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 5);
+    assert_eq!(mem[0x0042], 0b0000_0001);
+    assert_eq!(cpu.c(), 1);
+}
+
+#[test]
+fn ror_absolute_x_rotates_the_carry_into_bit_7() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFC, &mut mem);
+
+    //given:
+    cpu.set_c(1);
+    cpu.set_x(0x01);
+    mem[0xFFFC] = CPU::INS_ROR_ABSX;
+    mem[0xFFFD] = 0x00;
+    mem[0xFFFE] = 0x80;
+    mem[0x8001] = 0b0000_0010;
+
+    //when:
+    let cycles_used = cpu.execute(7, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 7);
+    assert_eq!(mem[0x8001], 0b1000_0001);
+    assert_eq!(cpu.c(), 0);
+    assert_eq!(cpu.n(), 1);
+}
+
+#[test]
+fn read_modify_write_writes_the_unmodified_value_back_first() {
+    //given:
+    let mut bus = RecordingBus::new();
+    let mut cpu = CPU::new();
+    cpu.set_pc(0x0200);
+    bus.mem[0x0200] = CPU::INS_ASL_ZP;
+    bus.mem[0x0201] = 0x10;
+    bus.mem[0x0010] = 0x41;
+
+    //when:
+    let cycles_used = cpu.step(&mut bus);
+
+    //then:
+    assert_eq!(cycles_used, 5);
+    let writes: Vec<Byte> = bus
+        .cycles
+        .iter()
+        .filter(|cycle| cycle.2 == BusOp::Write)
+        .map(|cycle| cycle.1)
+        .collect();
+    assert_eq!(writes, [0x41, 0x82]);
+}
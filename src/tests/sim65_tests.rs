@@ -0,0 +1,225 @@
+use crate::loader::LoadError;
+use crate::m6502::*;
+use crate::sim65::*;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/** Captures what the program writes so the test can look at it */
+#[derive(Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<Byte>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/** A tiny assembler for the instructions these tests need */
+struct Program {
+    code: Vec<Byte>,
+}
+
+impl Program {
+    const LOAD_ADDRESS: Word = 0x0200;
+    /** the C stack pointer lives at $00/$01 */
+    const SP_ADDRESS: Byte = 0x00;
+
+    fn new() -> Self {
+        let mut program = Self { code: Vec::new() };
+        program.store_word(0x0FF0, Self::SP_ADDRESS as Word);
+        program
+    }
+
+    fn lda(&mut self, value: Byte) -> &mut Self {
+        self.code.extend_from_slice(&[CPU::INS_LDA_IM, value]);
+        self
+    }
+
+    fn ldx(&mut self, value: Byte) -> &mut Self {
+        self.code.extend_from_slice(&[CPU::INS_LDX_IM, value]);
+        self
+    }
+
+    fn ldy(&mut self, value: Byte) -> &mut Self {
+        self.code.extend_from_slice(&[CPU::INS_LDY_IM, value]);
+        self
+    }
+
+    fn sta(&mut self, address: Word) -> &mut Self {
+        let [lo, hi] = address.to_le_bytes();
+        self.code.extend_from_slice(&[CPU::INS_STA_ABS, lo, hi]);
+        self
+    }
+
+    fn lda_abs(&mut self, address: Word) -> &mut Self {
+        let [lo, hi] = address.to_le_bytes();
+        self.code.extend_from_slice(&[CPU::INS_LDA_ABS, lo, hi]);
+        self
+    }
+
+    fn jsr(&mut self, address: Word) -> &mut Self {
+        let [lo, hi] = address.to_le_bytes();
+        self.code.extend_from_slice(&[CPU::INS_JSR, lo, hi]);
+        self
+    }
+
+    fn store_word(&mut self, value: Word, address: Word) -> &mut Self {
+        let [lo, hi] = value.to_le_bytes();
+        self.lda(lo).sta(address).lda(hi).sta(address + 1)
+    }
+
+    /** Put the parameters on the C stack, first one at the lowest address */
+    fn push_params(&mut self, params: &[Word]) -> &mut Self {
+        let sp = 0x0FF0 - 2 * params.len() as Word;
+        self.store_word(sp, Self::SP_ADDRESS as Word);
+        for (index, param) in params.iter().rev().enumerate() {
+            self.store_word(*param, sp + 2 * index as Word);
+        }
+        self
+    }
+
+    fn image(&self, data: &[Byte]) -> Vec<Byte> {
+        let mut image = MAGIC.to_vec();
+        image.extend_from_slice(&[HEADER_VERSION, 0, Self::SP_ADDRESS]);
+        image.extend_from_slice(&Self::LOAD_ADDRESS.to_le_bytes());
+        image.extend_from_slice(&Self::LOAD_ADDRESS.to_le_bytes());
+        image.extend_from_slice(&self.code);
+        image.resize(HEADER_LENGTH + 0x100, 0);
+        image.extend_from_slice(data);
+        image
+    }
+}
+
+/** data appended to the program is found here */
+const DATA: Word = Program::LOAD_ADDRESS + 0x100;
+
+#[test]
+fn program_can_write_to_stdout_and_exit_with_a_code() {
+    let output = SharedOutput::default();
+
+    //given: write(1, "hi\n", 3); exit(42);
+    let mut program = Program::new();
+    program
+        .push_params(&[1, DATA])
+        .lda(3)
+        .ldx(0)
+        .jsr(PV_WRITE)
+        .sta(0x0400)
+        .lda(42)
+        .jsr(PV_EXIT);
+
+    //when:
+    let mut sim = Sim65::load(&program.image(b"hi\n"), vec![]).unwrap().with_stdout(output.clone());
+    let exit_code = sim.run(10_000).unwrap();
+
+    //then:
+    assert_eq!(exit_code, 42);
+    assert_eq!(output.0.borrow().as_slice(), b"hi\n");
+    assert_eq!(sim.mem[0x0400], 3);
+    assert_eq!(sim.cpu.sp(), 0xFD);
+}
+
+#[test]
+fn program_can_open_read_and_close_a_file() {
+    let path = std::env::temp_dir().join(format!("sim65_test_{}.txt", std::process::id()));
+    std::fs::write(&path, b"abc").unwrap();
+    let mut data = path.to_str().unwrap().as_bytes().to_vec();
+    data.push(0);
+
+    //given: fd = open(path, O_RDONLY); read(fd, $0500, 3); close(fd); exit(buf[1]);
+    let mut program = Program::new();
+    program
+        .push_params(&[DATA, 0x0001])
+        .ldy(4)
+        .jsr(PV_OPEN)
+        .sta(0x0400)
+        .push_params(&[3, 0x0500])
+        .lda(3)
+        .ldx(0)
+        .jsr(PV_READ)
+        .sta(0x0401)
+        .lda(3)
+        .ldx(0)
+        .jsr(PV_CLOSE)
+        .sta(0x0402)
+        .lda_abs(0x0501)
+        .jsr(PV_EXIT);
+
+    //when:
+    let mut sim = Sim65::load(&program.image(&data), vec![]).unwrap();
+    let exit_code = sim.run(10_000).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    //then:
+    assert_eq!(exit_code, b'b');
+    assert_eq!(sim.mem[0x0400], 3, "first free file descriptor");
+    assert_eq!(sim.mem[0x0401], 3, "bytes read");
+    assert_eq!(sim.mem[0x0402], 0, "closed");
+}
+
+#[test]
+fn program_gets_argc_and_argv() {
+    //given: argc = args(&argv);
+    let mut program = Program::new();
+    program.lda(0x00).ldx(0x04).jsr(PV_ARGS).sta(0x0402).lda(0).jsr(PV_EXIT);
+
+    //when:
+    let args = vec!["prog".to_string(), "-v".to_string()];
+    let mut sim = Sim65::load(&program.image(&[]), args).unwrap();
+    sim.run(10_000).unwrap();
+
+    //then:
+    let read_word = |address: Word| Word::from_le_bytes([sim.mem[address], sim.mem[address + 1]]);
+    let read_string = |address: Word| {
+        (address..)
+            .map(|address| sim.mem[address])
+            .take_while(|byte| *byte != 0)
+            .collect::<Vec<Byte>>()
+    };
+    assert_eq!(sim.mem[0x0402], 2);
+    let argv = read_word(0x0400);
+    assert_eq!(argv, 0x0FF0 - 6);
+    assert_eq!(read_string(read_word(argv)), b"prog");
+    assert_eq!(read_string(read_word(argv + 2)), b"-v");
+    assert_eq!(read_word(argv + 4), 0);
+    assert!(read_word(0x0000) < read_word(argv));
+}
+
+#[test]
+fn header_is_checked() {
+    assert!(Sim65::load(b"sim65\x01\x00\x00\x00\x02\x00\x02", vec![]).is_err());
+    assert!(Sim65::load(b"notsim\x02\x00\x00\x00\x02\x00", vec![]).is_err());
+    assert!(matches!(
+        Sim65::load(b"sim65\x02\x01\x00\x00\x02\x00\x02", vec![]),
+        Err(LoadError::Malformed { offset: 6, .. })
+    ));
+}
+
+#[test]
+fn program_that_never_exits_times_out() {
+    let mut program = Program::new();
+    program.jsr(Program::LOAD_ADDRESS + program.code.len() as Word + 3);
+    program.code.extend_from_slice(&[CPU::INS_JMP_ABS, 0x00, 0x02]);
+
+    let mut sim = Sim65::load(&program.image(&[]), vec![]).unwrap();
+
+    assert!(matches!(sim.run(1_000), Err(Sim65Error::TimedOut { .. })));
+}
+
+#[test]
+fn program_that_halts_the_cpu_is_reported_where_it_stopped() {
+    let mut program = Program::new();
+    program.lda(1);
+    let halt_at = Program::LOAD_ADDRESS + program.code.len() as Word;
+    program.code.push(0x02);
+
+    let mut sim = Sim65::load(&program.image(&[]), vec![]).unwrap();
+
+    assert!(matches!(sim.run(1_000), Err(Sim65Error::Halted { pc, .. }) if pc == halt_at));
+}
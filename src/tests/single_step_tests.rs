@@ -0,0 +1,168 @@
+use crate::single_step::*;
+use super::test_rom_path;
+
+const LDA_IMMEDIATE: &str = r#"[
+  {
+    "name": "a9 84 00",
+    "initial": { "pc": 4096, "s": 253, "a": 0, "x": 0, "y": 0, "p": 38, "ram": [[4096, 169], [4097, 132]] },
+    "final": { "pc": 4098, "s": 253, "a": 132, "x": 0, "y": 0, "p": 164, "ram": [[4096, 169], [4097, 132]] },
+    "cycles": [[4096, 169, "read"], [4097, 132, "read"]]
+  },
+  {
+    "name": "a9 00 00",
+    "initial": { "pc": 8192, "s": 253, "a": 7, "x": 0, "y": 0, "p": 160, "ram": [[8192, 169], [8193, 0]] },
+    "final": { "pc": 8194, "s": 253, "a": 0, "x": 0, "y": 0, "p": 34, "ram": [[8192, 169], [8193, 0]] },
+    "cycles": [[8192, 169, "read"], [8193, 0, "read"]]
+  }
+]"#;
+
+const STA_ZERO_PAGE: &str = r#"[
+  {
+    "name": "85 40 00",
+    "initial": { "pc": 512, "s": 255, "a": 90, "x": 0, "y": 0, "p": 32, "ram": [[512, 133], [513, 64], [64, 0]] },
+    "final": { "pc": 514, "s": 255, "a": 90, "x": 0, "y": 0, "p": 32, "ram": [[512, 133], [513, 64], [64, 90]] },
+    "cycles": [[512, 133, "read"], [513, 64, "read"], [64, 90, "write"]]
+  }
+]"#;
+
+/** the real chip reads from the wrong page before fixing up the high byte */
+const LDA_ABSOLUTE_X_PAGE_CROSS: &str = r#"[
+  {
+    "name": "bd ff 10",
+    "initial": { "pc": 768, "s": 255, "a": 0, "x": 1, "y": 0, "p": 32, "ram": [[768, 189], [769, 255], [770, 16], [4096, 1], [4352, 2]] },
+    "final": { "pc": 771, "s": 255, "a": 2, "x": 1, "y": 0, "p": 32, "ram": [[4352, 2]] },
+    "cycles": [[768, 189, "read"], [769, 255, "read"], [770, 16, "read"], [4096, 1, "read"], [4352, 2, "read"]]
+  }
+]"#;
+
+/** the same case with the read from the wrong page left out, as a core without dummy reads would do it */
+const LDA_ABSOLUTE_X_NO_DUMMY_READ: &str = r#"[
+  {
+    "name": "bd ff 10",
+    "initial": { "pc": 768, "s": 255, "a": 0, "x": 1, "y": 0, "p": 32, "ram": [[768, 189], [769, 255], [770, 16], [4096, 1], [4352, 2]] },
+    "final": { "pc": 771, "s": 255, "a": 2, "x": 1, "y": 0, "p": 32, "ram": [[4352, 2]] },
+    "cycles": [[768, 189, "read"], [769, 255, "read"], [770, 16, "read"], [4352, 2, "read"]]
+  }
+]"#;
+
+/** one case for each kind of cycle the CPU spends busy inside an instruction, as the real chip runs them */
+const DUMMY_CYCLES: &str = r#"[
+  {
+    "name": "20 34 12",
+    "initial": { "pc": 768, "s": 253, "a": 0, "x": 0, "y": 0, "p": 32, "ram": [[768, 32], [769, 52], [770, 18], [509, 0]] },
+    "final": { "pc": 4660, "s": 251, "a": 0, "x": 0, "y": 0, "p": 32, "ram": [[509, 3], [508, 2]] },
+    "cycles": [[768, 32, "read"], [769, 52, "read"], [509, 0, "read"], [509, 3, "write"], [508, 2, "write"], [770, 18, "read"]]
+  },
+  {
+    "name": "60 00 00",
+    "initial": { "pc": 4660, "s": 251, "a": 0, "x": 0, "y": 0, "p": 32, "ram": [[4660, 96], [4661, 0], [507, 0], [508, 2], [509, 3], [770, 18]] },
+    "final": { "pc": 771, "s": 253, "a": 0, "x": 0, "y": 0, "p": 32, "ram": [] },
+    "cycles": [[4660, 96, "read"], [4661, 0, "read"], [507, 0, "read"], [508, 2, "read"], [509, 3, "read"], [770, 18, "read"]]
+  },
+  {
+    "name": "40 00 00",
+    "initial": { "pc": 1792, "s": 250, "a": 0, "x": 0, "y": 0, "p": 32, "ram": [[1792, 64], [1793, 0], [506, 0], [507, 3], [508, 52], [509, 18]] },
+    "final": { "pc": 4660, "s": 253, "a": 0, "x": 0, "y": 0, "p": 35, "ram": [] },
+    "cycles": [[1792, 64, "read"], [1793, 0, "read"], [506, 0, "read"], [507, 3, "read"], [508, 52, "read"], [509, 18, "read"]]
+  },
+  {
+    "name": "68 00 00",
+    "initial": { "pc": 1024, "s": 252, "a": 0, "x": 0, "y": 0, "p": 32, "ram": [[1024, 104], [1025, 0], [508, 0], [509, 128]] },
+    "final": { "pc": 1025, "s": 253, "a": 128, "x": 0, "y": 0, "p": 160, "ram": [] },
+    "cycles": [[1024, 104, "read"], [1025, 0, "read"], [508, 0, "read"], [509, 128, "read"]]
+  },
+  {
+    "name": "d0 05 00",
+    "initial": { "pc": 765, "s": 253, "a": 0, "x": 0, "y": 0, "p": 32, "ram": [[765, 208], [766, 5], [767, 0], [516, 0]] },
+    "final": { "pc": 772, "s": 253, "a": 0, "x": 0, "y": 0, "p": 32, "ram": [] },
+    "cycles": [[765, 208, "read"], [766, 5, "read"], [767, 0, "read"], [516, 0, "read"]]
+  },
+  {
+    "name": "b5 80 00",
+    "initial": { "pc": 2048, "s": 253, "a": 0, "x": 144, "y": 0, "p": 32, "ram": [[2048, 181], [2049, 128], [128, 0], [16, 7]] },
+    "final": { "pc": 2050, "s": 253, "a": 7, "x": 144, "y": 0, "p": 32, "ram": [] },
+    "cycles": [[2048, 181, "read"], [2049, 128, "read"], [128, 0, "read"], [16, 7, "read"]]
+  },
+  {
+    "name": "91 10 00",
+    "initial": { "pc": 1536, "s": 253, "a": 85, "x": 0, "y": 16, "p": 32, "ram": [[1536, 145], [1537, 16], [16, 240], [17, 32], [8192, 0]] },
+    "final": { "pc": 1538, "s": 253, "a": 85, "x": 0, "y": 16, "p": 32, "ram": [[8448, 85]] },
+    "cycles": [[1536, 145, "read"], [1537, 16, "read"], [16, 240, "read"], [17, 32, "read"], [8192, 0, "read"], [8448, 85, "write"]]
+  },
+  {
+    "name": "fe 00 10",
+    "initial": { "pc": 1280, "s": 253, "a": 0, "x": 1, "y": 0, "p": 32, "ram": [[1280, 254], [1281, 0], [1282, 16], [4097, 65]] },
+    "final": { "pc": 1283, "s": 253, "a": 0, "x": 1, "y": 0, "p": 32, "ram": [[4097, 66]] },
+    "cycles": [[1280, 254, "read"], [1281, 0, "read"], [1282, 16, "read"], [4097, 65, "read"], [4097, 65, "read"], [4097, 65, "write"], [4097, 66, "write"]]
+  },
+  {
+    "name": "e8 00 00",
+    "initial": { "pc": 1280, "s": 253, "a": 0, "x": 1, "y": 0, "p": 32, "ram": [[1280, 232], [1281, 0]] },
+    "final": { "pc": 1281, "s": 253, "a": 0, "x": 2, "y": 0, "p": 32, "ram": [] },
+    "cycles": [[1280, 232, "read"], [1281, 0, "read"]]
+  }
+]"#;
+
+#[test]
+fn passing_cases_are_counted() {
+    let report = run_cases("a9", LDA_IMMEDIATE).unwrap();
+
+    assert_eq!(report.total, 2);
+    assert_eq!(report.passed(), 2);
+    assert_eq!(report.to_string(), "a9: 2/2 passed\n");
+}
+
+#[test]
+fn writes_are_compared_cycle_by_cycle() {
+    let report = run_cases("85", STA_ZERO_PAGE).unwrap();
+
+    assert!(report.failures.is_empty(), "{}", report);
+}
+
+#[test]
+fn dummy_reads_are_compared_cycle_by_cycle() {
+    let report = run_cases("bd", LDA_ABSOLUTE_X_PAGE_CROSS).unwrap();
+
+    assert!(report.failures.is_empty(), "{}", report);
+}
+
+#[test]
+fn every_busy_cycle_is_a_bus_access() {
+    let report = run_cases("mixed", DUMMY_CYCLES).unwrap();
+
+    assert_eq!(report.total, 9);
+    assert!(report.failures.is_empty(), "{}", report);
+}
+
+#[test]
+fn failing_cases_are_reported_by_name() {
+    let report = run_cases("bd", LDA_ABSOLUTE_X_NO_DUMMY_READ).unwrap();
+
+    assert_eq!(report.passed(), 0);
+    assert_eq!(report.failures.len(), 1);
+    let (name, differences) = &report.failures[0];
+    assert_eq!(name, "bd ff 10");
+    assert!(differences.iter().any(|difference| difference.starts_with("cycles:")));
+    assert!(report.to_string().contains("  bd ff 10: "));
+}
+
+#[test]
+fn malformed_json_is_an_error() {
+    assert!(run_cases("a9", "[{\"name\": 1}]").is_err());
+}
+
+/** A directory of NN.json files, e.g. 65x02/6502/v1 */
+#[test]
+#[ignore = "needs the SingleStepTests vectors in test_roms/single_step/ or SINGLE_STEP_TESTS"]
+fn single_step_test_vectors() {
+    let directory = test_rom_path("SINGLE_STEP_TESTS", "single_step");
+
+    let reports = run_directory(directory).unwrap();
+
+    let failing: Vec<String> = reports
+        .iter()
+        .filter(|report| !report.failures.is_empty())
+        .map(|report| format!("{}: {}/{} passed", report.opcode, report.passed(), report.total))
+        .collect();
+    assert!(failing.is_empty(), "{}", failing.join("\n"));
+}
@@ -0,0 +1,282 @@
+use crate::devices::via::{self, Via};
+use crate::devices::{DeviceBus, DeviceId};
+use crate::m6502::*;
+use crate::snapshot::*;
+
+/** LDA #$42 / STA $0300 / LDX $0300 / LDY #$07 */
+fn program() -> (CPU, Mem) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut mem);
+
+    mem[0x0200] = CPU::INS_LDA_IM;
+    mem[0x0201] = 0x42;
+    mem[0x0202] = CPU::INS_STA_ABS;
+    mem[0x0203] = 0x00;
+    mem[0x0204] = 0x03;
+    mem[0x0205] = CPU::INS_LDX_ABS;
+    mem[0x0206] = 0x00;
+    mem[0x0207] = 0x03;
+    mem[0x0208] = CPU::INS_LDY_IM;
+    mem[0x0209] = 0x07;
+
+    (cpu, mem)
+}
+
+#[test]
+fn restored_snapshot_resumes_exactly_where_it_was_captured() {
+    //given:
+    let (mut cpu, mut mem) = program();
+    let cycles = cpu.execute(6, &mut mem) as u64;
+    let snapshot = Snapshot::capture(&cpu, cycles, &mem);
+    cpu.execute(6, &mut mem);
+    let (expected_cpu, expected_mem) = (cpu.clone(), mem.clone());
+
+    //when:
+    let mut restored_cpu = CPU::new();
+    let mut restored_mem = Mem::new();
+    let restored_cycles = Snapshot::from_bytes(&snapshot.to_bytes())
+        .unwrap()
+        .restore(&mut restored_cpu, &mut restored_mem)
+        .unwrap();
+    restored_cpu.execute(6, &mut restored_mem);
+
+    //then:
+    assert_eq!(restored_cycles, 6);
+    assert_eq!(restored_mem[0x0300], 0x42);
+    assert_eq!(Snapshot::capture(&restored_cpu, 0, &restored_mem), Snapshot::capture(&expected_cpu, 0, &expected_mem));
+    assert_eq!(restored_cpu.x(), 0x42);
+    assert_eq!(restored_cpu.y(), 0x07);
+}
+
+#[test]
+fn snapshot_round_trips_registers_and_flags() {
+    let (mut cpu, mem) = program();
+    cpu.set_pc(0x1234);
+    cpu.set_sp(0xF0);
+    cpu.set_a(1);
+    cpu.set_x(2);
+    cpu.set_y(3);
+    cpu.set_status(0b1100_1011);
+    let snapshot = Snapshot::capture(&cpu, 123_456_789_000, &mem);
+
+    let mut bytes = Vec::new();
+    snapshot.write_to(&mut bytes).unwrap();
+    let read = Snapshot::read_from(&bytes[..]).unwrap();
+
+    assert_eq!(read, snapshot);
+    assert_eq!(read.p, 0b1110_1011);
+    assert_eq!(read.cycles, 123_456_789_000);
+    assert_eq!(version_of(&bytes).unwrap(), VERSION);
+}
+
+#[test]
+fn snapshot_from_another_version_is_rejected() {
+    let (cpu, mem) = program();
+    let mut bytes = Snapshot::capture(&cpu, 0, &mem).to_bytes();
+    bytes[8..10].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+    let error = Snapshot::from_bytes(&bytes).unwrap_err();
+
+    assert!(matches!(
+        error,
+        SnapshotError::UnsupportedVersion { found, supported: (OLDEST_SUPPORTED_VERSION, VERSION) } if found == VERSION + 1
+    ));
+}
+
+#[test]
+fn damaged_snapshots_are_reported() {
+    let (cpu, mem) = program();
+    let bytes = Snapshot::capture(&cpu, 0, &mem).to_bytes();
+
+    assert!(matches!(Snapshot::from_bytes(b"not a snapshot"), Err(SnapshotError::NotASnapshot)));
+    assert!(matches!(
+        Snapshot::from_bytes(&bytes[..1000]),
+        Err(SnapshotError::Truncated { offset: 1000 })
+    ));
+}
+
+/** Memory with one latch register, standing in for a peripheral */
+struct LatchBus {
+    mem: Mem,
+    latch: Byte,
+}
+
+impl SaveState for LatchBus {
+    fn save_memory(&self) -> Vec<Byte> {
+        self.mem.save_memory()
+    }
+
+    fn restore_memory(&mut self, memory: &[Byte]) -> Result<(), SnapshotError> {
+        self.mem.restore_memory(memory)
+    }
+
+    fn save_devices(&self) -> Option<Vec<Byte>> {
+        Some(vec![self.latch])
+    }
+
+    fn restore_devices(&mut self, state: &[Byte]) -> Result<(), SnapshotError> {
+        self.latch = state[0];
+        Ok(())
+    }
+}
+
+#[test]
+fn device_state_is_saved_when_the_bus_supports_it() {
+    let cpu = CPU::new();
+    let bus = LatchBus {
+        mem: Mem::new(),
+        latch: 0x5A,
+    };
+    let snapshot = Snapshot::from_bytes(&Snapshot::capture(&cpu, 0, &bus).to_bytes()).unwrap();
+
+    let mut restored = LatchBus {
+        mem: Mem::new(),
+        latch: 0,
+    };
+    snapshot.restore(&mut CPU::new(), &mut restored).unwrap();
+
+    assert_eq!(restored.latch, 0x5A);
+    //plain memory has nowhere to put it
+    assert!(matches!(snapshot.restore(&mut CPU::new(), &mut Mem::new()), Err(SnapshotError::Bus(_))));
+}
+
+#[test]
+fn version_1_snapshots_load_with_the_interrupt_inputs_released() {
+    let (mut cpu, mem) = program();
+    cpu.set_irq(true);
+    let mut bytes = Snapshot::capture(&cpu, 0, &mem).to_bytes();
+    //version 1 had no interrupt, halted or decimal byte after P, and no pending cycles after the cycle count
+    bytes.drain(28..32);
+    bytes.drain(17..20);
+    bytes[8..10].copy_from_slice(&1u16.to_le_bytes());
+
+    let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+    let mut restored = CPU::new();
+    restored.set_irq(true);
+    snapshot.restore(&mut restored, &mut Mem::new()).unwrap();
+
+    assert_eq!(snapshot.interrupts, 0);
+    assert!(!restored.irq());
+    assert_eq!(restored.pc(), 0x0200);
+}
+
+#[test]
+fn snapshot_round_trips_the_halted_state_and_pending_cycles() {
+    //given:
+    let (mut cpu, mut mem) = program();
+    mem[0x0200] = 0x02;
+    cpu.execute(10, &mut mem);
+    let snapshot = Snapshot::capture(&cpu, 0, &mem).with_pending_cycles(-3);
+
+    //when:
+    let read = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+    let mut restored = CPU::new();
+    read.restore(&mut restored, &mut Mem::new()).unwrap();
+
+    //then:
+    assert_eq!(read, snapshot);
+    assert!(read.halted);
+    assert_eq!(read.pending_cycles, -3);
+    assert!(restored.halted());
+    assert_eq!(restored.pc(), 0x0200);
+}
+
+#[test]
+fn version_2_snapshots_load_running_with_nothing_pending() {
+    let (mut cpu, mut mem) = program();
+    mem[0x0200] = 0x02;
+    cpu.execute(10, &mut mem);
+    let mut bytes = Snapshot::capture(&cpu, 0, &mem).with_pending_cycles(5).to_bytes();
+    bytes.drain(28..32);
+    bytes.drain(18..20);
+    bytes[8..10].copy_from_slice(&2u16.to_le_bytes());
+
+    let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+    let mut restored = CPU::new();
+    restored.set_halted(true);
+    snapshot.restore(&mut restored, &mut Mem::new()).unwrap();
+
+    assert!(!snapshot.halted);
+    assert_eq!(snapshot.pending_cycles, 0);
+    assert!(!restored.halted());
+}
+
+#[test]
+fn snapshot_round_trips_decimal_mode_being_turned_off() {
+    let (mut cpu, mem) = program();
+    cpu.set_decimal_enabled(false);
+    let snapshot = Snapshot::from_bytes(&Snapshot::capture(&cpu, 0, &mem).to_bytes()).unwrap();
+
+    let mut restored = CPU::new();
+    snapshot.restore(&mut restored, &mut Mem::new()).unwrap();
+
+    assert!(!snapshot.decimal_enabled);
+    assert!(!restored.decimal_enabled());
+}
+
+#[test]
+fn version_3_snapshots_load_with_decimal_mode_working() {
+    let (cpu, mem) = program();
+    let mut bytes = Snapshot::capture(&cpu, 0, &mem).to_bytes();
+    bytes.remove(19);
+    bytes[8..10].copy_from_slice(&3u16.to_le_bytes());
+
+    let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+    let mut restored = CPU::new();
+    restored.set_decimal_enabled(false);
+    snapshot.restore(&mut restored, &mut Mem::new()).unwrap();
+
+    assert!(snapshot.decimal_enabled);
+    assert!(restored.decimal_enabled());
+}
+
+/** Memory with a VIA at $6000 whose T1 has been counting down */
+fn via_bus() -> (DeviceBus<Mem>, DeviceId<Via>) {
+    let mut bus = DeviceBus::new(Mem::new());
+    let via = bus.attach(0x6000, 0x10, Via::new());
+    bus.write(0x6000 + via::ACR as Word, 0x40);
+    bus.write(0x6000 + via::T1C_L as Word, 0x34);
+    bus.write(0x6000 + via::T1C_H as Word, 0x12);
+    bus.write(0x6000 + via::IER as Word, 0x80 | via::IRQ_T1);
+    bus.write(0x0300, 0x42);
+    bus.tick(0x100, &mut CPU::new());
+
+    (bus, via)
+}
+
+#[test]
+fn device_bus_saves_the_state_of_each_device() {
+    //given:
+    let (bus, _) = via_bus();
+    let snapshot = Snapshot::from_bytes(&Snapshot::capture(&CPU::new(), 0, &bus).to_bytes()).unwrap();
+    let mut restored = DeviceBus::new(Mem::new());
+    let via = restored.attach(0x6000, 0x10, Via::new());
+
+    //when:
+    snapshot.restore(&mut CPU::new(), &mut restored).unwrap();
+
+    //then:
+    assert_eq!(restored.peek(0x0300), 0x42);
+    for register in 0..0x10 {
+        assert_eq!(restored.peek(0x6000 + register), bus.peek(0x6000 + register));
+    }
+    //and it keeps counting from there
+    let (mut expected, expected_via) = via_bus();
+    expected.tick(0x1300, &mut CPU::new());
+    restored.tick(0x1300, &mut CPU::new());
+    assert!(restored.device(via).irq());
+    assert_eq!(restored.device(via).peek(via::T1C_L), expected.device(expected_via).peek(via::T1C_L));
+}
+
+#[test]
+fn device_state_for_another_set_of_devices_is_refused() {
+    let (bus, _) = via_bus();
+    let snapshot = Snapshot::capture(&CPU::new(), 0, &bus);
+
+    let mut other = DeviceBus::new(Mem::new());
+    other.attach(0x6000, 0x10, Via::new());
+    other.attach(0x7000, 0x10, Via::new());
+
+    assert!(matches!(snapshot.restore(&mut CPU::new(), &mut other), Err(SnapshotError::Bus(_))));
+}
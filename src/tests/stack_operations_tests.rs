@@ -0,0 +1,124 @@
+use crate::m6502::*;
+
+#[test]
+fn tsx_can_transfer_the_stack_pointer_to_x() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    cpu.set_sp(0x80);
+    mem[0xFF00] = CPU::INS_TSX;
+
+    //when:
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(cpu.x(), 0x80);
+    assert_eq!(cpu.n(), 1);
+}
+
+#[test]
+fn txs_can_transfer_x_to_the_stack_pointer_without_touching_the_flags() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    cpu.set_x(0x00);
+    mem[0xFF00] = CPU::INS_TXS;
+
+    //when:
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(cpu.sp(), 0x00);
+    assert_eq!(cpu.z(), 0);
+}
+
+#[test]
+fn pha_and_pla_can_push_and_pull_the_a_register() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    cpu.set_a(0x42);
+    mem[0xFF00] = CPU::INS_PHA;
+    mem[0xFF01] = CPU::INS_LDA_IM;
+    mem[0xFF02] = 0x00;
+    mem[0xFF03] = CPU::INS_PLA;
+
+    //when:
+    let cycles_used = cpu.execute(3 + 2 + 4, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 9);
+    assert_eq!(cpu.a(), 0x42);
+    assert_eq!(mem[0x01FF], 0x42);
+    assert_eq!(cpu.sp(), 0xFF);
+    assert_eq!(cpu.z(), 0);
+}
+
+#[test]
+fn php_pushes_the_status_with_the_break_flag_set() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    cpu.set_c(1);
+    cpu.set_n(1);
+    mem[0xFF00] = CPU::INS_PHP;
+
+    //when:
+    let cycles_used = cpu.execute(3, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 3);
+    assert_eq!(mem[0x01FF], 0b1011_0001);
+    assert_eq!(cpu.sp(), 0xFE);
+}
+
+#[test]
+fn plp_pulls_the_status_but_leaves_the_break_flag_alone() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    cpu.set_sp(0xFE);
+    mem[0x01FF] = 0xFF;
+    mem[0xFF00] = CPU::INS_PLP;
+
+    //when:
+    let cycles_used = cpu.execute(4, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 4);
+    assert_eq!(cpu.status(), 0b1110_1111);
+    assert_eq!(cpu.sp(), 0xFF);
+}
+
+#[test]
+fn the_stack_pointer_wraps_around_within_page_1() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    cpu.set_sp(0x00);
+    mem[0xFF00] = CPU::INS_JSR;
+    mem[0xFF01] = 0x00;
+    mem[0xFF02] = 0x80;
+
+    //when:
+    cpu.execute(6, &mut mem);
+
+    //then:
+    assert_eq!(cpu.sp(), 0xFE);
+    assert_eq!(mem[0x0100], 0xFF);
+    assert_eq!(mem[0x01FF], 0x02);
+}
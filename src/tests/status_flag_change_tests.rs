@@ -0,0 +1,53 @@
+use crate::m6502::*;
+
+fn test_flag_change(opcode_to_test: Byte, flag: fn(&CPU) -> Byte, set_flag: fn(&mut CPU, Byte), expected: Byte) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    set_flag(&mut cpu, 1 - expected);
+    mem[0xFF00] = opcode_to_test;
+
+    //when:
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(flag(&cpu), expected);
+}
+
+#[test]
+fn clc_will_clear_the_carry_flag() {
+    test_flag_change(CPU::INS_CLC, CPU::c, CPU::set_c, 0);
+}
+
+#[test]
+fn sec_will_set_the_carry_flag() {
+    test_flag_change(CPU::INS_SEC, CPU::c, CPU::set_c, 1);
+}
+
+#[test]
+fn cli_will_clear_the_interrupt_flag() {
+    test_flag_change(CPU::INS_CLI, CPU::i, CPU::set_i, 0);
+}
+
+#[test]
+fn sei_will_set_the_interrupt_flag() {
+    test_flag_change(CPU::INS_SEI, CPU::i, CPU::set_i, 1);
+}
+
+#[test]
+fn cld_will_clear_the_decimal_flag() {
+    test_flag_change(CPU::INS_CLD, CPU::d, CPU::set_d, 0);
+}
+
+#[test]
+fn sed_will_set_the_decimal_flag() {
+    test_flag_change(CPU::INS_SED, CPU::d, CPU::set_d, 1);
+}
+
+#[test]
+fn clv_will_clear_the_overflow_flag() {
+    test_flag_change(CPU::INS_CLV, CPU::v, CPU::set_v, 0);
+}
@@ -57,7 +57,7 @@ fn test_store_register_absolute(
     let actual_cycles = cpu.execute(EXPECTED_CYCLES, &mut mem);
 
     //then:
-    assert_eq!(actual_cycles, actual_cycles);
+    assert_eq!(actual_cycles, EXPECTED_CYCLES);
     assert_eq!(mem[0x8000], 0x2F);
     verify_unmodified_flags_from_load_register!(cpu, cpu_copy);
 }
@@ -83,7 +83,7 @@ fn test_store_register_zero_page_x(
     let actual_cycles = cpu.execute(EXPECTED_CYCLES, &mut mem);
 
     //then:
-    assert_eq!(actual_cycles, actual_cycles);
+    assert_eq!(actual_cycles, EXPECTED_CYCLES);
     assert_eq!(mem[0x008F], 0x42);
     verify_unmodified_flags_from_load_register!(cpu, cpu_copy);
 }
@@ -148,7 +148,7 @@ fn sta_absolute_x_can_store_the_register_into_memory() {
     let actual_cycles = cpu.execute(EXPECTED_CYCLES, &mut mem);
 
     //then:
-    assert_eq!(actual_cycles, actual_cycles);
+    assert_eq!(actual_cycles, EXPECTED_CYCLES);
     assert_eq!(mem[0x800F], 0x42);
     verify_unmodified_flags_from_load_register!(cpu, cpu_copy);
 }
@@ -173,7 +173,7 @@ fn sta_absolute_y_can_store_the_register_into_memory() {
     let actual_cycles = cpu.execute(EXPECTED_CYCLES, &mut mem);
 
     //then:
-    assert_eq!(actual_cycles, actual_cycles);
+    assert_eq!(actual_cycles, EXPECTED_CYCLES);
     assert_eq!(mem[0x800F], 0x42);
     verify_unmodified_flags_from_load_register!(cpu, cpu_copy);
 }
@@ -199,7 +199,7 @@ fn sta_indirect_x_can_store_the_register_into_memory() {
     let actual_cycles = cpu.execute(EXPECTED_CYCLES, &mut mem);
 
     //then:
-    assert_eq!(actual_cycles, actual_cycles);
+    assert_eq!(actual_cycles, EXPECTED_CYCLES);
     assert_eq!(mem[0x8000], 0x42);
     verify_unmodified_flags_from_load_register!(cpu, cpu_copy);
 }
@@ -225,7 +225,7 @@ fn sta_indirect_y_can_store_the_register_into_memory() {
     let actual_cycles = cpu.execute(EXPECTED_CYCLES, &mut mem);
 
     //then:
-    assert_eq!(actual_cycles, actual_cycles);
+    assert_eq!(actual_cycles, EXPECTED_CYCLES);
     assert_eq!(mem[0x8000 + 0x0F], 0x42);
     verify_unmodified_flags_from_load_register!(cpu, cpu_copy);
 }
@@ -0,0 +1,154 @@
+use crate::coverage::*;
+use crate::disassembler::disassemble_with;
+use crate::gdb::GdbStub;
+use crate::loader::LoadError;
+use crate::m6502::*;
+use crate::symbols::*;
+use crate::trace::Tracer;
+
+/** What ld65 writes for a tiny ROM: code at $C000, a table at $C100 and a macro */
+const DBG: &str = "version\tmajor=2,minor=0
+info\tcsym=0,file=2,lib=0,line=6,mod=1,scope=1,seg=2,span=4,sym=5,type=1
+file\tid=0,name=\"main.s\",size=120,mtime=0x5F000000,mod=0
+file\tid=1,name=\"macros.inc\",size=20,mtime=0x5F000000,mod=0
+mod\tid=0,name=\"main.o\",file=0
+seg\tid=0,name=\"CODE\",start=0x00C000,size=0x0008,addrsize=absolute,type=ro,oname=\"rom.bin\",ooffs=0
+seg\tid=1,name=\"RODATA\",start=0x00C100,size=0x0002,addrsize=absolute,type=ro,oname=\"rom.bin\",ooffs=256
+span\tid=0,seg=0,start=0,size=2
+span\tid=1,seg=0,start=2,size=3
+span\tid=2,seg=0,start=5,size=3
+span\tid=3,seg=1,start=0,size=2,type=0
+line\tid=0,file=0,line=3,span=0
+line\tid=1,file=0,line=4,span=1
+line\tid=2,file=0,line=6,span=2
+line\tid=3,file=1,line=2,type=2,span=2
+line\tid=4,file=0,line=9,span=3
+line\tid=5,file=0,line=1
+scope\tid=0,name=\"\",mod=0,size=8,span=0+1+2
+sym\tid=0,name=\"start\",addrsize=absolute,scope=0,def=0,ref=4,val=0xC000,seg=0,type=lab
+sym\tid=1,name=\"main_loop\",addrsize=absolute,scope=0,def=1,val=0xC002,seg=0,type=lab
+sym\tid=2,name=\"VIA_PORTB\",addrsize=absolute,scope=0,def=5,val=0x6000,type=equ
+sym\tid=3,name=\"table\",addrsize=absolute,scope=0,def=4,val=0xC100,seg=1,type=lab
+sym\tid=4,name=\"CHROUT\",addrsize=absolute,scope=0,ref=2,type=imp
+type\tid=0,val=\"800120\"
+";
+
+/**
+ * start:     LDA #$01
+ * main_loop: STA VIA_PORTB
+ *            JMP main_loop
+ * */
+fn rom() -> (CPU, Mem) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xC000, &mut mem);
+
+    let code = [
+        CPU::INS_LDA_IM, 0x01,
+        CPU::INS_STA_ABS, 0x00, 0x60,
+        CPU::INS_JMP_ABS, 0x02, 0xC0,
+    ];
+    for (offset, byte) in code.iter().enumerate() {
+        mem[0xC000 + offset as Word] = *byte;
+    }
+
+    (cpu, mem)
+}
+
+#[test]
+fn dbg_file_gives_symbols_and_source_lines() {
+    //when:
+    let info = ca65::parse(DBG).unwrap();
+
+    //then:
+    assert_eq!(info.symbols.len(), 4);
+    assert_eq!(info.symbols.address_of("main_loop"), Some(0xC002));
+    assert_eq!(info.symbols.address_of("VIA_PORTB"), Some(0x6000));
+    assert_eq!(info.symbols.address_of("CHROUT"), None);
+    assert_eq!(info.symbols.name_of(0xC100), Some("table"));
+
+    let mut lines = LineMap::new();
+    lines.add_code("main.s", 3, 0xC000, 2);
+    lines.add_code("main.s", 4, 0xC002, 3);
+    lines.add_code("main.s", 6, 0xC005, 3);
+    lines.add_data("main.s", 9, 0xC100, 2);
+    assert_eq!(info.lines, lines);
+}
+
+#[test]
+fn dbg_file_of_another_version_is_rejected() {
+    let error = ca65::parse("version\tmajor=3,minor=0\n").unwrap_err();
+
+    assert!(matches!(error, LoadError::Syntax { line: 1, .. }));
+}
+
+#[test]
+fn vice_labels_are_read_with_or_without_a_memory_space() {
+    let text = "al C:c002 .main_loop\nal 00C000 .start\nbreak c002\n\nal C:6000 .VIA_PORTB\n";
+
+    let symbols = vice::parse(text).unwrap();
+
+    assert_eq!(symbols.address_of("main_loop"), Some(0xC002));
+    assert_eq!(symbols.address_of("start"), Some(0xC000));
+    assert_eq!(symbols.label(0x6000), "VIA_PORTB");
+    assert_eq!(symbols.label(0x6001), "$6001");
+    assert!(matches!(vice::parse("al C:zz .oops"), Err(LoadError::Syntax { line: 1, .. })));
+    assert!(matches!(vice::parse("al 10000 .far"), Err(LoadError::OutOfRange { line: 1, address: 0x10000 })));
+}
+
+#[test]
+fn disassembly_and_traces_use_labels() {
+    let (mut cpu, mut mem) = rom();
+    let symbols = ca65::parse(DBG).unwrap().symbols;
+
+    let listing = disassemble_with(&mem, 0xC000, 3, &symbols);
+    let mut tracer = Tracer::new(Vec::new()).with_symbols(symbols);
+    tracer.execute(2 + 4 + 3, &mut cpu, &mut mem).unwrap();
+    let trace = String::from_utf8(tracer.into_inner()).unwrap();
+
+    assert_eq!(
+        listing,
+        vec![
+            "start:",
+            "C000  A9 01     LDA #$01",
+            "main_loop:",
+            "C002  8D 00 60  STA VIA_PORTB",
+            "C005  4C 02 C0  JMP main_loop",
+        ]
+    );
+    let trace: Vec<&str> = trace.lines().collect();
+    assert!(trace[1].starts_with("C002  8D 00 60  STA VIA_PORTB = 00 "));
+    assert!(trace[2].starts_with("C005  4C 02 C0  JMP main_loop "));
+}
+
+#[test]
+fn breakpoints_can_be_set_by_symbol() {
+    let (cpu, mem) = rom();
+    let symbols = ca65::parse(DBG).unwrap().symbols;
+    let mut stub = GdbStub::new(cpu, mem);
+
+    assert_eq!(stub.add_breakpoint_at(&symbols, "main_loop"), Some(0xC002));
+    assert_eq!(stub.add_breakpoint_at(&symbols, "$C005"), Some(0xC005));
+    assert_eq!(stub.add_breakpoint_at(&symbols, "nowhere"), None);
+    assert!(stub.remove_breakpoint(0xC002));
+    assert!(stub.remove_breakpoint(0xC005));
+}
+
+#[test]
+fn debug_info_drives_coverage_reports() {
+    let (mut cpu, mut mem) = rom();
+    let info = ca65::parse(DBG).unwrap();
+    let mut coverage = Coverage::new();
+    for _ in 0..3 {
+        coverage.step(&mut cpu, &mut mem);
+    }
+    let mut out = Vec::new();
+
+    coverage
+        .write_lcov(&mut out, &mem, &info.lines, info.symbols.names())
+        .unwrap();
+
+    let lcov = String::from_utf8(out).unwrap();
+    assert!(lcov.contains("FN:3,start\nFN:4,main_loop\n"));
+    assert!(lcov.contains("DA:3,1\nDA:4,1\nDA:6,1\nLF:3\nLH:3\n"));
+}
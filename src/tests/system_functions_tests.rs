@@ -0,0 +1,127 @@
+use crate::m6502::*;
+
+#[test]
+fn nop_will_do_nothing_but_consume_a_cycle() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    mem[0xFF00] = CPU::INS_NOP;
+
+    //when:
+    let cpu_copy = cpu.clone();
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(cpu.pc(), 0xFF01);
+    assert_eq!(cpu.status(), cpu_copy.status());
+}
+
+#[test]
+fn brk_pushes_the_address_past_its_padding_byte_and_jumps_through_the_irq_vector() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    cpu.set_c(1);
+    mem[0xFF00] = CPU::INS_BRK;
+    mem[0xFFFE] = 0x00;
+    mem[0xFFFF] = 0x80;
+
+    //when:
+    let cycles_used = cpu.execute(7, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 7);
+    assert_eq!(cpu.pc(), 0x8000);
+    assert_eq!(cpu.i(), 1);
+    assert_eq!(mem[0x01FF], 0xFF);
+    assert_eq!(mem[0x01FE], 0x02);
+    assert_eq!(mem[0x01FD], 0b0011_0001);
+    assert_eq!(cpu.sp(), 0xFC);
+}
+
+#[test]
+fn rti_returns_from_brk_past_the_padding_byte() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    mem[0xFF00] = CPU::INS_BRK;
+    mem[0xFFFE] = 0x00;
+    mem[0xFFFF] = 0x80;
+    mem[0x8000] = CPU::INS_RTI;
+
+    //when:
+    let cycles_used = cpu.execute(7 + 6, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 13);
+    assert_eq!(cpu.pc(), 0xFF02);
+    assert_eq!(cpu.sp(), 0xFF);
+    assert_eq!(cpu.b(), 0);
+}
+
+#[test]
+fn an_opcode_the_core_does_not_run_halts_the_cpu_on_it() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    mem[0xFF00] = CPU::INS_LDA_IM;
+    mem[0xFF01] = 0x42;
+    mem[0xFF02] = 0x02;
+
+    //when:
+    let cycles_used = cpu.execute(100, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert!(cpu.halted());
+    assert_eq!(cpu.pc(), 0xFF02);
+    assert_eq!(cpu.step(&mut mem), 0);
+    assert_eq!(cpu.pc(), 0xFF02);
+}
+
+#[test]
+fn a_halted_cpu_does_not_take_interrupts() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    mem[0xFF00] = 0x02;
+    cpu.step(&mut mem);
+    cpu.set_nmi(true);
+
+    //when:
+    let cycles_used = cpu.step(&mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 0);
+    assert_eq!(cpu.pc(), 0xFF00);
+}
+
+#[test]
+fn the_program_counter_wraps_around_the_top_of_memory() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFFFF, &mut mem);
+
+    //given:
+    mem[0xFFFF] = CPU::INS_LDA_IM;
+    mem[0x0000] = 0x42;
+
+    //when:
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(cpu.a(), 0x42);
+    assert_eq!(cpu.pc(), 0x0001);
+}
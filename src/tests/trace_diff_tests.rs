@@ -0,0 +1,132 @@
+use crate::m6502::*;
+use crate::trace::*;
+use crate::trace_diff::*;
+
+/** LDA #$42 / LDX #$07 / STA $0200 / JSR $8000 ... $8000: RTS */
+fn little_program() -> (CPU, Mem) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x1000, &mut mem);
+
+    mem[0x1000] = CPU::INS_LDA_IM;
+    mem[0x1001] = 0x42;
+    mem[0x1002] = CPU::INS_LDX_IM;
+    mem[0x1003] = 0x07;
+    mem[0x1004] = CPU::INS_STA_ABS;
+    mem[0x1005] = 0x00;
+    mem[0x1006] = 0x02;
+    mem[0x1007] = CPU::INS_JSR;
+    mem[0x1008] = 0x00;
+    mem[0x1009] = 0x80;
+    mem[0x8000] = CPU::INS_RTS;
+    mem[0x100A] = CPU::INS_LDY_IM;
+    mem[0x100B] = 0x01;
+
+    (cpu, mem)
+}
+
+fn reference_trace() -> String {
+    let (mut cpu, mut mem) = little_program();
+    let mut tracer = Tracer::new(Vec::new()).with_cycles(NESTEST_START_CYCLES);
+    for _ in 0..6 {
+        tracer.step(&mut cpu, &mut mem).unwrap();
+    }
+
+    String::from_utf8(tracer.into_inner()).unwrap()
+}
+
+#[test]
+fn can_parse_a_nestest_log_line() {
+    let line = "C000  4C F5 C5  JMP $C5F5                       A:01 X:02 Y:03 P:24 SP:FD PPU:  0, 21 CYC:7";
+
+    let record = TraceRecord::parse(line).unwrap();
+
+    assert_eq!(
+        record,
+        TraceRecord {
+            pc: 0xC000,
+            a: 0x01,
+            x: 0x02,
+            y: 0x03,
+            p: 0x24,
+            sp: 0xFD,
+            cycles: Some(7),
+        }
+    );
+    assert_eq!(TraceRecord::parse("not a trace line"), None);
+}
+
+#[test]
+fn matching_trace_has_no_divergence() {
+    let reference = reference_trace();
+    let (mut cpu, mut mem) = little_program();
+
+    let divergence =
+        diff_against_reference(reference.as_bytes(), &mut cpu, &mut mem, NESTEST_START_CYCLES, 2).unwrap();
+
+    assert_eq!(divergence, None);
+    assert_eq!(cpu.y(), 0x01);
+}
+
+#[test]
+fn diff_stops_at_the_first_divergence_with_context() {
+    //given: a reference where STA took 5 cycles instead of 4
+    let reference = reference_trace().replace("CYC:15", "CYC:16").replace("CYC:21", "CYC:22");
+    let (mut cpu, mut mem) = little_program();
+
+    //when:
+    let divergence = diff_against_reference(reference.as_bytes(), &mut cpu, &mut mem, NESTEST_START_CYCLES, 2)
+        .unwrap()
+        .unwrap();
+
+    //then:
+    assert_eq!(divergence.line_number, 4);
+    assert_eq!(divergence.fields, vec![Field::Cycles]);
+    assert_eq!(divergence.before.len(), 3);
+    assert!(divergence.before[2].expected.starts_with("1007  20 00 80  JSR $8000"));
+    assert!(divergence.before[2].actual.ends_with("CYC:15"));
+    assert_eq!(divergence.after.len(), 2);
+    assert!(divergence.after[0].actual.starts_with("8000  60"));
+    assert!(divergence.to_string().contains(">>      4 ref: 1007"));
+}
+
+#[test]
+fn context_keeps_the_line_numbers_of_the_reference() {
+    //given: lines that aren't trace records before and after the divergence
+    let reference = reference_trace().replace("CYC:15", "CYC:16").replace("CYC:21", "CYC:22");
+    let mut lines: Vec<&str> = reference.lines().collect();
+    lines.insert(4, "; back from the subroutine");
+    lines.insert(2, "; the store");
+    let reference = lines.join("\n");
+    let (mut cpu, mut mem) = little_program();
+
+    //when:
+    let divergence = diff_against_reference(reference.as_bytes(), &mut cpu, &mut mem, NESTEST_START_CYCLES, 2)
+        .unwrap()
+        .unwrap();
+
+    //then:
+    let numbers = |context: &[ContextLine]| context.iter().map(|line| line.line_number).collect::<Vec<_>>();
+    assert_eq!(divergence.line_number, 5);
+    assert_eq!(numbers(&divergence.before), vec![2, 4, 5]);
+    assert_eq!(numbers(&divergence.after), vec![7, 8]);
+    assert!(divergence.to_string().contains(">>      5 ref: 1007"));
+    assert!(divergence.to_string().contains("       7 got: 8000"));
+}
+
+#[test]
+fn diff_reports_register_mismatches() {
+    //given: a reference where the load put a different value into A
+    let reference = reference_trace().replacen("A:42", "A:43", 1);
+    let (mut cpu, mut mem) = little_program();
+
+    //when:
+    let divergence = diff_against_reference(reference.as_bytes(), &mut cpu, &mut mem, NESTEST_START_CYCLES, 0)
+        .unwrap()
+        .unwrap();
+
+    //then:
+    assert_eq!(divergence.line_number, 2);
+    assert_eq!(divergence.fields, vec![Field::A]);
+    assert!(divergence.after.is_empty());
+}
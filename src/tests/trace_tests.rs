@@ -0,0 +1,147 @@
+use crate::m6502::*;
+use crate::trace::*;
+
+#[test]
+fn trace_matches_the_nestest_layout() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xC000, &mut mem);
+
+    //given:
+    cpu.set_sp(0xFD);
+    cpu.set_i(1);
+    mem[0xC000] = CPU::INS_LDX_IM;
+    mem[0xC001] = 0x00;
+    mem[0xC002] = CPU::INS_STX_ZP;
+    mem[0xC003] = 0x01;
+    mem[0xC004] = CPU::INS_JSR;
+    mem[0xC005] = 0x2D;
+    mem[0xC006] = 0xC7;
+    mem[0xC72D] = 0x04; //*NOP zero page
+    mem[0xC72E] = 0x2D;
+
+    //when:
+    let mut tracer = Tracer::new(Vec::new()).with_cycles(NESTEST_START_CYCLES);
+    let cycles_used = tracer.execute(2 + 3 + 6, &mut cpu, &mut mem).unwrap();
+    let log = String::from_utf8(tracer.into_inner()).unwrap();
+
+    //then:
+    assert_eq!(cycles_used, 11);
+    let expected = [
+        "C000  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD CYC:7",
+        "C002  86 01     STX $01 = 00                    A:00 X:00 Y:00 P:26 SP:FD CYC:9",
+        "C004  20 2D C7  JSR $C72D                       A:00 X:00 Y:00 P:26 SP:FD CYC:12",
+    ];
+    assert_eq!(log.lines().collect::<Vec<_>>(), expected);
+    assert_eq!(
+        trace_line(&cpu, &mem, 18),
+        "C72D  04 2D    *NOP $2D = 00                    A:00 X:00 Y:00 P:26 SP:FB CYC:18"
+    );
+}
+
+#[test]
+fn trace_annotates_indexed_and_indirect_operands() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0x0600, &mut mem);
+
+    //given:
+    cpu.set_x(0x02);
+    cpu.set_y(0x10);
+    mem[0x0080] = 0x00;
+    mem[0x0081] = 0x02;
+    mem[0x0082] = 0x00;
+    mem[0x0083] = 0x03;
+    mem[0x0210] = 0x5A;
+    mem[0x0300] = 0x89;
+    mem[0x0600] = CPU::INS_LDA_INDX;
+    mem[0x0601] = 0x80;
+    mem[0x0602] = CPU::INS_LDA_INDY;
+    mem[0x0603] = 0x80;
+    mem[0x0604] = CPU::INS_LDA_ABSY;
+    mem[0x0605] = 0xF0;
+    mem[0x0606] = 0x02;
+    mem[0x0607] = CPU::INS_LDA_ZPX;
+    mem[0x0608] = 0xFF;
+
+    //when:
+    let mut tracer = Tracer::new(Vec::new());
+    for _ in 0..4 {
+        tracer.step(&mut cpu, &mut mem).unwrap();
+    }
+    let log = String::from_utf8(tracer.into_inner()).unwrap();
+
+    //then:
+    let disassembly: Vec<&str> = log.lines().map(|line| line[16..48].trim_end()).collect();
+    assert_eq!(
+        disassembly,
+        vec![
+            "LDA ($80,X) @ 82 = 0300 = 89",
+            "LDA ($80),Y = 0200 @ 0210 = 5A",
+            "LDA $02F0,Y @ 0300 = 89",
+            "LDA $FF,X @ 01 = 00",
+        ]
+    );
+}
+
+#[test]
+fn trace_keeps_counting_cycles_across_steps() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    mem[0xFF00] = CPU::INS_JSR;
+    mem[0xFF01] = 0x00;
+    mem[0xFF02] = 0x80;
+    mem[0x8000] = CPU::INS_RTS;
+
+    //when:
+    let mut tracer = Tracer::new(std::io::sink());
+    tracer.step(&mut cpu, &mut mem).unwrap();
+    tracer.step(&mut cpu, &mut mem).unwrap();
+
+    //then:
+    assert_eq!(tracer.cycles(), 12);
+    assert_eq!(cpu.pc(), 0xFF03);
+}
+
+/** Takes `room` lines, then fails every write */
+struct FullLog {
+    room: usize,
+}
+
+impl std::io::Write for FullLog {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.room == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::StorageFull, "log full"));
+        }
+        self.room -= buf.iter().filter(|&&byte| byte == b'\n').count();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn trace_stops_at_the_first_line_that_cannot_be_written() {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    mem[0xFF00] = CPU::INS_NOP;
+    mem[0xFF01] = CPU::INS_NOP;
+    mem[0xFF02] = CPU::INS_NOP;
+
+    //when:
+    let mut tracer = Tracer::new(FullLog { room: 2 });
+    let error = tracer.execute(6, &mut cpu, &mut mem).unwrap_err();
+
+    //then:
+    assert_eq!(error.kind(), std::io::ErrorKind::StorageFull);
+    assert_eq!(tracer.cycles(), 4);
+    assert_eq!(cpu.pc(), 0xFF02);
+}
@@ -0,0 +1,40 @@
+use crate::m6502::*;
+
+fn test_transfer(opcode_to_test: Byte, set_from: fn(&mut CPU, Byte), to: fn(&CPU) -> Byte) {
+    let mut mem: Mem = Mem::new();
+    let mut cpu = CPU::new();
+    cpu.reset(0xFF00, &mut mem);
+
+    //given:
+    set_from(&mut cpu, 0x80);
+    mem[0xFF00] = opcode_to_test;
+
+    //when:
+    let cycles_used = cpu.execute(2, &mut mem);
+
+    //then:
+    assert_eq!(cycles_used, 2);
+    assert_eq!(to(&cpu), 0x80);
+    assert_eq!(cpu.n(), 1);
+    assert_eq!(cpu.z(), 0);
+}
+
+#[test]
+fn tax_can_transfer_a_to_x() {
+    test_transfer(CPU::INS_TAX, CPU::set_a, CPU::x);
+}
+
+#[test]
+fn tay_can_transfer_a_to_y() {
+    test_transfer(CPU::INS_TAY, CPU::set_a, CPU::y);
+}
+
+#[test]
+fn txa_can_transfer_x_to_a() {
+    test_transfer(CPU::INS_TXA, CPU::set_x, CPU::a);
+}
+
+#[test]
+fn tya_can_transfer_y_to_a() {
+    test_transfer(CPU::INS_TYA, CPU::set_y, CPU::a);
+}
@@ -0,0 +1,232 @@
+use crate::devices::via::*;
+use crate::devices::DeviceBus;
+use crate::m6502::*;
+
+#[test]
+fn ports_mix_outputs_and_inputs_by_ddr() {
+    //given:
+    let mut via = Via::new();
+    via.set_port_a_input(0x05);
+    via.set_port_b_input(0x3C);
+
+    //when:
+    via.write(DDRA, 0xF0);
+    via.write(ORA, 0xAB);
+    via.write(DDRB, 0xFF);
+    via.write(ORB, 0x81);
+
+    //then:
+    assert_eq!(via.port_a(), 0xA5);
+    assert_eq!(via.read(ORA), 0xA5);
+    assert_eq!(via.read(ORA_NH), 0xA5);
+    assert_eq!(via.port_b(), 0x81);
+    assert_eq!(via.read(DDRA), 0xF0);
+}
+
+#[test]
+fn t1_one_shot_interrupts_once() {
+    let mut via = Via::new();
+    via.write(IER, 0x80 | IRQ_T1);
+    via.write(T1C_L, 10);
+    via.write(T1C_H, 0);
+
+    via.tick(10);
+    assert!(!via.irq());
+    via.tick(1);
+    assert!(via.irq());
+    assert_eq!(via.read(IFR), IRQ_ANY | IRQ_T1);
+
+    via.read(T1C_L);
+    via.tick(0x20000);
+    assert!(!via.irq());
+}
+
+#[test]
+fn t1_free_run_toggles_pb7_every_period() {
+    let mut via = Via::new();
+    via.write(ACR, 0xC0);
+    via.write(T1C_L, 10);
+    via.write(T1C_H, 0);
+    assert_eq!(via.port_b() & 0x80, 0);
+
+    via.tick(11);
+    assert_ne!(via.read(IFR) & IRQ_T1, 0);
+    assert_eq!(via.port_b() & 0x80, 0x80);
+
+    via.write(IFR, IRQ_T1);
+    via.tick(11);
+    assert_eq!(via.read(IFR) & IRQ_T1, 0);
+    via.tick(1);
+    assert_ne!(via.read(IFR) & IRQ_T1, 0);
+    assert_eq!(via.port_b() & 0x80, 0);
+}
+
+#[test]
+fn orb_reads_t1s_pb7_level_while_t1_drives_it() {
+    //given:
+    let mut via = Via::new();
+    via.write(DDRB, 0xFF);
+    via.write(ORB, 0x81);
+    via.write(ACR, 0xC0);
+    via.write(T1C_L, 10);
+    via.write(T1C_H, 0);
+
+    //then: low from the T1C-H write, whatever ORB holds
+    assert_eq!(via.peek(ORB), 0x01);
+    assert_eq!(via.read(ORB), 0x01);
+
+    //when:
+    via.tick(11);
+
+    //then:
+    assert_eq!(via.peek(ORB), 0x81);
+    via.write(ORB, 0x00);
+    assert_eq!(via.read(ORB), 0x80);
+
+    //and ORB has bit 7 back once T1 lets go of it
+    via.write(ACR, 0x00);
+    assert_eq!(via.read(ORB), 0x00);
+}
+
+#[test]
+fn t2_times_out_or_counts_pb6_pulses() {
+    let mut via = Via::new();
+    via.write(T2C_L, 5);
+    via.write(T2C_H, 0);
+    via.tick(5);
+    assert_eq!(via.read(IFR) & IRQ_T2, 0);
+    via.tick(1);
+    assert_ne!(via.read(IFR) & IRQ_T2, 0);
+
+    let mut counter = Via::new();
+    counter.write(ACR, 0x20);
+    counter.write(T2C_L, 3);
+    counter.write(T2C_H, 0);
+    counter.tick(100);
+    for _ in 0..3 {
+        counter.set_port_b_input(0x00);
+        counter.set_port_b_input(0x40);
+    }
+    assert_ne!(counter.read(IFR) & IRQ_T2, 0);
+    assert_eq!(counter.read(T2C_L), 0);
+}
+
+#[test]
+fn shift_register_shifts_out_and_in() {
+    //out under φ2: a bit every 2 cycles, rotating the byte back into place
+    let mut out = Via::new();
+    out.write(ACR, 0b110 << 2);
+    out.write(SR, 0xA5);
+    out.tick(15);
+    assert_eq!(out.read(IFR) & IRQ_SR, 0);
+    out.tick(1);
+    assert_ne!(out.read(IFR) & IRQ_SR, 0);
+    assert_eq!(out.peek(SR), 0xA5);
+    assert!(out.cb2());
+
+    //in under the CB1 clock from outside
+    let mut external = Via::new();
+    external.write(ACR, 0b011 << 2);
+    external.read(SR);
+    for bit in (0..8).rev() {
+        external.set_cb2((0x5A >> bit) & 1 == 1);
+        external.set_cb1(false);
+        external.set_cb1(true);
+    }
+    assert_eq!(external.read(SR), 0x5A);
+    assert_eq!(external.peek(IFR) & IRQ_SR, 0);
+
+    //in under T2: a bit every latch + 2 cycles
+    let mut timed = Via::new();
+    timed.write(ACR, 0b001 << 2);
+    timed.write(T2C_L, 4);
+    timed.set_cb2(true);
+    timed.read(SR);
+    timed.tick(8 * 6);
+    assert_ne!(timed.read(IFR) & IRQ_SR, 0);
+    assert_eq!(timed.read(SR), 0xFF);
+}
+
+#[test]
+fn ca1_latches_port_a_and_ends_the_ca2_handshake() {
+    let mut via = Via::new();
+    //CA1 on the rising edge, CA2 handshake output, latching on
+    via.write(PCR, 0x01 | 0b100 << 1);
+    via.write(ACR, 0x01);
+    via.write(IER, 0x80 | IRQ_CA1);
+    via.set_ca1(false);
+
+    via.set_port_a_input(0x42);
+    via.set_ca1(true);
+    via.set_port_a_input(0x00);
+
+    assert!(via.irq());
+    assert_eq!(via.read(ORA), 0x42);
+    assert!(!via.irq());
+    assert!(!via.ca2());
+    via.set_ca1(false);
+    via.set_ca1(true);
+    assert!(via.ca2());
+}
+
+#[test]
+fn cb2_pulses_low_for_one_cycle_after_writing_port_b() {
+    let mut via = Via::new();
+    via.write(PCR, 0b101 << 5);
+
+    via.write(ORB, 0x00);
+    assert!(!via.cb2());
+    via.tick(1);
+    assert!(via.cb2());
+}
+
+#[test]
+fn ier_sets_and_clears_bits_and_reads_back_bit_7() {
+    let mut via = Via::new();
+
+    via.write(IER, 0x80 | IRQ_CA1 | IRQ_T2);
+    assert_eq!(via.read(IER), 0x80 | IRQ_CA1 | IRQ_T2);
+    via.write(IER, IRQ_CA1);
+    assert_eq!(via.read(IER), 0x80 | IRQ_T2);
+}
+
+#[test]
+fn via_interrupt_reaches_the_cpu() {
+    //given:
+    let mut bus = DeviceBus::new(Mem::new());
+    let via = bus.attach(0x6000, 16, Via::new());
+    let mut cpu = CPU::new();
+    cpu.reset(0x0200, &mut bus.bus);
+    let main = [
+        CPU::INS_LDA_IM, 0xC0,
+        CPU::INS_STA_ABS, 0x0E, 0x60,
+        CPU::INS_LDA_IM, 0x10,
+        CPU::INS_STA_ABS, 0x04, 0x60,
+        CPU::INS_LDA_IM, 0x00,
+        CPU::INS_STA_ABS, 0x05, 0x60,
+        CPU::INS_JMP_ABS, 0x0F, 0x02,
+    ];
+    let handler = [
+        CPU::INS_LDY_IM, 0x01,
+        CPU::INS_STY_ZP, 0x10,
+        CPU::INS_LDA_ABS, 0x04, 0x60,
+        CPU::INS_RTI,
+    ];
+    for (offset, byte) in main.iter().enumerate() {
+        bus.bus[0x0200 + offset as Word] = *byte;
+    }
+    for (offset, byte) in handler.iter().enumerate() {
+        bus.bus[0x0500 + offset as Word] = *byte;
+    }
+    bus.bus[0xFFFE] = 0x00;
+    bus.bus[0xFFFF] = 0x05;
+
+    //when:
+    bus.execute(100, &mut cpu);
+
+    //then:
+    assert_eq!(bus.bus[0x0010], 0x01);
+    assert!(!bus.device(via).irq());
+    assert_eq!(cpu.sp(), 0xFF);
+    assert_eq!(cpu.i(), 0);
+}
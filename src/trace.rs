@@ -0,0 +1,165 @@
+//! Execution trace logging in the nestest.log layout
+
+use crate::disassembler::{self, AddressingMode, Instruction};
+use crate::m6502::*;
+use crate::symbols::Symbols;
+use std::io::{self, Write};
+
+/** nestest.log starts counting after the 7 cycles of the reset sequence */
+pub const NESTEST_START_CYCLES: u64 = 7;
+
+/**
+ * Writes one line per executed instruction:
+ * `C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD CYC:7`
+ * - the line shows the state *before* the instruction runs, like nestest.log
+ * - the PPU column of nestest.log is left out, we have no PPU
+ */
+pub struct Tracer<W: Write> {
+    out: W,
+    cycles: u64,
+    symbols: Option<Symbols>,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            cycles: 0,
+            symbols: None,
+        }
+    }
+
+    /** Start the cycle counter at `cycles`, e.g. NESTEST_START_CYCLES */
+    pub fn with_cycles(mut self, cycles: u64) -> Self {
+        self.cycles = cycles;
+        self
+    }
+
+    /** Show labels instead of addresses in the disassembly column */
+    pub fn with_symbols(mut self, symbols: Symbols) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    /** @return the total number of cycles executed so far */
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /**
+     * Log the instruction at PC, then execute it
+     * @return the number of cycles that were used
+     * */
+    pub fn step<B: Bus>(&mut self, cpu: &mut CPU, memory: &mut B) -> io::Result<s32> {
+        let line = match &self.symbols {
+            Some(symbols) => trace_line_with(cpu, memory, self.cycles, &|address| symbols.label(address)),
+            None => trace_line(cpu, memory, self.cycles),
+        };
+        writeln!(self.out, "{}", line)?;
+        let cycles_used = cpu.step(memory);
+        self.cycles += cycles_used as u64;
+
+        Ok(cycles_used)
+    }
+
+    /**
+     * Same as CPU::execute, logging every instruction
+     * @return the number of cycles that were used
+     * */
+    pub fn execute<B: Bus>(&mut self, cycles: s32, cpu: &mut CPU, memory: &mut B) -> io::Result<s32> {
+        let mut cycles_used = 0;
+        while cycles_used < cycles {
+            let step_cycles = self.step(cpu, memory)?;
+            if step_cycles == 0 {
+                break;
+            }
+            cycles_used += step_cycles;
+        }
+
+        Ok(cycles_used)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/** Format the trace line for the instruction at PC */
+pub fn trace_line<B: Bus>(cpu: &CPU, memory: &B, cycles: u64) -> String {
+    trace_line_with(cpu, memory, cycles, &|address| format!("${:04X}", address))
+}
+
+/** Like trace_line(), with absolute addresses in the disassembly rendered by `name` */
+pub fn trace_line_with<B: Bus>(cpu: &CPU, memory: &B, cycles: u64, name: &dyn Fn(Word) -> String) -> String {
+    let instruction = disassembler::decode(memory, cpu.pc());
+    format!(
+        "{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+        cpu.pc(),
+        disassembler::hex_bytes(&instruction.bytes),
+        if instruction.opcode.illegal { '*' } else { ' ' },
+        annotated_text_with(&instruction, cpu, memory, name),
+        cpu.a(),
+        cpu.x(),
+        cpu.y(),
+        cpu.status(),
+        cpu.sp(),
+        cycles
+    )
+}
+
+/**
+ * The disassembly as nestest.log prints it: with the effective address and
+ * the value found there, e.g. "LDA ($89),Y = 0300 @ 0300 = 89"
+ * */
+pub fn annotated_text<B: Bus>(instruction: &Instruction, cpu: &CPU, memory: &B) -> String {
+    annotated_text_with(instruction, cpu, memory, &|address| format!("${:04X}", address))
+}
+
+/** Like annotated_text(), with absolute addresses rendered by `name` */
+pub fn annotated_text_with<B: Bus>(
+    instruction: &Instruction,
+    cpu: &CPU,
+    memory: &B,
+    name: &dyn Fn(Word) -> String,
+) -> String {
+    let text = instruction.text_with(name);
+    let operand = instruction.operand();
+    let zero_page_word = |address: Byte| {
+        Word::from_le_bytes([memory.peek(address as Word), memory.peek(address.wrapping_add(1) as Word)])
+    };
+
+    match instruction.opcode.mode {
+        AddressingMode::ZeroPage => format!("{} = {:02X}", text, memory.peek(operand)),
+        AddressingMode::ZeroPageX | AddressingMode::ZeroPageY => {
+            let index = if instruction.opcode.mode == AddressingMode::ZeroPageX { cpu.x() } else { cpu.y() };
+            let address = (operand as Byte).wrapping_add(index) as Word;
+            format!("{} @ {:02X} = {:02X}", text, address, memory.peek(address))
+        }
+        AddressingMode::Absolute => match instruction.opcode.mnemonic {
+            "JMP" | "JSR" => text,
+            _ => format!("{} = {:02X}", text, memory.peek(operand)),
+        },
+        AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => {
+            let index = if instruction.opcode.mode == AddressingMode::AbsoluteX { cpu.x() } else { cpu.y() };
+            let address = operand.wrapping_add(index as Word);
+            format!("{} @ {:04X} = {:02X}", text, address, memory.peek(address))
+        }
+        AddressingMode::Indirect => {
+            //the pointer's high byte is fetched without carrying into the page
+            let hi_address = (operand & 0xFF00) | (operand.wrapping_add(1) & 0x00FF);
+            let target = Word::from_le_bytes([memory.peek(operand), memory.peek(hi_address)]);
+            format!("{} = {:04X}", text, target)
+        }
+        AddressingMode::IndirectX => {
+            let pointer = (operand as Byte).wrapping_add(cpu.x());
+            let address = zero_page_word(pointer);
+            format!("{} @ {:02X} = {:04X} = {:02X}", text, pointer, address, memory.peek(address))
+        }
+        AddressingMode::IndirectY => {
+            let base = zero_page_word(operand as Byte);
+            let address = base.wrapping_add(cpu.y() as Word);
+            format!("{} = {:04X} @ {:04X} = {:02X}", text, base, address, memory.peek(address))
+        }
+        _ => text,
+    }
+}
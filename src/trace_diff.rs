@@ -0,0 +1,221 @@
+//! Run the core alongside a reference trace and find the first divergence
+
+use crate::m6502::*;
+use crate::trace::trace_line;
+use std::fmt;
+use std::io::{self, BufRead};
+
+/** The machine state parsed from one nestest-style trace line */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub pc: Word,
+    pub a: Byte,
+    pub x: Byte,
+    pub y: Byte,
+    pub p: Byte,
+    pub sp: Byte,
+    /** not every published log carries a cycle count */
+    pub cycles: Option<u64>,
+}
+
+impl TraceRecord {
+    /**
+     * Parse a line like
+     * `C000  4C F5 C5  JMP $C5F5    A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7`
+     * @return None if the line carries no PC or registers
+     * - extra columns such as PPU are ignored
+     * */
+    pub fn parse(line: &str) -> Option<Self> {
+        let pc = u16::from_str_radix(line.get(0..4)?, 16).ok()?;
+        let register = |name: &str| -> Option<Byte> {
+            let start = line.find(name)? + name.len();
+            u8::from_str_radix(line.get(start..start + 2)?, 16).ok()
+        };
+        let cycles = line.find("CYC:").and_then(|start| {
+            line[start + 4..]
+                .split_whitespace()
+                .next()
+                .and_then(|cycles| cycles.parse().ok())
+        });
+
+        Some(Self {
+            pc,
+            a: register(" A:")?,
+            x: register(" X:")?,
+            y: register(" Y:")?,
+            p: register(" P:")?,
+            sp: register(" SP:")?,
+            cycles,
+        })
+    }
+
+    /** Capture the current state of the core */
+    pub fn of(cpu: &CPU, cycles: u64) -> Self {
+        Self {
+            pc: cpu.pc(),
+            a: cpu.a(),
+            x: cpu.x(),
+            y: cpu.y(),
+            p: cpu.status(),
+            sp: cpu.sp(),
+            cycles: Some(cycles),
+        }
+    }
+
+    /** Put the registers and flags of this record into the CPU */
+    pub fn apply_to(&self, cpu: &mut CPU) {
+        cpu.set_pc(self.pc);
+        cpu.set_a(self.a);
+        cpu.set_x(self.x);
+        cpu.set_y(self.y);
+        cpu.set_status(self.p);
+        cpu.set_sp(self.sp);
+    }
+
+    /** @return the fields where `actual` differs, only comparing cycles when both have them */
+    pub fn mismatches(&self, actual: &TraceRecord) -> Vec<Field> {
+        let mut fields = Vec::new();
+        if self.pc != actual.pc {
+            fields.push(Field::Pc);
+        }
+        if self.a != actual.a {
+            fields.push(Field::A);
+        }
+        if self.x != actual.x {
+            fields.push(Field::X);
+        }
+        if self.y != actual.y {
+            fields.push(Field::Y);
+        }
+        if self.p != actual.p {
+            fields.push(Field::P);
+        }
+        if self.sp != actual.sp {
+            fields.push(Field::Sp);
+        }
+        if let (Some(expected), Some(actual)) = (self.cycles, actual.cycles) {
+            if expected != actual {
+                fields.push(Field::Cycles);
+            }
+        }
+
+        fields
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Pc,
+    A,
+    X,
+    Y,
+    P,
+    Sp,
+    Cycles,
+}
+
+/** Where and how the core went its own way */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /** 1-based line number in the reference trace */
+    pub line_number: usize,
+    pub fields: Vec<Field>,
+    /** the lines leading up to and including the divergence */
+    pub before: Vec<ContextLine>,
+    /** the lines following the divergence */
+    pub after: Vec<ContextLine>,
+}
+
+/** A reference line next to what the core did at that point */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextLine {
+    /** 1-based line number in the reference trace, lines that aren't trace records are skipped */
+    pub line_number: usize,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "first divergence at reference line {}: {:?}",
+            self.line_number, self.fields
+        )?;
+        for line in self.before.iter().chain(self.after.iter()) {
+            let marker = if line.line_number == self.line_number { ">>" } else { "  " };
+            writeln!(f, "{} {:>6} ref: {}", marker, line.line_number, line.expected)?;
+            writeln!(f, "{} {:>6} got: {}", marker, line.line_number, line.actual)?;
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * Step the core once per reference line and compare the state before each instruction
+ * @return None if the whole reference matched
+ * - the CPU should already be set up to match the first reference line
+ * - `cycles` is the cycle count the core starts at
+ * - `context` is the number of lines to keep before and after the divergence
+ * */
+pub fn diff_against_reference<R: BufRead, B: Bus>(
+    reference: R,
+    cpu: &mut CPU,
+    memory: &mut B,
+    cycles: u64,
+    context: usize,
+) -> io::Result<Option<Divergence>> {
+    let mut cycles = cycles;
+    let mut lines = reference.lines().enumerate();
+    let mut before: Vec<ContextLine> = Vec::new();
+
+    while let Some((index, expected_line)) = lines.next() {
+        let expected_line = expected_line?;
+        let Some(expected) = TraceRecord::parse(&expected_line) else {
+            continue;
+        };
+
+        let actual_line = trace_line(cpu, memory, cycles);
+        let fields = expected.mismatches(&TraceRecord::of(cpu, cycles));
+
+        before.push(ContextLine {
+            line_number: index + 1,
+            expected: expected_line,
+            actual: actual_line,
+        });
+        if before.len() > context + 1 {
+            before.remove(0);
+        }
+
+        if !fields.is_empty() {
+            let mut after = Vec::new();
+            while after.len() < context {
+                let Some((index, expected_line)) = lines.next() else {
+                    break;
+                };
+                let expected_line = expected_line?;
+                if TraceRecord::parse(&expected_line).is_none() {
+                    continue;
+                }
+                cycles += cpu.step(memory) as u64;
+                after.push(ContextLine {
+                    line_number: index + 1,
+                    expected: expected_line,
+                    actual: trace_line(cpu, memory, cycles),
+                });
+            }
+
+            return Ok(Some(Divergence {
+                line_number: index + 1,
+                fields,
+                before,
+                after,
+            }));
+        }
+
+        cycles += cpu.step(memory) as u64;
+    }
+
+    Ok(None)
+}